@@ -0,0 +1,524 @@
+use diff::{self, Hunk};
+use egui::{
+    text::LayoutJob, vec2, Align2, Color32, CursorIcon, FontId, Id, Response, ScrollArea, Sense,
+    TextFormat, TextStyle, Ui, Vec2, Widget,
+};
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap, HashSet},
+    hash::{Hash, Hasher},
+    ops::Range,
+    sync::Arc,
+};
+use theme::Theme;
+
+/// Room for a line number.
+const NUMBER_WIDTH: f32 = 44.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DiffLayout {
+    #[default]
+    SideBySide,
+    /// Removed lines above the added ones, in a single column.
+    Inline,
+}
+
+/// Colors a whole text, e.g. `syntax_highlighter::highlight`.
+pub type Highlighter<'a> = &'a dyn Fn(&str) -> LayoutJob;
+
+/// Compares two texts line by line, marking the changed characters of
+/// modified lines. Runs of unchanged lines are folded beyond `context`
+/// lines around the changes, and unfold on click.
+pub struct DiffView<'a> {
+    id: Id,
+    old: &'a str,
+    new: &'a str,
+    layout: DiffLayout,
+    context: usize,
+    highlighter: Option<Highlighter<'a>>,
+}
+
+impl<'a> DiffView<'a> {
+    pub fn new(id_source: impl Hash, old: &'a str, new: &'a str) -> Self {
+        Self {
+            id: Id::new(id_source),
+            old,
+            new,
+            layout: DiffLayout::default(),
+            context: 3,
+            highlighter: None,
+        }
+    }
+
+    pub fn layout(mut self, layout: DiffLayout) -> Self {
+        self.layout = layout;
+        self
+    }
+
+    /// Unchanged lines shown around each change.
+    pub fn context(mut self, lines: usize) -> Self {
+        self.context = lines;
+        self
+    }
+
+    pub fn highlighter(mut self, highlighter: Highlighter<'a>) -> Self {
+        self.highlighter = Some(highlighter);
+        self
+    }
+}
+
+/// One side of the comparison.
+struct Side {
+    /// Byte ranges, without the line break.
+    lines: Vec<Range<usize>>,
+    /// The changed characters of modified lines, relative to the line.
+    marks: HashMap<usize, Vec<Range<usize>>>,
+    highlighted: Option<LayoutJob>,
+}
+
+/// The diff of two texts, kept in memory until either changes.
+struct Model {
+    key: u64,
+    hunks: Vec<Hunk>,
+    old: Side,
+    new: Side,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Row {
+    Line {
+        old: Option<usize>,
+        new: Option<usize>,
+        changed: bool,
+    },
+    /// Unchanged lines from `start` of the old text.
+    Fold { start: usize, count: usize },
+}
+
+impl Side {
+    fn new(text: &str, highlighter: Option<Highlighter>) -> Self {
+        let mut start = 0;
+        let lines = text
+            .split_inclusive('\n')
+            .map(|line| {
+                let range = start..start + line.trim_end_matches(['\n', '\r']).len();
+                start += line.len();
+                range
+            })
+            .collect();
+
+        Side {
+            lines,
+            marks: HashMap::new(),
+            // Only trusted when it colors the very same text.
+            highlighted: highlighter
+                .map(|highlight| highlight(text))
+                .filter(|job| job.text == text),
+        }
+    }
+
+    /// The line as a job of its own, with the changed characters on a
+    /// stronger background.
+    fn line_job(
+        &self,
+        text: &str,
+        line: usize,
+        font: &FontId,
+        color: Color32,
+        mark: Color32,
+    ) -> LayoutJob {
+        let range = self.lines[line].clone();
+        let marks = self.marks.get(&line).map_or(&[][..], Vec::as_slice);
+        let plain = TextFormat::simple(font.clone(), color);
+
+        let mut spans: Vec<(Range<usize>, TextFormat)> = Vec::new();
+        let mut at = range.start;
+
+        if let Some(job) = &self.highlighted {
+            let first = job
+                .sections
+                .partition_point(|section| section.byte_range.end <= range.start);
+
+            for section in job.sections[first..]
+                .iter()
+                .take_while(|section| section.byte_range.start < range.end)
+            {
+                let start = section.byte_range.start.max(range.start);
+                let end = section.byte_range.end.min(range.end);
+
+                if start > at {
+                    spans.push((at..start, plain.clone()));
+                }
+
+                spans.push((start..end, section.format.clone()));
+                at = end;
+            }
+        }
+
+        if at < range.end {
+            spans.push((at..range.end, plain.clone()));
+        }
+
+        let mut job = LayoutJob::default();
+
+        for (span, format) in spans {
+            let mut cuts = vec![span.start, span.end];
+
+            for mark in marks {
+                let mark = range.start + mark.start..range.start + mark.end;
+
+                cuts.extend(
+                    [mark.start, mark.end]
+                        .into_iter()
+                        .filter(|cut| span.contains(cut)),
+                );
+            }
+
+            cuts.sort_unstable();
+            cuts.dedup();
+
+            for cut in cuts.windows(2) {
+                let marked = marks
+                    .iter()
+                    .any(|mark| mark.contains(&(cut[0] - range.start)));
+
+                job.append(
+                    &text[cut[0]..cut[1]],
+                    0.0,
+                    TextFormat {
+                        font_id: font.clone(),
+                        background: if marked { mark } else { Color32::TRANSPARENT },
+                        ..format.clone()
+                    },
+                );
+            }
+        }
+
+        job
+    }
+}
+
+impl Model {
+    fn new(key: u64, old: &str, new: &str, highlighter: Option<Highlighter>) -> Self {
+        let mut model = Model {
+            key,
+            hunks: diff::hunks(old, new),
+            old: Side::new(old, highlighter),
+            new: Side::new(new, highlighter),
+        };
+
+        for hunk in &model.hunks {
+            for (old_line, new_line) in hunk.old_lines.clone().zip(hunk.new_lines.clone()) {
+                let (old_marks, new_marks) = diff::changed_chars(
+                    &old[model.old.lines[old_line].clone()],
+                    &new[model.new.lines[new_line].clone()],
+                );
+
+                model.old.marks.insert(old_line, old_marks);
+                model.new.marks.insert(new_line, new_marks);
+            }
+        }
+
+        model
+    }
+
+    fn rows(&self, layout: DiffLayout, context: usize, unfolded: &HashSet<usize>) -> Vec<Row> {
+        let end = Hunk {
+            old_lines: self.old.lines.len()..self.old.lines.len(),
+            new_lines: self.new.lines.len()..self.new.lines.len(),
+            old_text: String::new(),
+            new_text: String::new(),
+        };
+
+        let mut rows = Vec::new();
+        let (mut old, mut new) = (0, 0);
+        let unchanged = |rows: &mut Vec<Row>, old: Range<usize>, new: usize| {
+            rows.extend(old.enumerate().map(|(index, line)| Row::Line {
+                old: Some(line),
+                new: Some(new + index),
+                changed: false,
+            }));
+        };
+
+        for (index, hunk) in self.hunks.iter().chain([&end]).enumerate() {
+            let count = hunk.old_lines.start - old;
+            // No context to keep before the first change or after the last.
+            let before = if index == 0 { 0 } else { context };
+            let after = if index == self.hunks.len() {
+                0
+            } else {
+                context
+            };
+
+            if count > before + after + 1 && !unfolded.contains(&old) {
+                let folded = count - before - after;
+
+                unchanged(&mut rows, old..old + before, new);
+                rows.push(Row::Fold {
+                    start: old,
+                    count: folded,
+                });
+                unchanged(
+                    &mut rows,
+                    old + before + folded..hunk.old_lines.start,
+                    new + before + folded,
+                );
+            } else {
+                unchanged(&mut rows, old..hunk.old_lines.start, new);
+            }
+
+            match layout {
+                DiffLayout::SideBySide => {
+                    let count = hunk.old_lines.len().max(hunk.new_lines.len());
+
+                    rows.extend((0..count).map(|offset| {
+                        Row::Line {
+                            old: Some(hunk.old_lines.start + offset)
+                                .filter(|line| hunk.old_lines.contains(line)),
+                            new: Some(hunk.new_lines.start + offset)
+                                .filter(|line| hunk.new_lines.contains(line)),
+                            changed: true,
+                        }
+                    }));
+                }
+                DiffLayout::Inline => {
+                    rows.extend(hunk.old_lines.clone().map(|line| Row::Line {
+                        old: Some(line),
+                        new: None,
+                        changed: true,
+                    }));
+                    rows.extend(hunk.new_lines.clone().map(|line| Row::Line {
+                        old: None,
+                        new: Some(line),
+                        changed: true,
+                    }));
+                }
+            }
+
+            old = hunk.old_lines.end;
+            new = hunk.new_lines.end;
+        }
+
+        rows
+    }
+}
+
+impl Widget for DiffView<'_> {
+    fn ui(self, ui: &mut Ui) -> Response {
+        let theme = Theme::dark();
+        let id = self.id;
+
+        let mut hasher = DefaultHasher::new();
+        (self.old, self.new, self.highlighter.is_some()).hash(&mut hasher);
+        let key = hasher.finish();
+
+        let model = ui
+            .data(|data| data.get_temp::<Arc<Model>>(id.with("model")))
+            .filter(|model| model.key == key)
+            .unwrap_or_else(|| {
+                let model = Arc::new(Model::new(key, self.old, self.new, self.highlighter));
+
+                ui.data_mut(|data| data.insert_temp(id.with("model"), model.clone()));
+                model
+            });
+
+        let mut unfolded: HashSet<usize> = ui
+            .data(|data| data.get_temp(id.with("unfolded")))
+            .unwrap_or_default();
+        let rows = model.rows(self.layout, self.context, &unfolded);
+
+        let font = TextStyle::Monospace.resolve(ui.style());
+        let row_height = ui.fonts(|fonts| fonts.row_height(&font)) + 2.0;
+        let texts = [self.old, self.new];
+        let painter = RowPainter {
+            model: &model,
+            texts,
+            font,
+            row_height,
+            color: ui.visuals().text_color(),
+            theme: &theme,
+        };
+
+        let mut unfold = None;
+        let response = ui
+            .scope(|ui| {
+                ui.spacing_mut().item_spacing = Vec2::ZERO;
+
+                if model.hunks.is_empty() {
+                    ui.weak("The two versions are identical.");
+                }
+
+                match self.layout {
+                    DiffLayout::SideBySide => {
+                        side_by_side(ui, id, &rows, &painter, &mut unfold);
+                    }
+                    DiffLayout::Inline => {
+                        ScrollArea::both()
+                            .id_source(id.with("inline"))
+                            .auto_shrink(false)
+                            .show_rows(ui, row_height, rows.len(), |ui, visible| {
+                                for row in &rows[visible] {
+                                    painter.row(ui, *row, None, &mut unfold);
+                                }
+                            });
+                    }
+                }
+            })
+            .response;
+
+        if let Some(start) = unfold {
+            unfolded.insert(start);
+            ui.data_mut(|data| data.insert_temp(id.with("unfolded"), unfolded));
+        }
+
+        response
+    }
+}
+
+/// Two columns scrolling together: whichever one was scrolled moves the
+/// other.
+fn side_by_side(
+    ui: &mut Ui,
+    id: Id,
+    rows: &[Row],
+    painter: &RowPainter,
+    unfold: &mut Option<usize>,
+) {
+    let offset_id = id.with("offset");
+    let mut offset: Vec2 = ui.data(|data| data.get_temp(offset_id)).unwrap_or_default();
+    let size = vec2(ui.available_width() / 2.0, ui.available_height());
+
+    ui.horizontal_top(|ui| {
+        for side in 0..2 {
+            ui.allocate_ui(size, |ui| {
+                let output = ScrollArea::both()
+                    .id_source(id.with(("side", side)))
+                    .auto_shrink(false)
+                    .scroll_offset(offset)
+                    .show_rows(ui, painter.row_height, rows.len(), |ui, visible| {
+                        for row in &rows[visible] {
+                            painter.row(ui, *row, Some(side), unfold);
+                        }
+                    });
+
+                if output.state.offset != offset {
+                    offset = output.state.offset;
+                    ui.ctx().request_repaint();
+                }
+            });
+        }
+    });
+
+    ui.data_mut(|data| data.insert_temp(offset_id, offset));
+}
+
+struct RowPainter<'a> {
+    model: &'a Model,
+    texts: [&'a str; 2],
+    font: FontId,
+    row_height: f32,
+    color: Color32,
+    theme: &'a Theme,
+}
+
+impl RowPainter<'_> {
+    /// Paints the row for one side, 0 for old and 1 for new, or for both
+    /// when inline.
+    fn row(&self, ui: &mut Ui, row: Row, side: Option<usize>, unfold: &mut Option<usize>) {
+        match row {
+            Row::Line { old, new, changed } => self.line(ui, [old, new], changed, side),
+            Row::Fold { start, count } => {
+                if self.fold(ui, count).clicked() {
+                    *unfold = Some(start);
+                }
+            }
+        }
+    }
+
+    fn fold(&self, ui: &mut Ui, count: usize) -> Response {
+        let (rect, response) =
+            ui.allocate_exact_size(vec2(ui.available_width(), self.row_height), Sense::click());
+
+        ui.painter()
+            .rect_filled(rect, 0.0, self.theme.info.main.gamma_multiply(0.1));
+        ui.painter().text(
+            rect.left_center() + vec2(NUMBER_WIDTH, 0.0),
+            Align2::LEFT_CENTER,
+            format!("⋯ {count} unchanged lines"),
+            self.font.clone(),
+            self.theme.text_color.disabled,
+        );
+
+        response.on_hover_cursor(CursorIcon::PointingHand)
+    }
+
+    fn line(&self, ui: &mut Ui, lines: [Option<usize>; 2], changed: bool, side: Option<usize>) {
+        let (numbers, shown) = match side {
+            Some(side) => (vec![lines[side]], lines[side].map(|_| side)),
+            // Inline rows show the new line, or the removed one.
+            None => (lines.to_vec(), Some(lines[1].map_or(0, |_| 1))),
+        };
+
+        let (background, mark) = match (shown, changed) {
+            (_, false) => (Color32::TRANSPARENT, Color32::TRANSPARENT),
+            // Across from lines the other side doesn't have.
+            (None, true) => (
+                self.theme.action.disabled_bg.gamma_multiply(0.3),
+                Color32::TRANSPARENT,
+            ),
+            (Some(0), true) => (
+                self.theme.error.main.gamma_multiply(0.15),
+                self.theme.error.main.gamma_multiply(0.4),
+            ),
+            (Some(_), true) => (
+                self.theme.success.main.gamma_multiply(0.15),
+                self.theme.success.main.gamma_multiply(0.4),
+            ),
+        };
+
+        let galley = shown.and_then(|side| {
+            let line = lines[side]?;
+            let model_side = match side {
+                0 => &self.model.old,
+                _ => &self.model.new,
+            };
+            let job = model_side.line_job(self.texts[side], line, &self.font, self.color, mark);
+
+            Some(ui.fonts(|fonts| fonts.layout_job(job)))
+        });
+
+        let gutter = NUMBER_WIDTH * numbers.len() as f32;
+        let text_width = galley.as_ref().map_or(0.0, |galley| galley.size().x);
+        let (rect, _) = ui.allocate_exact_size(
+            vec2(
+                ui.available_width().max(gutter + text_width + 8.0),
+                self.row_height,
+            ),
+            Sense::hover(),
+        );
+
+        if !ui.is_rect_visible(rect) {
+            return;
+        }
+
+        let painter = ui.painter();
+        painter.rect_filled(rect, 0.0, background);
+
+        for (index, number) in numbers.iter().enumerate() {
+            if let Some(number) = number {
+                painter.text(
+                    rect.left_center() + vec2(NUMBER_WIDTH * (index + 1) as f32 - 8.0, 0.0),
+                    Align2::RIGHT_CENTER,
+                    (number + 1).to_string(),
+                    self.font.clone(),
+                    self.theme.text_color.disabled,
+                );
+            }
+        }
+
+        if let Some(galley) = galley {
+            let top = rect.center().y - galley.size().y / 2.0;
+
+            painter.galley(egui::pos2(rect.left() + gutter, top), galley, self.color);
+        }
+    }
+}
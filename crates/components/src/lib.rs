@@ -1,2 +1,3 @@
 pub mod default_message_modal;
+pub mod diff_view;
 pub mod selectable_label;
@@ -0,0 +1,316 @@
+//! Line diffs between two versions of a file, as hunks without context
+//! that can be applied or reverted one at a time.
+
+use std::ops::Range;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HunkKind {
+    Added,
+    Modified,
+    Deleted,
+}
+
+/// A changed run of lines. Line numbers count from 0; for an insertion or
+/// a deletion, the empty range is where the lines were added or removed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hunk {
+    pub old_lines: Range<usize>,
+    pub new_lines: Range<usize>,
+    pub old_text: String,
+    pub new_text: String,
+}
+
+impl Hunk {
+    pub fn kind(&self) -> HunkKind {
+        if self.old_lines.is_empty() {
+            HunkKind::Added
+        } else if self.new_lines.is_empty() {
+            HunkKind::Deleted
+        } else {
+            HunkKind::Modified
+        }
+    }
+}
+
+/// The hunks turning `old` into `new`.
+pub fn hunks(old: &str, new: &str) -> Vec<Hunk> {
+    let old_lines: Vec<&str> = old.split_inclusive('\n').collect();
+    let new_lines: Vec<&str> = new.split_inclusive('\n').collect();
+    let mut old_kept = vec![false; old_lines.len()];
+    let mut new_kept = vec![false; new_lines.len()];
+
+    common_lines(&old_lines, &new_lines, &mut old_kept, &mut new_kept);
+
+    let mut hunks = Vec::new();
+    let (mut i, mut j) = (0, 0);
+
+    while i < old_lines.len() || j < new_lines.len() {
+        if old_kept.get(i) == Some(&true) && new_kept.get(j) == Some(&true) {
+            i += 1;
+            j += 1;
+            continue;
+        }
+
+        let (old_start, new_start) = (i, j);
+
+        while old_kept.get(i) == Some(&false) {
+            i += 1;
+        }
+
+        while new_kept.get(j) == Some(&false) {
+            j += 1;
+        }
+
+        hunks.push(Hunk {
+            old_lines: old_start..i,
+            new_lines: new_start..j,
+            old_text: old_lines[old_start..i].concat(),
+            new_text: new_lines[new_start..j].concat(),
+        });
+    }
+
+    hunks
+}
+
+/// Marks the lines both sides keep in a shortest edit script between them,
+/// with Myers' divide and conquer in linear space.
+fn common_lines(old: &[&str], new: &[&str], old_kept: &mut [bool], new_kept: &mut [bool]) {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    old_kept[..prefix].fill(true);
+    new_kept[..prefix].fill(true);
+    old_kept[old.len() - suffix..].fill(true);
+    new_kept[new.len() - suffix..].fill(true);
+
+    let old = &old[prefix..old.len() - suffix];
+    let new = &new[prefix..new.len() - suffix];
+    let old_kept = &mut old_kept[prefix..prefix + old.len()];
+    let new_kept = &mut new_kept[prefix..prefix + new.len()];
+
+    if old.is_empty() || new.is_empty() {
+        return;
+    }
+
+    let (start, end) = middle_snake(old, new);
+
+    old_kept[start.0..end.0].fill(true);
+    new_kept[start.1..end.1].fill(true);
+
+    let (old_before, old_after) = old_kept.split_at_mut(start.0);
+    let (new_before, new_after) = new_kept.split_at_mut(start.1);
+
+    common_lines(&old[..start.0], &new[..start.1], old_before, new_before);
+    common_lines(
+        &old[end.0..],
+        &new[end.1..],
+        &mut old_after[end.0 - start.0..],
+        &mut new_after[end.1 - start.1..],
+    );
+}
+
+/// The run of common lines in the middle of a shortest edit script, from
+/// its start to its end `(old, new)` positions: where the searches from
+/// both ends meet.
+fn middle_snake(old: &[&str], new: &[&str]) -> ((usize, usize), (usize, usize)) {
+    let (n, m) = (old.len() as isize, new.len() as isize);
+    let delta = n - m;
+    let odd = delta % 2 != 0;
+    let max = (n + m + 1) / 2;
+    let index = |diagonal: isize| (diagonal + max + 1) as usize;
+
+    // The furthest `x` reached on each diagonal `x - y`, from the start,
+    // and counting backwards from the end.
+    let mut forward = vec![0isize; 2 * max as usize + 3];
+    let mut backward = vec![0isize; 2 * max as usize + 3];
+
+    for d in 0..=max {
+        for k in (-d..=d).step_by(2) {
+            let down = k == -d || (k != d && forward[index(k - 1)] < forward[index(k + 1)]);
+            let mut x = match down {
+                true => forward[index(k + 1)],
+                false => forward[index(k - 1)] + 1,
+            };
+            let mut y = x - k;
+            let start = (x as usize, y as usize);
+
+            while x < n && y < m && old[x as usize] == new[y as usize] {
+                x += 1;
+                y += 1;
+            }
+
+            forward[index(k)] = x;
+
+            let c = delta - k;
+
+            if odd && (1 - d..d).contains(&c) && x + backward[index(c)] >= n {
+                return (start, (x as usize, y as usize));
+            }
+        }
+
+        for c in (-d..=d).step_by(2) {
+            let down = c == -d || (c != d && backward[index(c - 1)] < backward[index(c + 1)]);
+            let mut x = match down {
+                true => backward[index(c + 1)],
+                false => backward[index(c - 1)] + 1,
+            };
+            let mut y = x - c;
+            let end = ((n - x) as usize, (m - y) as usize);
+
+            while x < n && y < m && old[(n - x - 1) as usize] == new[(m - y - 1) as usize] {
+                x += 1;
+                y += 1;
+            }
+
+            backward[index(c)] = x;
+
+            let k = delta - c;
+
+            if !odd && (-d..=d).contains(&k) && forward[index(k)] + x >= n {
+                return (((n - x) as usize, (m - y) as usize), end);
+            }
+        }
+    }
+
+    unreachable!("the searches meet halfway through the shortest edit script")
+}
+
+/// `old` with the hunk applied, e.g. to stage it.
+pub fn apply(old: &str, hunk: &Hunk) -> String {
+    replace_lines(old, hunk.old_lines.clone(), &hunk.new_text)
+}
+
+/// `new` with the hunk undone, e.g. to revert it or unstage it.
+pub fn revert(new: &str, hunk: &Hunk) -> String {
+    replace_lines(new, hunk.new_lines.clone(), &hunk.old_text)
+}
+
+fn replace_lines(text: &str, lines: Range<usize>, replacement: &str) -> String {
+    let offsets = line_offsets(text);
+    let offset = |line: usize| offsets.get(line).copied().unwrap_or(text.len());
+
+    let mut result = String::with_capacity(text.len() + replacement.len());
+
+    result.push_str(&text[..offset(lines.start)]);
+    result.push_str(replacement);
+    result.push_str(&text[offset(lines.end)..]);
+    result
+}
+
+fn line_offsets(text: &str) -> Vec<usize> {
+    std::iter::once(0)
+        .chain(text.match_indices('\n').map(|(index, _)| index + 1))
+        .collect()
+}
+
+/// Where line `line` of the old text is in the new one, given the hunks
+/// between them. Lines inside a hunk go to its start.
+pub fn map_line(hunks: &[Hunk], line: usize) -> usize {
+    let mut shift = 0isize;
+
+    for hunk in hunks {
+        if line < hunk.old_lines.start {
+            break;
+        }
+
+        if line < hunk.old_lines.end {
+            return hunk.new_lines.start;
+        }
+
+        shift = hunk.new_lines.end as isize - hunk.old_lines.end as isize;
+    }
+
+    (line as isize + shift).max(0) as usize
+}
+
+/// Beyond this many character pairs, changed lines are compared by their
+/// common prefix and suffix only.
+const MAX_CHAR_PAIRS: usize = 1 << 20;
+
+/// The byte ranges of `old` and `new` that differ character by character,
+/// e.g. to mark what changed inside a modified line.
+pub fn changed_chars(old: &str, new: &str) -> (Vec<Range<usize>>, Vec<Range<usize>>) {
+    let old_chars: Vec<(usize, char)> = old.char_indices().collect();
+    let new_chars: Vec<(usize, char)> = new.char_indices().collect();
+
+    let prefix = old_chars
+        .iter()
+        .zip(&new_chars)
+        .take_while(|((_, a), (_, b))| a == b)
+        .count();
+    let suffix = old_chars[prefix..]
+        .iter()
+        .rev()
+        .zip(new_chars[prefix..].iter().rev())
+        .take_while(|((_, a), (_, b))| a == b)
+        .count();
+
+    let old_middle = &old_chars[prefix..old_chars.len() - suffix];
+    let new_middle = &new_chars[prefix..new_chars.len() - suffix];
+
+    let (old_kept, new_kept) = match old_middle.len() * new_middle.len() {
+        1..=MAX_CHAR_PAIRS => common_chars(old_middle, new_middle),
+        _ => (vec![false; old_middle.len()], vec![false; new_middle.len()]),
+    };
+
+    (
+        changed_ranges(old_middle, &old_kept),
+        changed_ranges(new_middle, &new_kept),
+    )
+}
+
+/// Which characters of each side are part of their longest common
+/// subsequence.
+fn common_chars(old: &[(usize, char)], new: &[(usize, char)]) -> (Vec<bool>, Vec<bool>) {
+    let width = new.len() + 1;
+    let mut lengths = vec![0u32; (old.len() + 1) * width];
+
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lengths[i * width + j] = match old[i].1 == new[j].1 {
+                true => lengths[(i + 1) * width + j + 1] + 1,
+                false => lengths[(i + 1) * width + j].max(lengths[i * width + j + 1]),
+            };
+        }
+    }
+
+    let mut old_kept = vec![false; old.len()];
+    let mut new_kept = vec![false; new.len()];
+    let (mut i, mut j) = (0, 0);
+
+    while i < old.len() && j < new.len() {
+        if old[i].1 == new[j].1 {
+            old_kept[i] = true;
+            new_kept[j] = true;
+            i += 1;
+            j += 1;
+        } else if lengths[(i + 1) * width + j] >= lengths[i * width + j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+
+    (old_kept, new_kept)
+}
+
+/// Runs of characters that aren't kept, merged into byte ranges.
+fn changed_ranges(chars: &[(usize, char)], kept: &[bool]) -> Vec<Range<usize>> {
+    let mut ranges: Vec<Range<usize>> = Vec::new();
+
+    for (&(start, char), _) in chars.iter().zip(kept).filter(|(_, kept)| !**kept) {
+        let end = start + char.len_utf8();
+
+        match ranges.last_mut() {
+            Some(range) if range.end == start => range.end = end,
+            _ => ranges.push(start..end),
+        }
+    }
+
+    ranges
+}
@@ -0,0 +1,105 @@
+use diff::{self, Hunk, HunkKind};
+
+const OLD: &str = "one\ntwo\nthree\nfour\nfive\n";
+
+#[test]
+fn finds_added_modified_and_deleted_lines() {
+    let new = "zero\none\nTWO\nthree\nfive\n";
+    let hunks = diff::hunks(OLD, new);
+
+    assert_eq!(
+        hunks,
+        vec![
+            Hunk {
+                old_lines: 0..0,
+                new_lines: 0..1,
+                old_text: "".into(),
+                new_text: "zero\n".into(),
+            },
+            Hunk {
+                old_lines: 1..2,
+                new_lines: 2..3,
+                old_text: "two\n".into(),
+                new_text: "TWO\n".into(),
+            },
+            Hunk {
+                old_lines: 3..4,
+                new_lines: 4..4,
+                old_text: "four\n".into(),
+                new_text: "".into(),
+            },
+        ]
+    );
+
+    let kinds: Vec<HunkKind> = hunks.iter().map(Hunk::kind).collect();
+    assert_eq!(
+        kinds,
+        [HunkKind::Added, HunkKind::Modified, HunkKind::Deleted]
+    );
+}
+
+#[test]
+fn applies_and_reverts_single_hunks() {
+    let new = "zero\none\nTWO\nthree\nfive\n";
+    let hunks = diff::hunks(OLD, new);
+
+    assert_eq!(diff::apply(OLD, &hunks[1]), "one\nTWO\nthree\nfour\nfive\n");
+    assert_eq!(
+        diff::revert(new, &hunks[2]),
+        "zero\none\nTWO\nthree\nfour\nfive\n"
+    );
+    assert_eq!(diff::revert(new, &hunks[0]), "one\nTWO\nthree\nfive\n");
+}
+
+#[test]
+fn keeps_a_missing_final_newline() {
+    let new = "one\ntwo\nthree\nfour\nFIVE";
+    let hunks = diff::hunks(OLD, new);
+
+    assert_eq!(hunks.len(), 1);
+    assert_eq!(hunks[0].old_text, "five\n");
+    assert_eq!(hunks[0].new_text, "FIVE");
+    assert_eq!(diff::apply(OLD, &hunks[0]), new);
+    assert_eq!(diff::revert(new, &hunks[0]), OLD);
+}
+
+#[test]
+fn maps_lines_across_hunks() {
+    let new = "zero\none\nTWO\nthree\nfive\n";
+    let hunks = diff::hunks(OLD, new);
+
+    assert_eq!(diff::map_line(&hunks, 0), 1);
+    assert_eq!(diff::map_line(&hunks, 1), 2);
+    assert_eq!(diff::map_line(&hunks, 2), 3);
+    assert_eq!(diff::map_line(&hunks, 4), 4);
+}
+
+type Pairs = Vec<(usize, usize)>;
+
+/// The changed byte ranges of each side, as `(start, end)` pairs.
+fn changed_chars(old: &str, new: &str) -> (Pairs, Pairs) {
+    let pairs = |ranges: Vec<std::ops::Range<usize>>| {
+        ranges
+            .into_iter()
+            .map(|range| (range.start, range.end))
+            .collect()
+    };
+    let (old, new) = diff::changed_chars(old, new);
+
+    (pairs(old), pairs(new))
+}
+
+#[test]
+fn finds_the_changed_characters_of_a_line() {
+    assert_eq!(
+        changed_chars("let value = 1;", "let values = 2;"),
+        (vec![(12, 13)], vec![(9, 10), (13, 14)])
+    );
+    assert_eq!(changed_chars("same", "same"), (vec![], vec![]));
+    assert_eq!(changed_chars("", "new"), (vec![], vec![(0, 3)]));
+    // Ranges are in bytes, whole characters.
+    assert_eq!(
+        changed_chars("café au lait", "cafés au lait"),
+        (vec![], vec![(5, 6)])
+    );
+}
@@ -0,0 +1,94 @@
+//! Which commit last changed each line of a file, unsaved edits included.
+
+use git2::Oid;
+use std::{collections::HashMap, path::Path};
+
+use crate::{history::Revision, index, Error};
+
+#[derive(Debug, Default)]
+pub struct Blame {
+    pub revisions: Vec<Revision>,
+    /// The committed file, with `\n` line endings like the text it's
+    /// compared to, and the revision of each of its lines.
+    head: String,
+    committed: Vec<usize>,
+    /// For each line of the text, the index of the revision that last
+    /// changed it. `None` for lines that aren't committed yet.
+    pub lines: Vec<Option<usize>>,
+}
+
+impl Blame {
+    /// Blames the file as of its last commit; see [`Blame::update`] for the
+    /// content in the editor.
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        let mut blame = Blame {
+            head: index::head_text(path)?
+                .unwrap_or_default()
+                .replace("\r\n", "\n"),
+            ..Default::default()
+        };
+
+        if blame.head.is_empty() {
+            return Ok(blame);
+        }
+
+        let (repository, relative) = index::open(path)?;
+        let file_blame = repository.blame_file(&relative, None)?;
+        let mut revisions: HashMap<Oid, usize> = HashMap::new();
+
+        for hunk in file_blame.iter() {
+            let id = hunk.final_commit_id();
+            let revision = match revisions.get(&id) {
+                Some(revision) => *revision,
+                None => {
+                    let commit = repository.find_commit(id)?;
+
+                    blame.revisions.push(Revision::from_commit(&commit));
+                    revisions.insert(id, blame.revisions.len() - 1);
+                    blame.revisions.len() - 1
+                }
+            };
+
+            blame
+                .committed
+                .extend(std::iter::repeat_n(revision, hunk.lines_in_hunk()));
+        }
+
+        blame.lines = blame.committed.iter().copied().map(Some).collect();
+
+        Ok(blame)
+    }
+
+    /// Blames `text` instead: lines that differ from the last commit are
+    /// uncommitted, the others keep the blame of their committed line.
+    /// `text` has `\n` line endings.
+    pub fn update(&mut self, text: &str) {
+        let hunks = diff::hunks(&self.head, text);
+        let mut hunks = hunks.iter().peekable();
+        let mut shift = 0isize;
+
+        self.lines.clear();
+
+        for line in 0..text.lines().count() {
+            while let Some(hunk) = hunks.next_if(|hunk| hunk.new_lines.end <= line) {
+                shift = hunk.old_lines.end as isize - hunk.new_lines.end as isize;
+            }
+
+            let changed = hunks
+                .peek()
+                .is_some_and(|hunk| hunk.new_lines.contains(&line));
+            let old_line = (line as isize + shift) as usize;
+
+            self.lines.push(match changed {
+                true => None,
+                false => self.committed.get(old_line).copied(),
+            });
+        }
+    }
+
+    pub fn line(&self, line: usize) -> Option<&Revision> {
+        let revision = (*self.lines.get(line)?)?;
+
+        self.revisions.get(revision)
+    }
+}
@@ -0,0 +1,75 @@
+//! Local branches: listing, creating and switching between them.
+
+use git2::{build::CheckoutBuilder, BranchType, Repository};
+use std::path::Path;
+
+use crate::Error;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Branch {
+    pub name: String,
+    pub is_head: bool,
+}
+
+/// The local branches, sorted by name.
+pub fn branches(path: &Path) -> Result<Vec<Branch>, Error> {
+    let repository = Repository::discover(path)?;
+    let mut branches = Vec::new();
+
+    for branch in repository.branches(Some(BranchType::Local))? {
+        let (branch, _) = branch?;
+
+        if let Some(name) = branch.name()? {
+            branches.push(Branch {
+                name: name.into(),
+                is_head: branch.is_head(),
+            });
+        }
+    }
+
+    branches.sort_by(|a, b| a.name.cmp(&b.name));
+
+    Ok(branches)
+}
+
+/// The checked out branch, or `None` when HEAD is detached or there are no
+/// commits yet.
+pub fn current(path: &Path) -> Result<Option<String>, Error> {
+    let repository = Repository::discover(path)?;
+    let head = match repository.head() {
+        Ok(head) => head,
+        Err(_) => return Ok(None),
+    };
+
+    Ok(head
+        .is_branch()
+        .then(|| head.shorthand().map(String::from))
+        .flatten())
+}
+
+/// Creates a branch at HEAD and switches to it.
+pub fn create(path: &Path, name: &str) -> Result<(), Error> {
+    {
+        let repository = Repository::discover(path)?;
+        let head = repository.head()?.peel_to_commit()?;
+
+        repository.branch(name, &head, false)?;
+    }
+
+    checkout(path, name)
+}
+
+/// Switches to the branch. Fails rather than overwrite local changes that
+/// the branch would touch.
+pub fn checkout(path: &Path, name: &str) -> Result<(), Error> {
+    let repository = Repository::discover(path)?;
+    let branch = repository.find_branch(name, BranchType::Local)?;
+    let reference = branch
+        .get()
+        .name()
+        .ok_or_else(|| Error::from_str("The branch name isn't valid UTF-8"))?;
+    let tree = branch.get().peel_to_tree()?;
+
+    repository.checkout_tree(tree.as_object(), Some(CheckoutBuilder::new().safe()))?;
+    repository.set_head(reference)
+}
@@ -0,0 +1,184 @@
+//! Staged and unstaged changes, moving files between them, and committing.
+
+use git2::{build::CheckoutBuilder, Oid, Repository, Status, StatusOptions};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{Error, FileStatus};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Change {
+    /// Relative to the working directory.
+    pub path: PathBuf,
+    pub status: FileStatus,
+}
+
+#[derive(Debug, Default)]
+pub struct Changes {
+    pub staged: Vec<Change>,
+    pub unstaged: Vec<Change>,
+}
+
+fn index_status(status: Status) -> Option<FileStatus> {
+    if status.is_index_new() {
+        Some(FileStatus::Added)
+    } else if status.is_index_renamed() {
+        Some(FileStatus::Renamed)
+    } else if status.is_index_deleted() {
+        Some(FileStatus::Deleted)
+    } else if status.is_index_modified() || status.is_index_typechange() {
+        Some(FileStatus::Modified)
+    } else {
+        None
+    }
+}
+
+fn workdir_status(status: Status) -> Option<FileStatus> {
+    if status.is_conflicted() {
+        Some(FileStatus::Conflicted)
+    } else if status.is_wt_new() {
+        Some(FileStatus::Untracked)
+    } else if status.is_wt_deleted() {
+        Some(FileStatus::Deleted)
+    } else if status.is_wt_modified() || status.is_wt_typechange() || status.is_wt_renamed() {
+        Some(FileStatus::Modified)
+    } else {
+        None
+    }
+}
+
+/// The changes of the repository containing `path`, sorted by path. A file
+/// can be both staged and changed again since.
+pub fn changes(path: &Path) -> Result<Changes, Error> {
+    let repository = Repository::discover(path)?;
+    let mut options = StatusOptions::new();
+    options
+        .include_untracked(true)
+        .recurse_untracked_dirs(true)
+        .renames_head_to_index(true);
+
+    let mut changes = Changes::default();
+
+    for entry in repository.statuses(Some(&mut options))?.iter() {
+        let status = entry.status();
+        let renamed = entry
+            .head_to_index()
+            .filter(|_| status.is_index_renamed())
+            .and_then(|delta| delta.new_file().path().map(Path::to_path_buf));
+        let Some(path) = renamed.or_else(|| entry.path().map(PathBuf::from)) else {
+            continue;
+        };
+
+        // Conflicts are resolved in the working directory, then staged.
+        if let Some(status) = index_status(status).filter(|_| !status.is_conflicted()) {
+            changes.staged.push(Change {
+                path: path.clone(),
+                status,
+            });
+        }
+
+        if let Some(status) = workdir_status(status) {
+            changes.unstaged.push(Change { path, status });
+        }
+    }
+
+    changes.staged.sort_by(|a, b| a.path.cmp(&b.path));
+    changes.unstaged.sort_by(|a, b| a.path.cmp(&b.path));
+
+    Ok(changes)
+}
+
+/// Stages the file as it is in the working directory, deleted or not.
+pub fn stage(repository_path: &Path, path: &Path) -> Result<(), Error> {
+    let repository = Repository::discover(repository_path)?;
+    let workdir = workdir(&repository)?;
+    let mut index = repository.index()?;
+
+    match workdir.join(path).exists() {
+        true => index.add_path(path)?,
+        false => index.remove_path(path)?,
+    }
+
+    index.write()
+}
+
+/// Puts the committed version back into the index, leaving the working
+/// directory alone.
+pub fn unstage(repository_path: &Path, path: &Path) -> Result<(), Error> {
+    let repository = Repository::discover(repository_path)?;
+    let head = repository.head().and_then(|head| head.peel_to_commit());
+
+    match head {
+        Ok(commit) => repository.reset_default(Some(commit.as_object()), [path]),
+        // Nothing is committed yet, so unstaging removes the file.
+        Err(_) => {
+            let mut index = repository.index()?;
+            index.remove_path(path)?;
+            index.write()
+        }
+    }
+}
+
+/// Throws away the unstaged changes to the file: restores the staged
+/// version, or deletes the file when it's untracked.
+pub fn discard(repository_path: &Path, path: &Path) -> Result<(), Error> {
+    let repository = Repository::discover(repository_path)?;
+    let status = repository.status_file(path)?;
+
+    if status.is_wt_new() {
+        let path = workdir(&repository)?.join(path);
+
+        return fs::remove_file(&path).map_err(|error| Error::from_str(&error.to_string()));
+    }
+
+    repository.checkout_index(
+        None,
+        Some(
+            CheckoutBuilder::new()
+                .path(path)
+                .force()
+                .update_index(false),
+        ),
+    )
+}
+
+/// Commits the index with the author from the git configuration.
+pub fn commit(repository_path: &Path, message: &str) -> Result<Oid, Error> {
+    let repository = Repository::discover(repository_path)?;
+    let signature = repository.signature()?;
+    let mut index = repository.index()?;
+
+    if index.has_conflicts() {
+        return Err(Error::from_str("Resolve the conflicts before committing"));
+    }
+
+    let tree = repository.find_tree(index.write_tree()?)?;
+    let parent = repository
+        .head()
+        .ok()
+        .and_then(|head| head.peel_to_commit().ok());
+    let parents: Vec<_> = parent.iter().collect();
+
+    if let Some(parent) = &parent {
+        if parent.tree_id() == tree.id() {
+            return Err(Error::from_str("There are no staged changes to commit"));
+        }
+    }
+
+    repository.commit(
+        Some("HEAD"),
+        &signature,
+        &signature,
+        message,
+        &tree,
+        &parents,
+    )
+}
+
+pub(crate) fn workdir(repository: &Repository) -> Result<&Path, Error> {
+    repository
+        .workdir()
+        .ok_or_else(|| Error::from_str("The repository has no working directory"))
+}
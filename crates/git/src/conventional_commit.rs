@@ -0,0 +1,138 @@
+//! Commit messages following the Conventional Commits format, which the
+//! commitizen setup of this repository expects:
+//! `type(scope)!: subject`, a body, then footers.
+
+/// The types commitizen's `cz_conventional_commits` offers.
+pub const TYPES: [(&str, &str); 11] = [
+    ("feat", "A new feature"),
+    ("fix", "A bug fix"),
+    ("docs", "Documentation only changes"),
+    (
+        "style",
+        "Changes that do not affect the meaning of the code",
+    ),
+    (
+        "refactor",
+        "A code change that neither fixes a bug nor adds a feature",
+    ),
+    ("perf", "A code change that improves performance"),
+    ("test", "Adding missing tests or correcting existing tests"),
+    (
+        "build",
+        "Changes that affect the build system or external dependencies",
+    ),
+    ("ci", "Changes to the CI configuration files and scripts"),
+    (
+        "chore",
+        "Other changes that don't modify source or test files",
+    ),
+    ("revert", "Reverts a previous commit"),
+];
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConventionalCommit {
+    pub kind: String,
+    pub scope: String,
+    pub subject: String,
+    pub body: String,
+    /// Describes what breaks, empty when nothing does.
+    pub breaking_change: String,
+}
+
+impl ConventionalCommit {
+    pub fn message(&self) -> String {
+        let scope = match self.scope.trim() {
+            "" => String::new(),
+            scope => format!("({scope})"),
+        };
+        let breaking = self.breaking_change.trim();
+        let bang = if breaking.is_empty() { "" } else { "!" };
+
+        let mut message = format!("{}{scope}{bang}: {}", self.kind, self.subject.trim());
+
+        if !self.body.trim().is_empty() {
+            message.push_str("\n\n");
+            message.push_str(self.body.trim());
+        }
+
+        if !breaking.is_empty() {
+            message.push_str("\n\nBREAKING CHANGE: ");
+            message.push_str(breaking);
+        }
+
+        message
+    }
+
+    /// Reads a message back, `None` when its first line isn't conventional.
+    pub fn parse(message: &str) -> Option<Self> {
+        let (header, rest) = message.split_once('\n').unwrap_or((message, ""));
+        let (prefix, subject) = header.split_once(": ")?;
+        let (prefix, bang) = match prefix.strip_suffix('!') {
+            Some(prefix) => (prefix, true),
+            None => (prefix, false),
+        };
+
+        let (kind, scope) = match prefix.split_once('(') {
+            Some((kind, scope)) => (kind, scope.strip_suffix(')')?),
+            None => (prefix, ""),
+        };
+
+        let valid_word = |word: &str| {
+            !word.is_empty()
+                && word
+                    .chars()
+                    .all(|char| char.is_ascii_alphanumeric() || "-_/.".contains(char))
+        };
+
+        if !valid_word(kind) || !(scope.is_empty() || valid_word(scope)) {
+            return None;
+        }
+
+        let (body, breaking_change) = match rest.split_once("BREAKING CHANGE: ") {
+            Some((body, breaking)) => (body, breaking.trim().to_string()),
+            None => (rest, String::new()),
+        };
+
+        Some(ConventionalCommit {
+            kind: kind.into(),
+            scope: scope.into(),
+            subject: subject.trim().into(),
+            body: body.trim().into(),
+            breaking_change: match (bang, breaking_change.is_empty()) {
+                (true, true) => subject.trim().into(),
+                _ => breaking_change,
+            },
+        })
+    }
+}
+
+/// What's wrong with the message, if anything.
+pub fn lint(message: &str) -> Option<String> {
+    let header = message.lines().next().unwrap_or_default();
+
+    if header.trim().is_empty() {
+        return Some("The message is empty.".into());
+    }
+
+    let Some(commit) = ConventionalCommit::parse(message) else {
+        return Some("The first line should read `type(scope): subject`.".into());
+    };
+
+    if !TYPES.iter().any(|(kind, _)| *kind == commit.kind) {
+        return Some(format!("`{}` isn't one of the commit types.", commit.kind));
+    }
+
+    if commit.subject.is_empty() {
+        return Some("The subject is empty.".into());
+    }
+
+    if header.chars().count() > 100 {
+        return Some("The first line is longer than 100 characters.".into());
+    }
+
+    message
+        .lines()
+        .nth(1)
+        .filter(|line| !line.is_empty())
+        .map(|_| "Leave the second line empty.".into())
+}
@@ -0,0 +1,119 @@
+//! The commits that touched a file, and the file as of any of them.
+
+use git2::{Commit, Oid, Sort};
+use std::path::Path;
+
+use crate::{index, Error};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Revision {
+    pub id: Oid,
+    pub author: String,
+    pub email: String,
+    /// Seconds since the Unix epoch.
+    pub time: i64,
+    /// The first line of the message.
+    pub summary: String,
+    pub message: String,
+}
+
+impl Revision {
+    pub(crate) fn from_commit(commit: &Commit) -> Self {
+        let author = commit.author();
+
+        Revision {
+            id: commit.id(),
+            author: author.name().unwrap_or_default().into(),
+            email: author.email().unwrap_or_default().into(),
+            time: commit.time().seconds(),
+            summary: commit.summary().unwrap_or_default().into(),
+            message: commit.message().unwrap_or_default().trim_end().into(),
+        }
+    }
+
+    /// The first 7 characters of the id, as `git log --oneline` shows it.
+    pub fn short_id(&self) -> String {
+        self.id.to_string()[..7].into()
+    }
+}
+
+/// The commits reachable from HEAD that changed the file, newest first.
+/// Renames aren't followed.
+pub fn file_history(path: &Path) -> Result<Vec<Revision>, Error> {
+    let (repository, relative) = index::open(path)?;
+    let mut walk = repository.revwalk()?;
+
+    if walk.push_head().is_err() {
+        // No commits yet.
+        return Ok(Vec::new());
+    }
+
+    walk.set_sorting(Sort::TIME)?;
+
+    let blob = |commit: &Commit| {
+        commit
+            .tree()
+            .ok()
+            .and_then(|tree| tree.get_path(&relative).ok())
+            .map(|entry| entry.id())
+    };
+
+    let mut revisions = Vec::new();
+
+    for id in walk {
+        let commit = repository.find_commit(id?)?;
+        let current = blob(&commit);
+
+        // Merges only count when they differ from every parent.
+        let changed = match commit.parent_count() {
+            0 => current.is_some(),
+            _ => commit.parents().all(|parent| blob(&parent) != current),
+        };
+
+        if changed {
+            revisions.push(Revision::from_commit(&commit));
+        }
+    }
+
+    Ok(revisions)
+}
+
+/// The file as of the commit, `None` when it didn't exist then or isn't
+/// text.
+pub fn text_at(path: &Path, id: Oid) -> Result<Option<String>, Error> {
+    let (repository, relative) = index::open(path)?;
+    let tree = repository.find_commit(id)?.tree()?;
+
+    let Ok(entry) = tree.get_path(&relative) else {
+        return Ok(None);
+    };
+
+    let blob = repository.find_blob(entry.id())?;
+
+    Ok(String::from_utf8(blob.content().to_vec()).ok())
+}
+
+/// How long ago `time` was, roughly: "3 days ago".
+pub fn relative_time(time: i64, now: i64) -> String {
+    const UNITS: [(&str, i64); 6] = [
+        ("year", 365 * 24 * 60 * 60),
+        ("month", 30 * 24 * 60 * 60),
+        ("week", 7 * 24 * 60 * 60),
+        ("day", 24 * 60 * 60),
+        ("hour", 60 * 60),
+        ("minute", 60),
+    ];
+
+    let elapsed = (now - time).max(0);
+
+    UNITS
+        .iter()
+        .find(|(_, seconds)| elapsed >= *seconds)
+        .map_or_else(
+            || "just now".into(),
+            |(unit, seconds)| match elapsed / seconds {
+                1 => format!("1 {unit} ago"),
+                count => format!("{count} {unit}s ago"),
+            },
+        )
+}
@@ -0,0 +1,95 @@
+//! A file's committed and staged versions, and staging new content for it.
+
+use git2::{IndexEntry, IndexTime, Oid, Repository};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::Error;
+
+/// The repository containing `path`, and the path relative to its working
+/// directory.
+pub(crate) fn open(path: &Path) -> Result<(Repository, PathBuf), Error> {
+    let path = fs::canonicalize(path).map_err(|error| Error::from_str(&error.to_string()))?;
+    let repository = Repository::discover(path.parent().unwrap_or(&path))?;
+    let root = repository
+        .workdir()
+        .and_then(|root| fs::canonicalize(root).ok())
+        .ok_or_else(|| Error::from_str("The repository has no working directory"))?;
+    let relative = path
+        .strip_prefix(&root)
+        .map_err(|_| Error::from_str("The file is outside of the repository"))?
+        .to_path_buf();
+
+    Ok((repository, relative))
+}
+
+/// The commit HEAD points to in the repository containing `path`, `None`
+/// before the first commit.
+pub fn head_id(path: &Path) -> Result<Option<Oid>, Error> {
+    let (repository, _) = open(path)?;
+    let head = repository.head().ok().and_then(|head| head.target());
+
+    Ok(head)
+}
+
+/// The file as of the last commit, `None` when it isn't committed or isn't
+/// text.
+pub fn head_text(path: &Path) -> Result<Option<String>, Error> {
+    let (repository, relative) = open(path)?;
+
+    let Ok(head) = repository.head().and_then(|head| head.peel_to_tree()) else {
+        // No commits yet.
+        return Ok(None);
+    };
+
+    let Ok(entry) = head.get_path(&relative) else {
+        return Ok(None);
+    };
+
+    let blob = repository.find_blob(entry.id())?;
+
+    Ok(String::from_utf8(blob.content().to_vec()).ok())
+}
+
+/// The file as staged, `None` when it isn't in the index or isn't text.
+pub fn index_text(path: &Path) -> Result<Option<String>, Error> {
+    let (repository, relative) = open(path)?;
+    let index = repository.index()?;
+
+    let Some(entry) = index.get_path(&relative, 0) else {
+        return Ok(None);
+    };
+
+    let blob = repository.find_blob(entry.id)?;
+
+    Ok(String::from_utf8(blob.content().to_vec()).ok())
+}
+
+/// Stages `text` as the file's content, whatever is on disk.
+pub fn stage_text(path: &Path, text: &str) -> Result<(), Error> {
+    let (repository, relative) = open(path)?;
+    let mut index = repository.index()?;
+
+    let entry = match index.get_path(&relative, 0) {
+        Some(entry) => entry,
+        None => IndexEntry {
+            ctime: IndexTime::new(0, 0),
+            mtime: IndexTime::new(0, 0),
+            dev: 0,
+            ino: 0,
+            mode: 0o100644,
+            uid: 0,
+            gid: 0,
+            file_size: 0,
+            id: git2::Oid::zero(),
+            flags: 0,
+            flags_extended: 0,
+            path: relative.to_string_lossy().replace('\\', "/").into_bytes(),
+        },
+    };
+
+    index.add_frombuffer(&entry, text.as_bytes())?;
+    index.write()
+}
@@ -0,0 +1,12 @@
+pub mod blame;
+pub mod branches;
+pub mod changes;
+pub mod conventional_commit;
+pub mod history;
+pub mod index;
+pub mod stash;
+pub mod status;
+
+pub use git2;
+pub use git2::Error;
+pub use status::{FileStatus, Statuses};
@@ -0,0 +1,58 @@
+//! Setting changes aside and bringing them back.
+
+use git2::{Repository, StashApplyOptions, StashFlags};
+use std::path::Path;
+
+use crate::Error;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Stash {
+    /// 0 is the latest.
+    pub index: usize,
+    pub message: String,
+}
+
+pub fn list(path: &Path) -> Result<Vec<Stash>, Error> {
+    let mut repository = Repository::discover(path)?;
+    let mut stashes = Vec::new();
+
+    repository.stash_foreach(|index, message, _| {
+        stashes.push(Stash {
+            index,
+            message: message.into(),
+        });
+        true
+    })?;
+
+    Ok(stashes)
+}
+
+/// Stashes the staged and unstaged changes, and the untracked files too.
+pub fn save(path: &Path, message: Option<&str>) -> Result<(), Error> {
+    let mut repository = Repository::discover(path)?;
+    let signature = repository.signature()?;
+
+    repository.stash_save(
+        &signature,
+        message.unwrap_or_default(),
+        Some(StashFlags::INCLUDE_UNTRACKED),
+    )?;
+
+    Ok(())
+}
+
+/// Applies the stash, keeping it when `pop` is false.
+pub fn apply(path: &Path, index: usize, pop: bool) -> Result<(), Error> {
+    let mut repository = Repository::discover(path)?;
+    let mut options = StashApplyOptions::new();
+    options.reinstantiate_index();
+
+    match pop {
+        true => repository.stash_pop(index, Some(&mut options)),
+        false => repository.stash_apply(index, Some(&mut options)),
+    }
+}
+
+pub fn drop(path: &Path, index: usize) -> Result<(), Error> {
+    Repository::discover(path)?.stash_drop(index)
+}
@@ -0,0 +1,172 @@
+//! The status of every changed file of a repository, rolled up into the
+//! folders that contain them.
+
+use git2::{Repository, Status, StatusOptions};
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::Error;
+
+/// Ordered by importance: a folder shows the first status of its files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum FileStatus {
+    Conflicted,
+    Modified,
+    Deleted,
+    Added,
+    Renamed,
+    Untracked,
+    Ignored,
+}
+
+impl FileStatus {
+    fn from_git2(status: Status) -> Option<Self> {
+        let file_status = if status.is_conflicted() {
+            FileStatus::Conflicted
+        } else if status.is_index_new() {
+            FileStatus::Added
+        } else if status.is_wt_new() {
+            FileStatus::Untracked
+        } else if status.intersects(Status::INDEX_RENAMED | Status::WT_RENAMED) {
+            FileStatus::Renamed
+        } else if status.intersects(Status::INDEX_DELETED | Status::WT_DELETED) {
+            FileStatus::Deleted
+        } else if status.intersects(
+            Status::INDEX_MODIFIED
+                | Status::WT_MODIFIED
+                | Status::INDEX_TYPECHANGE
+                | Status::WT_TYPECHANGE,
+        ) {
+            FileStatus::Modified
+        } else if status.is_ignored() {
+            FileStatus::Ignored
+        } else {
+            return None;
+        };
+
+        Some(file_status)
+    }
+
+    /// The badge shown next to the file, as in `git status --short`.
+    pub fn letter(self) -> &'static str {
+        match self {
+            FileStatus::Conflicted => "C",
+            FileStatus::Modified => "M",
+            FileStatus::Deleted => "D",
+            FileStatus::Added => "A",
+            FileStatus::Renamed => "R",
+            FileStatus::Untracked => "U",
+            FileStatus::Ignored => "!",
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            FileStatus::Conflicted => "Conflicted",
+            FileStatus::Modified => "Modified",
+            FileStatus::Deleted => "Deleted",
+            FileStatus::Added => "Added",
+            FileStatus::Renamed => "Renamed",
+            FileStatus::Untracked => "Untracked",
+            FileStatus::Ignored => "Ignored",
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Statuses {
+    root: PathBuf,
+    /// Relative to the root. Ignored folders are listed once, not their
+    /// files.
+    files: HashMap<PathBuf, FileStatus>,
+    /// The most important status of the files under each folder, ignored
+    /// files left out.
+    folders: HashMap<PathBuf, FileStatus>,
+}
+
+impl Statuses {
+    /// Reads the status of the repository containing `path`.
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        let repository = Repository::discover(path)?;
+        let root = repository
+            .workdir()
+            .ok_or_else(|| Error::from_str("The repository has no working directory"))?;
+        let root = fs::canonicalize(root).unwrap_or_else(|_| root.to_path_buf());
+
+        let mut options = StatusOptions::new();
+        options
+            .include_untracked(true)
+            .recurse_untracked_dirs(true)
+            .include_ignored(true)
+            .recurse_ignored_dirs(false)
+            .renames_head_to_index(true);
+
+        let mut statuses = Statuses {
+            root,
+            ..Default::default()
+        };
+
+        for entry in repository.statuses(Some(&mut options))?.iter() {
+            let Some(status) = FileStatus::from_git2(entry.status()) else {
+                continue;
+            };
+
+            // Renames are listed under their old path.
+            let renamed = entry
+                .head_to_index()
+                .filter(|_| status == FileStatus::Renamed)
+                .and_then(|delta| delta.new_file().path().map(Path::to_path_buf));
+
+            let Some(path) = renamed.or_else(|| entry.path().map(PathBuf::from)) else {
+                continue;
+            };
+
+            statuses.insert(path, status);
+        }
+
+        Ok(statuses)
+    }
+
+    fn insert(&mut self, path: PathBuf, status: FileStatus) {
+        if status != FileStatus::Ignored {
+            for folder in path.ancestors().skip(1) {
+                let rolled_up = self.folders.entry(folder.to_path_buf()).or_insert(status);
+                *rolled_up = (*rolled_up).min(status);
+            }
+        }
+
+        self.files.insert(path, status);
+    }
+
+    /// The working directory, canonicalized.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// The status of a file, given its absolute path. Files in an ignored
+    /// folder are ignored too.
+    pub fn file(&self, path: &Path) -> Option<FileStatus> {
+        let path = path.strip_prefix(&self.root).ok()?;
+
+        self.files.get(path).copied().or_else(|| self.ignored(path))
+    }
+
+    /// The most important status of the files in a folder, given its
+    /// absolute path.
+    pub fn folder(&self, path: &Path) -> Option<FileStatus> {
+        let path = path.strip_prefix(&self.root).ok()?;
+
+        self.ignored(path)
+            .or_else(|| self.folders.get(path).copied())
+    }
+
+    fn ignored(&self, path: &Path) -> Option<FileStatus> {
+        path.ancestors()
+            .filter(|ancestor| !ancestor.as_os_str().is_empty())
+            .any(|ancestor| self.files.get(ancestor) == Some(&FileStatus::Ignored))
+            .then_some(FileStatus::Ignored)
+    }
+}
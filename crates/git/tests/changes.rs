@@ -0,0 +1,221 @@
+mod common;
+
+use common::write;
+use git::{
+    branches, changes,
+    changes::Change,
+    conventional_commit::{self, ConventionalCommit},
+    git2::Repository,
+    stash, FileStatus,
+};
+use std::{fs, path::Path};
+use tempfile::TempDir;
+
+/// A repository with `a.txt` and `b.txt` committed.
+fn repository() -> (TempDir, Repository) {
+    let (directory, repository) = common::repository();
+
+    write(&repository, "a.txt", "a\n");
+    write(&repository, "b.txt", "b\n");
+    changes::stage(directory.path(), Path::new("a.txt")).unwrap();
+    changes::stage(directory.path(), Path::new("b.txt")).unwrap();
+    changes::commit(directory.path(), "chore: initial commit").unwrap();
+
+    (directory, repository)
+}
+
+fn change(path: &str, status: FileStatus) -> Change {
+    Change {
+        path: path.into(),
+        status,
+    }
+}
+
+#[test]
+fn lists_staged_and_unstaged_changes() {
+    let (directory, repository) = repository();
+    let root = directory.path();
+
+    write(&repository, "a.txt", "staged\n");
+    changes::stage(root, Path::new("a.txt")).unwrap();
+    write(&repository, "a.txt", "changed again\n");
+    fs::remove_file(root.join("b.txt")).unwrap();
+    write(&repository, "c.txt", "new\n");
+
+    let changes = changes::changes(root).unwrap();
+
+    assert_eq!(changes.staged, [change("a.txt", FileStatus::Modified)]);
+    assert_eq!(
+        changes.unstaged,
+        [
+            change("a.txt", FileStatus::Modified),
+            change("b.txt", FileStatus::Deleted),
+            change("c.txt", FileStatus::Untracked),
+        ]
+    );
+}
+
+#[test]
+fn stages_unstages_and_discards() {
+    let (directory, repository) = repository();
+    let root = directory.path();
+
+    write(&repository, "a.txt", "changed\n");
+    write(&repository, "c.txt", "new\n");
+    fs::remove_file(root.join("b.txt")).unwrap();
+
+    for path in ["a.txt", "b.txt", "c.txt"] {
+        changes::stage(root, Path::new(path)).unwrap();
+    }
+
+    let staged = changes::changes(root).unwrap();
+    assert_eq!(
+        staged.staged,
+        [
+            change("a.txt", FileStatus::Modified),
+            change("b.txt", FileStatus::Deleted),
+            change("c.txt", FileStatus::Added),
+        ]
+    );
+    assert!(staged.unstaged.is_empty());
+
+    changes::unstage(root, Path::new("a.txt")).unwrap();
+    changes::unstage(root, Path::new("c.txt")).unwrap();
+    changes::discard(root, Path::new("a.txt")).unwrap();
+    changes::discard(root, Path::new("c.txt")).unwrap();
+
+    assert_eq!(fs::read_to_string(root.join("a.txt")).unwrap(), "a\n");
+    assert!(!root.join("c.txt").exists());
+
+    let changes = changes::changes(root).unwrap();
+    assert_eq!(changes.staged, [change("b.txt", FileStatus::Deleted)]);
+    assert!(changes.unstaged.is_empty());
+}
+
+#[test]
+fn commits_the_index() {
+    let (directory, repository) = repository();
+    let root = directory.path();
+
+    assert!(changes::commit(root, "fix: nothing").is_err());
+
+    write(&repository, "a.txt", "changed\n");
+    changes::stage(root, Path::new("a.txt")).unwrap();
+    changes::commit(root, "fix: change a").unwrap();
+
+    let head = repository.head().unwrap().peel_to_commit().unwrap();
+    assert_eq!(head.message(), Some("fix: change a"));
+    assert_eq!(head.parent_count(), 1);
+    assert!(changes::changes(root).unwrap().staged.is_empty());
+}
+
+#[test]
+fn creates_and_switches_branches() {
+    let (directory, repository) = repository();
+    let root = directory.path();
+    let initial = branches::current(root).unwrap().unwrap();
+
+    branches::create(root, "feature").unwrap();
+    assert_eq!(branches::current(root).unwrap().as_deref(), Some("feature"));
+
+    write(&repository, "a.txt", "feature\n");
+    changes::stage(root, Path::new("a.txt")).unwrap();
+    changes::commit(root, "feat: on a branch").unwrap();
+
+    branches::checkout(root, &initial).unwrap();
+    assert_eq!(fs::read_to_string(root.join("a.txt")).unwrap(), "a\n");
+
+    let names: Vec<(String, bool)> = branches::branches(root)
+        .unwrap()
+        .into_iter()
+        .map(|branch| (branch.name, branch.is_head))
+        .collect();
+    assert!(names.contains(&("feature".into(), false)));
+    assert!(names.contains(&(initial.clone(), true)));
+
+    // Switching would overwrite the local change to a.txt.
+    write(&repository, "a.txt", "local\n");
+    assert!(branches::checkout(root, "feature").is_err());
+    assert_eq!(branches::current(root).unwrap(), Some(initial));
+}
+
+#[test]
+fn stashes_and_restores_changes() {
+    let (directory, repository) = repository();
+    let root = directory.path();
+
+    write(&repository, "a.txt", "work in progress\n");
+    write(&repository, "c.txt", "untracked\n");
+    stash::save(root, Some("wip")).unwrap();
+
+    assert_eq!(fs::read_to_string(root.join("a.txt")).unwrap(), "a\n");
+    assert!(!root.join("c.txt").exists());
+
+    let stashes = stash::list(root).unwrap();
+    assert_eq!(stashes.len(), 1);
+    assert!(stashes[0].message.contains("wip"));
+
+    stash::apply(root, 0, false).unwrap();
+    assert_eq!(stash::list(root).unwrap().len(), 1);
+    assert_eq!(
+        fs::read_to_string(root.join("a.txt")).unwrap(),
+        "work in progress\n"
+    );
+
+    changes::discard(root, Path::new("a.txt")).unwrap();
+    changes::discard(root, Path::new("c.txt")).unwrap();
+    stash::apply(root, 0, true).unwrap();
+
+    assert!(stash::list(root).unwrap().is_empty());
+    assert!(root.join("c.txt").exists());
+
+    stash::save(root, None).unwrap();
+    stash::drop(root, 0).unwrap();
+    assert!(stash::list(root).unwrap().is_empty());
+}
+
+#[test]
+fn writes_and_reads_conventional_commits() {
+    let commit = ConventionalCommit {
+        kind: "feat".into(),
+        scope: "editor".into(),
+        subject: "add a source control panel".into(),
+        body: "Stage, commit and switch branches.".into(),
+        breaking_change: "".into(),
+    };
+    let message = commit.message();
+
+    assert_eq!(
+        message,
+        "feat(editor): add a source control panel\n\nStage, commit and switch branches."
+    );
+    assert_eq!(ConventionalCommit::parse(&message), Some(commit));
+
+    let breaking = ConventionalCommit {
+        kind: "refactor".into(),
+        breaking_change: "State moved".into(),
+        subject: "split the state".into(),
+        ..Default::default()
+    };
+
+    assert_eq!(
+        breaking.message(),
+        "refactor!: split the state\n\nBREAKING CHANGE: State moved"
+    );
+    assert_eq!(
+        ConventionalCommit::parse(&breaking.message()),
+        Some(breaking)
+    );
+}
+
+#[test]
+fn lints_commit_messages() {
+    assert_eq!(
+        conventional_commit::lint("fix(lsp): restart crashed servers"),
+        None
+    );
+    assert!(conventional_commit::lint("").is_some());
+    assert!(conventional_commit::lint("Fixed things").is_some());
+    assert!(conventional_commit::lint("fixes: things").is_some());
+    assert!(conventional_commit::lint("fix: things\nmore").is_some());
+}
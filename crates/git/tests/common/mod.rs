@@ -0,0 +1,63 @@
+//! The repository the tests start from, and the ways they change it.
+
+// Every test file uses its own share of these.
+#![allow(dead_code)]
+
+use git::git2::{Oid, Repository, Signature, Time};
+use std::{fs, path::Path};
+use tempfile::TempDir;
+
+/// An empty repository in a temporary folder, with a user to commit as.
+pub fn repository() -> (TempDir, Repository) {
+    let directory = tempfile::tempdir().unwrap();
+    let repository = Repository::init(directory.path()).unwrap();
+
+    let mut config = repository.config().unwrap();
+    config.set_str("user.name", "Test").unwrap();
+    config.set_str("user.email", "test@example.com").unwrap();
+
+    (directory, repository)
+}
+
+/// Writes `path`, relative to the working directory, and its folders.
+pub fn write(repository: &Repository, path: &str, content: &str) {
+    let path = repository.workdir().unwrap().join(path);
+
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(path, content).unwrap();
+}
+
+pub fn stage(repository: &Repository, path: &str) {
+    let mut index = repository.index().unwrap();
+
+    index.add_path(Path::new(path)).unwrap();
+    index.write().unwrap();
+}
+
+/// Commits the index on top of HEAD.
+pub fn commit(repository: &Repository, message: &str) -> Oid {
+    let signature = Signature::now("Test", "test@example.com").unwrap();
+
+    commit_with(repository, message, &signature)
+}
+
+/// Commits the index on top of HEAD, by `author` at `time`.
+pub fn commit_by(repository: &Repository, message: &str, author: &str, time: i64) -> Oid {
+    let signature = Signature::new(author, "test@example.com", &Time::new(time, 0)).unwrap();
+
+    commit_with(repository, message, &signature)
+}
+
+fn commit_with(repository: &Repository, message: &str, signature: &Signature) -> Oid {
+    let mut index = repository.index().unwrap();
+    let tree = repository.find_tree(index.write_tree().unwrap()).unwrap();
+    let parent = repository
+        .head()
+        .ok()
+        .and_then(|head| head.peel_to_commit().ok());
+    let parents: Vec<_> = parent.iter().collect();
+
+    repository
+        .commit(Some("HEAD"), signature, signature, message, &tree, &parents)
+        .unwrap()
+}
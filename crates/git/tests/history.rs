@@ -0,0 +1,96 @@
+mod common;
+
+use common::{commit_by, repository, stage, write};
+use git::{
+    blame::Blame,
+    git2::{Oid, Repository},
+    history,
+};
+
+/// Commits `content` as `file.txt`, by `author` at `time`.
+fn commit(repository: &Repository, content: &str, author: &str, time: i64) -> Oid {
+    write(repository, "file.txt", content);
+    stage(repository, "file.txt");
+    commit_by(
+        repository,
+        &format!("Change by {author}\n\nDetails."),
+        author,
+        time,
+    )
+}
+
+#[test]
+fn lists_the_commits_that_touched_a_file() {
+    let (directory, repository) = repository();
+    let root = directory.path();
+
+    let first = commit(&repository, "a\n", "Ada", 1_000);
+    write(&repository, "other.txt", "other\n");
+    stage(&repository, "other.txt");
+    commit(&repository, "a\n", "Grace", 2_000);
+    let last = commit(&repository, "a\nb\n", "Linus", 3_000);
+
+    let path = root.join("file.txt");
+    let history = history::file_history(&path).unwrap();
+    let ids: Vec<Oid> = history.iter().map(|revision| revision.id).collect();
+
+    assert_eq!(ids, [last, first]);
+    assert_eq!(history[0].author, "Linus");
+    assert_eq!(history[0].summary, "Change by Linus");
+    assert_eq!(history[0].message, "Change by Linus\n\nDetails.");
+    assert_eq!(history[0].short_id().len(), 7);
+
+    assert_eq!(
+        history::text_at(&path, first).unwrap().as_deref(),
+        Some("a\n")
+    );
+    assert_eq!(
+        history::text_at(&path, last).unwrap().as_deref(),
+        Some("a\nb\n")
+    );
+}
+
+#[test]
+fn blames_committed_and_edited_lines() {
+    let (directory, repository) = repository();
+    let root = directory.path();
+
+    commit(&repository, "one\ntwo\n", "Ada", 1_000);
+    commit(&repository, "one\ntwo\nthree\n", "Grace", 2_000);
+
+    let mut blame = Blame::load(&root.join("file.txt")).unwrap();
+    assert_eq!(blame.lines, [Some(0), Some(0), Some(1)]);
+
+    blame.update("zero\none\ntwo\nthree\n");
+    let authors: Vec<Option<&str>> = (0..4)
+        .map(|line| blame.line(line).map(|revision| revision.author.as_str()))
+        .collect();
+
+    assert_eq!(authors, [None, Some("Ada"), Some("Ada"), Some("Grace")]);
+    assert_eq!(blame.revisions.len(), 2);
+
+    commit(&repository, "zero\r\none\r\n", "Linus", 3_000);
+    let mut blame = Blame::load(&root.join("file.txt")).unwrap();
+    blame.update("zero\none\n");
+    assert_eq!(blame.lines, [Some(0), Some(0)]);
+
+    write(&repository, "new.txt", "new\n");
+    let mut blame = Blame::load(&root.join("new.txt")).unwrap();
+    blame.update("new\n");
+    assert_eq!(blame.lines, [None]);
+}
+
+#[test]
+fn formats_relative_times() {
+    let now = 10_000_000;
+
+    assert_eq!(history::relative_time(now - 20, now), "just now");
+    assert_eq!(history::relative_time(now - 60, now), "1 minute ago");
+    assert_eq!(history::relative_time(now - 3 * 3600, now), "3 hours ago");
+    assert_eq!(history::relative_time(now - 8 * 86_400, now), "1 week ago");
+    assert_eq!(
+        history::relative_time(now - 400 * 86_400, now),
+        "1 year ago"
+    );
+    assert_eq!(history::relative_time(now + 60, now), "just now");
+}
@@ -0,0 +1,64 @@
+mod common;
+
+use common::{commit, repository, stage, write};
+use git::{git2::Repository, index};
+use std::{fs, path::Path};
+use tempfile::TempDir;
+
+/// A repository with `content` committed as `file.txt`.
+fn repository_with(content: &str) -> (TempDir, Repository) {
+    let (directory, repository) = repository();
+
+    write(&repository, "file.txt", content);
+    stage(&repository, "file.txt");
+    commit(&repository, "Initial");
+
+    (directory, repository)
+}
+
+#[test]
+fn reads_the_committed_and_staged_versions() {
+    let (directory, _repository) = repository_with("committed\n");
+    let path = directory.path().join("file.txt");
+
+    fs::write(&path, "on disk\n").unwrap();
+
+    assert_eq!(
+        index::head_text(&path).unwrap().as_deref(),
+        Some("committed\n")
+    );
+    assert_eq!(
+        index::index_text(&path).unwrap().as_deref(),
+        Some("committed\n")
+    );
+
+    fs::write(directory.path().join("new.txt"), "new\n").unwrap();
+    assert_eq!(
+        index::head_text(&directory.path().join("new.txt")).unwrap(),
+        None
+    );
+}
+
+#[test]
+fn stages_one_hunk_and_unstages_it_again() {
+    let (directory, repository) = repository_with("a\nb\nc\n");
+    let path = directory.path().join("file.txt");
+    let buffer = "A\nb\nC\n";
+
+    let unstaged = diff::hunks("a\nb\nc\n", buffer);
+    assert_eq!(unstaged.len(), 2);
+
+    index::stage_text(&path, &diff::apply("a\nb\nc\n", &unstaged[0])).unwrap();
+    let staged_text = index::index_text(&path).unwrap().unwrap();
+    assert_eq!(staged_text, "A\nb\nc\n");
+
+    // The file on disk is left alone.
+    assert_eq!(fs::read_to_string(&path).unwrap(), "a\nb\nc\n");
+
+    let status = repository.status_file(Path::new("file.txt")).unwrap();
+    assert!(status.is_index_modified());
+
+    let staged = diff::hunks("a\nb\nc\n", &staged_text);
+    index::stage_text(&path, &diff::revert(&staged_text, &staged[0])).unwrap();
+    assert_eq!(index::index_text(&path).unwrap().unwrap(), "a\nb\nc\n");
+}
@@ -0,0 +1,180 @@
+mod common;
+
+use common::{commit, repository, stage, write};
+use git::{
+    git2::{build::CheckoutBuilder, BranchType, Repository},
+    FileStatus, Statuses,
+};
+use std::{fs, path::Path};
+
+fn load(repository: &Repository) -> (Statuses, impl Fn(&str) -> std::path::PathBuf) {
+    let statuses = Statuses::load(repository.workdir().unwrap()).unwrap();
+    let root = statuses.root().to_path_buf();
+
+    (statuses, move |path: &str| root.join(path))
+}
+
+#[test]
+fn reports_the_status_of_each_file() {
+    let (_directory, repository) = repository();
+
+    write(&repository, "src/main.rs", "fn main() {}\n");
+    write(&repository, "src/lib.rs", "\n");
+    write(&repository, ".gitignore", "target/\n*.log\n");
+    stage(&repository, "src/main.rs");
+    stage(&repository, "src/lib.rs");
+    stage(&repository, ".gitignore");
+    commit(&repository, "Initial commit");
+
+    write(&repository, "src/main.rs", "fn main() { println!(); }\n");
+    write(&repository, "src/new.rs", "\n");
+    stage(&repository, "src/new.rs");
+    write(&repository, "notes.txt", "\n");
+    write(&repository, "debug.log", "\n");
+    write(&repository, "target/debug/app", "\n");
+    fs::remove_file(repository.workdir().unwrap().join("src/lib.rs")).unwrap();
+
+    let (statuses, path) = load(&repository);
+
+    assert_eq!(
+        statuses.file(&path("src/main.rs")),
+        Some(FileStatus::Modified)
+    );
+    assert_eq!(statuses.file(&path("src/new.rs")), Some(FileStatus::Added));
+    assert_eq!(
+        statuses.file(&path("src/lib.rs")),
+        Some(FileStatus::Deleted)
+    );
+    assert_eq!(
+        statuses.file(&path("notes.txt")),
+        Some(FileStatus::Untracked)
+    );
+    assert_eq!(statuses.file(&path("debug.log")), Some(FileStatus::Ignored));
+    assert_eq!(statuses.file(&path(".gitignore")), None);
+}
+
+#[test]
+fn files_in_ignored_folders_are_ignored() {
+    let (_directory, repository) = repository();
+
+    write(&repository, ".gitignore", "target/\n");
+    write(&repository, "target/debug/app", "\n");
+
+    let (statuses, path) = load(&repository);
+
+    assert_eq!(statuses.folder(&path("target")), Some(FileStatus::Ignored));
+    assert_eq!(
+        statuses.folder(&path("target/debug")),
+        Some(FileStatus::Ignored)
+    );
+    assert_eq!(
+        statuses.file(&path("target/debug/app")),
+        Some(FileStatus::Ignored)
+    );
+}
+
+#[test]
+fn folders_show_the_most_important_status_of_their_files() {
+    let (_directory, repository) = repository();
+
+    write(&repository, "src/a.rs", "\n");
+    write(&repository, "src/nested/b.rs", "\n");
+    write(&repository, "docs/readme.md", "\n");
+    stage(&repository, "src/a.rs");
+    stage(&repository, "src/nested/b.rs");
+    stage(&repository, "docs/readme.md");
+    commit(&repository, "Initial commit");
+
+    write(&repository, "src/nested/b.rs", "changed\n");
+    write(&repository, "src/untracked.rs", "\n");
+    write(&repository, "docs/guide.md", "\n");
+
+    let (statuses, path) = load(&repository);
+
+    assert_eq!(statuses.folder(&path("src")), Some(FileStatus::Modified));
+    assert_eq!(
+        statuses.folder(&path("src/nested")),
+        Some(FileStatus::Modified)
+    );
+    assert_eq!(statuses.folder(&path("docs")), Some(FileStatus::Untracked));
+    assert_eq!(statuses.folder(&path("unknown")), None);
+}
+
+#[test]
+fn staged_renames_are_reported_under_the_new_path() {
+    let (_directory, repository) = repository();
+
+    write(
+        &repository,
+        "old.txt",
+        "some content\nthat stays the same\n",
+    );
+    stage(&repository, "old.txt");
+    commit(&repository, "Initial commit");
+
+    let workdir = repository.workdir().unwrap();
+    fs::rename(workdir.join("old.txt"), workdir.join("new.txt")).unwrap();
+
+    let mut index = repository.index().unwrap();
+    index.remove_path(Path::new("old.txt")).unwrap();
+    index.add_path(Path::new("new.txt")).unwrap();
+    index.write().unwrap();
+
+    let (statuses, path) = load(&repository);
+
+    assert_eq!(statuses.file(&path("new.txt")), Some(FileStatus::Renamed));
+    assert_eq!(statuses.file(&path("old.txt")), None);
+}
+
+#[test]
+fn merge_conflicts_are_reported() {
+    let (_directory, repository) = repository();
+
+    write(&repository, "shared.txt", "base\n");
+    stage(&repository, "shared.txt");
+    commit(&repository, "Base");
+
+    let base = repository.head().unwrap().peel_to_commit().unwrap();
+    repository.branch("other", &base, false).unwrap();
+
+    write(&repository, "shared.txt", "ours\n");
+    stage(&repository, "shared.txt");
+    commit(&repository, "Ours");
+
+    repository.set_head("refs/heads/other").unwrap();
+    repository
+        .checkout_head(Some(CheckoutBuilder::new().force()))
+        .unwrap();
+    write(&repository, "shared.txt", "theirs\n");
+    stage(&repository, "shared.txt");
+    commit(&repository, "Theirs");
+
+    let theirs = repository
+        .find_branch("other", BranchType::Local)
+        .unwrap()
+        .get()
+        .peel_to_commit()
+        .unwrap();
+    let main = repository
+        .branches(Some(BranchType::Local))
+        .unwrap()
+        .map(|branch| branch.unwrap().0)
+        .find(|branch| branch.name().unwrap() != Some("other"))
+        .unwrap();
+
+    repository.set_head(main.get().name().unwrap()).unwrap();
+    repository
+        .checkout_head(Some(CheckoutBuilder::new().force()))
+        .unwrap();
+
+    let annotated = repository.find_annotated_commit(theirs.id()).unwrap();
+    repository.merge(&[&annotated], None, None).unwrap();
+
+    let (statuses, path) = load(&repository);
+
+    assert_eq!(
+        statuses.file(&path("shared.txt")),
+        Some(FileStatus::Conflicted)
+    );
+    assert_eq!(statuses.folder(&path("")), Some(FileStatus::Conflicted));
+}
@@ -11,6 +11,10 @@ use hot_lib::*;
 #[cfg(not(feature = "reload"))]
 use main::*;
 
+/// Where the library is built to and swapped in from.
+#[cfg(feature = "reload")]
+const LIB_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../../target/debug");
+
 #[cfg(feature = "reload")]
 #[hot_lib_reloader::hot_module(dylib = "main", lib_dir = concat!(env!("CARGO_MANIFEST_DIR"), "/../../target/debug"))]
 mod hot_lib {
@@ -23,9 +27,11 @@ mod hot_lib {
     pub fn subscribe() -> hot_lib_reloader::LibReloadObserver {}
 }
 
-#[derive(Default)]
 pub struct App {
-    state: State,
+    /// Only ever dropped by the library that made it.
+    state: Option<Box<State>>,
+    #[cfg(feature = "reload")]
+    reloads: hot_lib_reloader::LibReloadObserver,
 }
 
 fn setup_custom_fonts(ctx: &Context) {
@@ -76,12 +82,36 @@ fn setup_custom_fonts(ctx: &Context) {
         .or_default()
         .insert(1, "fira_code_bold".to_owned());
 
+    // The terminal asks for bold text by this name.
+    fonts.families.insert(
+        FontFamily::Name("fira_code_bold".into()),
+        vec!["fira_code_bold".to_owned()],
+    );
+
     ctx.set_fonts(fonts);
 }
 
 impl EFrameApp for App {
     fn update(&mut self, ctx: &Context, frame: &mut Frame) {
-        render(&mut self.state, ctx, frame);
+        #[cfg(feature = "reload")]
+        if let Some(block) = self
+            .reloads
+            .wait_for_about_to_reload_timeout(std::time::Duration::ZERO)
+        {
+            self.reload(ctx, block);
+        }
+
+        if let Some(state) = &mut self.state {
+            render(state, ctx, frame);
+        }
+    }
+}
+
+impl Drop for App {
+    fn drop(&mut self) {
+        if let Some(state) = self.state.take() {
+            drop_state(state);
+        }
     }
 }
 
@@ -101,9 +131,70 @@ impl App {
         });
 
         Self {
-            state: State::default(),
+            state: Some(Box::default()),
+            #[cfg(feature = "reload")]
+            reloads: hot_lib::subscribe(),
         }
     }
+
+    /// Lets the library be swapped. The state is kept when the rebuilt
+    /// library lays it out the same; otherwise it's taken apart before and
+    /// rebuilt from its snapshot after.
+    #[cfg(feature = "reload")]
+    fn reload(&mut self, ctx: &Context, block: hot_lib_reloader::BlockReload) {
+        let Some(state) = self.state.take() else {
+            return;
+        };
+
+        if rebuilt_layout() == Some(state_layout()) {
+            keep_state();
+
+            drop(block);
+            self.reloads.wait_for_reload();
+
+            self.state = Some(state);
+            return;
+        }
+
+        let snapshot = unload_state(state, ctx);
+
+        drop(block);
+        self.reloads.wait_for_reload();
+
+        self.state = Some(restore_state(snapshot, ctx));
+    }
+}
+
+/// The state's layout in the rebuilt library, from a copy of it loaded
+/// next to the one in use. `None` when it can't be told.
+#[cfg(feature = "reload")]
+fn rebuilt_layout() -> Option<u64> {
+    let name = libloading::library_filename("main");
+    let rebuilt = std::path::Path::new(LIB_DIR).join(&name);
+    let copy = std::env::temp_dir().join(format!(
+        "{}-layout-{}",
+        std::process::id(),
+        name.to_string_lossy()
+    ));
+
+    std::fs::copy(rebuilt, &copy).ok()?;
+
+    // SAFETY: the copy is the library the host is built against, and
+    // `state_layout` only reads constants.
+    let layout = unsafe {
+        libloading::Library::new(&copy).ok().and_then(|library| {
+            let layout = library
+                .get::<fn() -> u64>(b"state_layout")
+                .ok()
+                .map(|state_layout| state_layout());
+
+            let _ = library.close();
+            layout
+        })
+    };
+
+    let _ = std::fs::remove_file(&copy);
+    layout
 }
 
 fn main() -> Result<(), Error> {
@@ -124,9 +215,14 @@ fn main() -> Result<(), Error> {
             {
                 let ctx = cc.egui_ctx.clone();
 
-                std::thread::spawn(move || loop {
-                    hot_lib::subscribe().wait_for_reload();
-                    ctx.request_repaint();
+                // Wakes the app up to snapshot its state before a reload.
+                std::thread::spawn(move || {
+                    let reloads = hot_lib::subscribe();
+
+                    loop {
+                        drop(reloads.wait_for_about_to_reload());
+                        ctx.request_repaint();
+                    }
                 });
             }
             Ok(Box::new(App::new(cc)))
@@ -0,0 +1,143 @@
+//! A tiny language server used by the integration tests. It keeps documents
+//! in memory and reports a warning for every `TODO` it finds. A document
+//! containing `CRASH` makes it exit with an error, to exercise restarts.
+
+use lsp::transport::{read_message, write_message};
+use serde_json::{json, Value};
+use std::{
+    collections::HashMap,
+    io::{self, BufReader},
+    process,
+};
+
+fn main() -> io::Result<()> {
+    let mut reader = BufReader::new(io::stdin().lock());
+    let mut writer = io::stdout().lock();
+    let mut documents: HashMap<String, String> = HashMap::new();
+
+    eprintln!("mock language server started");
+
+    while let Some(message) = read_message(&mut reader)? {
+        let method = message["method"].as_str().unwrap_or_default();
+        let params = &message["params"];
+
+        match method {
+            "initialize" => {
+                let result = json!({
+                    "capabilities": {
+                        "textDocumentSync": {
+                            "openClose": true,
+                            "change": 1,
+                            "save": { "includeText": true },
+                        },
+                    },
+                    "serverInfo": { "name": "mock-language-server" },
+                });
+
+                write_message(&mut writer, &response(&message, result))?;
+            }
+            "initialized" => {
+                write_message(
+                    &mut writer,
+                    &notification(
+                        "window/logMessage",
+                        json!({ "type": 3, "message": "initialized" }),
+                    ),
+                )?;
+            }
+            "textDocument/didOpen" | "textDocument/didChange" => {
+                let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+                let text = match method {
+                    "textDocument/didOpen" => params["textDocument"]["text"].as_str(),
+                    _ => params["contentChanges"][0]["text"].as_str(),
+                };
+
+                let text = text.unwrap_or_default().to_string();
+
+                if text.contains("CRASH") {
+                    eprintln!("crashing on request");
+                    process::exit(1);
+                }
+
+                let diagnostics = diagnostics(&text);
+                documents.insert(uri.into(), text);
+
+                write_message(
+                    &mut writer,
+                    &notification(
+                        "textDocument/publishDiagnostics",
+                        json!({
+                            "uri": uri,
+                            "version": params["textDocument"]["version"],
+                            "diagnostics": diagnostics,
+                        }),
+                    ),
+                )?;
+            }
+            "textDocument/didSave" => {
+                let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+
+                write_message(
+                    &mut writer,
+                    &notification(
+                        "window/logMessage",
+                        json!({ "type": 4, "message": format!("saved {uri}") }),
+                    ),
+                )?;
+            }
+            "textDocument/didClose" => {
+                let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+
+                documents.remove(uri);
+                write_message(
+                    &mut writer,
+                    &notification(
+                        "textDocument/publishDiagnostics",
+                        json!({ "uri": uri, "diagnostics": [] }),
+                    ),
+                )?;
+            }
+            "shutdown" => write_message(&mut writer, &response(&message, Value::Null))?,
+            "exit" => process::exit(0),
+            _ if message.get("id").is_some() => {
+                let error = json!({
+                    "jsonrpc": "2.0",
+                    "id": message["id"],
+                    "error": { "code": -32601, "message": format!("Unhandled method {method}") },
+                });
+
+                write_message(&mut writer, &error)?;
+            }
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+fn response(request: &Value, result: Value) -> Value {
+    json!({ "jsonrpc": "2.0", "id": request["id"], "result": result })
+}
+
+fn notification(method: &str, params: Value) -> Value {
+    json!({ "jsonrpc": "2.0", "method": method, "params": params })
+}
+
+fn diagnostics(text: &str) -> Vec<Value> {
+    text.lines()
+        .enumerate()
+        .flat_map(|(line, content)| {
+            content.match_indices("TODO").map(move |(column, _)| {
+                json!({
+                    "range": {
+                        "start": { "line": line, "character": column },
+                        "end": { "line": line, "character": column + 4 },
+                    },
+                    "severity": 2,
+                    "source": "mock",
+                    "message": "TODO found",
+                })
+            })
+        })
+        .collect()
+}
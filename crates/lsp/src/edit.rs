@@ -0,0 +1,98 @@
+//! Applying the `TextEdit`s servers send for renames, formatting and code
+//! actions. Their ranges all refer to the document before any of them.
+
+use lsp_types::{DocumentChangeOperation, DocumentChanges, OneOf, TextEdit, Url, WorkspaceEdit};
+
+use crate::position;
+
+/// Returns `text` with every edit applied. Edits inserting at the same
+/// position keep their order.
+pub fn apply(text: &str, edits: &[TextEdit]) -> String {
+    let mut text = text.to_string();
+
+    for (range, new_text) in byte_ranges(&text, edits).into_iter().rev() {
+        text.replace_range(range, new_text);
+    }
+
+    text
+}
+
+/// Where `offset` ends up once `edits` are applied to `text`. An offset
+/// inside a replaced range stays as far into the new text as it can.
+pub fn map_offset(text: &str, edits: &[TextEdit], offset: usize) -> usize {
+    let mut mapped = offset as isize;
+
+    for (range, new_text) in byte_ranges(text, edits) {
+        if range.start >= offset && !(range.is_empty() && range.start == offset) {
+            break;
+        }
+
+        if range.end <= offset {
+            mapped += new_text.len() as isize - range.len() as isize;
+        } else {
+            let into = (offset - range.start).min(new_text.len());
+            mapped -= (offset - range.start) as isize - into as isize;
+        }
+    }
+
+    mapped as usize
+}
+
+fn byte_ranges<'a>(text: &str, edits: &'a [TextEdit]) -> Vec<(std::ops::Range<usize>, &'a str)> {
+    let mut ranges: Vec<_> = edits
+        .iter()
+        .map(|edit| (position::to_range(text, edit.range), edit.new_text.as_str()))
+        .collect();
+
+    ranges.sort_by_key(|(range, _)| range.start);
+    ranges
+}
+
+/// The text edits of a `WorkspaceEdit` grouped by document, whether the
+/// server sent `changes` or `documentChanges`. File operations are left out.
+pub fn workspace_changes(edit: WorkspaceEdit) -> Vec<(Url, Vec<TextEdit>)> {
+    let mut changes: Vec<(Url, Vec<TextEdit>)> = Vec::new();
+    let mut add = |uri: Url, edits: Vec<TextEdit>| match changes
+        .iter_mut()
+        .find(|(existing, _)| *existing == uri)
+    {
+        Some((_, existing)) => existing.extend(edits),
+        None => changes.push((uri, edits)),
+    };
+
+    let document_edits = match edit.document_changes {
+        Some(DocumentChanges::Edits(edits)) => edits,
+        Some(DocumentChanges::Operations(operations)) => operations
+            .into_iter()
+            .filter_map(|operation| match operation {
+                DocumentChangeOperation::Edit(edit) => Some(edit),
+                DocumentChangeOperation::Op(_) => None,
+            })
+            .collect(),
+        None => Vec::new(),
+    };
+
+    for document in document_edits {
+        let edits = document
+            .edits
+            .into_iter()
+            .map(|edit| match edit {
+                OneOf::Left(edit) => edit,
+                OneOf::Right(annotated) => annotated.text_edit,
+            })
+            .collect();
+
+        add(document.text_document.uri, edits);
+    }
+
+    // `changes` is only meant to be used without `documentChanges`.
+    let mut legacy: Vec<(Url, Vec<TextEdit>)> =
+        edit.changes.unwrap_or_default().into_iter().collect();
+    legacy.sort_by(|(a, _), (b, _)| a.as_str().cmp(b.as_str()));
+
+    for (uri, edits) in legacy {
+        add(uri, edits);
+    }
+
+    changes
+}
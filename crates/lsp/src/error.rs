@@ -0,0 +1,41 @@
+use std::{fmt, io};
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    Json(serde_json::Error),
+    /// The server answered the request with an error response.
+    Response {
+        code: i64,
+        message: String,
+    },
+    Timeout,
+    /// The server process is gone, or never finished initializing.
+    NotRunning,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(error) => write!(f, "{error}"),
+            Error::Json(error) => write!(f, "{error}"),
+            Error::Response { code, message } => write!(f, "{message} ({code})"),
+            Error::Timeout => write!(f, "The language server did not respond in time"),
+            Error::NotRunning => write!(f, "The language server is not running"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Self {
+        Error::Io(error)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(error: serde_json::Error) -> Self {
+        Error::Json(error)
+    }
+}
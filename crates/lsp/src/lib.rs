@@ -0,0 +1,15 @@
+mod capabilities;
+pub mod edit;
+mod error;
+mod manager;
+pub mod position;
+mod server;
+pub mod transport;
+
+pub use error::Error;
+pub use lsp_types;
+pub use manager::{find_root, LspManager};
+pub use server::{
+    LanguageServer, LogEntry, LogLevel, PendingRequest, ServerConfig, ServerEvent, ServerStatus,
+    Waker,
+};
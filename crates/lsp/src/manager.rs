@@ -0,0 +1,223 @@
+use lsp_types::Url;
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use crate::server::{LanguageServer, LogLevel, ServerConfig, ServerEvent, ServerStatus, Waker};
+
+/// How many times a crashed server is started again before we give up on it.
+const MAX_RESTARTS: usize = 3;
+
+/// Starts one server per workspace root and configuration on demand and
+/// routes documents to them.
+pub struct LspManager {
+    configs: Vec<ServerConfig>,
+    servers: Vec<LanguageServer>,
+    default_root: PathBuf,
+    uris: HashMap<PathBuf, Url>,
+    waker: Option<Waker>,
+}
+
+impl Default for LspManager {
+    fn default() -> Self {
+        LspManager::new(
+            ServerConfig::defaults(),
+            std::env::current_dir().unwrap_or_default(),
+        )
+    }
+}
+
+impl LspManager {
+    pub fn new(configs: Vec<ServerConfig>, default_root: PathBuf) -> Self {
+        Self {
+            configs,
+            servers: Vec::new(),
+            default_root,
+            uris: HashMap::new(),
+            waker: None,
+        }
+    }
+
+    /// Called from the reader threads whenever a server sends something, so
+    /// the UI can repaint and poll.
+    pub fn set_waker(&mut self, waker: Waker) {
+        self.waker = Some(waker);
+    }
+
+    pub fn has_waker(&self) -> bool {
+        self.waker.is_some()
+    }
+
+    pub fn servers(&self) -> &[LanguageServer] {
+        &self.servers
+    }
+
+    pub fn uri(&mut self, path: &Path) -> Option<Url> {
+        if let Some(uri) = self.uris.get(path) {
+            return Some(uri.clone());
+        }
+
+        let absolute = path
+            .canonicalize()
+            .or_else(|_| std::path::absolute(path))
+            .ok()?;
+        let uri = Url::from_file_path(absolute).ok()?;
+
+        self.uris.insert(path.to_path_buf(), uri.clone());
+        Some(uri)
+    }
+
+    /// The server responsible for `path`, if one is configured for the
+    /// language and has been started.
+    pub fn server_for(&self, path: &Path, language_id: &str) -> Option<&LanguageServer> {
+        let config = self.config_for(language_id)?;
+        let root = self.root_for(path, config);
+
+        self.servers
+            .iter()
+            .find(|server| server.name() == config.name && server.root() == root)
+    }
+
+    fn config_for(&self, language_id: &str) -> Option<&ServerConfig> {
+        self.configs
+            .iter()
+            .find(|config| config.languages.iter().any(|id| id == language_id))
+    }
+
+    fn root_for(&self, path: &Path, config: &ServerConfig) -> PathBuf {
+        let absolute = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+
+        find_root(&absolute, &config.root_markers).unwrap_or_else(|| self.default_root.clone())
+    }
+
+    /// Opens the document on its server, starting the server if needed, or
+    /// sends the new content when `version` changed. Cheap to call every
+    /// frame.
+    pub fn sync_document(&mut self, path: &Path, language_id: &str, version: i32, text: &str) {
+        let Some(config) = self.config_for(language_id).cloned() else {
+            return;
+        };
+        let Some(uri) = self.uri(path) else {
+            return;
+        };
+
+        let root = self.root_for(path, &config);
+
+        let index = match self
+            .servers
+            .iter()
+            .position(|server| server.name() == config.name && server.root() == root)
+        {
+            Some(index) => index,
+            None => {
+                let server = LanguageServer::start(config, &root, self.waker.clone());
+                self.servers.push(server);
+                self.servers.len() - 1
+            }
+        };
+
+        let server = &mut self.servers[index];
+
+        if !matches!(server.status(), ServerStatus::Failed(_)) {
+            server.sync_document(&uri, language_id, version, text);
+        }
+    }
+
+    pub fn did_save(&mut self, path: &Path) {
+        let Some(uri) = self.uri(path) else {
+            return;
+        };
+
+        for server in self
+            .servers
+            .iter_mut()
+            .filter(|server| server.is_open(&uri))
+        {
+            server.did_save(&uri);
+        }
+    }
+
+    pub fn did_close(&mut self, path: &Path) {
+        let Some(uri) = self.uri(path) else {
+            return;
+        };
+
+        for server in &mut self.servers {
+            server.did_close(&uri);
+        }
+    }
+
+    /// Closes every document that isn't in `open`.
+    pub fn retain_documents(&mut self, open: &[PathBuf]) {
+        let open: Vec<Url> = open.iter().filter_map(|path| self.uri(path)).collect();
+
+        for server in &mut self.servers {
+            let closed: Vec<Url> = server
+                .open_documents()
+                .filter(|uri| !open.contains(uri))
+                .cloned()
+                .collect();
+
+            for uri in closed {
+                server.did_close(&uri);
+            }
+        }
+    }
+
+    pub fn restart(&mut self, index: usize) {
+        if let Some(server) = self.servers.get_mut(index) {
+            server.restart();
+        }
+    }
+
+    pub fn shutdown(&mut self) {
+        for server in &mut self.servers {
+            server.shutdown();
+        }
+    }
+
+    /// Polls every server, restarting the ones that crashed, and returns
+    /// their events tagged with the server's name.
+    pub fn poll(&mut self) -> Vec<(String, ServerEvent)> {
+        let mut events = Vec::new();
+
+        for server in &mut self.servers {
+            let server_events = server.poll();
+            let crashed = server_events
+                .iter()
+                .any(|event| matches!(event, ServerEvent::Exited));
+
+            events.extend(
+                server_events
+                    .into_iter()
+                    .map(|event| (server.name().to_string(), event)),
+            );
+
+            if crashed && server.restarts < MAX_RESTARTS {
+                server.restart();
+            } else if crashed {
+                events.push((
+                    server.name().to_string(),
+                    ServerEvent::Message {
+                        level: LogLevel::Error,
+                        message: format!(
+                            "{} crashed {MAX_RESTARTS} times and will not be restarted",
+                            server.name()
+                        ),
+                    },
+                ));
+            }
+        }
+
+        events
+    }
+}
+
+/// The closest ancestor of `path` containing one of `markers`.
+pub fn find_root(path: &Path, markers: &[String]) -> Option<PathBuf> {
+    path.ancestors()
+        .skip(1)
+        .find(|directory| markers.iter().any(|marker| directory.join(marker).exists()))
+        .map(Path::to_path_buf)
+}
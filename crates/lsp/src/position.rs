@@ -0,0 +1,51 @@
+//! Conversions between byte offsets and LSP positions, whose `character` is
+//! counted in UTF-16 code units.
+
+use lsp_types::{Position, Range};
+
+/// The byte offset of `position` in `text`, clamped to the end of its line
+/// and to the end of the text.
+pub fn to_offset(text: &str, position: Position) -> usize {
+    let line_start: usize = text
+        .split_inclusive('\n')
+        .take(position.line as usize)
+        .map(str::len)
+        .sum();
+
+    let line = text[line_start..].split('\n').next().unwrap_or_default();
+    let mut units = 0;
+
+    for (index, char) in line.char_indices() {
+        if units >= position.character as usize {
+            return line_start + index;
+        }
+
+        units += char.len_utf16();
+    }
+
+    line_start + line.trim_end_matches('\r').len()
+}
+
+pub fn from_offset(text: &str, offset: usize) -> Position {
+    let before = &text[..offset.min(text.len())];
+    let line_start = before.rfind('\n').map_or(0, |index| index + 1);
+
+    Position {
+        line: before.matches('\n').count() as u32,
+        character: before[line_start..].encode_utf16().count() as u32,
+    }
+}
+
+pub fn to_range(text: &str, range: Range) -> std::ops::Range<usize> {
+    let start = to_offset(text, range.start);
+    let end = to_offset(text, range.end);
+
+    start..end.max(start)
+}
+
+pub fn from_range(text: &str, range: std::ops::Range<usize>) -> Range {
+    Range {
+        start: from_offset(text, range.start),
+        end: from_offset(text, range.end),
+    }
+}
@@ -0,0 +1,796 @@
+use lsp_types::{
+    notification::{self, Notification},
+    request::{self, Request},
+    ClientInfo, InitializeParams, InitializeResult, InitializedParams, ServerCapabilities,
+    TextDocumentSyncCapability, TextDocumentSyncKind, TextDocumentSyncSaveOptions, Url,
+    WorkspaceFolder,
+};
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+use std::{
+    collections::{HashMap, VecDeque},
+    io::{BufRead, BufReader},
+    marker::PhantomData,
+    path::{Path, PathBuf},
+    process::{Child, ChildStdin, Command, Stdio},
+    sync::{
+        atomic::{AtomicBool, AtomicI64, Ordering},
+        mpsc::{self, Receiver, RecvTimeoutError, Sender, TryRecvError},
+        Arc, Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+use crate::{capabilities, transport, Error};
+
+const MAX_LOG_ENTRIES: usize = 1000;
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(1);
+
+pub type Waker = Arc<dyn Fn() + Send + Sync>;
+
+#[derive(Debug, Clone)]
+pub struct ServerConfig {
+    pub name: String,
+    pub command: String,
+    pub args: Vec<String>,
+    /// LSP language identifiers this server handles, e.g. `typescriptreact`.
+    pub languages: Vec<String>,
+    /// Files or directories marking the root of a workspace for this server.
+    pub root_markers: Vec<String>,
+    pub initialization_options: Option<Value>,
+}
+
+impl ServerConfig {
+    pub fn new(name: &str, command: &str, args: &[&str], languages: &[&str]) -> Self {
+        Self {
+            name: name.into(),
+            command: command.into(),
+            args: args.iter().map(|arg| arg.to_string()).collect(),
+            languages: languages
+                .iter()
+                .map(|language| language.to_string())
+                .collect(),
+            root_markers: vec![".git".into()],
+            initialization_options: None,
+        }
+    }
+
+    pub fn root_markers(mut self, markers: &[&str]) -> Self {
+        self.root_markers = markers.iter().map(|marker| marker.to_string()).collect();
+        self
+    }
+
+    pub fn defaults() -> Vec<Self> {
+        vec![
+            ServerConfig::new("rust-analyzer", "rust-analyzer", &[], &["rust"])
+                .root_markers(&["Cargo.toml", ".git"]),
+            ServerConfig::new(
+                "typescript-language-server",
+                "typescript-language-server",
+                &["--stdio"],
+                &[
+                    "javascript",
+                    "javascriptreact",
+                    "typescript",
+                    "typescriptreact",
+                ],
+            )
+            .root_markers(&["tsconfig.json", "jsconfig.json", "package.json", ".git"]),
+            ServerConfig::new(
+                "vscode-json-language-server",
+                "vscode-json-language-server",
+                &["--stdio"],
+                &["json"],
+            ),
+            ServerConfig::new(
+                "vscode-html-language-server",
+                "vscode-html-language-server",
+                &["--stdio"],
+                &["html"],
+            ),
+            ServerConfig::new("taplo", "taplo", &["lsp", "stdio"], &["toml"]),
+        ]
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogLevel {
+    Error,
+    Warning,
+    Info,
+    Log,
+    /// A line the server wrote to its standard error.
+    Stderr,
+}
+
+#[derive(Debug, Clone)]
+pub struct LogEntry {
+    pub level: LogLevel,
+    pub message: String,
+}
+
+#[derive(Debug, Clone)]
+pub enum ServerEvent {
+    Notification {
+        method: String,
+        params: Value,
+    },
+    /// `window/showMessage`, meant to be shown to the user.
+    Message {
+        level: LogLevel,
+        message: String,
+    },
+    Exited,
+}
+
+impl ServerEvent {
+    /// The parameters of this event if it is an `N` notification.
+    pub fn notification<N: Notification>(&self) -> Option<N::Params>
+    where
+        N::Params: DeserializeOwned,
+    {
+        match self {
+            ServerEvent::Notification { method, params } if method == N::METHOD => {
+                serde_json::from_value(params.clone()).ok()
+            }
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ServerStatus {
+    Starting,
+    Running,
+    /// The process could not be spawned or did not initialize.
+    Failed(String),
+    Exited,
+}
+
+struct Document {
+    language_id: String,
+    version: i32,
+    text: String,
+}
+
+/// Log entries outlive a connection so the output of a crashed server can
+/// still be read after it was restarted.
+#[derive(Clone, Default)]
+struct Logs(Arc<Mutex<VecDeque<LogEntry>>>);
+
+impl Logs {
+    fn push(&self, level: LogLevel, message: impl Into<String>) {
+        let mut logs = self.0.lock().unwrap();
+
+        logs.push_back(LogEntry {
+            level,
+            message: message.into(),
+        });
+
+        while logs.len() > MAX_LOG_ENTRIES {
+            logs.pop_front();
+        }
+    }
+
+    fn entries(&self) -> Vec<LogEntry> {
+        self.0.lock().unwrap().iter().cloned().collect()
+    }
+}
+
+/// State shared with the threads reading the server's output.
+struct Shared {
+    writer: Mutex<ChildStdin>,
+    pending: Mutex<HashMap<i64, Sender<Result<Value, Error>>>>,
+    logs: Logs,
+    waker: Mutex<Option<Waker>>,
+    exited: AtomicBool,
+}
+
+impl Shared {
+    fn send(&self, message: &Value) -> Result<(), Error> {
+        let mut writer = self.writer.lock().unwrap();
+        transport::write_message(&mut *writer, message).map_err(Error::from)
+    }
+
+    fn log(&self, level: LogLevel, message: impl Into<String>) {
+        self.logs.push(level, message);
+    }
+
+    fn wake(&self) {
+        if let Some(waker) = self.waker.lock().unwrap().as_ref() {
+            waker();
+        }
+    }
+}
+
+/// A running server process and the threads reading from it.
+struct Connection {
+    process: Child,
+    shared: Arc<Shared>,
+    events: Receiver<ServerEvent>,
+    next_id: AtomicI64,
+}
+
+impl Connection {
+    fn spawn(
+        config: &ServerConfig,
+        root: &Path,
+        logs: Logs,
+        waker: Option<Waker>,
+    ) -> Result<Self, Error> {
+        let mut process = Command::new(&config.command)
+            .args(&config.args)
+            .current_dir(root)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+
+        let stdin = process.stdin.take().ok_or(Error::NotRunning)?;
+        let stdout = process.stdout.take().ok_or(Error::NotRunning)?;
+        let stderr = process.stderr.take().ok_or(Error::NotRunning)?;
+
+        let shared = Arc::new(Shared {
+            writer: Mutex::new(stdin),
+            pending: Mutex::new(HashMap::new()),
+            logs,
+            waker: Mutex::new(waker),
+            exited: AtomicBool::new(false),
+        });
+
+        let (sender, events) = mpsc::channel();
+
+        {
+            let shared = shared.clone();
+            thread::spawn(move || read_stdout(BufReader::new(stdout), shared, sender));
+        }
+
+        {
+            let shared = shared.clone();
+            thread::spawn(move || {
+                for line in BufReader::new(stderr).lines().map_while(Result::ok) {
+                    shared.log(LogLevel::Stderr, line);
+                }
+            });
+        }
+
+        Ok(Self {
+            process,
+            shared,
+            events,
+            next_id: AtomicI64::new(0),
+        })
+    }
+
+    fn request<R: Request>(&self, params: R::Params) -> PendingRequest<R> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let (sender, receiver) = mpsc::channel();
+
+        self.shared.pending.lock().unwrap().insert(id, sender);
+
+        let message = json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": R::METHOD,
+            "params": params,
+        });
+
+        if let Err(error) = self.shared.send(&message) {
+            if let Some(sender) = self.shared.pending.lock().unwrap().remove(&id) {
+                let _ = sender.send(Err(error));
+            }
+        }
+
+        PendingRequest {
+            receiver,
+            _request: PhantomData,
+        }
+    }
+
+    fn notify<N: Notification>(&self, params: N::Params) -> Result<(), Error> {
+        self.shared.send(&json!({
+            "jsonrpc": "2.0",
+            "method": N::METHOD,
+            "params": params,
+        }))
+    }
+
+    fn shutdown(mut self) {
+        if !self.shared.exited.load(Ordering::Relaxed) {
+            let _ = self.request::<request::Shutdown>(()).wait(SHUTDOWN_TIMEOUT);
+            let _ = self.notify::<notification::Exit>(());
+        }
+
+        for _ in 0..10 {
+            if let Ok(Some(_)) = self.process.try_wait() {
+                return;
+            }
+
+            thread::sleep(SHUTDOWN_TIMEOUT / 10);
+        }
+
+        let _ = self.process.kill();
+        let _ = self.process.wait();
+    }
+}
+
+fn read_stdout(mut reader: impl BufRead, shared: Arc<Shared>, events: Sender<ServerEvent>) {
+    loop {
+        let message = match transport::read_message(&mut reader) {
+            Ok(Some(message)) => message,
+            Ok(None) => break,
+            Err(error) => {
+                shared.log(LogLevel::Error, format!("Unreadable message: {error}"));
+                continue;
+            }
+        };
+
+        let method = message.get("method").and_then(Value::as_str);
+        let id = message.get("id").cloned();
+
+        match (method, id) {
+            (Some(method), Some(id)) => {
+                let result = reply_to_server_request(method, &message["params"]);
+
+                let reply = match result {
+                    Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+                    Err(message) => json!({
+                        "jsonrpc": "2.0",
+                        "id": id,
+                        "error": { "code": -32601, "message": message },
+                    }),
+                };
+
+                if let Err(error) = shared.send(&reply) {
+                    shared.log(LogLevel::Error, format!("Could not reply: {error}"));
+                }
+            }
+            (Some(method), None) => {
+                let params = message.get("params").cloned().unwrap_or(Value::Null);
+
+                let event = match method {
+                    notification::LogMessage::METHOD | notification::ShowMessage::METHOD => {
+                        let level = match params["type"].as_i64() {
+                            Some(1) => LogLevel::Error,
+                            Some(2) => LogLevel::Warning,
+                            Some(3) => LogLevel::Info,
+                            _ => LogLevel::Log,
+                        };
+                        let text = params["message"].as_str().unwrap_or_default().to_string();
+
+                        shared.log(level, text.clone());
+
+                        (method == notification::ShowMessage::METHOD).then_some(
+                            ServerEvent::Message {
+                                level,
+                                message: text,
+                            },
+                        )
+                    }
+                    _ => Some(ServerEvent::Notification {
+                        method: method.into(),
+                        params,
+                    }),
+                };
+
+                if let Some(event) = event {
+                    let _ = events.send(event);
+                    shared.wake();
+                }
+            }
+            (None, Some(id)) => {
+                let sender = id
+                    .as_i64()
+                    .and_then(|id| shared.pending.lock().unwrap().remove(&id));
+
+                let Some(sender) = sender else {
+                    continue;
+                };
+
+                let result = match message.get("error") {
+                    Some(error) => Err(Error::Response {
+                        code: error["code"].as_i64().unwrap_or_default(),
+                        message: error["message"].as_str().unwrap_or_default().into(),
+                    }),
+                    None => Ok(message.get("result").cloned().unwrap_or(Value::Null)),
+                };
+
+                let _ = sender.send(result);
+                shared.wake();
+            }
+            (None, None) => {}
+        }
+    }
+
+    shared.exited.store(true, Ordering::Relaxed);
+
+    for (_, sender) in shared.pending.lock().unwrap().drain() {
+        let _ = sender.send(Err(Error::NotRunning));
+    }
+
+    let _ = events.send(ServerEvent::Exited);
+    shared.wake();
+}
+
+/// Answers the requests servers commonly send to their client. Anything else
+/// is refused as an unknown method.
+fn reply_to_server_request(method: &str, params: &Value) -> Result<Value, String> {
+    match method {
+        request::WorkspaceConfiguration::METHOD => {
+            let items = params["items"].as_array().map_or(0, Vec::len);
+            Ok(Value::Array(vec![Value::Null; items]))
+        }
+        request::RegisterCapability::METHOD
+        | request::UnregisterCapability::METHOD
+        | request::WorkDoneProgressCreate::METHOD => Ok(Value::Null),
+        request::WorkspaceFoldersRequest::METHOD => Ok(Value::Null),
+        _ => Err(format!("Unhandled method {method}")),
+    }
+}
+
+/// A request that has been sent; poll it every frame with
+/// [`PendingRequest::try_take`] or block on it with [`PendingRequest::wait`].
+pub struct PendingRequest<R: Request> {
+    receiver: Receiver<Result<Value, Error>>,
+    _request: PhantomData<R>,
+}
+
+impl<R: Request> PendingRequest<R>
+where
+    R::Result: DeserializeOwned,
+{
+    pub fn try_take(&self) -> Option<Result<R::Result, Error>> {
+        match self.receiver.try_recv() {
+            Ok(result) => Some(Self::parse(result)),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => Some(Err(Error::NotRunning)),
+        }
+    }
+
+    pub fn wait(self, timeout: Duration) -> Result<R::Result, Error> {
+        match self.receiver.recv_timeout(timeout) {
+            Ok(result) => Self::parse(result),
+            Err(RecvTimeoutError::Timeout) => Err(Error::Timeout),
+            Err(RecvTimeoutError::Disconnected) => Err(Error::NotRunning),
+        }
+    }
+
+    fn parse(result: Result<Value, Error>) -> Result<R::Result, Error> {
+        Ok(serde_json::from_value(result?)?)
+    }
+}
+
+/// One language server process for one workspace root. Documents are tracked
+/// here so they can be opened again after a restart.
+pub struct LanguageServer {
+    config: ServerConfig,
+    root: PathBuf,
+    connection: Option<Connection>,
+    initialize: Option<PendingRequest<request::Initialize>>,
+    status: ServerStatus,
+    capabilities: ServerCapabilities,
+    documents: HashMap<Url, Document>,
+    waker: Option<Waker>,
+    logs: Logs,
+    pub restarts: usize,
+}
+
+impl LanguageServer {
+    /// Spawns the server and sends `initialize` without waiting for the
+    /// answer; [`LanguageServer::poll`] finishes the handshake.
+    pub fn start(config: ServerConfig, root: &Path, waker: Option<Waker>) -> Self {
+        let mut server = Self {
+            config,
+            root: root.to_path_buf(),
+            connection: None,
+            initialize: None,
+            status: ServerStatus::Starting,
+            capabilities: ServerCapabilities::default(),
+            documents: HashMap::new(),
+            waker,
+            logs: Logs::default(),
+            restarts: 0,
+        };
+
+        server.connect();
+        server
+    }
+
+    fn connect(&mut self) {
+        self.status = ServerStatus::Starting;
+        self.capabilities = ServerCapabilities::default();
+
+        let connection = match Connection::spawn(
+            &self.config,
+            &self.root,
+            self.logs.clone(),
+            self.waker.clone(),
+        ) {
+            Ok(connection) => connection,
+            Err(error) => {
+                let message = format!("Could not start {}: {error}", self.config.command);
+
+                self.log(LogLevel::Error, message.clone());
+                self.status = ServerStatus::Failed(message);
+                return;
+            }
+        };
+
+        let root_uri = Url::from_directory_path(&self.root).ok();
+
+        #[allow(deprecated)]
+        let params = InitializeParams {
+            process_id: Some(std::process::id()),
+            root_path: Some(self.root.to_string_lossy().into()),
+            root_uri: root_uri.clone(),
+            initialization_options: self.config.initialization_options.clone(),
+            capabilities: capabilities::client(),
+            workspace_folders: root_uri.map(|uri| {
+                vec![WorkspaceFolder {
+                    uri,
+                    name: self
+                        .root
+                        .file_name()
+                        .map(|name| name.to_string_lossy().into())
+                        .unwrap_or_default(),
+                }]
+            }),
+            client_info: Some(ClientInfo {
+                name: "rust-editor".into(),
+                version: Some(env!("CARGO_PKG_VERSION").into()),
+            }),
+            ..InitializeParams::default()
+        };
+
+        self.initialize = Some(connection.request::<request::Initialize>(params));
+        self.connection = Some(connection);
+    }
+
+    pub fn name(&self) -> &str {
+        &self.config.name
+    }
+
+    pub fn config(&self) -> &ServerConfig {
+        &self.config
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    pub fn status(&self) -> &ServerStatus {
+        &self.status
+    }
+
+    pub fn capabilities(&self) -> &ServerCapabilities {
+        &self.capabilities
+    }
+
+    pub fn is_open(&self, uri: &Url) -> bool {
+        self.documents.contains_key(uri)
+    }
+
+    /// Our own messages and what the server logged, oldest first.
+    pub fn logs(&self) -> Vec<LogEntry> {
+        self.logs.entries()
+    }
+
+    fn log(&mut self, level: LogLevel, message: String) {
+        self.logs.push(level, message);
+    }
+
+    /// Drives the handshake and returns everything the server sent since the
+    /// last call. Call it once per frame.
+    pub fn poll(&mut self) -> Vec<ServerEvent> {
+        if let Some(result) = self.initialize.as_ref().and_then(PendingRequest::try_take) {
+            self.initialize = None;
+
+            match result {
+                Ok(InitializeResult { capabilities, .. }) => self.initialized(capabilities),
+                Err(error) => {
+                    let message = format!("{} failed to initialize: {error}", self.config.name);
+
+                    self.log(LogLevel::Error, message.clone());
+                    self.status = ServerStatus::Failed(message);
+                }
+            }
+        }
+
+        let Some(connection) = &self.connection else {
+            return Vec::new();
+        };
+
+        let events: Vec<ServerEvent> = connection.events.try_iter().collect();
+
+        if events
+            .iter()
+            .any(|event| matches!(event, ServerEvent::Exited))
+        {
+            self.status = ServerStatus::Exited;
+        }
+
+        events
+    }
+
+    fn initialized(&mut self, capabilities: ServerCapabilities) {
+        self.capabilities = capabilities;
+        self.status = ServerStatus::Running;
+
+        let _ = self.notify::<notification::Initialized>(InitializedParams {});
+
+        let documents: Vec<Url> = self.documents.keys().cloned().collect();
+
+        for uri in documents {
+            self.send_did_open(&uri);
+        }
+    }
+
+    /// Shuts the process down and starts a fresh one, reopening every
+    /// tracked document once it has initialized.
+    pub fn restart(&mut self) {
+        if let Some(connection) = self.connection.take() {
+            connection.shutdown();
+        }
+
+        self.restarts += 1;
+        self.log(LogLevel::Info, format!("Restarting {}", self.config.name));
+        self.connect();
+    }
+
+    pub fn shutdown(&mut self) {
+        if let Some(connection) = self.connection.take() {
+            connection.shutdown();
+        }
+
+        self.initialize = None;
+        self.status = ServerStatus::Exited;
+    }
+
+    pub fn request<R: Request>(&self, params: R::Params) -> Result<PendingRequest<R>, Error> {
+        match (&self.status, &self.connection) {
+            (ServerStatus::Running, Some(connection)) => Ok(connection.request::<R>(params)),
+            _ => Err(Error::NotRunning),
+        }
+    }
+
+    pub fn notify<N: Notification>(&self, params: N::Params) -> Result<(), Error> {
+        match (&self.status, &self.connection) {
+            (ServerStatus::Running, Some(connection)) => connection.notify::<N>(params),
+            _ => Err(Error::NotRunning),
+        }
+    }
+
+    /// Opens the document, or sends its new content if it is open already
+    /// and `version` has moved on.
+    pub fn sync_document(&mut self, uri: &Url, language_id: &str, version: i32, text: &str) {
+        match self.documents.get_mut(uri) {
+            Some(document) if document.version == version => {}
+            Some(document) => {
+                document.version = version;
+                document.text = text.into();
+                self.send_did_change(uri);
+            }
+            None => {
+                self.documents.insert(
+                    uri.clone(),
+                    Document {
+                        language_id: language_id.into(),
+                        version,
+                        text: text.into(),
+                    },
+                );
+                self.send_did_open(uri);
+            }
+        }
+    }
+
+    pub fn did_save(&mut self, uri: &Url) {
+        let Some(document) = self.documents.get(uri) else {
+            return;
+        };
+
+        let (notify, include_text) = match &self.capabilities.text_document_sync {
+            Some(TextDocumentSyncCapability::Options(options)) => match &options.save {
+                Some(TextDocumentSyncSaveOptions::Supported(supported)) => (*supported, false),
+                Some(TextDocumentSyncSaveOptions::SaveOptions(save)) => {
+                    (true, save.include_text.unwrap_or(false))
+                }
+                None => (false, false),
+            },
+            _ => (true, false),
+        };
+
+        if notify {
+            let _ = self.notify::<notification::DidSaveTextDocument>(
+                lsp_types::DidSaveTextDocumentParams {
+                    text_document: lsp_types::TextDocumentIdentifier { uri: uri.clone() },
+                    text: include_text.then(|| document.text.clone()),
+                },
+            );
+        }
+    }
+
+    pub fn did_close(&mut self, uri: &Url) {
+        if self.documents.remove(uri).is_some() && self.sends_open_close() {
+            let _ = self.notify::<notification::DidCloseTextDocument>(
+                lsp_types::DidCloseTextDocumentParams {
+                    text_document: lsp_types::TextDocumentIdentifier { uri: uri.clone() },
+                },
+            );
+        }
+    }
+
+    pub fn open_documents(&self) -> impl Iterator<Item = &Url> {
+        self.documents.keys()
+    }
+
+    fn sends_open_close(&self) -> bool {
+        match &self.capabilities.text_document_sync {
+            Some(TextDocumentSyncCapability::Kind(kind)) => *kind != TextDocumentSyncKind::NONE,
+            Some(TextDocumentSyncCapability::Options(options)) => {
+                options.open_close.unwrap_or(false)
+            }
+            None => false,
+        }
+    }
+
+    fn change_kind(&self) -> TextDocumentSyncKind {
+        match &self.capabilities.text_document_sync {
+            Some(TextDocumentSyncCapability::Kind(kind)) => *kind,
+            Some(TextDocumentSyncCapability::Options(options)) => {
+                options.change.unwrap_or(TextDocumentSyncKind::NONE)
+            }
+            None => TextDocumentSyncKind::NONE,
+        }
+    }
+
+    fn send_did_open(&self, uri: &Url) {
+        let Some(document) = self.documents.get(uri) else {
+            return;
+        };
+
+        if self.sends_open_close() {
+            let _ = self.notify::<notification::DidOpenTextDocument>(
+                lsp_types::DidOpenTextDocumentParams {
+                    text_document: lsp_types::TextDocumentItem {
+                        uri: uri.clone(),
+                        language_id: document.language_id.clone(),
+                        version: document.version,
+                        text: document.text.clone(),
+                    },
+                },
+            );
+        }
+    }
+
+    /// Always sends the whole text, which servers accept for both full and
+    /// incremental sync.
+    fn send_did_change(&self, uri: &Url) {
+        let Some(document) = self.documents.get(uri) else {
+            return;
+        };
+
+        if self.change_kind() != TextDocumentSyncKind::NONE {
+            let _ = self.notify::<notification::DidChangeTextDocument>(
+                lsp_types::DidChangeTextDocumentParams {
+                    text_document: lsp_types::VersionedTextDocumentIdentifier {
+                        uri: uri.clone(),
+                        version: document.version,
+                    },
+                    content_changes: vec![lsp_types::TextDocumentContentChangeEvent {
+                        range: None,
+                        range_length: None,
+                        text: document.text.clone(),
+                    }],
+                },
+            );
+        }
+    }
+}
+
+impl Drop for LanguageServer {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
@@ -0,0 +1,78 @@
+use std::collections::HashMap;
+
+use lsp::{
+    edit,
+    lsp_types::{
+        DocumentChanges, OneOf, OptionalVersionedTextDocumentIdentifier, Position, Range,
+        TextDocumentEdit, TextEdit, Url, WorkspaceEdit,
+    },
+};
+
+const TEXT: &str = "let count = 1;\nconsole.log(count);\n";
+
+fn edit(line: u32, start: u32, end: u32, new_text: &str) -> TextEdit {
+    TextEdit {
+        range: Range {
+            start: Position {
+                line,
+                character: start,
+            },
+            end: Position {
+                line,
+                character: end,
+            },
+        },
+        new_text: new_text.into(),
+    }
+}
+
+#[test]
+fn applies_edits_against_the_original_text() {
+    let edits = [
+        edit(1, 12, 17, "total"),
+        edit(0, 4, 9, "total"),
+        edit(0, 0, 0, "// a\n"),
+        edit(0, 0, 0, "// b\n"),
+    ];
+
+    assert_eq!(
+        edit::apply(TEXT, &edits),
+        "// a\n// b\nlet total = 1;\nconsole.log(total);\n"
+    );
+}
+
+#[test]
+fn maps_offsets_through_edits() {
+    let edits = [edit(0, 4, 9, "n"), edit(1, 0, 0, "  ")];
+
+    // Before, inside and after the renamed word.
+    assert_eq!(edit::map_offset(TEXT, &edits, 2), 2);
+    assert_eq!(edit::map_offset(TEXT, &edits, 7), 5);
+    assert_eq!(edit::map_offset(TEXT, &edits, 9), 5);
+    assert_eq!(edit::map_offset(TEXT, &edits, 12), 8);
+    // Text inserted at the offset ends up before it.
+    assert_eq!(edit::map_offset(TEXT, &edits, 15), 13);
+}
+
+#[test]
+fn flattens_workspace_edits() {
+    let a = Url::parse("file:///a.ts").unwrap();
+    let b = Url::parse("file:///b.ts").unwrap();
+
+    let changes = edit::workspace_changes(WorkspaceEdit {
+        changes: Some(HashMap::from([(b.clone(), vec![edit(0, 0, 1, "x")])])),
+        document_changes: Some(DocumentChanges::Edits(vec![TextDocumentEdit {
+            text_document: OptionalVersionedTextDocumentIdentifier {
+                uri: a.clone(),
+                version: Some(3),
+            },
+            edits: vec![OneOf::Left(edit(0, 0, 1, "y"))],
+        }])),
+        change_annotations: None,
+    });
+
+    assert_eq!(
+        changes,
+        vec![(a, vec![edit(0, 0, 1, "y")]), (b, vec![edit(0, 0, 1, "x")])]
+    );
+}
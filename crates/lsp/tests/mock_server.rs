@@ -0,0 +1,172 @@
+use lsp::{
+    lsp_types::{
+        notification::PublishDiagnostics, PublishDiagnosticsParams, TextDocumentSyncCapability,
+    },
+    LogEntry, LogLevel, LspManager, ServerConfig, ServerEvent, ServerStatus,
+};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    thread,
+    time::{Duration, Instant},
+};
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+fn workspace(name: &str) -> PathBuf {
+    let root = std::env::temp_dir().join(format!("lsp-{name}-{}", std::process::id()));
+
+    let _ = fs::remove_dir_all(&root);
+    fs::create_dir_all(&root).unwrap();
+    fs::write(root.join(".git"), "").unwrap();
+
+    root
+}
+
+fn manager(root: &Path) -> LspManager {
+    let config = ServerConfig::new(
+        "mock",
+        env!("CARGO_BIN_EXE_mock-language-server"),
+        &[],
+        &["plaintext"],
+    );
+
+    LspManager::new(vec![config], root.to_path_buf())
+}
+
+/// Polls until `found` picks something out of the events, or panics.
+fn wait_for<T>(manager: &mut LspManager, mut found: impl FnMut(&ServerEvent) -> Option<T>) -> T {
+    let start = Instant::now();
+
+    while start.elapsed() < TIMEOUT {
+        if let Some(value) = manager.poll().iter().find_map(|(_, event)| found(event)) {
+            return value;
+        }
+
+        thread::sleep(Duration::from_millis(10));
+    }
+
+    panic!("Timed out waiting for the mock language server");
+}
+
+/// Polls until the server for `path` logged a matching entry, or panics.
+fn wait_for_log(manager: &mut LspManager, path: &Path, found: impl Fn(&LogEntry) -> bool) {
+    let start = Instant::now();
+
+    while start.elapsed() < TIMEOUT {
+        manager.poll();
+
+        let server = manager.server_for(path, "plaintext").unwrap();
+
+        if server.logs().iter().any(&found) {
+            return;
+        }
+
+        thread::sleep(Duration::from_millis(10));
+    }
+
+    panic!("Timed out waiting for the mock language server to log");
+}
+
+fn diagnostics(event: &ServerEvent) -> Option<PublishDiagnosticsParams> {
+    event.notification::<PublishDiagnostics>()
+}
+
+#[test]
+fn negotiates_capabilities() {
+    let root = workspace("capabilities");
+    let file = root.join("notes.txt");
+    let mut manager = manager(&root);
+
+    manager.sync_document(&file, "plaintext", 0, "hello");
+    wait_for(&mut manager, diagnostics);
+
+    let server = manager.server_for(&file, "plaintext").unwrap();
+
+    assert_eq!(server.status(), &ServerStatus::Running);
+    assert_eq!(server.root(), root.as_path());
+    assert!(matches!(
+        server.capabilities().text_document_sync,
+        Some(TextDocumentSyncCapability::Options(_))
+    ));
+}
+
+#[test]
+fn keeps_documents_in_sync() {
+    let root = workspace("sync");
+    let file = root.join("notes.txt");
+    let mut manager = manager(&root);
+
+    manager.sync_document(&file, "plaintext", 0, "nothing to do");
+    let opened = wait_for(&mut manager, diagnostics);
+    assert!(opened.diagnostics.is_empty());
+
+    // The same version is not sent again.
+    manager.sync_document(&file, "plaintext", 0, "TODO: ignored");
+    manager.sync_document(&file, "plaintext", 1, "TODO: one\nTODO: two");
+    let changed = wait_for(&mut manager, diagnostics);
+
+    assert_eq!(changed.version, Some(1));
+    assert_eq!(changed.diagnostics.len(), 2);
+    assert_eq!(changed.diagnostics[1].range.start.line, 1);
+
+    manager.did_save(&file);
+    wait_for_log(&mut manager, &file, |entry| {
+        entry.message.starts_with("saved file://")
+    });
+
+    manager.retain_documents(&[]);
+    let closed = wait_for(&mut manager, diagnostics);
+    assert!(closed.diagnostics.is_empty());
+}
+
+#[test]
+fn restarts_after_a_crash() {
+    let root = workspace("restart");
+    let file = root.join("notes.txt");
+    let other = root.join("other.txt");
+    let mut manager = manager(&root);
+
+    manager.sync_document(&other, "plaintext", 0, "TODO: reopened after restart");
+    wait_for(&mut manager, diagnostics);
+
+    // Closed right away so the restarted server doesn't crash on it again.
+    manager.sync_document(&file, "plaintext", 0, "CRASH");
+    manager.retain_documents(std::slice::from_ref(&other));
+    wait_for(&mut manager, |event| {
+        matches!(event, ServerEvent::Exited).then_some(())
+    });
+
+    let reopened = wait_for(&mut manager, diagnostics);
+    let server = manager.server_for(&file, "plaintext").unwrap();
+
+    assert_eq!(server.restarts, 1);
+    assert_eq!(reopened.diagnostics.len(), 1);
+
+    wait_for_log(&mut manager, &file, |entry| {
+        entry.level == LogLevel::Stderr && entry.message == "crashing on request"
+    });
+}
+
+#[test]
+fn reports_servers_that_fail_to_start() {
+    let root = workspace("missing");
+    let file = root.join("notes.txt");
+    let config = ServerConfig::new(
+        "missing",
+        "does-not-exist-language-server",
+        &[],
+        &["plaintext"],
+    );
+    let mut manager = LspManager::new(vec![config], root.clone());
+
+    manager.sync_document(&file, "plaintext", 0, "hello");
+
+    let server = manager.server_for(&file, "plaintext").unwrap();
+
+    assert!(matches!(server.status(), ServerStatus::Failed(_)));
+    assert!(server
+        .logs()
+        .iter()
+        .any(|entry| entry.level == LogLevel::Error));
+}
@@ -0,0 +1,41 @@
+use lsp::{
+    lsp_types::{Position, Range},
+    position,
+};
+
+const TEXT: &str = "let a = 1;\r\nconst 𝒳 = \"é\";\nlast";
+
+#[test]
+fn converts_positions_to_offsets() {
+    let offset = |line, character| position::to_offset(TEXT, Position { line, character });
+
+    assert_eq!(offset(0, 4), 4);
+    // Past the end of the line stops before the line break.
+    assert_eq!(offset(0, 40), 10);
+    // `𝒳` is two UTF-16 code units but four bytes.
+    assert_eq!(offset(1, 8), 12 + 10);
+    assert_eq!(offset(1, 13), 12 + 16);
+    assert_eq!(offset(2, 2), TEXT.len() - 2);
+    assert_eq!(offset(9, 0), TEXT.len());
+}
+
+#[test]
+fn converts_offsets_to_positions() {
+    for (line, character) in [(0, 0), (0, 10), (1, 0), (1, 8), (1, 13), (2, 4)] {
+        let position = Position { line, character };
+        let offset = position::to_offset(TEXT, position);
+
+        assert_eq!(position::from_offset(TEXT, offset), position);
+    }
+}
+
+#[test]
+fn converts_ranges() {
+    let range = Range {
+        start: Position::new(1, 6),
+        end: Position::new(1, 8),
+    };
+
+    assert_eq!(&TEXT[position::to_range(TEXT, range)], "𝒳");
+    assert_eq!(position::from_range(TEXT, 18..22), range);
+}
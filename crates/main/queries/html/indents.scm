@@ -0,0 +1,9 @@
+[
+  (element)
+  (script_element)
+  (style_element)
+] @indent
+
+[
+  (end_tag)
+] @outdent
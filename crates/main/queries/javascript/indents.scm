@@ -0,0 +1,25 @@
+[
+  (array)
+  (object)
+  (object_pattern)
+  (array_pattern)
+  (arguments)
+  (formal_parameters)
+  (statement_block)
+  (class_body)
+  (switch_body)
+  (switch_case)
+  (switch_default)
+  (named_imports)
+  (export_clause)
+  (parenthesized_expression)
+  (jsx_element)
+  (jsx_self_closing_element)
+] @indent
+
+[
+  "}"
+  "]"
+  ")"
+  (jsx_closing_element)
+] @outdent
@@ -0,0 +1,9 @@
+[
+  (array)
+  (object)
+] @indent
+
+[
+  "}"
+  "]"
+] @outdent
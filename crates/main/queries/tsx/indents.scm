@@ -0,0 +1,8 @@
+; inherits: typescript
+
+[
+  (jsx_element)
+  (jsx_self_closing_element)
+] @indent
+
+(jsx_closing_element) @outdent
@@ -0,0 +1,30 @@
+[
+  (array)
+  (object)
+  (object_pattern)
+  (array_pattern)
+  (arguments)
+  (formal_parameters)
+  (statement_block)
+  (class_body)
+  (switch_body)
+  (switch_case)
+  (switch_default)
+  (named_imports)
+  (export_clause)
+  (parenthesized_expression)
+  (interface_body)
+  (object_type)
+  (enum_body)
+  (type_arguments)
+  (type_parameters)
+] @indent
+
+[
+  "}"
+  "]"
+  ")"
+] @outdent
+
+(type_arguments ">" @outdent)
+(type_parameters ">" @outdent)
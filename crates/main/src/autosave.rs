@@ -0,0 +1,394 @@
+//! Saving without being asked: edited buffers are written a while after the
+//! last edit, when the editor loses focus or when the window does. And Save
+//! All, for every buffer with unsaved edits.
+
+use egui::{
+    Align2, Context, DragValue, Id, Key, KeyboardShortcut, Modifiers, RichText, Ui, Window,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    io,
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use crate::{formatting, State};
+
+pub const SAVE_ALL_SHORTCUT: KeyboardShortcut =
+    KeyboardShortcut::new(Modifiers::COMMAND.plus(Modifiers::ALT), Key::S);
+
+/// In milliseconds, when After Delay is picked.
+const DEFAULT_DELAY: u64 = 1000;
+
+/// The longest After Delay waits to try a failed save again; the wait
+/// doubles with every failure until then.
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(tag = "mode", rename_all = "camelCase")]
+pub enum AutoSaveMode {
+    #[default]
+    Off,
+    /// `delay` milliseconds after the last edit.
+    AfterDelay { delay: u64 },
+    /// When another editor, or anything else, gets the focus, or another
+    /// tab is shown.
+    OnFocusChange,
+    /// When the window loses the focus.
+    OnWindowChange,
+}
+
+#[derive(Default)]
+pub struct AutoSave {
+    pub mode: AutoSaveMode,
+    /// The version of every buffer as of the last frame.
+    versions: HashMap<String, i32>,
+    /// Buffers edited since they were saved, and when they last were.
+    pending: HashMap<String, Instant>,
+    /// Buffers whose last save failed, when it did and how many times in a
+    /// row.
+    failed: HashMap<String, (Instant, u32)>,
+    error: Option<String>,
+    /// The focused widget and the active file as of the last frame.
+    focus: (Option<Id>, String),
+    window_focused: bool,
+}
+
+impl AutoSave {
+    pub fn is_pending(&self) -> bool {
+        !self.pending.is_empty()
+    }
+
+    /// The buffers due for a save at `now` when After Delay waits `delay`,
+    /// and how long until the next one is.
+    fn after_delay(&self, delay: Duration, now: Instant) -> (Vec<String>, Option<Duration>) {
+        let mut next = None::<Duration>;
+
+        let due = self
+            .pending
+            .iter()
+            .filter_map(|(path, edited_at)| {
+                let mut left = delay.saturating_sub(now.saturating_duration_since(*edited_at));
+
+                if let Some((failed_at, failures)) = self.failed.get(path) {
+                    let retry = retry_delay(delay, *failures);
+
+                    left =
+                        left.max(retry.saturating_sub(now.saturating_duration_since(*failed_at)));
+                }
+
+                if left.is_zero() {
+                    return Some(path.clone());
+                }
+
+                next = Some(next.map_or(left, |next| next.min(left)));
+                None
+            })
+            .collect();
+
+        (due, next)
+    }
+}
+
+pub fn handle_input(ctx: &Context, state: &mut State) {
+    // Before Cmd+S, which would take Cmd+Alt+S too.
+    if ctx.input_mut(|input| input.consume_shortcut(&SAVE_ALL_SHORTCUT)) {
+        save_all(ctx, state);
+    }
+}
+
+/// Writes the file, formatting it first if it's the active one and its
+/// language formats on save.
+pub fn save(ctx: &Context, state: &mut State, path: &str) -> io::Result<()> {
+    if path == state.file_store.active_file {
+        formatting::before_save(ctx, state);
+    }
+
+    let Some(version) = state.file_store.files.get(path).map(|file| file.version) else {
+        return Ok(());
+    };
+
+    if let Err(error) = state.file_store.save(path) {
+        let autosave = &mut state.autosave;
+        let failures = autosave
+            .failed
+            .get(path)
+            .map_or(0, |(_, failures)| *failures);
+
+        autosave
+            .failed
+            .insert(path.into(), (Instant::now(), failures + 1));
+        autosave.error = Some(format!("Couldn't save {path}: {error}"));
+
+        return Err(error);
+    }
+
+    state.lsp.did_save(Path::new(path));
+    state.autosave.versions.insert(path.into(), version);
+    state.autosave.pending.remove(path);
+    state.autosave.failed.remove(path);
+
+    Ok(())
+}
+
+pub fn save_active(ctx: &Context, state: &mut State) {
+    let path = state.file_store.active_file.clone();
+
+    // The error is shown by `show_error`; the file stays unsaved.
+    let _ = save(ctx, state, &path);
+}
+
+/// Writes every buffer that differs from its file.
+pub fn save_all(ctx: &Context, state: &mut State) {
+    let mut unsaved: Vec<String> = state
+        .file_store
+        .files
+        .values()
+        .filter(|file| !file.is_saved())
+        .map(|file| file.path.clone())
+        .collect();
+    unsaved.sort();
+
+    for path in unsaved {
+        let _ = save(ctx, state, &path);
+    }
+}
+
+/// Notices edits and saves them when the mode says so.
+pub fn update(ctx: &Context, state: &mut State) {
+    let now = Instant::now();
+    let files = &state.file_store.files;
+    let autosave = &mut state.autosave;
+
+    for (path, file) in files {
+        // Opening a file isn't an edit.
+        match autosave.versions.insert(path.clone(), file.version) {
+            Some(version) if version != file.version && autosave.mode != AutoSaveMode::Off => {
+                autosave.pending.insert(path.clone(), now);
+            }
+            _ => {}
+        }
+    }
+
+    // Nothing waits for a save while autosave is off.
+    if autosave.mode == AutoSaveMode::Off {
+        autosave.pending.clear();
+        autosave.failed.clear();
+    }
+
+    autosave.versions.retain(|path, _| files.contains_key(path));
+    autosave.pending.retain(|path, _| files.contains_key(path));
+    autosave.failed.retain(|path, _| files.contains_key(path));
+
+    let focus = (
+        ctx.memory(|memory| memory.focused()),
+        state.file_store.active_file.clone(),
+    );
+    let focus_changed = focus != autosave.focus;
+    let window_focused = ctx.input(|input| input.viewport().focused.unwrap_or(true));
+    let window_left = autosave.window_focused && !window_focused;
+
+    autosave.focus = focus;
+    autosave.window_focused = window_focused;
+
+    let due: Vec<String> = match autosave.mode {
+        AutoSaveMode::Off => Vec::new(),
+        AutoSaveMode::AfterDelay { delay } => {
+            let (due, next) = autosave.after_delay(Duration::from_millis(delay), now);
+
+            if let Some(next) = next {
+                ctx.request_repaint_after(next);
+            }
+
+            due
+        }
+        AutoSaveMode::OnFocusChange if focus_changed || window_left => {
+            autosave.pending.keys().cloned().collect()
+        }
+        AutoSaveMode::OnWindowChange if window_left => autosave.pending.keys().cloned().collect(),
+        AutoSaveMode::OnFocusChange | AutoSaveMode::OnWindowChange => Vec::new(),
+    };
+
+    for path in due {
+        let saved = state
+            .file_store
+            .files
+            .get(&path)
+            .is_some_and(|file| file.is_saved());
+
+        // Saved some other way since, nothing to write.
+        if saved {
+            state.autosave.pending.remove(&path);
+            state.autosave.failed.remove(&path);
+            continue;
+        }
+
+        // Kept pending to try again when saving fails.
+        let _ = save(ctx, state, &path);
+    }
+}
+
+/// How long After Delay waits after the `failures`th failed save in a row.
+fn retry_delay(delay: Duration, failures: u32) -> Duration {
+    delay
+        .saturating_mul(2u32.saturating_pow(failures))
+        .min(MAX_RETRY_DELAY)
+}
+
+/// The Auto Save submenu.
+pub fn menu(ui: &mut Ui, state: &mut State) {
+    let mode = &mut state.autosave.mode;
+
+    ui.radio_value(mode, AutoSaveMode::Off, "Off");
+
+    let after_delay = matches!(mode, AutoSaveMode::AfterDelay { .. });
+
+    if ui.radio(after_delay, "After Delay").clicked() && !after_delay {
+        *mode = AutoSaveMode::AfterDelay {
+            delay: DEFAULT_DELAY,
+        };
+    }
+
+    if let AutoSaveMode::AfterDelay { delay } = mode {
+        ui.horizontal(|ui| {
+            ui.add_space(24.0);
+            ui.add(
+                DragValue::new(delay)
+                    .range(100..=60_000)
+                    .speed(50)
+                    .suffix(" ms"),
+            );
+        });
+    }
+
+    ui.radio_value(mode, AutoSaveMode::OnFocusChange, "On Focus Change");
+    ui.radio_value(mode, AutoSaveMode::OnWindowChange, "On Window Change");
+}
+
+/// The files waiting for their autosave, in the menu bar.
+pub fn indicator(ui: &mut Ui, state: &State) {
+    let autosave = &state.autosave;
+
+    if autosave.mode == AutoSaveMode::Off || !autosave.is_pending() {
+        return;
+    }
+
+    let mut pending: Vec<&str> = autosave.pending.keys().map(String::as_str).collect();
+    pending.sort();
+
+    let text = match pending.len() {
+        1 => "● 1 autosave pending".to_string(),
+        count => format!("● {count} autosaves pending"),
+    };
+
+    ui.weak(text).on_hover_text(pending.join("\n"));
+}
+
+pub fn show_error(ctx: &Context, state: &mut State) {
+    let Some(error) = &state.autosave.error else {
+        return;
+    };
+
+    let mut dismissed = false;
+
+    Window::new("Saving failed")
+        .collapsible(false)
+        .resizable(false)
+        .anchor(Align2::RIGHT_BOTTOM, [-12.0, -12.0])
+        .show(ctx, |ui| {
+            ui.set_max_width(360.0);
+            ui.label(RichText::new(error).color(state.theme.error.main));
+
+            if ui.button("Dismiss").clicked() {
+                dismissed = true;
+            }
+        });
+
+    if dismissed {
+        state.autosave.error = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DELAY: Duration = Duration::from_secs(1);
+
+    #[test]
+    fn doubles_the_retry_delay_up_to_a_minute() {
+        let delays: Vec<u64> = (0..8)
+            .map(|failures| retry_delay(DELAY, failures).as_secs())
+            .collect();
+
+        assert_eq!(delays, [1, 2, 4, 8, 16, 32, 60, 60]);
+        assert_eq!(retry_delay(DELAY, u32::MAX), MAX_RETRY_DELAY);
+        assert_eq!(
+            retry_delay(Duration::from_millis(100), 2),
+            Duration::from_millis(400)
+        );
+    }
+
+    #[test]
+    fn saves_after_the_delay() {
+        let now = Instant::now();
+        let mut autosave = AutoSave::default();
+
+        autosave.pending.insert("a".into(), now - DELAY);
+        autosave.pending.insert("b".into(), now - DELAY / 4);
+
+        assert_eq!(
+            autosave.after_delay(DELAY, now),
+            (vec!["a".to_string()], Some(DELAY * 3 / 4))
+        );
+    }
+
+    #[test]
+    fn backs_off_after_failed_saves() {
+        let now = Instant::now();
+        let mut autosave = AutoSave::default();
+
+        autosave.pending.insert("a".into(), now - DELAY * 10);
+        autosave.failed.insert("a".into(), (now - DELAY, 2));
+
+        assert_eq!(autosave.after_delay(DELAY, now), (vec![], Some(DELAY * 3)));
+        assert_eq!(
+            autosave.after_delay(DELAY, now + DELAY * 3),
+            (vec!["a".to_string()], None)
+        );
+
+        autosave.failed.insert("a".into(), (now, 10));
+
+        assert_eq!(
+            autosave.after_delay(DELAY, now),
+            (vec![], Some(MAX_RETRY_DELAY))
+        );
+    }
+
+    #[test]
+    fn records_edits_only_while_on() {
+        let ctx = Context::default();
+        let mut state = State::default();
+        let path = "./Cargo.toml";
+
+        state.file_store.insert(&path.into(), true);
+        update(&ctx, &mut state);
+        state.file_store.files.get_mut(path).unwrap().version += 1;
+        update(&ctx, &mut state);
+
+        assert!(!state.autosave.is_pending());
+
+        // Long enough for nothing to be written.
+        state.autosave.mode = AutoSaveMode::AfterDelay { delay: 60_000 };
+        state.file_store.files.get_mut(path).unwrap().version += 1;
+        update(&ctx, &mut state);
+
+        assert!(state.autosave.pending.contains_key(path));
+
+        state.autosave.mode = AutoSaveMode::Off;
+        update(&ctx, &mut state);
+
+        assert!(!state.autosave.is_pending());
+    }
+}
@@ -0,0 +1,213 @@
+//! The bar above the editor: the folders down to the file, then the
+//! symbols around the cursor. Folders list what's next to them, symbols
+//! their peers, to go there.
+
+use egui::{vec2, Color32, Id, Label, RichText, ScrollArea, Stroke, TextStyle, Ui, Vec2};
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+use theme::Theme;
+
+use crate::{
+    editor, editor_groups,
+    file_store::FileStore,
+    languages,
+    symbols::{self, Symbol},
+    State,
+};
+
+const MAX_MENU_HEIGHT: f32 = 420.0;
+
+#[derive(Default)]
+pub struct Breadcrumbs {
+    /// The symbols of every file, as of its version.
+    outlines: HashMap<String, (i32, Vec<Symbol>)>,
+}
+
+impl Breadcrumbs {
+    fn outline(&mut self, path: &str, version: i32, text: &str) -> &[Symbol] {
+        let (cached, symbols) = self
+            .outlines
+            .entry(path.into())
+            .or_insert_with(|| (-1, Vec::new()));
+
+        if *cached != version {
+            *cached = version;
+            *symbols = languages::from_path(path)
+                .map(|language| symbols::outline(language, text))
+                .unwrap_or_default();
+        }
+
+        symbols
+    }
+}
+
+enum Action {
+    Open(String),
+    Reveal(usize),
+}
+
+/// The bar of the file shown in the group at `index`.
+pub fn show(ui: &mut Ui, state: &mut State, index: usize, path: &str) {
+    let group = state.file_store.groups.groups[index].id;
+    let id: Id = editor_groups::editor_id(group, path).into();
+    let Some(file) = state.file_store.files.get(path) else {
+        return;
+    };
+
+    let cursor = editor::byte_offset(
+        &file.content,
+        editor::get_selection(ui.ctx(), id, file).primary.index,
+    );
+    let outline = state.breadcrumbs.outline(path, file.version, &file.content);
+    let theme = &state.theme;
+    let mut action = None;
+
+    let (.., max_rect) = ui.allocate_space(ui.available_size_before_wrap());
+
+    ui.spacing_mut().item_spacing = Vec2::default();
+
+    ui.allocate_ui_at_rect(max_rect, |ui| {
+        egui::Frame::none()
+            .fill(Color32::BLACK)
+            .inner_margin(vec2(5.0, 4.0))
+            .outer_margin(vec2(0.0, 0.0))
+            .stroke(Stroke::NONE)
+            .show(ui, |ui| {
+                ui.set_width(ui.available_width());
+
+                ui.horizontal(|ui| {
+                    ui.style_mut().visuals.button_frame = false;
+
+                    let mut components = FileStore::get_components(path).into_iter().peekable();
+
+                    while let Some((dirent_name, dirent_path)) = components.next() {
+                        let dirent_path = PathBuf::from(dirent_path);
+                        let folder = match dirent_path.parent() {
+                            Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+                            _ => dirent_path.clone(),
+                        };
+
+                        segment(ui, &dirent_name, theme, |ui| {
+                            entries(ui, &folder, &dirent_path, &mut action);
+                        });
+
+                        if components.peek().is_some() {
+                            separator(ui, "/", theme);
+                        }
+                    }
+
+                    let symbols = symbols::path_at(outline, cursor);
+
+                    for (symbol, peers) in &symbols {
+                        separator(ui, ">", theme);
+                        segment(ui, &symbol.label(), theme, |ui| {
+                            peer_symbols(ui, peers, Some(symbol), &mut action);
+                        });
+                    }
+
+                    // Somewhere to go from outside every symbol too.
+                    if symbols.is_empty() && !outline.is_empty() {
+                        separator(ui, ">", theme);
+                        segment(ui, "…", theme, |ui| {
+                            peer_symbols(ui, outline, None, &mut action);
+                        });
+                    }
+                });
+            });
+    });
+
+    match action {
+        Some(Action::Open(path)) => {
+            state.file_store.focus_group(index);
+            state.file_store.insert(&path, true);
+        }
+        Some(Action::Reveal(offset)) => {
+            state.file_store.focus_group(index);
+
+            if let Some(file) = state.file_store.files.get(path) {
+                editor::reveal(ui.ctx(), id, file, offset);
+            }
+        }
+        None => {}
+    }
+}
+
+fn segment(ui: &mut Ui, text: &str, theme: &Theme, menu: impl FnOnce(&mut Ui)) {
+    ui.menu_button(RichText::new(text).color(theme.text_color.primary), |ui| {
+        ScrollArea::vertical()
+            .max_height(MAX_MENU_HEIGHT)
+            .show(ui, menu);
+    });
+}
+
+fn separator(ui: &mut Ui, text: &str, theme: &Theme) {
+    ui.add(Label::new(
+        RichText::new(format!("{:^width$}", text, width = 5)).color(theme.text_color.primary),
+    ));
+}
+
+/// The folder's entries, folders first; folders open as submenus.
+fn entries(ui: &mut Ui, folder: &Path, current: &Path, action: &mut Option<Action>) {
+    ui.set_min_width(200.0);
+
+    let Ok(read_dir) = fs::read_dir(folder) else {
+        ui.weak("Couldn't read the folder");
+        return;
+    };
+
+    let mut listed: Vec<(bool, String, PathBuf)> = read_dir
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            let is_dir = entry.file_type().ok()?.is_dir();
+
+            Some((
+                !is_dir,
+                entry.file_name().to_string_lossy().into(),
+                entry.path(),
+            ))
+        })
+        .collect();
+    listed.sort();
+
+    if listed.is_empty() {
+        ui.weak("Empty folder");
+    }
+
+    for (is_file, name, path) in listed {
+        let selected = path == current || current.starts_with(&path);
+
+        if is_file {
+            if ui.selectable_label(selected, name).clicked() {
+                *action = Some(Action::Open(FileStore::get_file_path(&path)));
+                ui.close_menu();
+            }
+        } else {
+            ui.menu_button(format!("{name}/"), |ui| {
+                ScrollArea::vertical()
+                    .max_height(MAX_MENU_HEIGHT)
+                    .show(ui, |ui| entries(ui, &path, current, action));
+            });
+        }
+    }
+}
+
+fn peer_symbols(
+    ui: &mut Ui,
+    peers: &[Symbol],
+    current: Option<&Symbol>,
+    action: &mut Option<Action>,
+) {
+    ui.set_min_width(200.0);
+
+    for peer in peers {
+        let text = RichText::new(peer.label()).text_style(TextStyle::Monospace);
+
+        if ui.selectable_label(Some(peer) == current, text).clicked() {
+            *action = Some(Action::Reveal(peer.range.start));
+            ui.close_menu();
+        }
+    }
+}
@@ -0,0 +1,450 @@
+//! Cmd+Shift+P: every command, tasks included, searchable by fuzzy match.
+
+use egui::{
+    text::LayoutJob, Align2, Area, Context, Frame, Id, Key, KeyboardShortcut, Modifiers, Order,
+    ScrollArea, Sense, TextEdit, TextFormat, TextStyle, Ui, Vec2,
+};
+use encoding_rs::Encoding;
+use fuzzy_matcher::{skim::SkimMatcherV2, FuzzyMatcher};
+use theme::Theme;
+
+use crate::{
+    autosave, compare,
+    editor_groups::{self, Direction},
+    file_history, formatting,
+    git_blame::BlameMode,
+    navigation, rename, source_control, tasks, text_encoding, State,
+};
+
+pub const SHORTCUT: KeyboardShortcut =
+    KeyboardShortcut::new(Modifiers::COMMAND.plus(Modifiers::SHIFT), Key::P);
+
+const WIDTH: f32 = 520.0;
+const MAX_VISIBLE_ITEMS: usize = 12;
+
+/// Typed in front of the query to list only the tasks.
+pub const RUN_TASK_PREFIX: &str = "Run Task: ";
+
+#[derive(Default)]
+pub struct CommandPalette {
+    open: bool,
+    query: String,
+    selected: usize,
+    /// Set when opened, so the query gets the focus once.
+    focus: bool,
+}
+
+impl CommandPalette {
+    pub fn open(&mut self, query: &str) {
+        self.open = true;
+        self.query = query.into();
+        self.selected = 0;
+        self.focus = true;
+    }
+}
+
+#[derive(Clone)]
+enum Command {
+    RunTask(tasks::Task),
+    CancelTasks,
+    ReloadTasks,
+    SaveAll,
+    ToggleOutput,
+    ToggleProblems,
+    ToggleTerminal,
+    ToggleSourceControl,
+    SplitEditor(Direction),
+    FocusNextGroup,
+    CloseGroup,
+    TogglePinned,
+    ToggleBlame(BlameMode),
+    FileHistory,
+    CompareWithSaved,
+    CompareWithClipboard,
+    CompareWith(String),
+    ReopenWithEncoding(&'static Encoding),
+    SaveWithEncoding(&'static Encoding),
+    FormatDocument,
+    GoToDefinition,
+    FindReferences,
+    RenameSymbol,
+}
+
+struct Item {
+    label: String,
+    detail: Option<KeyboardShortcut>,
+    command: Command,
+}
+
+struct Match {
+    item: usize,
+    indices: Vec<usize>,
+}
+
+pub fn handle_input(ctx: &Context, state: &mut State) {
+    if ctx.input_mut(|input| input.consume_shortcut(&SHORTCUT)) {
+        state.command_palette.open("");
+    }
+}
+
+fn items(state: &mut State) -> Vec<Item> {
+    let item = |label: &str, detail, command| Item {
+        label: label.into(),
+        detail,
+        command,
+    };
+
+    let mut items: Vec<Item> = state
+        .tasks
+        .list()
+        .iter()
+        .map(|task| Item {
+            label: format!("{RUN_TASK_PREFIX}{}", task.label),
+            detail: None,
+            command: Command::RunTask(task.clone()),
+        })
+        .collect();
+
+    if state.tasks.is_running() {
+        items.push(item(
+            "Tasks: Cancel Running Tasks",
+            None,
+            Command::CancelTasks,
+        ));
+    }
+
+    items.extend([
+        item("Tasks: Reload Tasks", None, Command::ReloadTasks),
+        item(
+            "File: Save All",
+            Some(autosave::SAVE_ALL_SHORTCUT),
+            Command::SaveAll,
+        ),
+        item("View: Toggle Output", None, Command::ToggleOutput),
+        item("View: Toggle Problems", None, Command::ToggleProblems),
+        item(
+            "View: Toggle Terminal",
+            Some(crate::terminal::TOGGLE_SHORTCUT),
+            Command::ToggleTerminal,
+        ),
+        item(
+            "View: Toggle Source Control",
+            Some(source_control::TOGGLE_SHORTCUT),
+            Command::ToggleSourceControl,
+        ),
+        item(
+            "View: Split Editor Right",
+            Some(editor_groups::SPLIT_SHORTCUT),
+            Command::SplitEditor(Direction::Right),
+        ),
+        item(
+            "View: Split Editor Down",
+            None,
+            Command::SplitEditor(Direction::Down),
+        ),
+        item(
+            "View: Focus Next Editor Group",
+            None,
+            Command::FocusNextGroup,
+        ),
+        item("View: Close Editor Group", None, Command::CloseGroup),
+        item(
+            "Git: Toggle Line Blame",
+            None,
+            Command::ToggleBlame(BlameMode::CurrentLine),
+        ),
+        item(
+            "Git: Toggle Blame Gutter",
+            None,
+            Command::ToggleBlame(BlameMode::Gutter),
+        ),
+        item("Git: Open File History", None, Command::FileHistory),
+        item("File: Compare with Saved", None, Command::CompareWithSaved),
+        item(
+            "File: Compare with Clipboard",
+            None,
+            Command::CompareWithClipboard,
+        ),
+        item(
+            "Format Document",
+            Some(formatting::SHORTCUT),
+            Command::FormatDocument,
+        ),
+        item(
+            "Go to Definition",
+            Some(navigation::DEFINITION_SHORTCUT),
+            Command::GoToDefinition,
+        ),
+        item(
+            "Find All References",
+            Some(navigation::REFERENCES_SHORTCUT),
+            Command::FindReferences,
+        ),
+        item(
+            "Rename Symbol",
+            Some(rename::SHORTCUT),
+            Command::RenameSymbol,
+        ),
+    ]);
+
+    let active = &state.file_store.active_file;
+    let group = state.file_store.groups.focused();
+
+    if !active.is_empty() {
+        let label = match group.is_pinned(active) {
+            true => "View: Unpin Editor",
+            false => "View: Pin Editor",
+        };
+
+        items.push(item(label, None, Command::TogglePinned));
+
+        for encoding in text_encoding::ALL {
+            items.push(Item {
+                label: format!("File: Reopen with Encoding: {}", encoding.name()),
+                detail: None,
+                command: Command::ReopenWithEncoding(encoding),
+            });
+        }
+
+        for encoding in text_encoding::ALL {
+            items.push(Item {
+                label: format!("File: Save with Encoding: {}", encoding.name()),
+                detail: None,
+                command: Command::SaveWithEncoding(encoding),
+            });
+        }
+    }
+
+    let mut others: Vec<Item> = state
+        .file_store
+        .files
+        .values()
+        .filter(|file| !active.is_empty() && file.path != *active)
+        .map(|file| Item {
+            label: format!("File: Compare Active File with {}", file.name),
+            detail: None,
+            command: Command::CompareWith(file.path.clone()),
+        })
+        .collect();
+    others.sort_by(|a, b| a.label.cmp(&b.label));
+    items.extend(others);
+
+    items
+}
+
+fn filter(items: &[Item], query: &str) -> Vec<Match> {
+    let matcher = SkimMatcherV2::default();
+    let mut matches: Vec<(i64, Match)> = items
+        .iter()
+        .enumerate()
+        .filter_map(|(item, Item { label, .. })| {
+            if query.trim().is_empty() {
+                return Some((
+                    0,
+                    Match {
+                        item,
+                        indices: Vec::new(),
+                    },
+                ));
+            }
+
+            let (score, indices) = matcher.fuzzy_indices(label, query.trim())?;
+            Some((score, Match { item, indices }))
+        })
+        .collect();
+
+    // Stable, so equally good matches keep their order.
+    matches.sort_by(|(a, _), (b, _)| b.cmp(a));
+    matches.into_iter().map(|(_, m)| m).collect()
+}
+
+pub fn show(ctx: &Context, state: &mut State) {
+    if !state.command_palette.open {
+        return;
+    }
+
+    let items = items(state);
+    let palette = &mut state.command_palette;
+    let matches = filter(&items, &palette.query);
+    let key = |key: Key| ctx.input_mut(|input| input.consume_key(Modifiers::NONE, key));
+
+    let mut scroll = false;
+
+    palette.selected = palette.selected.min(matches.len().saturating_sub(1));
+
+    if key(Key::ArrowDown) {
+        palette.selected = (palette.selected + 1) % matches.len().max(1);
+        scroll = true;
+    }
+
+    if key(Key::ArrowUp) {
+        scroll = true;
+        palette.selected = palette
+            .selected
+            .checked_sub(1)
+            .unwrap_or(matches.len().saturating_sub(1));
+    }
+
+    if key(Key::Escape) {
+        palette.open = false;
+        return;
+    }
+
+    let opened = palette.focus;
+    let mut chosen = key(Key::Enter).then_some(palette.selected);
+    let row_height = ctx.style().text_styles[&TextStyle::Body].size + 10.0;
+    let id = Id::new("command-palette");
+
+    let response = Area::new(id)
+        .order(Order::Foreground)
+        .anchor(Align2::CENTER_TOP, [0.0, 48.0])
+        .show(ctx, |ui| {
+            Frame::popup(ui.style()).show(ui, |ui| {
+                ui.set_width(WIDTH);
+
+                let input = ui.add(
+                    TextEdit::singleline(&mut palette.query)
+                        .id(id.with("query"))
+                        .hint_text("Type a command or task")
+                        .desired_width(f32::INFINITY),
+                );
+
+                if palette.focus {
+                    input.request_focus();
+                    palette.focus = false;
+                }
+
+                if input.changed() {
+                    palette.selected = 0;
+                }
+
+                ui.add_space(4.0);
+
+                if matches.is_empty() {
+                    ui.weak("No matching commands");
+                }
+
+                ScrollArea::vertical()
+                    .max_height(row_height * MAX_VISIBLE_ITEMS as f32)
+                    .show(ui, |ui| {
+                        ui.spacing_mut().item_spacing = Vec2::ZERO;
+
+                        for (index, m) in matches.iter().enumerate() {
+                            let selected = index == palette.selected;
+                            let response =
+                                row(ui, &items[m.item], m, selected, row_height, &state.theme);
+
+                            if selected && scroll {
+                                response.scroll_to_me(None);
+                            }
+
+                            if response.clicked() {
+                                chosen = Some(index);
+                            }
+                        }
+                    });
+            });
+        })
+        .response;
+
+    // The click that opened it from a menu is somewhere else too.
+    if !opened && response.clicked_elsewhere() {
+        palette.open = false;
+    }
+
+    if let Some(m) = chosen.and_then(|index| matches.get(index)) {
+        palette.open = false;
+        run(ctx, state, items[m.item].command.clone());
+    }
+}
+
+fn row(
+    ui: &mut Ui,
+    item: &Item,
+    m: &Match,
+    selected: bool,
+    height: f32,
+    theme: &Theme,
+) -> egui::Response {
+    let (rect, response) =
+        ui.allocate_exact_size(Vec2::new(ui.available_width(), height), Sense::click());
+    let painter = ui.painter();
+
+    if selected {
+        painter.rect_filled(rect, theme.rounding, theme.action.selected);
+    } else if response.hovered() {
+        painter.rect_filled(rect, theme.rounding, theme.action.hover);
+    }
+
+    let font_id = TextStyle::Body.resolve(ui.style());
+    let mut job = LayoutJob::default();
+
+    for (index, char) in item.label.chars().enumerate() {
+        job.append(
+            &char.to_string(),
+            0.0,
+            TextFormat::simple(
+                font_id.clone(),
+                match m.indices.contains(&index) {
+                    true => theme.primary.main,
+                    false => theme.text_color.primary,
+                },
+            ),
+        );
+    }
+
+    let galley = ui.fonts(|fonts| fonts.layout_job(job));
+    let position = egui::pos2(rect.left() + 8.0, rect.center().y - galley.size().y / 2.0);
+
+    painter.galley(position, galley, theme.text_color.primary);
+
+    if let Some(shortcut) = &item.detail {
+        painter.text(
+            rect.right_center() - Vec2::new(8.0, 0.0),
+            Align2::RIGHT_CENTER,
+            ui.ctx().format_shortcut(shortcut),
+            font_id,
+            theme.text_color.secondary,
+        );
+    }
+
+    response
+}
+
+fn run(ctx: &Context, state: &mut State, command: Command) {
+    match command {
+        Command::RunTask(task) => tasks::run(ctx, state, task),
+        Command::CancelTasks => state.tasks.cancel_all(),
+        Command::ReloadTasks => {
+            state.tasks.reload();
+            state.command_palette.open(RUN_TASK_PREFIX);
+        }
+        Command::SaveAll => autosave::save_all(ctx, state),
+        Command::ToggleOutput => state.tasks.output_open = !state.tasks.output_open,
+        Command::ToggleProblems => state.problems.open = !state.problems.open,
+        Command::ToggleTerminal => state.terminal.toggle(ctx),
+        Command::ToggleSourceControl => {
+            state.source_control.open = !state.source_control.open;
+        }
+        Command::SplitEditor(direction) => editor_groups::split(ctx, state, direction),
+        Command::FocusNextGroup => editor_groups::focus_next(ctx, state),
+        Command::CloseGroup => editor_groups::close_focused(state),
+        Command::TogglePinned => {
+            let file_store = &mut state.file_store;
+            let focused = file_store.groups.focused;
+
+            file_store.groups.groups[focused].toggle_pinned(&file_store.active_file);
+        }
+        Command::ToggleBlame(mode) => state.git_blame.toggle(mode),
+        Command::FileHistory => file_history::open(state),
+        Command::CompareWithSaved => compare::with_saved(state),
+        Command::CompareWithClipboard => compare::with_clipboard(state),
+        Command::CompareWith(path) => compare::with_file(state, &path),
+        Command::ReopenWithEncoding(encoding) => text_encoding::reopen_with(state, encoding),
+        Command::SaveWithEncoding(encoding) => text_encoding::save_with(ctx, state, encoding),
+        Command::FormatDocument => formatting::format(ctx, state, false),
+        Command::GoToDefinition => navigation::go_to_definition(ctx, state),
+        Command::FindReferences => navigation::find_references(ctx, state),
+        Command::RenameSymbol => rename::start(ctx, state),
+    }
+}
@@ -0,0 +1,153 @@
+//! Two texts in the diff view: the active file next to its saved version,
+//! the clipboard or another open file.
+
+use components::diff_view::{DiffLayout, DiffView};
+use egui::{Context, Id, Window};
+
+use crate::{file_store::FileStore, syntax_highlighter, State};
+
+#[derive(Default)]
+pub struct Compare {
+    pub open: bool,
+    title: String,
+    old: Source,
+    new: Source,
+    layout: DiffLayout,
+    error: Option<String>,
+}
+
+/// Open files follow their edits; other texts are read once.
+enum Source {
+    Text(String),
+    Buffer(String),
+}
+
+impl Default for Source {
+    fn default() -> Self {
+        Source::Text(String::new())
+    }
+}
+
+impl Source {
+    fn text<'a>(&'a self, state: &'a State) -> &'a str {
+        match self {
+            Source::Text(text) => text,
+            Source::Buffer(path) => state
+                .file_store
+                .files
+                .get(path)
+                .map_or("", |file| file.content.as_str()),
+        }
+    }
+}
+
+fn start(state: &mut State, title: String, old: Source) {
+    let compare = &mut state.compare;
+
+    compare.open = true;
+    compare.title = title;
+    compare.old = old;
+    compare.new = Source::Buffer(state.file_store.active_file.clone());
+    compare.error = None;
+}
+
+/// The unsaved edits of the active file.
+pub fn with_saved(state: &mut State) {
+    let Some(file) = state.file_store.get_active_file() else {
+        return;
+    };
+
+    let title = format!("{} (saved) ↔ {}", file.name, file.name);
+    let saved = FileStore::read_saved(&file.path);
+
+    match saved {
+        Ok(saved) => start(state, title, Source::Text(saved)),
+        Err(error) => {
+            start(state, title, Source::Text(String::new()));
+            state.compare.error = Some(error.to_string());
+        }
+    }
+}
+
+pub fn with_clipboard(state: &mut State) {
+    let Some(file) = state.file_store.get_active_file() else {
+        return;
+    };
+
+    let title = format!("Clipboard ↔ {}", file.name);
+    let clipboard = arboard::Clipboard::new().and_then(|mut clipboard| clipboard.get_text());
+
+    match clipboard {
+        Ok(text) => start(state, title, Source::Text(text)),
+        Err(error) => {
+            start(state, title, Source::Text(String::new()));
+            state.compare.error = Some(format!("Couldn't read the clipboard: {error}"));
+        }
+    }
+}
+
+/// Another open file on the left of the active one.
+pub fn with_file(state: &mut State, path: &str) {
+    let (Some(file), Some(other)) = (
+        state.file_store.get_active_file(),
+        state.file_store.files.get(path),
+    ) else {
+        return;
+    };
+
+    let title = format!("{} ↔ {}", other.name, file.name);
+
+    start(state, title, Source::Buffer(path.into()));
+}
+
+pub fn show(ctx: &Context, state: &mut State) {
+    if !state.compare.open {
+        return;
+    }
+
+    let mut open = true;
+    let mut layout = state.compare.layout;
+    let mut swap = false;
+
+    Window::new(&state.compare.title)
+        .id(Id::new("compare"))
+        .open(&mut open)
+        .default_size([900.0, 560.0])
+        .resizable(true)
+        .collapsible(false)
+        .show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.selectable_value(&mut layout, DiffLayout::SideBySide, "Side by Side");
+                ui.selectable_value(&mut layout, DiffLayout::Inline, "Inline");
+                ui.separator();
+                swap = ui.button("Swap Sides").clicked();
+            });
+
+            if let Some(error) = &state.compare.error {
+                ui.colored_label(state.theme.error.main, error);
+            }
+
+            ui.separator();
+
+            let highlight = |text: &str| syntax_highlighter::highlight(text.to_string());
+
+            ui.add(
+                DiffView::new(
+                    "compare-diff",
+                    state.compare.old.text(state),
+                    state.compare.new.text(state),
+                )
+                .layout(layout)
+                .highlighter(&highlight),
+            );
+        });
+
+    let compare = &mut state.compare;
+
+    compare.open = open;
+    compare.layout = layout;
+
+    if swap {
+        std::mem::swap(&mut compare.old, &mut compare.new);
+    }
+}
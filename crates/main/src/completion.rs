@@ -0,0 +1,1034 @@
+use egui::{
+    text::{CCursor, LayoutJob},
+    text_edit::TextEditOutput,
+    Align2, Area, Color32, Context, Event, FontId, Frame, Id, Key, KeyboardShortcut, Modifiers,
+    Order, Rect, RichText, ScrollArea, Sense, TextFormat, TextStyle, Ui, Vec2,
+};
+use fuzzy_matcher::{skim::SkimMatcherV2, FuzzyMatcher};
+use lsp::{
+    lsp_types::{
+        request, CompletionContext, CompletionItem, CompletionItemKind, CompletionParams,
+        CompletionResponse, CompletionTextEdit, CompletionTriggerKind, Documentation,
+        InsertTextFormat, MarkupKind, TextDocumentIdentifier, TextDocumentPositionParams,
+    },
+    position, LspManager, PendingRequest,
+};
+use std::{cmp::Reverse, collections::HashSet, ops::Range, path::Path, time::Duration};
+use theme::Theme;
+
+use crate::{
+    editor,
+    file_store::FileData,
+    indent, language_servers, languages, markdown,
+    snippet::{self, Snippet, SnippetSession, Tabstop},
+};
+
+pub const TRIGGER_SHORTCUT: KeyboardShortcut = KeyboardShortcut::new(Modifiers::CTRL, Key::Space);
+
+const MAX_VISIBLE_ITEMS: usize = 10;
+const LIST_WIDTH: f32 = 320.0;
+const DOCUMENTATION_WIDTH: f32 = 360.0;
+/// How long accepting an item may wait for its additional edits.
+const RESOLVE_TIMEOUT: Duration = Duration::from_millis(300);
+
+/// The completion popup and the snippet being filled in, if any.
+#[derive(Default)]
+pub struct Completion {
+    popup: Option<Popup>,
+    snippet: Option<(Id, SnippetSession)>,
+    /// Text typed this frame, seen before the `TextEdit` handles it.
+    typed: Option<String>,
+    /// The content before this frame's edits, to keep the snippet's tab stops
+    /// where they belong.
+    previous: Option<String>,
+}
+
+struct Popup {
+    id: Id,
+    /// Where the word being completed starts.
+    anchor: usize,
+    /// Replaced on accept instead of the typed word, for snippet choices.
+    replace: Option<Range<usize>>,
+    items: Vec<CompletionItem>,
+    matches: Vec<Match>,
+    selected: usize,
+    query: String,
+    incomplete: bool,
+    request: Option<PendingRequest<request::Completion>>,
+    resolve: Option<(usize, PendingRequest<request::ResolveCompletionItem>)>,
+    resolved: HashSet<usize>,
+    scroll_to_selected: bool,
+    /// Where the popup was drawn last frame, so clicking it doesn't close it.
+    rect: Rect,
+}
+
+struct Match {
+    item: usize,
+    score: i64,
+    /// Characters of the label matched by the query.
+    indices: Vec<usize>,
+}
+
+/// Runs before the editor's `TextEdit` so the popup and snippet keys don't
+/// reach it.
+pub fn handle_input(
+    ctx: &Context,
+    id: Id,
+    file: &mut FileData,
+    completion: &mut Completion,
+    lsp: &mut LspManager,
+) {
+    if !ctx.memory(|memory| memory.has_focus(id)) {
+        return;
+    }
+
+    completion.typed = ctx.input(|input| {
+        let typed: String = input
+            .events
+            .iter()
+            .filter_map(|event| match event {
+                Event::Text(text) => Some(text.as_str()),
+                _ => None,
+            })
+            .collect();
+
+        (!typed.is_empty()).then_some(typed)
+    });
+
+    if matches!(&completion.snippet, Some((snippet_id, _)) if *snippet_id == id) {
+        completion.previous = Some(file.content.clone());
+    }
+
+    if ctx.input_mut(|input| input.consume_shortcut(&TRIGGER_SHORTCUT)) {
+        start(ctx, id, file, completion, lsp, None);
+    }
+
+    let popup_open = completion
+        .popup
+        .as_ref()
+        .is_some_and(|popup| popup.id == id && !popup.matches.is_empty());
+
+    if popup_open {
+        let key = |key: Key| ctx.input_mut(|input| input.consume_key(Modifiers::NONE, key));
+        let popup = completion.popup.as_mut().unwrap();
+        let count = popup.matches.len();
+
+        if key(Key::ArrowDown) {
+            popup.select((popup.selected + 1) % count);
+        }
+
+        if key(Key::ArrowUp) {
+            popup.select((popup.selected + count - 1) % count);
+        }
+
+        if key(Key::PageDown) {
+            popup.select((popup.selected + MAX_VISIBLE_ITEMS).min(count - 1));
+        }
+
+        if key(Key::PageUp) {
+            popup.select(popup.selected.saturating_sub(MAX_VISIBLE_ITEMS));
+        }
+
+        if key(Key::Escape) {
+            completion.popup = None;
+        } else if key(Key::Enter) || key(Key::Tab) {
+            accept(ctx, id, file, completion, lsp);
+        }
+    } else if matches!(&completion.snippet, Some((snippet_id, _)) if *snippet_id == id) {
+        if ctx.input_mut(|input| input.consume_key(Modifiers::SHIFT, Key::Tab)) {
+            jump(ctx, id, file, completion, false);
+        } else if ctx.input_mut(|input| input.consume_key(Modifiers::NONE, Key::Tab)) {
+            jump(ctx, id, file, completion, true);
+        } else if ctx.input_mut(|input| input.consume_key(Modifiers::NONE, Key::Escape)) {
+            completion.snippet = None;
+        }
+    }
+}
+
+/// Runs after the editor's `TextEdit`: follows what was typed, collects the
+/// server's answers and draws the popup.
+pub fn update(
+    ui: &Ui,
+    id: Id,
+    file: &mut FileData,
+    completion: &mut Completion,
+    lsp: &mut LspManager,
+    output: &TextEditOutput,
+    theme: &Theme,
+) {
+    let ctx = ui.ctx();
+    let typed = completion.typed.take();
+
+    if completion
+        .popup
+        .as_ref()
+        .is_some_and(|popup| popup.id != id)
+    {
+        completion.popup = None;
+    }
+
+    follow_snippet(ctx, id, file, completion);
+
+    let cursor = cursor(ctx, id, file);
+
+    if output.response.changed() {
+        let last = typed.as_deref().and_then(|typed| typed.chars().last());
+        let trigger = last.filter(|char| trigger_characters(lsp, file).contains(char));
+
+        match &mut completion.popup {
+            Some(popup) if popup.follows(&file.content, cursor) && trigger.is_none() => {
+                let query = &file.content[popup.anchor..cursor];
+
+                if popup.incomplete && query != popup.query {
+                    popup.request = request_items(lsp, file, cursor, None);
+                }
+
+                popup.refilter(&file.content, cursor);
+            }
+            _ => {
+                completion.popup = None;
+
+                if let Some(char) = trigger {
+                    start(ctx, id, file, completion, lsp, Some(char));
+                } else if last.is_some_and(is_word_char) {
+                    start(ctx, id, file, completion, lsp, None);
+                }
+            }
+        }
+    } else if completion
+        .popup
+        .as_ref()
+        .is_some_and(|popup| !popup.follows(&file.content, cursor))
+    {
+        completion.popup = None;
+    }
+
+    if let Some(popup) = &mut completion.popup {
+        popup.poll(&file.content, cursor);
+        popup.resolve_selected(lsp, file);
+    }
+
+    let pointer_over_popup = completion.popup.as_ref().is_some_and(|popup| {
+        ctx.input(|input| input.pointer.hover_pos())
+            .is_some_and(|pointer| popup.rect.contains(pointer))
+    });
+
+    if !ctx.memory(|memory| memory.has_focus(id)) && !pointer_over_popup {
+        completion.popup = None;
+    }
+
+    let clicked = completion
+        .popup
+        .as_mut()
+        .filter(|popup| !popup.matches.is_empty())
+        .and_then(|popup| show_popup(ctx, popup, output, theme));
+
+    if let Some(index) = clicked {
+        if let Some(popup) = &mut completion.popup {
+            popup.selected = index;
+        }
+
+        accept(ctx, id, file, completion, lsp);
+    }
+}
+
+impl Popup {
+    /// Whether the cursor is still at the end of the word being completed.
+    fn follows(&self, text: &str, cursor: usize) -> bool {
+        match &self.replace {
+            Some(range) => range.start <= cursor && cursor <= range.end,
+            None => {
+                cursor >= self.anchor
+                    && text
+                        .get(self.anchor..cursor)
+                        .is_some_and(|word| word.chars().all(is_word_char))
+            }
+        }
+    }
+
+    fn select(&mut self, index: usize) {
+        self.selected = index;
+        self.scroll_to_selected = true;
+    }
+
+    fn refilter(&mut self, text: &str, cursor: usize) {
+        let query = text
+            .get(self.anchor..cursor.max(self.anchor))
+            .unwrap_or_default();
+        let query = match self.replace {
+            Some(_) => "",
+            None => query,
+        };
+        let matcher = SkimMatcherV2::default();
+
+        self.matches = self
+            .items
+            .iter()
+            .enumerate()
+            .filter_map(|(item, completion)| {
+                if query.is_empty() {
+                    return Some(Match {
+                        item,
+                        score: 0,
+                        indices: Vec::new(),
+                    });
+                }
+
+                let target = completion
+                    .filter_text
+                    .as_deref()
+                    .unwrap_or(&completion.label);
+                let (score, indices) = matcher.fuzzy_indices(target, query)?;
+
+                // Indices into the filter text mean nothing on the label.
+                let indices = match target == completion.label {
+                    true => indices,
+                    false => Vec::new(),
+                };
+
+                Some(Match {
+                    item,
+                    score,
+                    indices,
+                })
+            })
+            .collect();
+
+        let items = &self.items;
+        self.matches.sort_by(|a, b| {
+            let sort_text = |index: usize| {
+                let item = &items[index];
+                item.sort_text.as_deref().unwrap_or(&item.label)
+            };
+
+            b.score
+                .cmp(&a.score)
+                .then_with(|| sort_text(a.item).cmp(sort_text(b.item)))
+        });
+
+        self.selected = self
+            .matches
+            .iter()
+            .position(|m| query.is_empty() && items[m.item].preselect == Some(true))
+            .unwrap_or(0);
+        self.scroll_to_selected = true;
+        self.query = query.into();
+    }
+
+    fn poll(&mut self, text: &str, cursor: usize) {
+        if let Some(result) = self.request.as_ref().and_then(PendingRequest::try_take) {
+            self.request = None;
+
+            let (items, incomplete) = match result {
+                Ok(Some(CompletionResponse::Array(items))) => (items, false),
+                Ok(Some(CompletionResponse::List(list))) => (list.items, list.is_incomplete),
+                Ok(None) | Err(_) => (Vec::new(), false),
+            };
+
+            self.items = match items.is_empty() {
+                true => buffer_words(text, self.anchor),
+                false => items,
+            };
+            self.incomplete = incomplete;
+            self.resolved.clear();
+            self.resolve = None;
+            self.refilter(text, cursor);
+        }
+
+        if let Some((index, pending)) = &self.resolve {
+            if let Some(result) = pending.try_take() {
+                let index = *index;
+
+                if let (Ok(item), Some(slot)) = (result, self.items.get_mut(index)) {
+                    *slot = item;
+                }
+
+                self.resolved.insert(index);
+                self.resolve = None;
+            }
+        }
+    }
+
+    /// Asks the server for the documentation of the selected item.
+    fn resolve_selected(&mut self, lsp: &mut LspManager, file: &FileData) {
+        let Some(index) = self.matches.get(self.selected).map(|m| m.item) else {
+            return;
+        };
+
+        let item = &self.items[index];
+        let pending = matches!(&self.resolve, Some((resolving, _)) if *resolving == index);
+
+        if pending || self.resolved.contains(&index) || item.documentation.is_some() {
+            return;
+        }
+
+        let item = item.clone();
+        self.resolve = resolve(lsp, file, item).map(|pending| (index, pending));
+
+        if self.resolve.is_none() {
+            self.resolved.insert(index);
+        }
+    }
+}
+
+fn resolve(
+    lsp: &mut LspManager,
+    file: &FileData,
+    item: CompletionItem,
+) -> Option<PendingRequest<request::ResolveCompletionItem>> {
+    language_servers::request::<request::ResolveCompletionItem>(
+        lsp,
+        file,
+        |capabilities| {
+            capabilities
+                .completion_provider
+                .as_ref()
+                .and_then(|provider| provider.resolve_provider)
+                .unwrap_or(false)
+        },
+        |_| item,
+    )
+}
+
+fn start(
+    ctx: &Context,
+    id: Id,
+    file: &FileData,
+    completion: &mut Completion,
+    lsp: &mut LspManager,
+    trigger: Option<char>,
+) {
+    let cursor = cursor(ctx, id, file);
+    let anchor = match trigger {
+        Some(_) => cursor,
+        None => word_start(&file.content, cursor),
+    };
+
+    let mut popup = Popup {
+        id,
+        anchor,
+        replace: None,
+        items: Vec::new(),
+        matches: Vec::new(),
+        selected: 0,
+        query: String::new(),
+        incomplete: false,
+        request: request_items(lsp, file, cursor, trigger),
+        resolve: None,
+        resolved: HashSet::new(),
+        scroll_to_selected: false,
+        rect: Rect::NOTHING,
+    };
+
+    if popup.request.is_none() {
+        popup.items = buffer_words(&file.content, anchor);
+        popup.refilter(&file.content, cursor);
+
+        // Offering nothing but the word that's already there isn't useful.
+        if trigger.is_none() && anchor == cursor {
+            return;
+        }
+    }
+
+    completion.popup = Some(popup);
+}
+
+fn request_items(
+    lsp: &mut LspManager,
+    file: &FileData,
+    cursor: usize,
+    trigger: Option<char>,
+) -> Option<PendingRequest<request::Completion>> {
+    language_servers::request::<request::Completion>(
+        lsp,
+        file,
+        |capabilities| capabilities.completion_provider.is_some(),
+        |uri| CompletionParams {
+            text_document_position: TextDocumentPositionParams {
+                text_document: TextDocumentIdentifier { uri },
+                position: position::from_offset(&file.content, cursor),
+            },
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+            context: Some(CompletionContext {
+                trigger_kind: match trigger {
+                    Some(_) => CompletionTriggerKind::TRIGGER_CHARACTER,
+                    None => CompletionTriggerKind::INVOKED,
+                },
+                trigger_character: trigger.map(String::from),
+            }),
+        },
+    )
+}
+
+fn trigger_characters(lsp: &LspManager, file: &FileData) -> Vec<char> {
+    let Some(language) = languages::from_path(&file.path) else {
+        return Vec::new();
+    };
+
+    lsp.server_for(Path::new(&file.path), language.language_id)
+        .and_then(|server| server.capabilities().completion_provider.as_ref())
+        .and_then(|provider| provider.trigger_characters.as_ref())
+        .map(|characters| {
+            characters
+                .iter()
+                .filter_map(|character| character.chars().next())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Every other word in the buffer, the fallback when no server completes.
+fn buffer_words(text: &str, anchor: usize) -> Vec<CompletionItem> {
+    let mut seen = HashSet::new();
+    let mut words = Vec::new();
+    let mut start = None;
+
+    for (index, char) in text.char_indices().chain([(text.len(), ' ')]) {
+        match (start, is_word_char(char)) {
+            (None, true) => start = Some(index),
+            (Some(word_start), false) => {
+                let word = &text[word_start..index];
+
+                if word_start != anchor
+                    && word.chars().count() > 1
+                    && !word.starts_with(|char: char| char.is_ascii_digit())
+                    && seen.insert(word)
+                {
+                    words.push(CompletionItem {
+                        label: word.into(),
+                        kind: Some(CompletionItemKind::TEXT),
+                        ..CompletionItem::default()
+                    });
+                }
+
+                start = None;
+            }
+            _ => {}
+        }
+    }
+
+    words
+}
+
+fn accept(
+    ctx: &Context,
+    id: Id,
+    file: &mut FileData,
+    completion: &mut Completion,
+    lsp: &mut LspManager,
+) {
+    let Some(popup) = completion.popup.take() else {
+        return;
+    };
+    let Some(index) = popup.matches.get(popup.selected).map(|m| m.item) else {
+        return;
+    };
+
+    let mut item = popup.items[index].clone();
+
+    // Servers often only compute the edits adding imports on resolve.
+    if item.additional_text_edits.is_none() && !popup.resolved.contains(&index) {
+        if let Some(Ok(resolved)) =
+            resolve(lsp, file, item.clone()).map(|pending| pending.wait(RESOLVE_TIMEOUT))
+        {
+            item = resolved;
+        }
+    }
+
+    apply_item(
+        ctx,
+        id,
+        file,
+        completion,
+        &item,
+        popup.anchor,
+        popup.replace,
+    );
+    ctx.memory_mut(|memory| memory.request_focus(id));
+}
+
+fn apply_item(
+    ctx: &Context,
+    id: Id,
+    file: &mut FileData,
+    completion: &mut Completion,
+    item: &CompletionItem,
+    anchor: usize,
+    replace: Option<Range<usize>>,
+) {
+    let content = &file.content;
+    let cursor = cursor(ctx, id, file);
+
+    let (range, new_text) = match &item.text_edit {
+        Some(CompletionTextEdit::Edit(edit)) => {
+            (position::to_range(content, edit.range), &edit.new_text)
+        }
+        Some(CompletionTextEdit::InsertAndReplace(edit)) => {
+            (position::to_range(content, edit.insert), &edit.new_text)
+        }
+        None => (
+            replace.clone().unwrap_or(anchor..cursor),
+            item.insert_text.as_ref().unwrap_or(&item.label),
+        ),
+    };
+
+    // The range was computed before the rest of the word was typed.
+    let range = match replace {
+        Some(_) => range,
+        None => range.start..range.end.max(cursor),
+    };
+
+    let line_start = content[..range.start].rfind('\n').map_or(0, |i| i + 1);
+    let line_prefix = indent::leading_whitespace(&content[line_start..]);
+
+    let snippet = match item.insert_text_format {
+        Some(InsertTextFormat::SNIPPET) => {
+            snippet::parse(new_text, line_prefix, &file.indent_style.unit(), |name| {
+                variable(file, range.start, name)
+            })
+        }
+        _ => Snippet {
+            text: new_text.clone(),
+            tabstops: vec![Tabstop::end(new_text.len())],
+        },
+    };
+
+    let mut edits: Vec<(Range<usize>, &str)> = vec![(range.clone(), &snippet.text)];
+
+    for edit in item.additional_text_edits.iter().flatten() {
+        let edit_range = position::to_range(content, edit.range);
+
+        if edit_range.end <= range.start || edit_range.start >= range.end {
+            edits.push((edit_range, &edit.new_text));
+        }
+    }
+
+    edits.sort_by_key(|(range, _)| Reverse(range.start));
+
+    // Edits before the completed word move it.
+    let offset = edits
+        .iter()
+        .filter(|(edit_range, _)| edit_range.start < range.start)
+        .fold(range.start as isize, |offset, (edit_range, text)| {
+            offset + text.len() as isize - edit_range.len() as isize
+        }) as usize;
+
+    let mut content = content.clone();
+
+    for (range, text) in &edits {
+        content.replace_range(range.clone(), text);
+    }
+
+    match (
+        SnippetSession::new(&snippet, offset),
+        &mut completion.snippet,
+    ) {
+        (Some(session), _) => completion.snippet = Some((id, session)),
+        (None, Some((snippet_id, session))) if *snippet_id == id => {
+            for (range, text) in &edits {
+                session.shift(range.clone(), text.len());
+            }
+        }
+        _ => {}
+    }
+
+    let first = &snippet.tabstops[0].ranges[0];
+    let selection = offset + first.start..offset + first.end;
+
+    editor::apply_edit(
+        ctx,
+        id,
+        file,
+        content,
+        editor::char_range(&file.content, selection),
+    );
+
+    // What was just applied doesn't need to move the tab stops again.
+    completion.previous = Some(file.content.clone());
+
+    if let Some((_, session)) = completion
+        .snippet
+        .as_ref()
+        .filter(|(snippet_id, _)| *snippet_id == id)
+    {
+        let tabstop = session.current().clone();
+        open_choices(id, file, completion, &tabstop);
+    }
+}
+
+/// Moves to the next or previous tab stop of the snippet.
+fn jump(ctx: &Context, id: Id, file: &FileData, completion: &mut Completion, forward: bool) {
+    let Some((_, session)) = &mut completion.snippet else {
+        return;
+    };
+
+    let tabstop = match forward {
+        true => session.next(),
+        false => session.previous(),
+    }
+    .clone();
+
+    if session.is_last() {
+        completion.snippet = None;
+    }
+
+    editor::select(ctx, id, file, tabstop.ranges[0].clone());
+    open_choices(id, file, completion, &tabstop);
+}
+
+fn open_choices(id: Id, file: &FileData, completion: &mut Completion, tabstop: &Tabstop) {
+    if tabstop.choices.is_empty() {
+        return;
+    }
+
+    let range = tabstop.ranges[0].clone();
+    let mut popup = Popup {
+        id,
+        anchor: range.start,
+        replace: Some(range.clone()),
+        items: tabstop
+            .choices
+            .iter()
+            .map(|choice| CompletionItem {
+                label: choice.clone(),
+                kind: Some(CompletionItemKind::VALUE),
+                ..CompletionItem::default()
+            })
+            .collect(),
+        matches: Vec::new(),
+        selected: 0,
+        query: String::new(),
+        incomplete: false,
+        request: None,
+        resolve: None,
+        resolved: HashSet::new(),
+        scroll_to_selected: false,
+        rect: Rect::NOTHING,
+    };
+
+    // Choices aren't resolved by anyone.
+    popup.resolved = (0..popup.items.len()).collect();
+    popup.refilter(&file.content, range.end);
+    completion.popup = Some(popup);
+}
+
+/// Moves the tab stops along with this frame's edit, mirrors the current
+/// stop's text into its copies and ends the snippet once the cursor leaves it.
+fn follow_snippet(ctx: &Context, id: Id, file: &mut FileData, completion: &mut Completion) {
+    let previous = completion.previous.take();
+
+    let Some((_, session)) = completion
+        .snippet
+        .as_mut()
+        .filter(|(snippet_id, _)| *snippet_id == id)
+    else {
+        return;
+    };
+
+    if let Some(previous) = previous.filter(|previous| *previous != file.content) {
+        let (removed, inserted) = diff(&previous, &file.content);
+        session.shift(removed, inserted);
+
+        mirror(ctx, id, file, session);
+    }
+
+    if !session.contains(cursor(ctx, id, file)) {
+        completion.snippet = None;
+    }
+}
+
+fn mirror(ctx: &Context, id: Id, file: &mut FileData, session: &mut SnippetSession) {
+    let ranges = session.current().ranges.clone();
+    let Some(primary) = ranges.first() else {
+        return;
+    };
+
+    let value = file.content[primary.clone()].to_string();
+    let [start, end] = editor::get_selection(ctx, id, file).sorted();
+    let mut selection = editor::byte_offset(&file.content, start.index)
+        ..editor::byte_offset(&file.content, end.index);
+    let mut changed = false;
+
+    for index in (1..ranges.len()).rev() {
+        let range = session.current().ranges[index].clone();
+
+        if file.content[range.clone()] == value {
+            continue;
+        }
+
+        file.content.replace_range(range.clone(), &value);
+        session.shift(range.clone(), value.len());
+        changed = true;
+
+        let shift = |offset: usize| match offset >= range.end {
+            true => offset + value.len() - range.len(),
+            false => offset,
+        };
+
+        selection = shift(selection.start)..shift(selection.end);
+    }
+
+    if changed {
+        file.version += 1;
+        editor::select(ctx, id, file, selection);
+    }
+}
+
+/// The bytes of `old` that were replaced and how many bytes replaced them.
+fn diff(old: &str, new: &str) -> (Range<usize>, usize) {
+    let prefix = old
+        .char_indices()
+        .zip(new.chars())
+        .find(|((_, a), b)| a != b)
+        .map_or(old.len().min(new.len()), |((index, _), _)| index);
+
+    let suffix = old[prefix..]
+        .chars()
+        .rev()
+        .zip(new[prefix..].chars().rev())
+        .take_while(|(a, b)| a == b)
+        .map(|(a, _)| a.len_utf8())
+        .sum::<usize>();
+
+    (prefix..old.len() - suffix, new.len() - suffix - prefix)
+}
+
+fn variable(file: &FileData, offset: usize, name: &str) -> Option<String> {
+    let path = Path::new(&file.path);
+    let line_start = file.content[..offset].rfind('\n').map_or(0, |i| i + 1);
+    let line_end = file.content[offset..]
+        .find('\n')
+        .map_or(file.content.len(), |i| offset + i);
+    let row = editor::row_of(&file.content, offset);
+
+    match name {
+        "TM_FILENAME" => Some(file.name.clone()),
+        "TM_FILENAME_BASE" => Some(path.file_stem()?.to_string_lossy().into()),
+        "TM_DIRECTORY" => Some(path.parent()?.to_string_lossy().into()),
+        "TM_FILEPATH" => Some(file.path.clone()),
+        "TM_LINE_INDEX" => Some(row.to_string()),
+        "TM_LINE_NUMBER" => Some((row + 1).to_string()),
+        "TM_CURRENT_LINE" => Some(file.content[line_start..line_end].into()),
+        "TM_CURRENT_WORD" => Some(file.content[word_start(&file.content, offset)..offset].into()),
+        _ => None,
+    }
+}
+
+fn cursor(ctx: &Context, id: Id, file: &FileData) -> usize {
+    let selection = editor::get_selection(ctx, id, file);
+    editor::byte_offset(&file.content, selection.primary.index)
+}
+
+fn is_word_char(char: char) -> bool {
+    char.is_alphanumeric() || char == '_' || char == '$'
+}
+
+fn word_start(text: &str, offset: usize) -> usize {
+    text[..offset]
+        .char_indices()
+        .rev()
+        .take_while(|(_, char)| is_word_char(*char))
+        .last()
+        .map_or(offset, |(index, _)| index)
+}
+
+/// Draws the list under the caret, or above it when there is no room, with
+/// the selected item's documentation beside it. Returns the clicked item.
+fn show_popup(
+    ctx: &Context,
+    popup: &mut Popup,
+    output: &TextEditOutput,
+    theme: &Theme,
+) -> Option<usize> {
+    let text = output.galley.text();
+    let anchor = output.galley.from_ccursor(CCursor::new(editor::char_offset(
+        text,
+        popup.anchor.min(text.len()),
+    )));
+    let caret = output
+        .galley
+        .pos_from_cursor(&anchor)
+        .translate(output.galley_pos.to_vec2());
+
+    let row_height = ctx.style().text_styles[&TextStyle::Body].size + 8.0;
+    let height = row_height * popup.matches.len().min(MAX_VISIBLE_ITEMS) as f32 + 12.0;
+
+    let (pivot, position) = match caret.bottom() + height > ctx.screen_rect().bottom() {
+        true => (Align2::LEFT_BOTTOM, caret.left_top()),
+        false => (Align2::LEFT_TOP, caret.left_bottom()),
+    };
+
+    let mut clicked = None;
+
+    let response = Area::new(popup.id.with("completion"))
+        .order(Order::Foreground)
+        .pivot(pivot)
+        .fixed_pos(position)
+        .constrain(true)
+        .show(ctx, |ui| {
+            ui.horizontal_top(|ui| {
+                Frame::popup(ui.style()).show(ui, |ui| {
+                    ui.set_width(LIST_WIDTH);
+
+                    ScrollArea::vertical()
+                        .max_height(row_height * MAX_VISIBLE_ITEMS as f32)
+                        .show(ui, |ui| {
+                            ui.spacing_mut().item_spacing = Vec2::ZERO;
+
+                            for (index, m) in popup.matches.iter().enumerate() {
+                                let item = &popup.items[m.item];
+                                let selected = index == popup.selected;
+                                let response = item_row(ui, item, m, selected, row_height, theme);
+
+                                if selected && popup.scroll_to_selected {
+                                    response.scroll_to_me(None);
+                                }
+
+                                if response.clicked() {
+                                    clicked = Some(index);
+                                }
+                            }
+                        });
+                });
+
+                if let Some(m) = popup.matches.get(popup.selected) {
+                    documentation(ui, &popup.items[m.item]);
+                }
+            });
+        })
+        .response;
+
+    popup.rect = response.rect;
+    popup.scroll_to_selected = false;
+
+    clicked
+}
+
+fn item_row(
+    ui: &mut Ui,
+    item: &CompletionItem,
+    m: &Match,
+    selected: bool,
+    height: f32,
+    theme: &Theme,
+) -> egui::Response {
+    let (rect, response) =
+        ui.allocate_exact_size(Vec2::new(ui.available_width(), height), Sense::click());
+
+    let painter = ui.painter();
+
+    if selected {
+        painter.rect_filled(rect, theme.rounding, theme.action.selected);
+    } else if response.hovered() {
+        painter.rect_filled(rect, theme.rounding, theme.action.hover);
+    }
+
+    let (letter, color) = kind_badge(item.kind, theme);
+    let badge = Rect::from_center_size(
+        rect.left_center() + Vec2::new(12.0, 0.0),
+        Vec2::splat(height - 8.0),
+    );
+
+    painter.rect_stroke(badge, theme.rounding, (1.0, color));
+    painter.text(
+        badge.center(),
+        Align2::CENTER_CENTER,
+        letter,
+        FontId::monospace(11.0),
+        color,
+    );
+
+    let font_id = TextStyle::Body.resolve(ui.style());
+    let mut job = LayoutJob::default();
+
+    for (index, char) in item.label.chars().enumerate() {
+        let matched = m.indices.contains(&index);
+
+        job.append(
+            &char.to_string(),
+            0.0,
+            TextFormat {
+                font_id: font_id.clone(),
+                color: match matched {
+                    true => theme.primary.main,
+                    false => theme.text_color.primary,
+                },
+                ..TextFormat::default()
+            },
+        );
+    }
+
+    let galley = ui.fonts(|fonts| fonts.layout_job(job));
+    let label_position = egui::pos2(rect.left() + 26.0, rect.center().y - galley.size().y / 2.0);
+
+    painter
+        .with_clip_rect(rect.shrink(2.0))
+        .galley(label_position, galley, Color32::WHITE);
+
+    response
+}
+
+fn documentation(ui: &mut Ui, item: &CompletionItem) {
+    let documentation = item.documentation.as_ref();
+
+    if item.detail.is_none() && documentation.is_none() {
+        return;
+    }
+
+    Frame::popup(ui.style()).show(ui, |ui| {
+        ui.set_width(DOCUMENTATION_WIDTH);
+
+        ScrollArea::vertical()
+            .id_source("completion-documentation")
+            .max_height(280.0)
+            .show(ui, |ui| {
+                if let Some(detail) = &item.detail {
+                    ui.label(RichText::new(detail).monospace());
+                }
+
+                match documentation {
+                    Some(Documentation::String(text)) => {
+                        ui.label(text);
+                    }
+                    Some(Documentation::MarkupContent(content)) => match content.kind {
+                        MarkupKind::Markdown => markdown::show(ui, &content.value),
+                        MarkupKind::PlainText => {
+                            ui.label(&content.value);
+                        }
+                    },
+                    None => {}
+                }
+            });
+    });
+}
+
+/// A letter and color for each kind of item.
+fn kind_badge(kind: Option<CompletionItemKind>, theme: &Theme) -> (&'static str, Color32) {
+    match kind.unwrap_or(CompletionItemKind::TEXT) {
+        CompletionItemKind::METHOD => ("m", theme.secondary.main),
+        CompletionItemKind::FUNCTION => ("f", theme.secondary.main),
+        CompletionItemKind::CONSTRUCTOR => ("c", theme.secondary.main),
+        CompletionItemKind::FIELD => ("f", theme.info.main),
+        CompletionItemKind::VARIABLE => ("v", theme.info.main),
+        CompletionItemKind::PROPERTY => ("p", theme.info.main),
+        CompletionItemKind::CLASS => ("C", theme.warning.main),
+        CompletionItemKind::STRUCT => ("S", theme.warning.main),
+        CompletionItemKind::INTERFACE => ("I", theme.info.light),
+        CompletionItemKind::ENUM => ("E", theme.warning.main),
+        CompletionItemKind::ENUM_MEMBER => ("e", theme.warning.light),
+        CompletionItemKind::TYPE_PARAMETER => ("T", theme.warning.light),
+        CompletionItemKind::MODULE => ("M", theme.primary.light),
+        CompletionItemKind::CONSTANT => ("K", theme.primary.main),
+        CompletionItemKind::KEYWORD => ("k", theme.primary.main),
+        CompletionItemKind::SNIPPET => ("s", theme.success.main),
+        CompletionItemKind::VALUE | CompletionItemKind::UNIT => ("#", theme.success.light),
+        CompletionItemKind::OPERATOR => ("±", theme.text_color.primary),
+        CompletionItemKind::EVENT => ("!", theme.error.light),
+        CompletionItemKind::FILE => ("F", theme.text_color.primary),
+        CompletionItemKind::FOLDER => ("D", theme.text_color.primary),
+        CompletionItemKind::REFERENCE => ("r", theme.text_color.primary),
+        CompletionItemKind::COLOR => ("●", theme.secondary.light),
+        _ => ("t", theme.text_color.secondary),
+    }
+}
@@ -0,0 +1,308 @@
+use egui::{
+    text::LayoutJob, text_edit::TextEditOutput, Color32, Painter, Pos2, Shape, Stroke, Ui, Vec2,
+};
+use lsp::{
+    lsp_types::{DiagnosticSeverity, NumberOrString, PublishDiagnosticsParams, Range, Url},
+    position,
+};
+use std::{collections::HashMap, ops::Range as ByteRange};
+use theme::Theme;
+
+use crate::editor;
+
+/// Room left of the text for the severity icons.
+pub const GUTTER_WIDTH: f32 = 14.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    Error,
+    Warning,
+    Information,
+    Hint,
+}
+
+impl Severity {
+    pub const ALL: [Severity; 4] = [
+        Severity::Error,
+        Severity::Warning,
+        Severity::Information,
+        Severity::Hint,
+    ];
+
+    /// Servers may leave the severity out, in which case it's an error.
+    fn from_lsp(severity: Option<DiagnosticSeverity>) -> Self {
+        match severity {
+            Some(DiagnosticSeverity::WARNING) => Severity::Warning,
+            Some(DiagnosticSeverity::INFORMATION) => Severity::Information,
+            Some(DiagnosticSeverity::HINT) => Severity::Hint,
+            _ => Severity::Error,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Severity::Error => "Errors",
+            Severity::Warning => "Warnings",
+            Severity::Information => "Infos",
+            Severity::Hint => "Hints",
+        }
+    }
+
+    pub fn color(self, theme: &Theme) -> Color32 {
+        match self {
+            Severity::Error => theme.error.main,
+            Severity::Warning => theme.warning.main,
+            Severity::Information => theme.info.main,
+            Severity::Hint => theme.text_color.secondary,
+        }
+    }
+
+    /// A filled circle for errors, a triangle for warnings, a ring for infos
+    /// and a dot for hints.
+    pub fn paint_icon(self, painter: &Painter, center: Pos2, size: f32, theme: &Theme) {
+        let color = self.color(theme);
+        let radius = size / 2.0;
+
+        match self {
+            Severity::Error => {
+                painter.circle_filled(center, radius, color);
+            }
+            Severity::Warning => {
+                let points = vec![
+                    center + Vec2::new(0.0, -radius),
+                    center + Vec2::new(radius, radius * 0.8),
+                    center + Vec2::new(-radius, radius * 0.8),
+                ];
+
+                painter.add(Shape::convex_polygon(points, color, Stroke::NONE));
+            }
+            Severity::Information => {
+                painter.circle_stroke(center, radius * 0.8, Stroke::new(1.5, color));
+            }
+            Severity::Hint => {
+                painter.circle_filled(center, radius * 0.4, color);
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub server: String,
+    pub severity: Severity,
+    pub range: Range,
+    pub message: String,
+    pub source: Option<String>,
+    pub code: Option<String>,
+}
+
+impl Diagnostic {
+    /// The source and code, e.g. `rustc(E0308)`.
+    pub fn origin(&self) -> String {
+        match (&self.source, &self.code) {
+            (Some(source), Some(code)) => format!("{source}({code})"),
+            (Some(source), None) => source.clone(),
+            (None, Some(code)) => code.clone(),
+            (None, None) => self.server.clone(),
+        }
+    }
+}
+
+/// The latest diagnostics published by every server, for open and closed
+/// files alike.
+#[derive(Default)]
+pub struct Diagnostics {
+    files: HashMap<Url, Vec<Diagnostic>>,
+}
+
+impl Diagnostics {
+    /// Replaces what `server` reported for the document before.
+    pub fn publish(&mut self, server: &str, params: PublishDiagnosticsParams) {
+        let diagnostics = self.files.entry(params.uri.clone()).or_default();
+
+        diagnostics.retain(|diagnostic| diagnostic.server != server);
+        diagnostics.extend(params.diagnostics.into_iter().map(|diagnostic| Diagnostic {
+            server: server.into(),
+            severity: Severity::from_lsp(diagnostic.severity),
+            range: diagnostic.range,
+            message: diagnostic.message,
+            source: diagnostic.source,
+            code: diagnostic.code.map(|code| match code {
+                NumberOrString::Number(number) => number.to_string(),
+                NumberOrString::String(string) => string,
+            }),
+        }));
+        diagnostics.sort_by_key(|diagnostic| (diagnostic.range.start, diagnostic.severity));
+
+        if diagnostics.is_empty() {
+            self.files.remove(&params.uri);
+        }
+    }
+
+    /// Forgets everything `server` reported, e.g. after it exited.
+    pub fn clear_server(&mut self, server: &str) {
+        for diagnostics in self.files.values_mut() {
+            diagnostics.retain(|diagnostic| diagnostic.server != server);
+        }
+
+        self.files.retain(|_, diagnostics| !diagnostics.is_empty());
+    }
+
+    pub fn for_uri(&self, uri: &Url) -> &[Diagnostic] {
+        self.files.get(uri).map_or(&[], Vec::as_slice)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&Url, &Diagnostic)> {
+        self.files.iter().flat_map(|(uri, diagnostics)| {
+            diagnostics.iter().map(move |diagnostic| (uri, diagnostic))
+        })
+    }
+
+    pub fn count(&self, severity: Severity) -> usize {
+        self.iter()
+            .filter(|(_, diagnostic)| diagnostic.severity == severity)
+            .count()
+    }
+}
+
+/// Underlines every diagnostic in the highlighted `job`, splitting its
+/// sections where a range starts or ends. The most severe one wins where
+/// ranges overlap.
+pub fn underline(job: &mut LayoutJob, diagnostics: &[Diagnostic], theme: &Theme) {
+    let mut ranges: Vec<(ByteRange<usize>, Severity)> = diagnostics
+        .iter()
+        .map(|diagnostic| {
+            (
+                visible_range(&job.text, diagnostic.range),
+                diagnostic.severity,
+            )
+        })
+        .filter(|(range, _)| !range.is_empty())
+        .collect();
+
+    ranges.sort_by_key(|(_, severity)| std::cmp::Reverse(*severity));
+
+    for (range, severity) in ranges {
+        split_section(job, range.start);
+        split_section(job, range.end);
+
+        let stroke = Stroke::new(1.5, severity.color(theme));
+
+        for section in &mut job.sections {
+            if section.byte_range.start >= range.start && section.byte_range.end <= range.end {
+                section.format.underline = stroke;
+            }
+        }
+    }
+}
+
+/// The diagnostic's byte range, widened to one character when it's empty so
+/// there is something to underline.
+fn visible_range(text: &str, range: Range) -> ByteRange<usize> {
+    let range = position::to_range(text, range);
+
+    if !range.is_empty() {
+        return range;
+    }
+
+    match text[range.start..].chars().next() {
+        Some(char) if char != '\n' => range.start..range.start + char.len_utf8(),
+        _ => match text[..range.start].chars().next_back() {
+            Some(char) if char != '\n' => range.start - char.len_utf8()..range.start,
+            _ => range,
+        },
+    }
+}
+
+fn split_section(job: &mut LayoutJob, offset: usize) {
+    let Some(index) = job.sections.iter().position(|section| {
+        section.byte_range.contains(&offset) && section.byte_range.start != offset
+    }) else {
+        return;
+    };
+
+    let mut tail = job.sections[index].clone();
+
+    tail.leading_space = 0.0;
+    tail.byte_range.start = offset;
+    job.sections[index].byte_range.end = offset;
+    job.sections.insert(index + 1, tail);
+}
+
+/// Paints the most severe diagnostic of each line next to it.
+pub fn paint_gutter(ui: &Ui, output: &TextEditOutput, diagnostics: &[Diagnostic], theme: &Theme) {
+    let mut lines: HashMap<u32, Severity> = HashMap::new();
+
+    for diagnostic in diagnostics {
+        let severity = lines
+            .entry(diagnostic.range.start.line)
+            .or_insert(diagnostic.severity);
+        *severity = (*severity).min(diagnostic.severity);
+    }
+
+    let painter = ui.painter_at(output.text_clip_rect.expand2(Vec2::new(GUTTER_WIDTH, 0.0)));
+    let text = output.galley.text();
+
+    for (line, severity) in lines {
+        let offset = position::to_offset(text, lsp::lsp_types::Position { line, character: 0 });
+        let cursor = output
+            .galley
+            .from_ccursor(egui::text::CCursor::new(editor::char_offset(text, offset)));
+        let row = output
+            .galley
+            .pos_from_cursor(&cursor)
+            .translate(output.galley_pos.to_vec2());
+        let center = Pos2::new(output.galley_pos.x - GUTTER_WIDTH / 2.0, row.center().y);
+
+        severity.paint_icon(&painter, center, 8.0, theme);
+    }
+}
+
+/// Shows the messages of the diagnostics under the pointer, or of the whole
+/// line when hovering its gutter icon.
+pub fn show_hover(ui: &Ui, output: &TextEditOutput, diagnostics: &[Diagnostic], theme: &Theme) {
+    let Some(pointer) = output.response.hover_pos() else {
+        return;
+    };
+
+    let text = output.galley.text();
+    let cursor = output.galley.cursor_from_pos(pointer - output.galley_pos);
+
+    let hovered: Vec<&Diagnostic> = if pointer.x < output.galley_pos.x {
+        diagnostics
+            .iter()
+            .filter(|diagnostic| diagnostic.range.start.line as usize == cursor.pcursor.paragraph)
+            .collect()
+    } else {
+        let offset = editor::byte_offset(text, cursor.ccursor.index);
+
+        diagnostics
+            .iter()
+            .filter(|diagnostic| {
+                let range = visible_range(text, diagnostic.range);
+                range.start <= offset && offset < range.end.max(range.start + 1)
+            })
+            .collect()
+    };
+
+    if hovered.is_empty() {
+        return;
+    }
+
+    // Shares the editor's id with the hover tooltip so the two stack.
+    egui::show_tooltip_at_pointer(ui.ctx(), ui.layer_id(), output.response.id, |ui| {
+        ui.set_max_width(480.0);
+
+        for diagnostic in hovered {
+            ui.horizontal(|ui| {
+                let (rect, _) = ui.allocate_exact_size(Vec2::splat(10.0), egui::Sense::hover());
+                diagnostic
+                    .severity
+                    .paint_icon(ui.painter(), rect.center(), 8.0, theme);
+
+                ui.label(&diagnostic.message);
+                ui.weak(diagnostic.origin());
+            });
+        }
+    });
+}
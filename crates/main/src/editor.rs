@@ -0,0 +1,333 @@
+use egui::{
+    text::{CCursor, CCursorRange},
+    text_edit::{TextEditOutput, TextEditState},
+    Context, Event, Id, Key, Ui,
+};
+
+use std::ops::Range;
+
+use crate::{
+    file_store::FileData,
+    indent::{self, CLOSING_BRACKETS, OPENING_BRACKETS},
+    languages,
+    line_commands::LineCommand,
+};
+
+/// Runs before the editor's `TextEdit` and takes over the input it would
+/// otherwise handle naively: line command shortcuts, newlines, multi-line
+/// pastes and closing brackets typed at the start of a line.
+pub fn handle_input(ctx: &Context, id: Id, file: &mut FileData) {
+    if !ctx.memory(|memory| memory.has_focus(id)) {
+        return;
+    }
+
+    // Shortcuts with more modifiers come first, as consuming Alt+Up would
+    // also swallow Alt+Shift+Up.
+    let mut commands = LineCommand::ALL;
+    commands.sort_by_key(|command| {
+        command.shortcut().map_or(0, |shortcut| {
+            let modifiers = shortcut.modifiers;
+            modifiers.alt as u8 + modifiers.shift as u8 + modifiers.command as u8
+        })
+    });
+
+    for command in commands.into_iter().rev() {
+        let Some(shortcut) = command.shortcut() else {
+            continue;
+        };
+
+        if ctx.input_mut(|input| input.consume_shortcut(&shortcut)) {
+            run_line_command(ctx, id, file, command);
+        }
+    }
+
+    let selection = get_selection(ctx, id, file);
+    let line_is_blank = {
+        let start = byte_offset(&file.content, selection.sorted()[0].index);
+        let line_start = file.content[..start].rfind('\n').map_or(0, |i| i + 1);
+
+        file.content[line_start..start].trim().is_empty()
+    };
+
+    let events = ctx.input_mut(|input| {
+        let mut taken = Vec::new();
+
+        input.events.retain(|event| {
+            let take = match event {
+                Event::Key {
+                    key: Key::Enter,
+                    pressed: true,
+                    modifiers,
+                    ..
+                } => modifiers.is_none(),
+                Event::Paste(text) => text.contains('\n'),
+                Event::Text(text) => {
+                    line_is_blank
+                        && selection.primary.index == selection.secondary.index
+                        && text.len() == 1
+                        && text.starts_with(CLOSING_BRACKETS)
+                }
+                _ => false,
+            };
+
+            if take {
+                taken.push(event.clone());
+            }
+
+            !take
+        });
+
+        taken
+    });
+
+    for event in events {
+        match event {
+            Event::Key { .. } => insert_newline(ctx, id, file),
+            Event::Paste(text) => paste(ctx, id, file, &text),
+            Event::Text(text) => insert_closing_bracket(ctx, id, file, &text),
+            _ => {}
+        }
+    }
+}
+
+pub fn run_line_command(ctx: &Context, id: Id, file: &mut FileData, command: LineCommand) {
+    let [start, end] = get_selection(ctx, id, file).sorted();
+    let selection = byte_offset(&file.content, start.index)..byte_offset(&file.content, end.index);
+    let language = languages::from_path(&file.path);
+
+    if let Some((content, selection)) = command.apply(&file.content, selection, language) {
+        let selection = CCursorRange::two(
+            CCursor::new(char_offset(&content, selection.start)),
+            CCursor::new(char_offset(&content, selection.end)),
+        );
+
+        apply_edit(ctx, id, file, content, selection);
+    }
+}
+
+/// Reindents every line touched by the selection as a single undo step.
+pub fn reindent_selection(ctx: &Context, id: Id, file: &mut FileData) {
+    let [start, end] = get_selection(ctx, id, file).sorted();
+    let start = byte_offset(&file.content, start.index);
+    let mut end = byte_offset(&file.content, end.index);
+
+    if end > start && file.content[..end].ends_with('\n') {
+        end -= 1;
+    }
+
+    let first_row = row_of(&file.content, start);
+    let last_row = row_of(&file.content, end);
+    let language = languages::from_path(&file.path);
+
+    let content = indent::reindent(
+        language,
+        &file.content,
+        first_row..last_row + 1,
+        file.indent_style,
+    );
+
+    let ranges = indent::line_ranges(&content);
+    let selection = CCursorRange::two(
+        CCursor::new(char_offset(&content, ranges[first_row].start)),
+        CCursor::new(char_offset(&content, ranges[last_row].end)),
+    );
+
+    apply_edit(ctx, id, file, content, selection);
+}
+
+fn insert_newline(ctx: &Context, id: Id, file: &mut FileData) {
+    let [start, end] = get_selection(ctx, id, file).sorted();
+    let start = byte_offset(&file.content, start.index);
+    let end = byte_offset(&file.content, end.index);
+
+    let before = &file.content[..start];
+    let after = file.content[end..].trim_start_matches([' ', '\t']);
+    let row = row_of(&file.content, start) + 1;
+
+    let language = languages::from_path(&file.path);
+    let style = file.indent_style;
+
+    // Copy the current line's indentation when the query has no opinion,
+    // e.g. inside a block comment.
+    let fallback = style.level_of(&before[before.rfind('\n').map_or(0, |i| i + 1)..]);
+    let level = |text: &str, row: usize| {
+        indent::indent_level(language, text, row, style).unwrap_or(fallback)
+    };
+
+    let between_brackets =
+        before.trim_end().ends_with(OPENING_BRACKETS) && after.starts_with(CLOSING_BRACKETS);
+
+    let (inserted, trailing) = if between_brackets {
+        let text = format!("{before}\n\n{after}");
+
+        (
+            format!("\n{}", style.indentation(level(&text, row))),
+            format!("\n{}", style.indentation(level(&text, row + 1))),
+        )
+    } else {
+        let text = format!("{before}\n{after}");
+
+        (
+            format!("\n{}", style.indentation(level(&text, row))),
+            "".into(),
+        )
+    };
+
+    let cursor = CCursor::new(char_offset(before, before.len()) + inserted.chars().count());
+    let content = format!("{before}{inserted}{trailing}{after}");
+
+    apply_edit(ctx, id, file, content, CCursorRange::one(cursor));
+}
+
+fn paste(ctx: &Context, id: Id, file: &mut FileData, pasted: &str) {
+    let [start, end] = get_selection(ctx, id, file).sorted();
+    let start = byte_offset(&file.content, start.index);
+    let end = byte_offset(&file.content, end.index);
+
+    let before = &file.content[..start];
+    let after = &file.content[end..];
+    let content = format!("{before}{pasted}{after}");
+
+    let first_row = row_of(before, before.len());
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    let from = if before[line_start..].trim().is_empty() {
+        first_row
+    } else {
+        first_row + 1
+    };
+    let to = first_row + pasted.matches('\n').count() + usize::from(!pasted.ends_with('\n'));
+
+    let content = indent::reindent_pasted(
+        languages::from_path(&file.path),
+        &content,
+        from..to,
+        file.indent_style,
+    );
+
+    let cursor = CCursor::new(char_offset(&content, content.len() - after.len()));
+
+    apply_edit(ctx, id, file, content, CCursorRange::one(cursor));
+}
+
+fn insert_closing_bracket(ctx: &Context, id: Id, file: &mut FileData, bracket: &str) {
+    let start = byte_offset(&file.content, get_selection(ctx, id, file).primary.index);
+    let after = &file.content[start..];
+    let row = row_of(&file.content, start);
+
+    let content = indent::reindent(
+        languages::from_path(&file.path),
+        &format!("{}{bracket}{after}", &file.content[..start]),
+        row..row + 1,
+        file.indent_style,
+    );
+
+    let cursor = CCursor::new(char_offset(&content, content.len() - after.len()));
+
+    apply_edit(ctx, id, file, content, CCursorRange::one(cursor));
+}
+
+pub fn get_selection(ctx: &Context, id: Id, file: &FileData) -> CCursorRange {
+    TextEditState::load(ctx, id)
+        .and_then(|state| state.cursor.char_range())
+        .unwrap_or_else(|| CCursorRange::one(CCursor::new(file.content.chars().count())))
+}
+
+/// Replaces the file's content and selection, recording the previous state so
+/// that the whole change is undone in one step.
+pub fn apply_edit(
+    ctx: &Context,
+    id: Id,
+    file: &mut FileData,
+    content: String,
+    selection: CCursorRange,
+) {
+    let mut state = TextEditState::load(ctx, id).unwrap_or_default();
+    let mut undoer = state.undoer();
+    let previous_selection = state.cursor.char_range().unwrap_or_default();
+
+    undoer.add_undo(&(previous_selection, file.content.clone()));
+    file.content = content;
+    file.version += 1;
+    undoer.add_undo(&(selection, file.content.clone()));
+
+    state.set_undoer(undoer);
+    state.cursor.set_char_range(Some(selection));
+    state.store(ctx, id);
+}
+
+/// Selects the byte `range` without touching the content.
+pub fn select(ctx: &Context, id: Id, file: &FileData, range: Range<usize>) {
+    let mut state = TextEditState::load(ctx, id).unwrap_or_default();
+
+    state
+        .cursor
+        .set_char_range(Some(char_range(&file.content, range)));
+    state.store(ctx, id);
+}
+
+pub fn char_range(text: &str, range: Range<usize>) -> CCursorRange {
+    CCursorRange::two(
+        CCursor::new(char_offset(text, range.start)),
+        CCursor::new(char_offset(text, range.end)),
+    )
+}
+
+/// Moves the cursor to `offset` and focuses the editor; the cursor is scrolled
+/// into view once the editor is shown, see [`scroll_to_revealed`].
+pub fn reveal(ctx: &Context, id: Id, file: &FileData, offset: usize) {
+    let mut state = TextEditState::load(ctx, id).unwrap_or_default();
+    let cursor = CCursor::new(char_offset(&file.content, offset.min(file.content.len())));
+
+    state.cursor.set_char_range(Some(CCursorRange::one(cursor)));
+    state.store(ctx, id);
+
+    ctx.memory_mut(|memory| memory.request_focus(id));
+    ctx.data_mut(|data| data.insert_temp(id.with("reveal"), true));
+}
+
+pub fn scroll_to_revealed(ui: &Ui, id: Id, output: &TextEditOutput) {
+    let revealed = ui
+        .ctx()
+        .data_mut(|data| data.remove_temp::<bool>(id.with("reveal")))
+        .unwrap_or_default();
+
+    if let Some(cursor) = output.state.cursor.char_range().filter(|_| revealed) {
+        let cursor = output.galley.from_ccursor(cursor.primary);
+        let rect = output.galley.pos_from_cursor(&cursor);
+        let rect = rect.translate(output.galley_pos.to_vec2()).expand(40.0);
+
+        ui.scroll_to_rect(rect, Some(egui::Align::Center));
+    }
+}
+
+/// The identifier touching `offset`, empty when there is none.
+pub fn word_at(text: &str, offset: usize) -> Range<usize> {
+    let is_word_char = |char: char| char.is_alphanumeric() || char == '_' || char == '$';
+
+    let start = text[..offset]
+        .char_indices()
+        .rev()
+        .take_while(|(_, char)| is_word_char(*char))
+        .last()
+        .map_or(offset, |(index, _)| index);
+    let end = text[offset..]
+        .char_indices()
+        .find(|(_, char)| !is_word_char(*char))
+        .map_or(text.len(), |(index, _)| offset + index);
+
+    start..end
+}
+
+pub fn byte_offset(text: &str, char_index: usize) -> usize {
+    text.char_indices()
+        .nth(char_index)
+        .map_or(text.len(), |(i, _)| i)
+}
+
+pub fn char_offset(text: &str, byte_index: usize) -> usize {
+    text[..byte_index].chars().count()
+}
+
+pub fn row_of(text: &str, byte_index: usize) -> usize {
+    text[..byte_index].matches('\n').count()
+}
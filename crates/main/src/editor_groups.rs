@@ -0,0 +1,1071 @@
+//! The editor area split into groups, side by side or stacked. Every group
+//! has its own tabs and active file, and a file open in two of them keeps a
+//! scroll position and cursor in each.
+
+use components::selectable_label::SelectableLabel;
+use egui::{
+    emath::RectTransform, scroll_area::ScrollBarVisibility, text::Fonts, vec2, Button, Color32,
+    Context, CursorIcon, DragAndDrop, Id, Key, KeyboardShortcut, Margin, Modifiers, Pos2, Rect,
+    RichText, ScrollArea, Sense, Stroke, TextEdit, TextStyle, Ui, Vec2,
+};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use theme::Theme;
+
+use crate::{
+    breadcrumbs, completion, diagnostics, editor, file_store::FileData, git_blame, git_gutter,
+    navigation, rename, syntax_highlighter, State,
+};
+
+pub const SPLIT_SHORTCUT: KeyboardShortcut =
+    KeyboardShortcut::new(Modifiers::COMMAND, Key::Backslash);
+
+/// Cmd+1 to Cmd+9 focus the groups in order.
+const FOCUS_KEYS: [Key; 9] = [
+    Key::Num1,
+    Key::Num2,
+    Key::Num3,
+    Key::Num4,
+    Key::Num5,
+    Key::Num6,
+    Key::Num7,
+    Key::Num8,
+    Key::Num9,
+];
+
+/// Room between two groups, grabbed to resize them.
+const SPLITTER_WIDTH: f32 = 6.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Direction {
+    Right,
+    Down,
+}
+
+#[derive(Default, Clone, Serialize, Deserialize)]
+pub struct EditorGroup {
+    /// Stays the same when other groups come and go, for the widget ids.
+    pub id: u64,
+    pub tabs: Vec<String>,
+    /// The first `pinned` tabs, kept left of the others.
+    pub pinned: usize,
+    /// Empty when the group has no tabs.
+    pub active: String,
+    /// The tabs, most recently active first.
+    pub recent: Vec<String>,
+    /// The tab the next file opened from the explorer replaces, until it's
+    /// edited or double-clicked.
+    pub preview: Option<String>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+enum Layout {
+    Group(u64),
+    Split {
+        direction: Direction,
+        /// The share of the first side.
+        ratio: f32,
+        first: Box<Layout>,
+        second: Box<Layout>,
+    },
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct EditorGroups {
+    /// Left to right and top to bottom, as laid out.
+    pub groups: Vec<EditorGroup>,
+    pub focused: usize,
+    layout: Layout,
+    next_id: u64,
+}
+
+/// A tab being dragged to another group.
+struct DraggedTab {
+    group: u64,
+    path: String,
+}
+
+impl Default for EditorGroups {
+    fn default() -> Self {
+        EditorGroups {
+            groups: vec![EditorGroup::default()],
+            focused: 0,
+            layout: Layout::Group(0),
+            next_id: 1,
+        }
+    }
+}
+
+impl EditorGroup {
+    /// Makes the file active, in a new tab right of the active one when it
+    /// has none.
+    pub fn open(&mut self, path: &str) {
+        if !self.tabs.iter().any(|tab| tab == path) {
+            let index = match self.tabs.iter().position(|tab| *tab == self.active) {
+                Some(active) => (active + 1).max(self.pinned),
+                None => self.tabs.len(),
+            };
+
+            self.tabs.insert(index, path.into());
+        }
+
+        self.recent.retain(|tab| tab != path);
+        self.recent.insert(0, path.into());
+        self.active = path.into();
+    }
+
+    /// Shows the file in the preview tab, in place of the previous preview,
+    /// which is returned.
+    pub fn open_preview(&mut self, path: &str) -> Option<String> {
+        if self.tabs.iter().any(|tab| tab == path) {
+            self.open(path);
+            return None;
+        }
+
+        let replaced = self.preview.take();
+        let index = replaced
+            .as_ref()
+            .and_then(|preview| self.tabs.iter().position(|tab| tab == preview));
+
+        if let Some(index) = index {
+            self.tabs[index] = path.into();
+            self.recent.retain(|tab| Some(tab) != replaced.as_ref());
+        }
+
+        self.open(path);
+        self.preview = Some(path.into());
+
+        replaced
+    }
+
+    /// Makes the preview tab a regular one.
+    pub fn keep(&mut self, path: &str) {
+        if self.preview.as_deref() == Some(path) {
+            self.preview = None;
+        }
+    }
+
+    /// Removes the tab; when it was the active one, the one used before it
+    /// takes over.
+    pub fn close(&mut self, path: &str) {
+        let Some(index) = self.tabs.iter().position(|tab| tab == path) else {
+            return;
+        };
+
+        self.tabs.remove(index);
+        self.recent.retain(|tab| tab != path);
+        self.keep(path);
+
+        if index < self.pinned {
+            self.pinned -= 1;
+        }
+
+        if self.active == path {
+            self.active = self.recent.first().cloned().unwrap_or_default();
+        }
+    }
+
+    pub fn is_pinned(&self, path: &str) -> bool {
+        self.tabs[..self.pinned].iter().any(|tab| tab == path)
+    }
+
+    /// Moves the tab after the pinned ones, or back out of them.
+    pub fn toggle_pinned(&mut self, path: &str) {
+        let Some(index) = self.tabs.iter().position(|tab| tab == path) else {
+            return;
+        };
+
+        let tab = self.tabs.remove(index);
+
+        match index < self.pinned {
+            true => {
+                self.pinned -= 1;
+                self.tabs.insert(self.pinned, tab);
+            }
+            false => {
+                self.tabs.insert(self.pinned, tab);
+                self.pinned += 1;
+            }
+        }
+    }
+
+    /// Moves the tab before or after `target`, but not across the pinned
+    /// ones' edge.
+    pub fn place(&mut self, path: &str, target: &str, after: bool) {
+        let Some(from) = self.tabs.iter().position(|tab| tab == path) else {
+            return;
+        };
+
+        let pinned = from < self.pinned;
+        let tab = self.tabs.remove(from);
+
+        if pinned {
+            self.pinned -= 1;
+        }
+
+        let index = match self.tabs.iter().position(|tab| tab == target) {
+            Some(index) => index + usize::from(after),
+            None => self.tabs.len(),
+        };
+
+        match pinned {
+            true => {
+                self.tabs.insert(index.min(self.pinned), tab);
+                self.pinned += 1;
+            }
+            false => self.tabs.insert(index.max(self.pinned), tab),
+        }
+    }
+}
+
+impl EditorGroups {
+    pub fn focused(&self) -> &EditorGroup {
+        &self.groups[self.focused]
+    }
+
+    pub fn index_of(&self, id: u64) -> Option<usize> {
+        self.groups.iter().position(|group| group.id == id)
+    }
+
+    /// Adds a group next to the focused one, showing its active file, and
+    /// focuses it.
+    pub fn split(&mut self, direction: Direction) {
+        let focused = &self.groups[self.focused];
+        let tabs = match focused.active.is_empty() {
+            true => Vec::new(),
+            false => vec![focused.active.clone()],
+        };
+        let group = EditorGroup {
+            id: self.next_id,
+            recent: tabs.clone(),
+            tabs,
+            active: focused.active.clone(),
+            ..Default::default()
+        };
+
+        self.layout.split(focused.id, group.id, direction);
+        self.next_id += 1;
+        self.focused += 1;
+        self.groups.insert(self.focused, group);
+    }
+
+    /// Closes the tabs of files `keep` says no to, and the groups left
+    /// without any.
+    pub fn retain(&mut self, keep: impl Fn(&str) -> bool) {
+        for group in &mut self.groups {
+            let closed: Vec<String> = group
+                .tabs
+                .iter()
+                .filter(|tab| !keep(tab))
+                .cloned()
+                .collect();
+
+            for tab in closed {
+                group.close(&tab);
+            }
+        }
+
+        while let Some(index) = self.groups.iter().position(|group| group.tabs.is_empty()) {
+            if self.groups.len() == 1 {
+                break;
+            }
+
+            self.remove(index);
+        }
+
+        self.focused = self.focused.min(self.groups.len() - 1);
+    }
+
+    /// Drops the group, its neighbour taking its room. The last one stays.
+    pub fn remove(&mut self, index: usize) {
+        if self.groups.len() == 1 {
+            return;
+        }
+
+        let group = self.groups.remove(index);
+
+        self.layout.remove(group.id);
+
+        if self.focused >= index {
+            self.focused = self.focused.saturating_sub(1);
+        }
+    }
+}
+
+impl Layout {
+    fn split(&mut self, id: u64, new: u64, direction: Direction) {
+        match self {
+            Layout::Group(group) if *group == id => {
+                *self = Layout::Split {
+                    direction,
+                    ratio: 0.5,
+                    first: Box::new(Layout::Group(id)),
+                    second: Box::new(Layout::Group(new)),
+                };
+            }
+            Layout::Group(_) => {}
+            Layout::Split { first, second, .. } => {
+                first.split(id, new, direction);
+                second.split(id, new, direction);
+            }
+        }
+    }
+
+    fn remove(&mut self, id: u64) {
+        let Layout::Split { first, second, .. } = self else {
+            return;
+        };
+
+        let sibling = if matches!(**first, Layout::Group(group) if group == id) {
+            second
+        } else if matches!(**second, Layout::Group(group) if group == id) {
+            first
+        } else {
+            first.remove(id);
+            second.remove(id);
+            return;
+        };
+
+        let sibling = std::mem::replace(&mut **sibling, Layout::Group(id));
+        *self = sibling;
+    }
+
+    /// The rect of every group in `rect`.
+    fn panes(&self, rect: Rect, panes: &mut Vec<(u64, Rect)>) {
+        match self {
+            Layout::Group(id) => panes.push((*id, rect)),
+            Layout::Split {
+                direction,
+                ratio,
+                first,
+                second,
+            } => {
+                let (a, b, _) = divide(rect, *direction, *ratio);
+
+                first.panes(a, panes);
+                second.panes(b, panes);
+            }
+        }
+    }
+
+    /// Lets the room between groups be dragged to resize them.
+    fn splitters(&mut self, ui: &Ui, rect: Rect, id: Id, theme: &Theme) {
+        let Layout::Split {
+            direction,
+            ratio,
+            first,
+            second,
+        } = self
+        else {
+            return;
+        };
+
+        let (a, b, splitter) = divide(rect, *direction, *ratio);
+        let response = ui.interact(splitter, id, Sense::drag());
+        let stroke = match response.hovered() || response.dragged() {
+            true => Stroke::new(2.0, theme.primary.main),
+            false => Stroke::new(1.0, theme.action.disabled_bg),
+        };
+
+        match direction {
+            Direction::Right => {
+                let response = response.on_hover_cursor(CursorIcon::ResizeHorizontal);
+
+                if let Some(pointer) = response.interact_pointer_pos() {
+                    *ratio = (pointer.x - rect.left()) / rect.width();
+                }
+
+                ui.painter()
+                    .vline(splitter.center().x, splitter.y_range(), stroke);
+            }
+            Direction::Down => {
+                let response = response.on_hover_cursor(CursorIcon::ResizeVertical);
+
+                if let Some(pointer) = response.interact_pointer_pos() {
+                    *ratio = (pointer.y - rect.top()) / rect.height();
+                }
+
+                ui.painter()
+                    .hline(splitter.x_range(), splitter.center().y, stroke);
+            }
+        }
+
+        *ratio = ratio.clamp(0.1, 0.9);
+
+        first.splitters(ui, a, id.with(0), theme);
+        second.splitters(ui, b, id.with(1), theme);
+    }
+}
+
+/// The two sides of a split and the splitter between them.
+fn divide(rect: Rect, direction: Direction, ratio: f32) -> (Rect, Rect, Rect) {
+    let half = SPLITTER_WIDTH / 2.0;
+
+    match direction {
+        Direction::Right => {
+            let x = rect.left() + rect.width() * ratio;
+
+            (
+                rect.with_max_x(x - half),
+                rect.with_min_x(x + half),
+                Rect::from_x_y_ranges(x - half..=x + half, rect.y_range()),
+            )
+        }
+        Direction::Down => {
+            let y = rect.top() + rect.height() * ratio;
+
+            (
+                rect.with_max_y(y - half),
+                rect.with_min_y(y + half),
+                Rect::from_x_y_ranges(rect.x_range(), y - half..=y + half),
+            )
+        }
+    }
+}
+
+/// The `TextEdit` id of a file in a group, so each copy keeps its own state.
+pub fn editor_id(group: u64, path: &str) -> String {
+    format!("{path}#{group}")
+}
+
+/// Cmd+\ splits, Cmd+1 to Cmd+9 move the focus.
+pub fn handle_input(ctx: &Context, state: &mut State) {
+    if ctx.input_mut(|input| input.consume_shortcut(&SPLIT_SHORTCUT)) {
+        split(ctx, state, Direction::Right);
+    }
+
+    for (index, key) in FOCUS_KEYS.into_iter().enumerate() {
+        if index < state.file_store.groups.groups.len()
+            && ctx.input_mut(|input| input.consume_key(Modifiers::COMMAND, key))
+        {
+            focus(ctx, state, index);
+        }
+    }
+}
+
+pub fn split(ctx: &Context, state: &mut State, direction: Direction) {
+    if state.file_store.active_file.is_empty() {
+        return;
+    }
+
+    state.file_store.groups.split(direction);
+    focus(ctx, state, state.file_store.groups.focused);
+}
+
+/// Makes the group the one commands apply to, and its editor take the
+/// keyboard.
+pub fn focus(ctx: &Context, state: &mut State, index: usize) {
+    state.file_store.focus_group(index);
+
+    let id = state.file_store.get_active_file_id();
+
+    ctx.memory_mut(|memory| memory.request_focus(id.into()));
+}
+
+pub fn focus_next(ctx: &Context, state: &mut State) {
+    let groups = &state.file_store.groups;
+
+    focus(ctx, state, (groups.focused + 1) % groups.groups.len());
+}
+
+/// Closes every tab of the focused group, and with the last one the group.
+pub fn close_focused(state: &mut State) {
+    let index = state.file_store.groups.focused;
+
+    state.file_store.close_group(index);
+}
+
+/// Fills the rest of `ui` with the groups.
+pub fn show(ui: &mut Ui, state: &mut State) {
+    let rect = ui.available_rect_before_wrap();
+    let mut panes = Vec::new();
+
+    state.file_store.groups.layout.panes(rect, &mut panes);
+
+    for (id, rect) in panes {
+        let Some(index) = state.file_store.groups.index_of(id) else {
+            continue;
+        };
+
+        let mut ui = ui.child_ui_with_id_source(rect, *ui.layout(), id, None);
+
+        ui.set_clip_rect(rect);
+        pane(&mut ui, state, index);
+    }
+
+    // After the panes, so the splitters win over the editors below them.
+    state
+        .file_store
+        .groups
+        .layout
+        .splitters(ui, rect, ui.id().with("splitters"), &state.theme);
+
+    ui.allocate_rect(rect, Sense::hover());
+}
+
+fn pane(ui: &mut Ui, state: &mut State, index: usize) {
+    let rect = ui.max_rect();
+
+    if ui.input(|input| input.pointer.any_pressed()) && ui.rect_contains_pointer(rect) {
+        state.file_store.focus_group(index);
+    }
+
+    tabs(ui, state, index);
+
+    let group = &state.file_store.groups.groups[index];
+    let id = group.id;
+    let path = group.active.clone();
+
+    if !path.is_empty() {
+        let version = |state: &State| state.file_store.files.get(&path).map(|file| file.version);
+        let before = version(state);
+
+        breadcrumbs::show(ui, state, index, &path);
+        editor(ui, state, id, &path);
+
+        // Editing a preview keeps it.
+        if version(state) != before {
+            state.file_store.groups.groups[index].keep(&path);
+        }
+    }
+
+    drop_tab(ui, state, index, rect);
+
+    if state.file_store.groups.groups.len() > 1 && state.file_store.groups.focused == index {
+        ui.painter().hline(
+            rect.x_range(),
+            rect.top() + 1.0,
+            Stroke::new(2.0, state.theme.primary.main),
+        );
+    }
+}
+
+fn tabs(ui: &mut Ui, state: &mut State, index: usize) {
+    let group = &state.file_store.groups.groups[index];
+    let group_id = group.id;
+    let active = group.active.clone();
+    let mut clicked = None;
+    let mut closed = None;
+    let mut action = None;
+    let mut kept = None;
+    let mut dropped = None;
+    let scrolled_id = ui.id().with("scrolled-to-tab");
+    let scrolled_to: Option<String> = ui.data(|data| data.get_temp(scrolled_id));
+    let overflow_width = 24.0;
+
+    let output = ScrollArea::horizontal()
+        .id_source("tabs-scroll-container")
+        .max_width(ui.available_width() - overflow_width)
+        .scroll_bar_visibility(ScrollBarVisibility::AlwaysHidden)
+        .show(ui, |ui| {
+            ui.horizontal(|ui| {
+                let mut tabs = group.tabs.iter().enumerate().peekable();
+
+                while let Some((tab, path)) = tabs.next() {
+                    let Some(file) = state.file_store.files.get(path) else {
+                        continue;
+                    };
+
+                    let pinned = tab < group.pinned;
+                    let mut text = RichText::new(&file.name);
+
+                    if group.preview.as_ref() == Some(path) {
+                        text = text.italics();
+                    }
+
+                    ui.spacing_mut().item_spacing = Vec2::default();
+
+                    let label = ui
+                        .add(
+                            SelectableLabel::new(*path == active, text)
+                                .rounding(theme::Theme::ROUNDING_NONE)
+                                .padding(Vec2 {
+                                    x: 26.0,
+                                    ..theme::Theme::PADDING
+                                }),
+                        )
+                        .interact(Sense::drag());
+
+                    label.dnd_set_drag_payload(DraggedTab {
+                        group: group_id,
+                        path: path.clone(),
+                    });
+
+                    // Dropped on the left half goes before, on the right half after.
+                    if let Some(dragged) = label.dnd_hover_payload::<DraggedTab>() {
+                        let after = ui
+                            .ctx()
+                            .pointer_interact_pos()
+                            .is_some_and(|pointer| pointer.x > label.rect.center().x);
+                        let x = match after {
+                            true => label.rect.right(),
+                            false => label.rect.left(),
+                        };
+
+                        if dragged.path != *path || dragged.group != group_id {
+                            ui.painter().vline(
+                                x,
+                                label.rect.y_range(),
+                                Stroke::new(2.0, state.theme.primary.main),
+                            );
+                        }
+
+                        if let Some(dragged) = label.dnd_release_payload::<DraggedTab>() {
+                            dropped = Some((dragged, path.clone(), after));
+                        }
+                    }
+
+                    // Once each time another tab becomes active.
+                    if *path == active && scrolled_to.as_ref() != Some(path) {
+                        label.scroll_to_me(None);
+                        ui.data_mut(|data| data.insert_temp(scrolled_id, path.clone()));
+                    }
+
+                    label.context_menu(|ui| {
+                        if let Some(chosen) = tab_menu(ui, pinned) {
+                            action = Some((chosen, path.clone()));
+                            ui.close_menu();
+                        }
+                    });
+
+                    if tabs.peek().is_some() {
+                        let available_space = if ui.is_sizing_pass() {
+                            Vec2::ZERO
+                        } else {
+                            ui.available_size_before_wrap()
+                        };
+
+                        let size = vec2(0.0, available_space.y);
+                        let (rect, response) = ui.allocate_at_least(size, Sense::hover());
+
+                        if ui.is_rect_visible(response.rect) {
+                            let stroke = Stroke::new(1.0, state.theme.action.disabled_bg);
+                            let painter = ui.painter();
+                            painter.vline(
+                                painter.round_to_pixel(rect.center().x),
+                                (rect.top())..=(rect.bottom()),
+                                stroke,
+                            );
+                        }
+                    }
+
+                    let to_screen = RectTransform::from_to(
+                        Rect::from_min_size(Pos2::ZERO, label.rect.size()),
+                        label.rect,
+                    );
+
+                    if label.clicked() {
+                        clicked = Some(path.clone());
+                    }
+
+                    if label.double_clicked() {
+                        kept = Some(path.clone());
+                    }
+
+                    // Pinned tabs aren't closed by accident.
+                    if pinned {
+                        ui.painter().text(
+                            to_screen.transform_pos(Pos2 {
+                                x: label.rect.width() - 13.0,
+                                y: label.rect.height() / 2.0,
+                            }),
+                            egui::Align2::CENTER_CENTER,
+                            "📌",
+                            TextStyle::Small.resolve(ui.style()),
+                            state.theme.text_color.secondary,
+                        );
+
+                        continue;
+                    }
+
+                    ui.add_visible_ui(ui.rect_contains_pointer(label.rect), |ui| {
+                        let size = 5.0;
+
+                        let position = Pos2 {
+                            x: label.rect.width() - 13.0,
+                            y: label.rect.height() / 2.0,
+                        };
+
+                        let max_rect = Rect {
+                            min: to_screen.transform_pos(position),
+                            max: to_screen.transform_pos(Pos2 {
+                                x: position.x,
+                                y: -size,
+                            }),
+                        };
+
+                        ui.style_mut().visuals.widgets.inactive.weak_bg_fill = Color32::TRANSPARENT;
+                        ui.style_mut().visuals.widgets.hovered.weak_bg_fill =
+                            state.theme.action.hover.gamma_multiply(0.3);
+
+                        let button =
+                            Button::new(RichText::new("x").color(state.theme.text_color.primary))
+                                .rounding(state.theme.rounding)
+                                .stroke(Stroke::NONE);
+
+                        let response = ui
+                            .put(max_rect, button)
+                            .on_hover_cursor(CursorIcon::PointingHand);
+
+                        if response.clicked() {
+                            closed = Some(path.clone());
+                        }
+                        response
+                    });
+                }
+            });
+        });
+
+    // In the room kept right of the tabs.
+    if output.content_size.x > output.inner_rect.width() {
+        let button = Rect::from_min_size(
+            output.inner_rect.right_top(),
+            vec2(overflow_width, output.inner_rect.height()),
+        );
+
+        ui.allocate_ui_at_rect(button, |ui| {
+            ui.menu_button("⏷", |ui| {
+                let group = &state.file_store.groups.groups[index];
+
+                for path in &group.tabs {
+                    let Some(file) = state.file_store.files.get(path) else {
+                        continue;
+                    };
+
+                    if ui
+                        .selectable_label(*path == active, &file.name)
+                        .on_hover_text(path)
+                        .clicked()
+                    {
+                        clicked = Some(path.clone());
+                        ui.close_menu();
+                    }
+                }
+            })
+            .response
+            .on_hover_text("Open Tabs");
+        });
+    }
+
+    if let Some(path) = clicked {
+        state.file_store.focus_group(index);
+        state.file_store.set_active_file(&path);
+    }
+
+    if let Some(path) = kept {
+        state.file_store.groups.groups[index].keep(&path);
+    }
+
+    if let Some((action, path)) = action {
+        run_tab_action(ui.ctx(), state, index, &path, action);
+        return;
+    }
+
+    if let Some((dragged, target, after)) = dropped {
+        let Some(from) = state.file_store.groups.index_of(dragged.group) else {
+            return;
+        };
+
+        if from != index {
+            state.file_store.move_tab(from, &dragged.path, index);
+        }
+
+        let groups = &mut state.file_store.groups;
+
+        if let Some(to) = groups.index_of(group_id) {
+            groups.groups[to].place(&dragged.path, &target, after);
+        }
+
+        focus(ui.ctx(), state, state.file_store.groups.focused);
+        return;
+    }
+
+    if let Some(path) = closed {
+        state.file_store.close_tab(index, &path);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TabAction {
+    Close,
+    CloseOthers,
+    CloseToTheRight,
+    CloseSaved,
+    CloseAll,
+    TogglePinned,
+    CopyPath,
+    Reveal,
+    SplitRight,
+}
+
+/// The right-click menu of a tab.
+fn tab_menu(ui: &mut Ui, pinned: bool) -> Option<TabAction> {
+    let mut chosen = None;
+    let mut item = |ui: &mut Ui, text: &str, action| {
+        if ui.button(text).clicked() {
+            chosen = Some(action);
+        }
+    };
+
+    ui.set_min_width(180.0);
+
+    item(ui, "Close", TabAction::Close);
+    item(ui, "Close Others", TabAction::CloseOthers);
+    item(ui, "Close to the Right", TabAction::CloseToTheRight);
+    item(ui, "Close Saved", TabAction::CloseSaved);
+    item(ui, "Close All", TabAction::CloseAll);
+    ui.separator();
+    item(
+        ui,
+        match pinned {
+            true => "Unpin",
+            false => "Pin",
+        },
+        TabAction::TogglePinned,
+    );
+    ui.separator();
+    item(ui, "Copy Path", TabAction::CopyPath);
+    item(ui, "Reveal in Explorer", TabAction::Reveal);
+    ui.separator();
+    item(ui, "Split Right", TabAction::SplitRight);
+
+    chosen
+}
+
+/// Pinned tabs are left open by everything but Close and Close All.
+fn run_tab_action(
+    ctx: &Context,
+    state: &mut State,
+    index: usize,
+    path: &String,
+    action: TabAction,
+) {
+    let group = &state.file_store.groups.groups[index];
+    let position = group.tabs.iter().position(|tab| tab == path).unwrap_or(0);
+    let unpinned = |tab: &(usize, &String)| tab.0 >= group.pinned;
+    let tabs = group.tabs.iter().enumerate();
+
+    let close: Vec<String> = match action {
+        TabAction::Close => vec![path.clone()],
+        TabAction::CloseOthers => tabs
+            .filter(|tab| unpinned(tab) && tab.1 != path)
+            .map(|(_, tab)| tab.clone())
+            .collect(),
+        TabAction::CloseToTheRight => tabs
+            .filter(|tab| unpinned(tab) && tab.0 > position)
+            .map(|(_, tab)| tab.clone())
+            .collect(),
+        TabAction::CloseSaved => tabs
+            .filter(|tab| {
+                let saved = state
+                    .file_store
+                    .files
+                    .get(tab.1)
+                    .is_some_and(FileData::is_saved);
+
+                unpinned(tab) && saved
+            })
+            .map(|(_, tab)| tab.clone())
+            .collect(),
+        TabAction::CloseAll => group.tabs.clone(),
+        TabAction::TogglePinned => {
+            state.file_store.groups.groups[index].toggle_pinned(path);
+            return;
+        }
+        TabAction::CopyPath => {
+            let absolute = std::fs::canonicalize(path).unwrap_or_else(|_| path.into());
+
+            ctx.copy_text(absolute.to_string_lossy().into());
+            return;
+        }
+        TabAction::Reveal => {
+            state.explorer_reveal = std::fs::canonicalize(path).ok();
+            return;
+        }
+        TabAction::SplitRight => {
+            state.file_store.focus_group(index);
+            state.file_store.set_active_file(path);
+            split(ctx, state, Direction::Right);
+            return;
+        }
+    };
+
+    // Closing several at once leaves the ones with unsaved edits open, the
+    // edits would go with them.
+    let close = match action {
+        TabAction::Close => close,
+        _ => close
+            .into_iter()
+            .filter(|tab| !loses_edits(state, index, tab))
+            .collect(),
+    };
+
+    // Only the last one can take the group with it.
+    for tab in &close {
+        state.file_store.close_tab(index, tab);
+    }
+}
+
+/// Whether closing the tab drops unsaved edits: no other group shows it.
+fn loses_edits(state: &State, index: usize, path: &String) -> bool {
+    let saved = state
+        .file_store
+        .files
+        .get(path)
+        .is_none_or(FileData::is_saved);
+    let shown_elsewhere = state
+        .file_store
+        .groups
+        .groups
+        .iter()
+        .enumerate()
+        .any(|(other, group)| other != index && group.tabs.contains(path));
+
+    !saved && !shown_elsewhere
+}
+
+/// Moves a tab dropped anywhere on the group into it.
+fn drop_tab(ui: &Ui, state: &mut State, index: usize, rect: Rect) {
+    let Some(tab) = DragAndDrop::payload::<DraggedTab>(ui.ctx()) else {
+        return;
+    };
+
+    let group = &state.file_store.groups.groups[index];
+
+    if tab.group == group.id || !ui.rect_contains_pointer(rect) {
+        return;
+    }
+
+    ui.painter()
+        .rect_filled(rect, 0.0, state.theme.primary.main.gamma_multiply(0.1));
+
+    if !ui.input(|input| input.pointer.any_released()) {
+        return;
+    }
+
+    let Some(from) = state.file_store.groups.index_of(tab.group) else {
+        return;
+    };
+
+    state.file_store.move_tab(from, &tab.path, index);
+    focus(ui.ctx(), state, state.file_store.groups.focused);
+}
+
+fn editor(ui: &mut Ui, state: &mut State, group: u64, path: &str) {
+    let editor_id = editor_id(group, path);
+    let id = Id::new(editor_id.as_str());
+
+    let uri = state.lsp.uri(Path::new(path));
+    let problems = uri
+        .as_ref()
+        .map_or(&[][..], |uri| state.diagnostics.for_uri(uri));
+    let theme = &state.theme;
+
+    let Some(active_file) = state.file_store.files.get_mut(path) else {
+        return;
+    };
+
+    let mut scroll_area = ScrollArea::vertical().id_source(id);
+
+    if let Some(offset) = state.session.restored_scroll(&editor_id) {
+        scroll_area = scroll_area.vertical_scroll_offset(offset);
+    }
+
+    let output = scroll_area.show(ui, |ui| {
+        let mut layouter = |ui: &Ui, string: &str, wrap_width: f32| {
+            let mut layout_job = syntax_highlighter::highlight(string.to_string());
+            layout_job.wrap.max_width = wrap_width;
+            diagnostics::underline(&mut layout_job, problems, theme);
+
+            ui.fonts(|f: &Fonts| f.layout_job(layout_job))
+        };
+
+        if active_file.content.is_empty() {
+            return;
+        }
+
+        completion::handle_input(
+            ui.ctx(),
+            id,
+            active_file,
+            &mut state.completion,
+            &mut state.lsp,
+        );
+        editor::handle_input(ui.ctx(), id, active_file);
+
+        ui.style_mut().visuals.widgets.hovered.bg_stroke = Stroke::NONE;
+        ui.style_mut().visuals.selection.stroke = Stroke::NONE;
+
+        let output = TextEdit::multiline(&mut active_file.content)
+            .id(id)
+            .font(TextStyle::Monospace)
+            .code_editor()
+            .desired_rows(10)
+            .desired_width(f32::INFINITY)
+            .min_size(ui.available_size())
+            .lock_focus(true)
+            .layouter(&mut layouter)
+            .margin(Margin {
+                left: 5.0
+                    + diagnostics::GUTTER_WIDTH
+                    + git_gutter::GUTTER_WIDTH
+                    + state.git_blame.gutter_width(),
+                ..Margin::symmetric(5.0, 5.0)
+            })
+            .show(ui);
+
+        if output.response.changed() {
+            active_file.version += 1;
+        }
+
+        diagnostics::paint_gutter(ui, &output, problems, theme);
+        git_gutter::update(
+            ui,
+            id,
+            active_file,
+            &mut state.git_gutter,
+            &mut state.git,
+            &output,
+            theme,
+        );
+        git_blame::update(
+            ui,
+            active_file,
+            &mut state.git_blame,
+            &state.git,
+            &output,
+            theme,
+        );
+        diagnostics::show_hover(ui, &output, problems, theme);
+        completion::update(
+            ui,
+            id,
+            active_file,
+            &mut state.completion,
+            &mut state.lsp,
+            &output,
+            theme,
+        );
+        navigation::update(
+            ui,
+            id,
+            active_file,
+            &mut state.navigation,
+            &mut state.lsp,
+            &output,
+            theme,
+        );
+        rename::show_input(
+            ui,
+            id,
+            active_file,
+            &mut state.rename,
+            &mut state.lsp,
+            &output,
+            theme,
+        );
+        editor::scroll_to_revealed(ui, id, &output);
+    });
+
+    state.session.scrolled(editor_id, output.state.offset.y);
+}
@@ -0,0 +1,176 @@
+//! The commits that touched the active file, and the file as of the
+//! selected one next to its content in the editor.
+
+use components::diff_view::DiffView;
+use egui::{Context, Sense, TextStyle, TopBottomPanel, Ui};
+use egui_extras::{Column, TableBuilder};
+use git::history::{self, Revision};
+use std::path::Path;
+
+use crate::{git_blame, syntax_highlighter, State};
+
+#[derive(Default)]
+pub struct FileHistory {
+    pub open: bool,
+    path: String,
+    revisions: Vec<Revision>,
+    selected: Option<usize>,
+    /// The file as of the selected revision, `None` when it didn't exist.
+    old_text: Option<String>,
+    error: Option<String>,
+}
+
+impl FileHistory {
+    fn select(&mut self, index: usize) {
+        let revision = &self.revisions[index];
+
+        self.selected = Some(index);
+        self.old_text = None;
+        self.error = None;
+
+        match history::text_at(Path::new(&self.path), revision.id) {
+            Ok(text) => self.old_text = text,
+            Err(error) => self.error = Some(error.message().into()),
+        }
+    }
+}
+
+/// Lists the history of the active file, and compares with its latest
+/// commit.
+pub fn open(state: &mut State) {
+    let Some(file) = state.file_store.get_active_file() else {
+        return;
+    };
+
+    let history = &mut state.file_history;
+
+    *history = FileHistory {
+        open: true,
+        path: file.path.clone(),
+        ..Default::default()
+    };
+
+    match history::file_history(Path::new(&file.path)) {
+        Ok(revisions) => history.revisions = revisions,
+        Err(error) => history.error = Some(error.message().into()),
+    }
+
+    if !history.revisions.is_empty() {
+        history.select(0);
+    }
+}
+
+pub fn show(ctx: &Context, state: &mut State) {
+    if !state.file_history.open {
+        return;
+    }
+
+    let history = &mut state.file_history;
+    let theme = &state.theme;
+    let current = state
+        .file_store
+        .files
+        .get(&history.path)
+        .map(|file| file.content.as_str())
+        .unwrap_or_default();
+
+    TopBottomPanel::bottom("file_history")
+        .resizable(true)
+        .default_height(320.0)
+        .min_height(120.0)
+        .show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.strong("File History");
+                ui.weak(&history.path);
+
+                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                    if ui.small_button("x").clicked() {
+                        history.open = false;
+                    }
+                });
+            });
+
+            ui.separator();
+
+            if let Some(error) = &history.error {
+                ui.colored_label(theme.error.main, error);
+            }
+
+            if history.revisions.is_empty() {
+                ui.weak("The file has no commits.");
+                return;
+            }
+
+            egui::SidePanel::left("file_history_revisions")
+                .resizable(true)
+                .default_width(320.0)
+                .show_inside(ui, |ui| revisions(ui, history));
+
+            let Some(revision) = history.selected.map(|index| &history.revisions[index]) else {
+                return;
+            };
+
+            ui.horizontal(|ui| {
+                ui.strong(revision.short_id());
+                ui.label(&revision.summary);
+                ui.weak("compared with the editor");
+            });
+
+            let highlight = |text: &str| syntax_highlighter::highlight(text.to_string());
+
+            ui.add(
+                DiffView::new(
+                    "file-history-diff",
+                    history.old_text.as_deref().unwrap_or_default(),
+                    current,
+                )
+                .highlighter(&highlight),
+            );
+        });
+}
+
+fn revisions(ui: &mut Ui, history: &mut FileHistory) {
+    let row_height = ui.text_style_height(&TextStyle::Body) + 4.0;
+    let mut clicked = None;
+
+    TableBuilder::new(ui)
+        .striped(true)
+        .sense(Sense::click())
+        .auto_shrink(false)
+        .column(Column::exact(64.0))
+        .column(Column::remainder().clip(true))
+        .column(Column::auto())
+        .body(|body| {
+            body.rows(row_height, history.revisions.len(), |mut row| {
+                let index = row.index();
+                let revision = &history.revisions[index];
+
+                row.set_selected(history.selected == Some(index));
+                row.col(|ui| {
+                    ui.monospace(revision.short_id());
+                });
+                row.col(|ui| {
+                    ui.label(&revision.summary);
+                });
+                row.col(|ui| {
+                    ui.weak(format!(
+                        "{}, {}",
+                        revision.author,
+                        git_blame::relative_time(revision)
+                    ));
+                });
+
+                let response = row.response();
+
+                if response.clicked() {
+                    clicked = Some(index);
+                }
+
+                response.on_hover_ui(|ui| git_blame::tooltip(ui, revision));
+            });
+        });
+
+    if let Some(index) = clicked {
+        history.select(index);
+    }
+}
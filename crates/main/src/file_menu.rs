@@ -1,11 +1,18 @@
 use eframe::egui::{gui_zoom, Key, KeyboardShortcut, Modifiers, Ui};
 
-use crate::{file_utils, State};
+use crate::{
+    autosave, command_palette, compare, editor,
+    editor_groups::{self, Direction},
+    file_history, file_utils, formatting, git_blame, git_gutter, languages,
+    line_commands::LineCommand,
+    navigation, rename, source_control, tasks, terminal, State,
+};
 
 pub fn create(ui: &mut Ui, state: &mut State) {
     let organize_shortcut = KeyboardShortcut::new(Modifiers::COMMAND | Modifiers::SHIFT, Key::O);
     let reset_shortcut = KeyboardShortcut::new(Modifiers::COMMAND | Modifiers::SHIFT, Key::R);
     let save_shortcut = KeyboardShortcut::new(Modifiers::COMMAND, Key::S);
+    let problems_shortcut = KeyboardShortcut::new(Modifiers::COMMAND | Modifiers::SHIFT, Key::M);
 
     if ui.input_mut(|i| i.consume_shortcut(&organize_shortcut)) {
         ui.ctx().memory_mut(|mem| mem.reset_areas());
@@ -16,7 +23,15 @@ pub fn create(ui: &mut Ui, state: &mut State) {
     }
 
     if ui.input_mut(|i| i.consume_shortcut(&save_shortcut)) {
-        state.file_store.save_active_file()
+        autosave::save_active(ui.ctx(), state);
+    }
+
+    if ui.input_mut(|i| i.consume_shortcut(&problems_shortcut)) {
+        state.problems.open = !state.problems.open;
+    }
+
+    if ui.input_mut(|i| i.consume_shortcut(&terminal::TOGGLE_SHORTCUT)) {
+        state.terminal.toggle(ui.ctx());
     }
 
     ui.menu_button("File", |ui| {
@@ -33,15 +48,364 @@ pub fn create(ui: &mut Ui, state: &mut State) {
             ui.close_menu();
         }
 
-        if ui.button("Save").clicked() {
-            state.file_store.save_active_file()
+        let save =
+            egui::Button::new("Save").shortcut_text(ui.ctx().format_shortcut(&save_shortcut));
+
+        if ui.add(save).clicked() {
+            autosave::save_active(ui.ctx(), state);
+        }
+
+        let save_all = egui::Button::new("Save All")
+            .shortcut_text(ui.ctx().format_shortcut(&autosave::SAVE_ALL_SHORTCUT));
+
+        if ui.add(save_all).clicked() {
+            autosave::save_all(ui.ctx(), state);
+            ui.close_menu();
+        }
+
+        ui.menu_button("Auto Save", |ui| autosave::menu(ui, state));
+
+        ui.separator();
+
+        let has_file = state.file_store.get_active_file().is_some();
+
+        if ui
+            .add_enabled(has_file, egui::Button::new("Compare with Saved"))
+            .clicked()
+        {
+            compare::with_saved(state);
+            ui.close_menu();
+        }
+
+        if ui
+            .add_enabled(has_file, egui::Button::new("Compare with Clipboard"))
+            .clicked()
+        {
+            compare::with_clipboard(state);
+            ui.close_menu();
         }
+
+        let mut others: Vec<(String, String)> = state
+            .file_store
+            .files
+            .values()
+            .filter(|file| file.path != state.file_store.active_file)
+            .map(|file| (file.path.clone(), file.name.clone()))
+            .collect();
+        others.sort();
+
+        ui.add_enabled_ui(has_file && !others.is_empty(), |ui| {
+            ui.menu_button("Compare with Open File", |ui| {
+                for (path, name) in others {
+                    if ui.button(name).on_hover_text(&path).clicked() {
+                        compare::with_file(state, &path);
+                        ui.close_menu();
+                    }
+                }
+            });
+        });
+    });
+
+    ui.menu_button("Edit", |ui| {
+        ui.set_min_width(220.0);
+
+        let id = state.file_store.get_active_file_id().into();
+
+        match state.file_store.get_active_file_as_mut() {
+            Some(active_file) => {
+                for command in LineCommand::ALL {
+                    let mut button = egui::Button::new(command.label());
+
+                    if let Some(shortcut) = command.shortcut() {
+                        button = button.shortcut_text(ui.ctx().format_shortcut(&shortcut));
+                    }
+
+                    if ui.add(button).clicked() {
+                        editor::run_line_command(ui.ctx(), id, active_file, command);
+                        ui.close_menu();
+                    }
+                }
+
+                ui.separator();
+
+                if ui.button("Reindent Selection").clicked() {
+                    editor::reindent_selection(ui.ctx(), id, active_file);
+                    ui.close_menu();
+                }
+
+                ui.weak(format!("Indentation: {}", active_file.indent_style));
+                ui.separator();
+
+                let button = egui::Button::new("Rename Symbol")
+                    .shortcut_text(ui.ctx().format_shortcut(&rename::SHORTCUT));
+
+                if ui.add(button).clicked() {
+                    rename::start(ui.ctx(), state);
+                    ui.close_menu();
+                }
+
+                ui.separator();
+
+                let button = egui::Button::new("Format Document")
+                    .shortcut_text(ui.ctx().format_shortcut(&formatting::SHORTCUT));
+
+                if ui.add(button).clicked() {
+                    formatting::format(ui.ctx(), state, false);
+                    ui.close_menu();
+                }
+
+                if ui.button("Format Selection").clicked() {
+                    formatting::format(ui.ctx(), state, true);
+                    ui.close_menu();
+                }
+
+                let language = state
+                    .file_store
+                    .get_active_file()
+                    .and_then(|file| languages::from_path(&file.path));
+
+                if let Some(language) = language {
+                    let format_on_save = &mut state.formatting.format_on_save;
+                    let mut enabled = format_on_save.contains(language.name);
+
+                    if ui
+                        .checkbox(&mut enabled, format!("Format {} on Save", language.name))
+                        .on_hover_text(
+                            "Saving waits up to 2 seconds for the language server to \
+                             format the file, with the editor blocked.",
+                        )
+                        .changed()
+                    {
+                        if enabled {
+                            format_on_save.insert(language.name.into());
+                        } else {
+                            format_on_save.remove(language.name);
+                        }
+                    }
+
+                    let commands = &mut state.formatting.commands;
+                    let mut command = commands.get(language.name).cloned().unwrap_or_default();
+
+                    ui.horizontal(|ui| {
+                        ui.label("Formatter");
+
+                        let edit = egui::TextEdit::singleline(&mut command)
+                            .hint_text(formatting::default_command(language))
+                            .desired_width(200.0);
+
+                        if ui.add(edit).changed() {
+                            match command.trim().is_empty() {
+                                true => commands.remove(language.name),
+                                false => commands.insert(language.name.into(), command),
+                            };
+                        }
+                    })
+                    .response
+                    .on_hover_text(
+                        "Run when no language server formats the file: reads it on stdin, \
+                         writes it formatted to stdout. {file} is its path. Quote \
+                         arguments with spaces.",
+                    );
+                }
+            }
+            None => {
+                ui.add_enabled(false, egui::Button::new("Reindent Selection"));
+            }
+        }
+    });
+
+    ui.menu_button("Go", |ui| {
+        ui.set_min_width(220.0);
+
+        let definition = egui::Button::new("Go to Definition")
+            .shortcut_text(ui.ctx().format_shortcut(&navigation::DEFINITION_SHORTCUT));
+
+        if ui.add(definition).clicked() {
+            navigation::go_to_definition(ui.ctx(), state);
+            ui.close_menu();
+        }
+
+        let references = egui::Button::new("Find All References")
+            .shortcut_text(ui.ctx().format_shortcut(&navigation::REFERENCES_SHORTCUT));
+
+        if ui.add(references).clicked() {
+            navigation::find_references(ui.ctx(), state);
+            ui.close_menu();
+        }
+
+        ui.separator();
+
+        let next = egui::Button::new("Next Change")
+            .shortcut_text(ui.ctx().format_shortcut(&git_gutter::NEXT_SHORTCUT));
+
+        if ui.add(next).clicked() {
+            git_gutter::go_to_change(ui.ctx(), state, true);
+            ui.close_menu();
+        }
+
+        let previous = egui::Button::new("Previous Change")
+            .shortcut_text(ui.ctx().format_shortcut(&git_gutter::PREVIOUS_SHORTCUT));
+
+        if ui.add(previous).clicked() {
+            git_gutter::go_to_change(ui.ctx(), state, false);
+            ui.close_menu();
+        }
+    });
+
+    ui.menu_button("Tasks", |ui| {
+        ui.set_min_width(220.0);
+
+        if ui.button("Run Task…").clicked() {
+            state.command_palette.open(command_palette::RUN_TASK_PREFIX);
+            ui.close_menu();
+        }
+
+        if ui
+            .add_enabled(
+                state.tasks.is_running(),
+                egui::Button::new("Cancel Running Tasks"),
+            )
+            .clicked()
+        {
+            state.tasks.cancel_all();
+            ui.close_menu();
+        }
+
+        ui.separator();
+
+        if ui.button("Reload Tasks").clicked() {
+            state.tasks.reload();
+            ui.close_menu();
+        }
+
+        ui.weak(format!(
+            "Workspace tasks are read from {}",
+            tasks::TASKS_FILE
+        ));
     });
 
     #[cfg(not(target_arch = "wasm32"))]
     ui.menu_button("View", |ui| {
         gui_zoom::zoom_menu_buttons(ui);
 
+        ui.separator();
+
+        let palette = egui::Button::new("Command Palette")
+            .shortcut_text(ui.ctx().format_shortcut(&command_palette::SHORTCUT));
+
+        if ui.add(palette).clicked() {
+            state.command_palette.open("");
+            ui.close_menu();
+        }
+
+        ui.separator();
+
+        let has_file = state.file_store.get_active_file().is_some();
+        let split = egui::Button::new("Split Editor Right")
+            .shortcut_text(ui.ctx().format_shortcut(&editor_groups::SPLIT_SHORTCUT));
+
+        if ui.add_enabled(has_file, split).clicked() {
+            editor_groups::split(ui.ctx(), state, Direction::Right);
+            ui.close_menu();
+        }
+
+        if ui
+            .add_enabled(has_file, egui::Button::new("Split Editor Down"))
+            .clicked()
+        {
+            editor_groups::split(ui.ctx(), state, Direction::Down);
+            ui.close_menu();
+        }
+
+        let groups = state.file_store.groups.groups.len();
+
+        if ui
+            .add_enabled(groups > 1, egui::Button::new("Focus Next Editor Group"))
+            .clicked()
+        {
+            editor_groups::focus_next(ui.ctx(), state);
+            ui.close_menu();
+        }
+
+        if ui
+            .add_enabled(has_file, egui::Button::new("Close Editor Group"))
+            .clicked()
+        {
+            editor_groups::close_focused(state);
+            ui.close_menu();
+        }
+
+        ui.separator();
+
+        let problems = egui::Button::new("Problems")
+            .selected(state.problems.open)
+            .shortcut_text(ui.ctx().format_shortcut(&problems_shortcut));
+
+        if ui.add(problems).clicked() {
+            state.problems.open = !state.problems.open;
+            ui.close_menu();
+        }
+
+        let output = egui::Button::new("Output").selected(state.tasks.output_open);
+
+        if ui.add(output).clicked() {
+            state.tasks.output_open = !state.tasks.output_open;
+            ui.close_menu();
+        }
+
+        let terminal = egui::Button::new("Terminal")
+            .selected(state.terminal.open)
+            .shortcut_text(ui.ctx().format_shortcut(&terminal::TOGGLE_SHORTCUT));
+
+        if ui.add(terminal).clicked() {
+            state.terminal.toggle(ui.ctx());
+            ui.close_menu();
+        }
+
+        let source_control = egui::Button::new("Source Control")
+            .selected(state.source_control.open)
+            .shortcut_text(ui.ctx().format_shortcut(&source_control::TOGGLE_SHORTCUT));
+
+        if ui.add(source_control).clicked() {
+            state.source_control.open = !state.source_control.open;
+            ui.close_menu();
+        }
+
+        ui.checkbox(&mut state.show_language_servers, "Language Servers");
+
+        let mut against_head = state.git_gutter.base == git_gutter::DiffBase::Head;
+
+        if ui
+            .checkbox(&mut against_head, "Show Staged Changes in the Gutter")
+            .changed()
+        {
+            state.git_gutter.base = match against_head {
+                true => git_gutter::DiffBase::Head,
+                false => git_gutter::DiffBase::Index,
+            };
+        }
+
+        ui.menu_button("Git Blame", |ui| {
+            for mode in git_blame::BlameMode::ALL {
+                if ui
+                    .radio_value(&mut state.git_blame.mode, mode, mode.label())
+                    .clicked()
+                {
+                    ui.close_menu();
+                }
+            }
+        });
+
+        let has_file = state.file_store.get_active_file().is_some();
+
+        if ui
+            .add_enabled(has_file, egui::Button::new("File History"))
+            .clicked()
+        {
+            file_history::open(state);
+            ui.close_menu();
+        }
+
         ui.weak(format!(
             "Current zoom: {:.0}%",
             100.0 * ui.ctx().zoom_factor()
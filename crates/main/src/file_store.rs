@@ -1,6 +1,16 @@
+use crate::{
+    editor_groups::{self, EditorGroups},
+    file_utils,
+    indent::IndentStyle,
+    line_ending::{self, LineEnding},
+    text_encoding,
+};
 use egui::TextBuffer;
+use encoding_rs::{Encoding, UTF_8};
 use std::collections::HashMap;
+use std::env;
 use std::fs::{self};
+use std::io;
 use std::path::{Path, PathBuf};
 
 #[derive(Debug, Clone)]
@@ -8,12 +18,85 @@ pub struct FileData {
     pub name: String,
     pub content: String,
     pub path: String,
+    pub indent_style: IndentStyle,
+    /// What the lines end with on disk; the content has `\n`.
+    pub line_ending: LineEnding,
+    /// Whether the file ended with a line break, for saving to keep one.
+    pub final_newline: bool,
+    /// What the file is decoded from and encoded to.
+    pub encoding: &'static Encoding,
+    /// Whether the file starts with the encoding's byte order mark.
+    pub bom: bool,
+    /// Bumped on every edit so language servers only get changed content.
+    pub version: i32,
+    /// The version the file was last read or written at.
+    pub saved_version: i32,
+}
+
+impl FileData {
+    /// The buffer of a file whose text is `text`.
+    fn new(file_path: &str, text: String) -> Self {
+        let line_ending = LineEnding::detect(&text);
+        let content = line_ending::normalize(text);
+
+        FileData {
+            name: FileStore::get_file_name(Path::new(file_path)),
+            indent_style: IndentStyle::detect(&content),
+            line_ending,
+            final_newline: content.ends_with('\n'),
+            content,
+            path: file_path.into(),
+            encoding: UTF_8,
+            bom: false,
+            version: 0,
+            saved_version: 0,
+        }
+    }
+
+    /// The buffer of the file on disk, decoded with `encoding` or else the
+    /// one it looks to be in.
+    pub fn read(file_path: &str, encoding: Option<&'static Encoding>) -> io::Result<Self> {
+        let bytes = fs::read(file_path)?;
+        let (encoding, bom) = match encoding {
+            Some(encoding) => (encoding, text_encoding::has_bom(&bytes, encoding)),
+            None => text_encoding::detect(&bytes),
+        };
+        let text = text_encoding::decode(&bytes, encoding, bom);
+
+        Ok(FileData {
+            encoding,
+            bom,
+            ..FileData::new(file_path, text)
+        })
+    }
+
+    /// The bytes saving writes.
+    pub fn to_disk(&self) -> io::Result<Vec<u8>> {
+        let mut text = self.line_ending.apply(&self.content);
+
+        if self.final_newline && !text.is_empty() && !text.ends_with('\n') {
+            text.push_str(self.line_ending.apply("\n").as_str());
+        }
+
+        text_encoding::encode(&text, self.encoding, self.bom)
+            .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+    }
+
+    /// Whether the buffer wasn't edited since the file was last read or
+    /// written. Not compared with the file itself, which a lossy decoding
+    /// or mixed line endings would never match.
+    pub fn is_saved(&self) -> bool {
+        self.version == self.saved_version
+    }
 }
 
 pub struct FileStore {
     pub files: HashMap<String, FileData>,
+    /// The active file of the focused group.
     pub active_file: String,
-    components: Vec<(String, String)>,
+    pub groups: EditorGroups,
+    /// Why the last file couldn't be opened, until dismissed.
+    pub open_error: Option<String>,
 }
 
 impl Default for FileStore {
@@ -21,7 +104,8 @@ impl Default for FileStore {
         FileStore {
             files: HashMap::new(),
             active_file: "".into(),
-            components: Vec::new(),
+            groups: EditorGroups::default(),
+            open_error: None,
         }
     }
 }
@@ -33,58 +117,93 @@ impl FileStore {
         }
     }
 
+    /// Opens the file, and shows it when `active`. A file that can't be
+    /// read is left out, with the reason in `open_error`.
     pub fn insert(&mut self, file_path: &String, active: bool) {
-        let path_buf = PathBuf::from(file_path).to_path_buf();
-        let name = FileStore::get_file_name(&path_buf);
+        if let Err(error) = self.open(file_path, active) {
+            self.open_error = Some(format!("Couldn't open {file_path}: {error}"));
+        }
+    }
 
-        let content = match self.files.get(file_path) {
-            Some(FileData { content, .. }) => {
-                // TODO: handle file mismatch.
-                content.to_string()
-            }
-            None => {
-                let buff = fs::read(&file_path).expect("Should have been able to read the file");
-                String::from_utf8_lossy(&buff).to_string()
-            }
-        };
+    fn open(&mut self, file_path: &String, active: bool) -> io::Result<()> {
+        // TODO: handle file mismatch.
+        if !self.files.contains_key(file_path) {
+            let file = FileData::read(file_path, None)?;
+
+            self.files.insert(file_path.to_string(), file);
+        }
 
         if active {
-            self.active_file = file_path.to_string();
-
-            self.components = path_buf
-                .components()
-                .into_iter()
-                .map(|a| {
-                    let component = a.as_os_str().to_string_lossy().take();
-
-                    let name = if component == "." {
-                        match Path::new(&a).canonicalize().unwrap().file_name() {
-                            Some(path) => path.to_string_lossy().take(),
-                            None => "".into(),
-                        }
-                    } else {
-                        component
-                    };
-
-                    (name, Path::new(&a).to_string_lossy().to_string())
-                })
-                .collect();
-        }
-
-        self.files.insert(
-            file_path.to_string(),
-            FileData {
-                name,
-                content,
-                path: file_path.to_string(),
+            self.set_active_file(file_path);
+        }
+
+        Ok(())
+    }
+
+    /// Opens the file again without showing it, with the unsaved `content`
+    /// it had. Returns whether there was something to open.
+    pub fn restore(&mut self, file_path: &String, content: Option<String>) -> bool {
+        let mut file = match (FileData::read(file_path, None), content) {
+            (Ok(mut file), content) => {
+                if let Some(content) = content {
+                    file.content = content;
+                    file.saved_version = -1;
+                }
+
+                file
+            }
+            (Err(_), Some(content)) => FileData {
+                saved_version: -1,
+                ..FileData::new(file_path, content)
             },
-        );
+            (Err(_), None) => return false,
+        };
+
+        file.indent_style = IndentStyle::detect(&file.content);
+        self.files.insert(file_path.to_string(), file);
+
+        true
+    }
+
+    /// The file's text as its buffer would have it when opened now.
+    pub fn read_saved(file_path: &str) -> io::Result<String> {
+        Ok(FileData::read(file_path, None)?.content)
+    }
+
+    /// Reads the file again, decoded with `encoding`.
+    pub fn reopen(&mut self, file_path: &str, encoding: &'static Encoding) -> io::Result<()> {
+        let Some(file) = self.files.get_mut(file_path) else {
+            return Ok(());
+        };
+
+        *file = FileData {
+            version: file.version + 1,
+            saved_version: file.version + 1,
+            ..FileData::read(file_path, Some(encoding))?
+        };
+
+        Ok(())
     }
 
     pub fn get_file_path(path: &Path) -> String {
         path.to_path_buf().into_os_string().into_string().unwrap()
     }
 
+    /// The path the store knows the file by: relative inside the workspace,
+    /// as the explorer opens it, absolute outside of it.
+    pub fn store_path(absolute: &Path) -> String {
+        let root = env::current_dir().unwrap_or_default();
+        let relative = absolute.strip_prefix(&root).ok().or_else(|| {
+            let root = root.canonicalize().ok()?;
+            absolute.strip_prefix(root).ok()
+        });
+
+        match relative {
+            Some(relative) => FileStore::get_file_path(&Path::new(".").join(relative)),
+            None => FileStore::get_file_path(absolute),
+        }
+    }
+
     pub fn get_file_name(path: &Path) -> String {
         path.to_path_buf()
             .file_name()
@@ -95,17 +214,77 @@ impl FileStore {
     }
 
     pub fn get_active_file_id(&self) -> String {
-        self.active_file.to_string()
+        editor_groups::editor_id(self.groups.focused().id, &self.active_file)
     }
 
-    pub fn get_active_file_components(&self) -> Vec<(String, String)> {
-        self.components.clone()
+    /// The name of every folder down to the file, and its path.
+    pub fn get_components(file_path: &str) -> Vec<(String, String)> {
+        let mut path = PathBuf::new();
+
+        Path::new(file_path)
+            .components()
+            .map(|a| {
+                path.push(a);
+
+                let component = a.as_os_str().to_string_lossy().take();
+
+                let name = if component == "." {
+                    match Path::new(&a).canonicalize().unwrap().file_name() {
+                        Some(path) => path.to_string_lossy().take(),
+                        None => "".into(),
+                    }
+                } else {
+                    component
+                };
+
+                (name, path.to_string_lossy().to_string())
+            })
+            .collect()
     }
 
+    /// Shows the file in the focused group, in a new tab if it has none.
     pub fn set_active_file(&mut self, file_path: &String) {
+        self.groups.groups[self.groups.focused].open(file_path);
         self.active_file = file_path.to_string();
     }
 
+    /// Shows the file in the focused group's preview tab. A preview with
+    /// unsaved edits is kept instead of replaced, however it was edited.
+    pub fn preview(&mut self, file_path: &String) {
+        self.insert(file_path, false);
+
+        if !self.files.contains_key(file_path) {
+            return;
+        }
+
+        let group = &mut self.groups.groups[self.groups.focused];
+        let edited = group
+            .preview
+            .as_ref()
+            .and_then(|preview| self.files.get(preview))
+            .is_some_and(|file| !file.is_saved());
+
+        if edited {
+            group.preview = None;
+        }
+
+        if let Some(replaced) = group.open_preview(file_path) {
+            self.close_unshown(&replaced);
+        }
+
+        self.active_file = file_path.to_string();
+    }
+
+    /// Makes the file's preview tab in the focused group a regular one.
+    pub fn keep(&mut self, file_path: &str) {
+        self.groups.groups[self.groups.focused].keep(file_path);
+    }
+
+    pub fn focus_group(&mut self, index: usize) {
+        self.groups.focused = index;
+        self.active_file = self.groups.focused().active.clone();
+    }
+
     pub fn get_active_file_as_mut(&mut self) -> Option<&mut FileData> {
         self.files.get_mut(&self.active_file)
     }
@@ -114,25 +293,276 @@ impl FileStore {
         self.files.get(&self.active_file)
     }
 
-    pub fn save_active_file(&self) {
-        let active_file = &self.get_active_file();
+    /// Writes the buffer to its file.
+    pub fn save(&mut self, file_path: &str) -> io::Result<()> {
+        let Some(file) = self.files.get_mut(file_path) else {
+            return Ok(());
+        };
+
+        file_utils::write_atomic(Path::new(file_path), &file.to_disk()?)?;
+        file.saved_version = file.version;
+
+        Ok(())
+    }
+
+    /// Reads the file from disk again, e.g. after git changed it.
+    pub fn reload(&mut self, file_path: &str) {
+        let Some(file) = self.files.get_mut(file_path) else {
+            return;
+        };
 
-        if active_file.is_some() {
-            let FileData { content, .. } = active_file.unwrap();
-            fs::write(self.active_file.to_string(), content).expect("Unable to write file");
+        // In the encoding it was reopened with, if it was.
+        if let Ok(reloaded) = FileData::read(file_path, Some(file.encoding)) {
+            *file = FileData {
+                version: file.version + 1,
+                saved_version: file.version + 1,
+                ..reloaded
+            };
         }
     }
 
-    pub fn close_file(&mut self, file_path: &String) {
-        if self.files.contains_key(file_path) {
+    /// Closes the tab, then the group when it was its last, and the file
+    /// when no other group shows it.
+    pub fn close_tab(&mut self, group: usize, file_path: &String) {
+        self.groups.groups[group].close(file_path);
+
+        if self.groups.groups[group].tabs.is_empty() {
+            self.groups.remove(group);
+        }
+
+        self.close_unshown(file_path);
+        self.focus_group(self.groups.focused);
+    }
+
+    /// Closes every tab of the group, which goes with its last one unless
+    /// it's the only group.
+    pub fn close_group(&mut self, index: usize) {
+        let id = self.groups.groups[index].id;
+        let tabs = self.groups.groups[index].tabs.clone();
+
+        for path in tabs.iter().rev() {
+            if let Some(index) = self.groups.index_of(id) {
+                self.close_tab(index, path);
+            }
+        }
+
+        // A group without tabs to begin with.
+        if let Some(index) = self.groups.index_of(id) {
+            self.groups.remove(index);
+        }
+
+        self.focus_group(self.groups.focused);
+    }
+
+    /// Drops the file when no group has a tab for it anymore.
+    fn close_unshown(&mut self, file_path: &String) {
+        let shown = self
+            .groups
+            .groups
+            .iter()
+            .any(|group| group.tabs.contains(file_path));
+
+        if !shown {
             self.files.remove(file_path);
-            self.active_file = "".to_string();
-            self.components.clear();
+        }
+    }
+
+    /// Moves the tab to another group, which gets the focus.
+    pub fn move_tab(&mut self, from: usize, file_path: &String, to: usize) {
+        let to_id = self.groups.groups[to].id;
+
+        self.focus_group(to);
+        self.set_active_file(file_path);
+
+        self.close_tab(from, file_path);
+
+        // Closing the last tab of `from` shifts the groups after it.
+        if let Some(to) = self.groups.index_of(to_id) {
+            self.focus_group(to);
+        }
+    }
+}
 
-            match &self.files.values().last() {
-                Some(file) => self.insert(&file.path.to_string(), true),
-                _ => println!("todo"),
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(bytes: &[u8]) -> Vec<u8> {
+        let folder = tempfile::tempdir().unwrap();
+        let path = folder.path().join("file.txt");
+
+        fs::write(&path, bytes).unwrap();
+
+        let file = FileData::read(path.to_str().unwrap(), None).unwrap();
+
+        assert!(!file.content.contains('\r'));
+        file.to_disk().unwrap()
+    }
+
+    #[test]
+    fn saves_the_line_endings_it_read() {
+        for bytes in [&b"a\r\nb\r\n"[..], b"a\nb\n", b"a\r\nb", b"a\nb", b""] {
+            assert_eq!(round_trip(bytes), bytes);
+        }
+    }
+
+    /// Three groups side by side, each showing its own file.
+    fn three_groups() -> FileStore {
+        let mut file_store = FileStore::new();
+
+        for (index, path) in ["./a.txt", "./b.txt", "./c.txt"].into_iter().enumerate() {
+            // A split shows the active file in the new group too.
+            if index > 0 {
+                let previous = file_store.active_file.clone();
+
+                file_store.groups.split(editor_groups::Direction::Right);
+                file_store.groups.groups[index].close(&previous);
             }
+
+            let path = path.to_string();
+
+            file_store
+                .files
+                .insert(path.clone(), FileData::new(&path, "text\n".into()));
+            file_store.set_active_file(&path);
         }
+
+        file_store
+    }
+
+    fn shown(file_store: &FileStore) -> Vec<&str> {
+        file_store
+            .groups
+            .groups
+            .iter()
+            .map(|group| group.active.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn closes_one_group_at_a_time() {
+        for (index, left) in [
+            (0, ["./b.txt", "./c.txt"]),
+            (1, ["./a.txt", "./c.txt"]),
+            (2, ["./a.txt", "./b.txt"]),
+        ] {
+            let mut file_store = three_groups();
+
+            file_store.focus_group(index);
+            file_store.close_group(index);
+
+            assert_eq!(shown(&file_store), left);
+            assert_eq!(file_store.files.len(), 2);
+            assert!(file_store.groups.focused < 2);
+        }
+    }
+
+    #[test]
+    fn keeps_the_last_group() {
+        let mut file_store = three_groups();
+
+        file_store.close_group(2);
+        file_store.close_group(1);
+        file_store.close_group(0);
+
+        assert_eq!(file_store.groups.groups.len(), 1);
+        assert!(file_store.groups.focused().tabs.is_empty());
+        assert!(file_store.files.is_empty());
+        assert_eq!(file_store.active_file, "");
+    }
+
+    #[test]
+    fn keeps_an_edited_preview() {
+        let folder = tempfile::tempdir().unwrap();
+        let path = |name: &str| folder.path().join(name).to_string_lossy().to_string();
+        let (a, b, c) = (path("a.txt"), path("b.txt"), path("c.txt"));
+        let mut file_store = FileStore::new();
+
+        for path in [&a, &b, &c] {
+            fs::write(path, "text\n").unwrap();
+        }
+
+        file_store.preview(&a);
+        file_store.preview(&b);
+        assert_eq!(file_store.groups.focused().tabs, std::slice::from_ref(&b));
+        assert!(!file_store.files.contains_key(&a));
+
+        // As a line command from the menu would, without the editor.
+        let file = file_store.files.get_mut(&b).unwrap();
+
+        file.content = "edited\n".into();
+        file.version += 1;
+        file_store.preview(&c);
+
+        assert_eq!(file_store.groups.focused().tabs, [b.clone(), c.clone()]);
+        assert_eq!(file_store.files[&b].content, "edited\n");
+    }
+
+    #[test]
+    fn tells_edits_from_a_misdecoded_file() {
+        let folder = tempfile::tempdir().unwrap();
+        let path = folder.path().join("file.txt").to_string_lossy().to_string();
+        let mut file_store = FileStore::new();
+
+        fs::write(&path, b"caf\xe9\r\nbar\n").unwrap();
+        file_store.insert(&path, true);
+        file_store.reopen(&path, encoding_rs::UTF_8).unwrap();
+        assert!(file_store.files[&path].content.contains('\u{FFFD}'));
+        assert!(file_store.files[&path].is_saved());
+
+        file_store.reopen(&path, encoding_rs::WINDOWS_1252).unwrap();
+        assert_eq!(file_store.files[&path].content, "café\nbar\n");
+
+        let file = file_store.get_active_file_as_mut().unwrap();
+
+        file.content.push('!');
+        file.version += 1;
+        assert!(!file.is_saved());
+
+        file_store.save(&path).unwrap();
+        assert!(file_store.files[&path].is_saved());
+    }
+
+    #[test]
+    fn keys_files_relative_to_the_workspace() {
+        let root = env::current_dir().unwrap();
+
+        assert_eq!(
+            FileStore::store_path(&root.join("src/lib.rs")),
+            "./src/lib.rs"
+        );
+        assert_eq!(
+            FileStore::store_path(&root.canonicalize().unwrap().join("src/lib.rs")),
+            "./src/lib.rs"
+        );
+        assert_eq!(
+            FileStore::store_path(Path::new("/elsewhere/lib.rs")),
+            "/elsewhere/lib.rs"
+        );
+    }
+
+    #[test]
+    fn reports_files_it_cannot_open() {
+        let folder = tempfile::tempdir().unwrap();
+        let path = folder.path().join("gone.txt").to_string_lossy().to_string();
+        let mut file_store = FileStore::new();
+
+        file_store.preview(&path);
+        file_store.insert(&path, true);
+
+        assert!(file_store.files.is_empty());
+        assert!(file_store.groups.focused().tabs.is_empty());
+        assert!(file_store
+            .open_error
+            .is_some_and(|error| error.starts_with("Couldn't open")));
+    }
+
+    #[test]
+    fn keeps_the_final_newline_after_edits() {
+        let mut file = FileData::new("file.txt", "a\r\n".into());
+
+        file.content = "a\nb".into();
+
+        assert_eq!(file.to_disk().unwrap(), b"a\r\nb\r\n");
     }
 }
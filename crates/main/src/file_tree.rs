@@ -1,10 +1,25 @@
-use std::{fs::DirEntry, path::PathBuf};
+use std::{
+    fs::{self, DirEntry},
+    path::PathBuf,
+};
 
-use egui::{CursorIcon, Ui};
+use egui::{Align, Align2, CursorIcon, Rect, Response, RichText, TextStyle, Ui};
+use git::FileStatus;
+use theme::Theme;
 
-use crate::{file_store, file_utils};
+use crate::{
+    file_store, file_utils,
+    git_status::{self, GitStatus},
+};
 
-pub fn create(ui: &mut Ui, paths: &mut Vec<DirEntry>, mut files: &mut file_store::FileStore) {
+pub fn create(
+    ui: &mut Ui,
+    paths: &mut Vec<DirEntry>,
+    mut files: &mut file_store::FileStore,
+    reveal: &mut Option<PathBuf>,
+    git: &GitStatus,
+    theme: &Theme,
+) {
     paths.sort_by(|a, b| {
         b.metadata()
             .unwrap()
@@ -19,25 +34,85 @@ pub fn create(ui: &mut Ui, paths: &mut Vec<DirEntry>, mut files: &mut file_store
             let path_buff: PathBuf = path.path();
             let mut new_paths = file_utils::map_paths(&path_buff);
 
-            let folder = egui::CollapsingHeader::new(file_name);
+            let status = git.folder(&path_buff);
+            let mut folder =
+                egui::CollapsingHeader::new(text(file_name, status, theme)).id_source(&path_buff);
 
-            folder
+            // Opens the way to the file being revealed.
+            if reveal.as_ref().is_some_and(|target| {
+                fs::canonicalize(&path_buff).is_ok_and(|folder| target.starts_with(folder))
+            }) {
+                folder = folder.open(Some(true));
+            }
+
+            let header = folder
                 .show(ui, |inner_ui| {
-                    create(inner_ui, &mut new_paths, &mut files);
+                    create(inner_ui, &mut new_paths, &mut files, reveal, git, theme);
                 })
                 .header_response
                 .on_hover_cursor(CursorIcon::PointingHand);
+
+            // Ignored folders are only dimmed, the dot tells about changes.
+            if let Some(status) = status.filter(|status| *status != FileStatus::Ignored) {
+                badge(ui, &header, "●", status, theme);
+            }
         } else {
+            let status = git.file(&path.path());
+            let revealed = reveal.as_ref().is_some_and(|target| {
+                fs::canonicalize(path.path()).is_ok_and(|file| file == *target)
+            });
             let label = ui
-                .selectable_label(false, file_name)
+                .selectable_label(revealed, text(file_name, status, theme))
                 .on_hover_cursor(CursorIcon::PointingHand);
 
+            if revealed {
+                label.scroll_to_me(Some(Align::Center));
+                *reveal = None;
+            }
+
+            if let Some(status) = status {
+                badge(ui, &label, status.letter(), status, theme);
+            }
+
+            // A click previews the file, a double-click keeps it open.
             if label.clicked() {
                 let path_buf = path.path();
                 let file_path = file_store::FileStore::get_file_path(&path_buf);
 
-                files.insert(&file_path, true);
+                files.preview(&file_path);
+            }
+
+            if label.double_clicked() {
+                let path_buf = path.path();
+                let file_path = file_store::FileStore::get_file_path(&path_buf);
+
+                files.keep(&file_path);
             }
         }
     }
 }
+
+fn text(name: String, status: Option<FileStatus>, theme: &Theme) -> RichText {
+    match status {
+        Some(status) => RichText::new(name).color(git_status::color(status, theme)),
+        None => RichText::new(name),
+    }
+}
+
+/// The status letter at the right edge of the explorer, on the entry's row.
+fn badge(ui: &Ui, response: &Response, badge: &str, status: FileStatus, theme: &Theme) {
+    let row = Rect::from_x_y_ranges(ui.max_rect().x_range(), response.rect.y_range());
+
+    ui.painter().text(
+        row.right_center() - egui::vec2(6.0, 0.0),
+        Align2::RIGHT_CENTER,
+        badge,
+        TextStyle::Small.resolve(ui.style()),
+        git_status::color(status, theme),
+    );
+
+    if status != FileStatus::Ignored {
+        ui.interact(row, response.id.with("git-status"), egui::Sense::hover())
+            .on_hover_text(status.label());
+    }
+}
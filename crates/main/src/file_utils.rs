@@ -1,14 +1,52 @@
 use crate::{file_store, State};
+use egui::{Align2, Context, RichText, Window};
 use rfd::AsyncFileDialog;
 use std::{
-    fs::{self, DirEntry},
+    fs::{self, DirEntry, File},
+    io::{self, Write},
     path::Path,
+    process,
 };
 
 pub fn map_paths(path: &Path) -> Vec<DirEntry> {
     fs::read_dir(path).unwrap().map(|p| p.unwrap()).collect()
 }
 
+/// Writes to a temporary file next to the target, then renames it over, so
+/// the file never ends up half written. A symlink is written through to
+/// its target, which keeps its permissions. In a folder no file can be
+/// created in, the target is written in place.
+pub fn write_atomic(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let target = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let folder = match target.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let name = target.file_name().unwrap_or_default().to_string_lossy();
+    let temp = folder.join(format!(".{name}.{}.tmp", process::id()));
+
+    let Ok(mut file) = File::create(&temp) else {
+        return fs::write(&target, contents);
+    };
+
+    let written = (|| {
+        file.write_all(contents)?;
+        file.sync_all()?;
+
+        if let Ok(metadata) = fs::metadata(&target) {
+            fs::set_permissions(&temp, metadata.permissions())?;
+        }
+
+        fs::rename(&temp, &target)
+    })();
+
+    if written.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+
+    written
+}
+
 pub fn open_file(state: &mut State, directory: &str) {
     let _future = async {
         let file = AsyncFileDialog::new()
@@ -30,3 +68,71 @@ pub fn open_file(state: &mut State, directory: &str) {
         }
     };
 }
+
+pub fn show_open_error(ctx: &Context, state: &mut State) {
+    let Some(error) = &state.file_store.open_error else {
+        return;
+    };
+
+    let mut dismissed = false;
+
+    Window::new("Opening failed")
+        .collapsible(false)
+        .resizable(false)
+        .anchor(Align2::RIGHT_BOTTOM, [-12.0, -12.0])
+        .show(ctx, |ui| {
+            ui.set_max_width(360.0);
+            ui.label(RichText::new(error).color(state.theme.error.main));
+
+            if ui.button("Dismiss").clicked() {
+                dismissed = true;
+            }
+        });
+
+    if dismissed {
+        state.file_store.open_error = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replaces_the_file() {
+        let folder = tempfile::tempdir().unwrap();
+        let path = folder.path().join("file.txt");
+
+        fs::write(&path, "old").unwrap();
+        write_atomic(&path, b"new").unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new");
+        assert_eq!(fs::read_dir(folder.path()).unwrap().count(), 1);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn writes_through_a_symlink_keeping_permissions() {
+        use std::os::unix::fs::{symlink, PermissionsExt};
+
+        let folder = tempfile::tempdir().unwrap();
+        let target = folder.path().join("script.sh");
+        let link = folder.path().join("link.sh");
+
+        fs::write(&target, "old").unwrap();
+        fs::set_permissions(&target, fs::Permissions::from_mode(0o755)).unwrap();
+        symlink(&target, &link).unwrap();
+
+        write_atomic(&link, b"new").unwrap();
+
+        assert!(fs::symlink_metadata(&link)
+            .unwrap()
+            .file_type()
+            .is_symlink());
+        assert_eq!(fs::read_to_string(&target).unwrap(), "new");
+        assert_eq!(
+            fs::metadata(&target).unwrap().permissions().mode() & 0o777,
+            0o755
+        );
+    }
+}
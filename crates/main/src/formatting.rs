@@ -0,0 +1,359 @@
+//! Format Document and Format Selection, through the file's language server
+//! or else the language's external formatter, and format on save.
+
+use egui::{Align2, Context, Id, Key, KeyboardShortcut, Modifiers, RichText, Window};
+use lsp::{
+    edit,
+    lsp_types::{
+        request, DocumentFormattingParams, DocumentRangeFormattingParams, FormattingOptions,
+        TextDocumentIdentifier, TextEdit,
+    },
+    position, LspManager,
+};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    io::Write,
+    ops::Range,
+    path::Path,
+    process::{Command, Stdio},
+    thread,
+    time::Duration,
+};
+
+use crate::{
+    editor,
+    file_store::FileData,
+    indent::IndentStyle,
+    language_servers,
+    languages::{self, Language},
+    State,
+};
+
+pub const SHORTCUT: KeyboardShortcut =
+    KeyboardShortcut::new(Modifiers::ALT.plus(Modifiers::SHIFT), Key::F);
+
+/// How long formatting waits for the language server's edits. The wait
+/// blocks the UI, so format on save can hold up every save by this much.
+const TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Default)]
+pub struct Formatting {
+    /// Names of the languages whose files are formatted before being saved.
+    pub format_on_save: BTreeSet<String>,
+    /// The formatter command line set for a language, by name, in place of
+    /// its default one. Split into arguments the way a shell would.
+    pub commands: BTreeMap<String, String>,
+    error: Option<String>,
+}
+
+impl Formatting {
+    /// The program and arguments formatting `language`'s files when no
+    /// language server does. Fails on a command line with unclosed quotes.
+    fn command(&self, language: &Language) -> Result<Option<Vec<String>>, String> {
+        match self.commands.get(language.name) {
+            Some(command) if !command.trim().is_empty() => shell_words::split(command)
+                .map(Some)
+                .map_err(|_| format!("The {} formatter has an unclosed quote.", language.name)),
+            _ => Ok(language
+                .formatter
+                .map(|formatter| formatter.iter().map(|arg| arg.to_string()).collect())),
+        }
+    }
+}
+
+/// The language's own formatter command line, shown until another is set.
+pub fn default_command(language: &Language) -> String {
+    shell_words::join(language.formatter.unwrap_or_default())
+}
+
+/// Shift+Alt+F while the editor has focus.
+pub fn handle_input(ctx: &Context, state: &mut State) {
+    let id = state.file_store.get_active_file_id().into();
+
+    if ctx.memory(|memory| memory.has_focus(id))
+        && ctx.input_mut(|input| input.consume_shortcut(&SHORTCUT))
+    {
+        format(ctx, state, false);
+    }
+}
+
+/// Formats the active file, or only its selection, as a single undo step.
+pub fn format(ctx: &Context, state: &mut State, selection_only: bool) {
+    let id = state.file_store.get_active_file_id().into();
+    let command = languages::from_path(&state.file_store.active_file)
+        .map(|language| state.formatting.command(language))
+        .transpose();
+
+    let command = match command {
+        Ok(command) => command.flatten(),
+        Err(error) => {
+            state.formatting.error = Some(error);
+            return;
+        }
+    };
+
+    if let Some(file) = state.file_store.get_active_file_as_mut() {
+        state.formatting.error = format_file(
+            ctx,
+            id,
+            file,
+            &mut state.lsp,
+            command.as_deref(),
+            selection_only,
+        )
+        .err();
+    }
+}
+
+/// Formats the active file when its language has format on save turned on.
+/// Runs right before the file is written.
+pub fn before_save(ctx: &Context, state: &mut State) {
+    let enabled = state
+        .file_store
+        .get_active_file()
+        .and_then(|file| languages::from_path(&file.path))
+        .is_some_and(|language| state.formatting.format_on_save.contains(language.name));
+
+    if enabled {
+        format(ctx, state, false);
+    }
+}
+
+fn format_file(
+    ctx: &Context,
+    id: Id,
+    file: &mut FileData,
+    lsp: &mut LspManager,
+    command: Option<&[String]>,
+    selection_only: bool,
+) -> Result<(), String> {
+    let selection = editor::get_selection(ctx, id, file);
+    let [secondary, primary] = [selection.secondary, selection.primary]
+        .map(|cursor| editor::byte_offset(&file.content, cursor.index));
+    let (start, end) = (secondary.min(primary), secondary.max(primary));
+
+    let range = match selection_only {
+        true if start == end => return Err("Select the text to format first.".into()),
+        true => Some(start..end),
+        false => None,
+    };
+
+    let (content, secondary, primary) = match server_edits(lsp, file, range.clone())? {
+        Some(edits) => (
+            edit::apply(&file.content, &edits),
+            edit::map_offset(&file.content, &edits, secondary),
+            edit::map_offset(&file.content, &edits, primary),
+        ),
+        None if range.is_some() => {
+            return Err("No language server can format a selection of this file.".into())
+        }
+        None => {
+            let content = run_formatter(file, command)?;
+
+            (
+                content.clone(),
+                follow_offset(&file.content, &content, secondary),
+                follow_offset(&file.content, &content, primary),
+            )
+        }
+    };
+
+    if content != file.content {
+        let selection = editor::char_range(&content, secondary..primary);
+        editor::apply_edit(ctx, id, file, content, selection);
+    }
+
+    Ok(())
+}
+
+/// The server's edits, or `None` when no server formats this file.
+fn server_edits(
+    lsp: &mut LspManager,
+    file: &FileData,
+    range: Option<Range<usize>>,
+) -> Result<Option<Vec<TextEdit>>, String> {
+    let options = options(file.indent_style);
+
+    let result = match range {
+        Some(range) => language_servers::request::<request::RangeFormatting>(
+            lsp,
+            file,
+            |capabilities| {
+                language_servers::enabled(capabilities.document_range_formatting_provider.as_ref())
+            },
+            |uri| DocumentRangeFormattingParams {
+                text_document: TextDocumentIdentifier { uri },
+                range: position::from_range(&file.content, range),
+                options,
+                work_done_progress_params: Default::default(),
+            },
+        )
+        .map(|pending| pending.wait(TIMEOUT)),
+        None => language_servers::request::<request::Formatting>(
+            lsp,
+            file,
+            |capabilities| {
+                language_servers::enabled(capabilities.document_formatting_provider.as_ref())
+            },
+            |uri| DocumentFormattingParams {
+                text_document: TextDocumentIdentifier { uri },
+                options,
+                work_done_progress_params: Default::default(),
+            },
+        )
+        .map(|pending| pending.wait(TIMEOUT)),
+    };
+
+    match result {
+        Some(Ok(edits)) => Ok(Some(edits.unwrap_or_default())),
+        Some(Err(error)) => Err(error.to_string()),
+        None => Ok(None),
+    }
+}
+
+fn options(indent_style: IndentStyle) -> FormattingOptions {
+    let (tab_size, insert_spaces) = match indent_style {
+        IndentStyle::Tabs => (4, false),
+        IndentStyle::Spaces(width) => (width as u32, true),
+    };
+
+    FormattingOptions {
+        tab_size,
+        insert_spaces,
+        ..Default::default()
+    }
+}
+
+/// Pipes the content through the language's formatter command.
+fn run_formatter(file: &FileData, command: Option<&[String]>) -> Result<String, String> {
+    let Some([program, args @ ..]) = command else {
+        return Err("There is no formatter for this file.".into());
+    };
+
+    let directory = Path::new(&file.path)
+        .parent()
+        .filter(|directory| directory.is_dir())
+        .unwrap_or(Path::new("."));
+
+    let mut child = Command::new(program)
+        .args(args.iter().map(|arg| arg.replace("{file}", &file.path)))
+        .current_dir(directory)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|error| format!("Couldn't run {program}: {error}"))?;
+
+    // Written from another thread so a formatter that starts answering
+    // before it has read everything can't deadlock us.
+    let mut stdin = child.stdin.take().unwrap();
+    let content = file.content.clone();
+    let writer = thread::spawn(move || stdin.write_all(content.as_bytes()));
+
+    let output = child
+        .wait_with_output()
+        .map_err(|error| format!("{program} failed: {error}"))?;
+    let _ = writer.join();
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("{program} failed: {}", stderr.trim()));
+    }
+
+    String::from_utf8(output.stdout).map_err(|_| format!("{program} printed invalid UTF-8"))
+}
+
+/// Where `offset` ends up after a formatter rewrote `old` into `new`, found
+/// by counting the non-whitespace characters before it, which formatting
+/// mostly leaves alone.
+fn follow_offset(old: &str, new: &str, offset: usize) -> usize {
+    let count = old[..offset]
+        .chars()
+        .filter(|char| !char.is_whitespace())
+        .count();
+
+    if count == 0 {
+        return 0;
+    }
+
+    new.char_indices()
+        .filter(|(_, char)| !char.is_whitespace())
+        .nth(count - 1)
+        .map_or(new.len(), |(index, char)| index + char.len_utf8())
+}
+
+/// Shows what went wrong the last time formatting was asked for.
+pub fn show_error(ctx: &Context, state: &mut State) {
+    let Some(error) = &state.formatting.error else {
+        return;
+    };
+
+    let mut dismissed = false;
+
+    Window::new("Formatting failed")
+        .collapsible(false)
+        .resizable(false)
+        .anchor(Align2::RIGHT_BOTTOM, [-12.0, -12.0])
+        .show(ctx, |ui| {
+            ui.set_max_width(360.0);
+            ui.label(RichText::new(error).color(state.theme.error.main));
+
+            if ui.button("Dismiss").clicked() {
+                dismissed = true;
+            }
+        });
+
+    if dismissed {
+        state.formatting.error = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rust() -> &'static Language {
+        languages::from_path("main.rs").unwrap()
+    }
+
+    fn command(line: &str) -> Result<Option<Vec<String>>, String> {
+        let mut formatting = Formatting::default();
+
+        formatting.commands.insert("rust".into(), line.into());
+        formatting.command(rust())
+    }
+
+    #[test]
+    fn splits_commands_like_a_shell() {
+        assert_eq!(
+            command(r#"'/opt/my tools/rustfmt' --config "max_width=80, hard_tabs=true" {file}"#),
+            Ok(Some(vec![
+                "/opt/my tools/rustfmt".into(),
+                "--config".into(),
+                "max_width=80, hard_tabs=true".into(),
+                "{file}".into(),
+            ]))
+        );
+        assert_eq!(
+            command(r"fmt a\ b"),
+            Ok(Some(vec!["fmt".into(), "a b".into()]))
+        );
+        assert_eq!(
+            command("fmt 'unclosed"),
+            Err("The rust formatter has an unclosed quote.".into())
+        );
+    }
+
+    #[test]
+    fn falls_back_to_the_default_command() {
+        assert_eq!(
+            command("  "),
+            Ok(Some(vec![
+                "rustfmt".into(),
+                "--edition".into(),
+                "2021".into()
+            ]))
+        );
+        assert_eq!(default_command(rust()), "rustfmt --edition 2021");
+    }
+}
@@ -0,0 +1,309 @@
+//! Who last changed the lines of the active file: after the cursor's line,
+//! or for every line in a gutter left of the text.
+
+use egui::{text_edit::TextEditOutput, Align2, Context, Rect, Sense, TextStyle, Ui, Vec2};
+use git::{
+    blame::Blame,
+    git2::Oid,
+    history::{self, Revision},
+    index,
+};
+use std::{
+    collections::HashMap,
+    path::Path,
+    sync::mpsc::{self, Receiver, TryRecvError},
+    thread,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use theme::Theme;
+
+use crate::{diagnostics, editor, file_store::FileData, git_gutter, git_status::GitStatus};
+
+/// Room left of the diff markers for the author and date in gutter mode.
+pub const GUTTER_WIDTH: f32 = 200.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BlameMode {
+    #[default]
+    Off,
+    /// At the end of the cursor's line.
+    CurrentLine,
+    /// Every line, in a gutter.
+    Gutter,
+}
+
+impl BlameMode {
+    pub const ALL: [BlameMode; 3] = [BlameMode::Off, BlameMode::CurrentLine, BlameMode::Gutter];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            BlameMode::Off => "Off",
+            BlameMode::CurrentLine => "Current Line",
+            BlameMode::Gutter => "Gutter",
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct GitBlame {
+    pub mode: BlameMode,
+    blames: HashMap<String, FileBlame>,
+}
+
+struct FileBlame {
+    generation: u64,
+    /// The commit the blame is of, `None` when the file isn't in a
+    /// repository or it has no commits.
+    head: Option<Oid>,
+    version: i32,
+    /// `None` when the file isn't in a repository, or until it's blamed.
+    blame: Option<Blame>,
+    /// The blame of the new HEAD, while a thread works it out.
+    loading: Option<Receiver<Option<Blame>>>,
+}
+
+impl GitBlame {
+    /// Switches to `mode`, or back off when it's already on.
+    pub fn toggle(&mut self, mode: BlameMode) {
+        self.mode = match self.mode == mode {
+            true => BlameMode::Off,
+            false => mode,
+        };
+    }
+
+    pub fn gutter_width(&self) -> f32 {
+        match self.mode {
+            BlameMode::Gutter => GUTTER_WIDTH,
+            _ => 0.0,
+        }
+    }
+}
+
+/// Runs after the editor's `TextEdit`: blames the file again when it or the
+/// repository changed, and paints the annotations.
+pub fn update(
+    ui: &Ui,
+    file: &FileData,
+    blame: &mut GitBlame,
+    git: &GitStatus,
+    output: &TextEditOutput,
+    theme: &Theme,
+) {
+    if blame.mode == BlameMode::Off {
+        return;
+    }
+
+    let generation = git.generation();
+    let file_blame = blame
+        .blames
+        .entry(file.path.clone())
+        .or_insert_with(|| FileBlame {
+            generation,
+            head: index::head_id(Path::new(&file.path)).ok().flatten(),
+            version: -1,
+            blame: None,
+            loading: Some(load(ui.ctx(), &file.path)),
+        });
+
+    // Every file system event bumps the generation, the blame only needs
+    // doing again when HEAD moved.
+    if file_blame.generation != generation {
+        file_blame.generation = generation;
+
+        let head = index::head_id(Path::new(&file.path)).ok().flatten();
+
+        if head != file_blame.head {
+            file_blame.head = head;
+            file_blame.loading = Some(load(ui.ctx(), &file.path));
+        }
+    }
+
+    if let Some(receiver) = &file_blame.loading {
+        match receiver.try_recv() {
+            Ok(loaded) => {
+                file_blame.blame = loaded;
+                file_blame.version = -1;
+                file_blame.loading = None;
+            }
+            Err(TryRecvError::Disconnected) => file_blame.loading = None,
+            Err(TryRecvError::Empty) => {}
+        }
+    }
+
+    if file_blame.version != file.version {
+        if let Some(blame) = &mut file_blame.blame {
+            blame.update(&file.content);
+        }
+
+        file_blame.version = file.version;
+    }
+
+    let Some(file_blame) = &file_blame.blame else {
+        return;
+    };
+
+    match blame.mode {
+        BlameMode::Off => {}
+        BlameMode::CurrentLine => paint_current_line(ui, file, file_blame, output, theme),
+        BlameMode::Gutter => paint_gutter(ui, file_blame, output, theme),
+    }
+}
+
+/// Blames the file on a thread, libgit2 going through its whole history.
+fn load(ctx: &Context, path: &str) -> Receiver<Option<Blame>> {
+    let (sender, receiver) = mpsc::channel();
+    let (ctx, path) = (ctx.clone(), path.to_string());
+
+    thread::spawn(move || {
+        let _ = sender.send(Blame::load(Path::new(&path)).ok());
+        ctx.request_repaint();
+    });
+
+    receiver
+}
+
+fn paint_current_line(
+    ui: &Ui,
+    file: &FileData,
+    blame: &Blame,
+    output: &TextEditOutput,
+    theme: &Theme,
+) {
+    let Some(cursor) = output.state.cursor.char_range() else {
+        return;
+    };
+
+    let line = editor::row_of(
+        &file.content,
+        editor::byte_offset(&file.content, cursor.primary.index),
+    );
+    let Some((_, last_row)) = line_rows(output).nth(line) else {
+        return;
+    };
+
+    let annotation = match blame.line(line) {
+        Some(revision) => format!(
+            "{}, {} • {}",
+            revision.author,
+            relative_time(revision),
+            revision.summary
+        ),
+        None if line < blame.lines.len() => "You, Uncommitted changes".into(),
+        None => return,
+    };
+
+    ui.painter_at(output.text_clip_rect).text(
+        last_row.right_center() + Vec2::new(32.0, 0.0),
+        Align2::LEFT_CENTER,
+        annotation,
+        TextStyle::Monospace.resolve(ui.style()),
+        theme.text_color.disabled,
+    );
+}
+
+fn paint_gutter(ui: &Ui, blame: &Blame, output: &TextEditOutput, theme: &Theme) {
+    let right = output.galley_pos.x - diagnostics::GUTTER_WIDTH - git_gutter::GUTTER_WIDTH - 8.0;
+    let left = right - GUTTER_WIDTH + 8.0;
+    let gutter = Rect::from_x_y_ranges(left..=right, output.text_clip_rect.y_range());
+    let painter = ui.painter_at(gutter);
+    let font = TextStyle::Small.resolve(ui.style());
+    let mut previous = None;
+
+    for (line, (row, _)) in line_rows(output).enumerate() {
+        let revision = blame.lines.get(line).copied().flatten();
+
+        // Only the first line of each run of lines from the same commit.
+        let first = line == 0 || revision != previous;
+        previous = revision;
+
+        if !first || row.bottom() < gutter.top() || row.top() > gutter.bottom() {
+            continue;
+        }
+
+        let (author, date) = match blame.line(line) {
+            Some(revision) => (revision.author.as_str(), relative_time(revision)),
+            None => ("You", "Uncommitted".into()),
+        };
+
+        painter.text(
+            egui::pos2(left, row.center().y),
+            Align2::LEFT_CENTER,
+            truncate(author, 16),
+            font.clone(),
+            theme.text_color.secondary,
+        );
+        painter.text(
+            egui::pos2(right, row.center().y),
+            Align2::RIGHT_CENTER,
+            date,
+            font.clone(),
+            theme.text_color.disabled,
+        );
+    }
+
+    let response = ui.interact(gutter, output.response.id.with("blame"), Sense::hover());
+
+    let Some(pointer) = response.hover_pos() else {
+        return;
+    };
+
+    let hovered = line_rows(output)
+        .position(|(row, last_row)| (row.top()..=last_row.bottom()).contains(&pointer.y));
+
+    if let Some(revision) = hovered.and_then(|line| blame.line(line)) {
+        response.on_hover_ui_at_pointer(|ui| tooltip(ui, revision));
+    }
+}
+
+/// The commit in full, for the blame gutter's hover.
+pub fn tooltip(ui: &mut Ui, revision: &Revision) {
+    ui.set_max_width(420.0);
+
+    ui.horizontal(|ui| {
+        ui.strong(&revision.author);
+        ui.weak(format!("<{}>", revision.email));
+    });
+    ui.weak(format!(
+        "{} • {}",
+        revision.short_id(),
+        relative_time(revision)
+    ));
+    ui.separator();
+    ui.label(&revision.message);
+}
+
+pub fn relative_time(revision: &Revision) -> String {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |now| now.as_secs() as i64);
+
+    history::relative_time(revision.time, now)
+}
+
+fn truncate(text: &str, length: usize) -> String {
+    match text.chars().count() > length {
+        true => format!("{}…", text.chars().take(length - 1).collect::<String>()),
+        false => text.into(),
+    }
+}
+
+/// The screen rects of the first and the last row of every line; wrapped
+/// lines have several.
+fn line_rows(output: &TextEditOutput) -> impl Iterator<Item = (Rect, Rect)> + '_ {
+    let offset = output.galley_pos.to_vec2();
+    let mut rows = output.galley.rows.iter();
+
+    std::iter::from_fn(move || {
+        let first = rows.next()?;
+        let mut last = first;
+
+        while !last.ends_with_newline {
+            match rows.next() {
+                Some(row) => last = row,
+                None => break,
+            }
+        }
+
+        Some((first.rect.translate(offset), last.rect.translate(offset)))
+    })
+}
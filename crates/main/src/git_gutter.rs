@@ -0,0 +1,495 @@
+//! Markers left of the text for the lines that differ from the staged or
+//! the committed version, and the peek that shows what they were before,
+//! with actions to stage, unstage or revert one change at a time.
+
+use diff::{self, Hunk, HunkKind};
+use egui::{
+    text_edit::TextEditOutput, Area, Context, CursorIcon, Frame, Id, Key, KeyboardShortcut,
+    Modifiers, Order, Pos2, Rect, RichText, ScrollArea, Sense, Shape, Stroke, TextStyle, Ui, Vec2,
+};
+use git::index;
+use std::{collections::HashMap, ops::Range, path::Path};
+use theme::Theme;
+
+use crate::{diagnostics, editor, file_store::FileData, git_status::GitStatus, line_ending, State};
+
+pub const NEXT_SHORTCUT: KeyboardShortcut = KeyboardShortcut::new(Modifiers::ALT, Key::F5);
+pub const PREVIOUS_SHORTCUT: KeyboardShortcut =
+    KeyboardShortcut::new(Modifiers::ALT.plus(Modifiers::SHIFT), Key::F5);
+
+/// Room left of the diagnostics for the markers.
+pub const GUTTER_WIDTH: f32 = 6.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DiffBase {
+    /// Only the changes that aren't staged yet.
+    #[default]
+    Index,
+    /// Staged changes too, shown dimmed.
+    Head,
+}
+
+#[derive(Default)]
+pub struct GitGutter {
+    pub base: DiffBase,
+    diffs: HashMap<String, FileDiff>,
+    /// The file and the index of the change shown inline.
+    peek: Option<(String, usize)>,
+    error: Option<String>,
+}
+
+struct FileDiff {
+    generation: u64,
+    version: i32,
+    base: DiffBase,
+    /// Both with `\n` line endings, as the buffer has them.
+    head: Option<String>,
+    index: Option<String>,
+    markers: Vec<Marker>,
+}
+
+struct Marker {
+    /// Lines of the buffer.
+    hunk: Hunk,
+    /// Staged changes are between HEAD and the index; their lines are
+    /// mapped onto the buffer.
+    staged: bool,
+}
+
+enum Action {
+    Stage(usize),
+    Unstage(usize),
+    Revert(usize),
+    Go(usize),
+    Close,
+}
+
+impl FileDiff {
+    fn load(path: &Path, generation: u64) -> Self {
+        FileDiff {
+            generation,
+            version: -1,
+            base: DiffBase::Index,
+            head: index::head_text(path)
+                .ok()
+                .flatten()
+                .map(line_ending::normalize),
+            index: index::index_text(path)
+                .ok()
+                .flatten()
+                .map(line_ending::normalize),
+            markers: Vec::new(),
+        }
+    }
+
+    fn update(&mut self, content: &str, base: DiffBase) {
+        let Some(index) = &self.index else {
+            self.markers.clear();
+            return;
+        };
+
+        let unstaged = diff::hunks(index, content);
+        let mut markers: Vec<Marker> = Vec::new();
+
+        if base == DiffBase::Head {
+            let head = self.head.as_deref().unwrap_or_default();
+
+            for mut hunk in diff::hunks(head, index) {
+                let start = diff::map_line(&unstaged, hunk.new_lines.start);
+                let end = diff::map_line(&unstaged, hunk.new_lines.end).max(start);
+
+                hunk.new_lines = start..end;
+                markers.push(Marker { hunk, staged: true });
+            }
+        }
+
+        markers.extend(unstaged.into_iter().map(|hunk| Marker {
+            hunk,
+            staged: false,
+        }));
+        markers.sort_by_key(|marker| (marker.hunk.new_lines.start, marker.staged));
+
+        self.markers = markers;
+    }
+}
+
+/// Alt+F5 and Shift+Alt+F5 while the editor has focus.
+pub fn handle_input(ctx: &Context, state: &mut State) {
+    let id = state.file_store.get_active_file_id().into();
+
+    if !ctx.memory(|memory| memory.has_focus(id)) {
+        return;
+    }
+
+    // Shift+Alt+F5 first, consuming Alt+F5 would swallow it too.
+    if ctx.input_mut(|input| input.consume_shortcut(&PREVIOUS_SHORTCUT)) {
+        go_to_change(ctx, state, false);
+    } else if ctx.input_mut(|input| input.consume_shortcut(&NEXT_SHORTCUT)) {
+        go_to_change(ctx, state, true);
+    }
+}
+
+/// Moves the cursor to the next or previous change, wrapping around, and
+/// keeps the peek open on it if it was.
+pub fn go_to_change(ctx: &Context, state: &mut State, forward: bool) {
+    let id = state.file_store.get_active_file_id().into();
+    let Some(file) = state.file_store.get_active_file() else {
+        return;
+    };
+    let Some(file_diff) = state.git_gutter.diffs.get(&file.path) else {
+        return;
+    };
+
+    let selection = editor::get_selection(ctx, id, file);
+    let line = editor::row_of(
+        &file.content,
+        editor::byte_offset(&file.content, selection.primary.index),
+    );
+    let starts: Vec<usize> = file_diff
+        .markers
+        .iter()
+        .map(|marker| marker.hunk.new_lines.start)
+        .collect();
+
+    let target = match forward {
+        true => starts
+            .iter()
+            .position(|start| *start > line)
+            .or((!starts.is_empty()).then_some(0)),
+        false => starts
+            .iter()
+            .rposition(|start| *start < line)
+            .or(starts.len().checked_sub(1)),
+    };
+
+    let Some(target) = target else {
+        return;
+    };
+
+    editor::reveal(ctx, id, file, line_offset(&file.content, starts[target]));
+
+    if let Some((path, index)) = &mut state.git_gutter.peek {
+        if *path == file.path {
+            *index = target;
+        }
+    }
+}
+
+/// Runs after the editor's `TextEdit`: paints the markers, opens the peek
+/// when one is clicked and shows it.
+pub fn update(
+    ui: &Ui,
+    id: Id,
+    file: &mut FileData,
+    gutter: &mut GitGutter,
+    git: &mut GitStatus,
+    output: &TextEditOutput,
+    theme: &Theme,
+) {
+    let generation = git.generation();
+    let file_diff = gutter
+        .diffs
+        .entry(file.path.clone())
+        .or_insert_with(|| FileDiff::load(Path::new(&file.path), generation));
+
+    if file_diff.generation != generation {
+        *file_diff = FileDiff::load(Path::new(&file.path), generation);
+    }
+
+    if file_diff.version != file.version || file_diff.base != gutter.base {
+        file_diff.update(&file.content, gutter.base);
+        file_diff.version = file.version;
+        file_diff.base = gutter.base;
+    }
+
+    let right = output.galley_pos.x - diagnostics::GUTTER_WIDTH;
+    let painter = ui.painter_at(
+        output
+            .text_clip_rect
+            .expand2(Vec2::new(diagnostics::GUTTER_WIDTH + GUTTER_WIDTH, 0.0)),
+    );
+
+    for (index, marker) in file_diff.markers.iter().enumerate() {
+        let rows = rows_rect(output, marker.hunk.new_lines.clone());
+        let color = color(marker.hunk.kind(), theme);
+        let color = match marker.staged {
+            true => color.gamma_multiply(0.45),
+            false => color,
+        };
+
+        let area = match marker.hunk.kind() {
+            HunkKind::Deleted => {
+                let tip = Pos2::new(right - GUTTER_WIDTH + 1.0, rows.top());
+                let size = 4.0;
+
+                painter.add(Shape::convex_polygon(
+                    vec![
+                        tip + Vec2::new(0.0, -size),
+                        tip + Vec2::new(size, 0.0),
+                        tip + Vec2::new(0.0, size),
+                    ],
+                    color,
+                    Stroke::NONE,
+                ));
+
+                Rect::from_x_y_ranges(
+                    right - GUTTER_WIDTH..=right,
+                    rows.top() - size..=rows.top() + size,
+                )
+            }
+            HunkKind::Added | HunkKind::Modified => {
+                let bar = Rect::from_x_y_ranges(
+                    right - GUTTER_WIDTH + 1.0..=right - GUTTER_WIDTH + 4.0,
+                    rows.y_range(),
+                );
+
+                painter.rect_filled(bar, 0.0, color);
+                Rect::from_x_y_ranges(right - GUTTER_WIDTH..=right, rows.y_range())
+            }
+        };
+
+        let response = ui
+            .interact(area, id.with(("git-marker", index)), Sense::click())
+            .on_hover_cursor(CursorIcon::PointingHand);
+
+        if response.clicked() {
+            let peeked = gutter.peek.as_ref() == Some(&(file.path.clone(), index));
+
+            gutter.peek = (!peeked).then(|| (file.path.clone(), index));
+        }
+    }
+
+    let Some((_, index)) = gutter
+        .peek
+        .as_ref()
+        .filter(|(path, index)| *path == file.path && *index < file_diff.markers.len())
+    else {
+        return;
+    };
+
+    let error = gutter.error.as_deref();
+
+    if let Some(action) = show_peek(ui, id, file_diff, *index, error, output, theme) {
+        let staging = matches!(action, Action::Stage(_) | Action::Unstage(_));
+
+        gutter.error = run(ui.ctx(), id, file, gutter, action).err();
+
+        if staging {
+            git.refresh();
+        }
+    }
+}
+
+fn run(
+    ctx: &Context,
+    id: Id,
+    file: &mut FileData,
+    gutter: &mut GitGutter,
+    action: Action,
+) -> Result<(), String> {
+    let path = Path::new(&file.path);
+    let Some(file_diff) = gutter.diffs.get_mut(&file.path) else {
+        return Ok(());
+    };
+    let marker = |index: usize| &file_diff.markers[index];
+    let error = |error: git::Error| error.message().to_string();
+
+    match action {
+        Action::Stage(index) => {
+            let staged = file_diff.index.as_deref().unwrap_or_default();
+            let hunk = &marker(index).hunk;
+
+            let text = file.line_ending.apply(&diff::apply(staged, hunk));
+
+            index::stage_text(path, &text).map_err(error)?;
+            gutter.peek = None;
+        }
+        Action::Unstage(index) => {
+            // The staged hunk's own lines, not the ones mapped onto the
+            // buffer.
+            let staged = file_diff.index.as_deref().unwrap_or_default();
+            let head = file_diff.head.as_deref().unwrap_or_default();
+            let hunk = diff::hunks(head, staged)
+                .into_iter()
+                .find(|hunk| hunk.old_lines == marker(index).hunk.old_lines);
+
+            if let Some(hunk) = hunk {
+                let text = file.line_ending.apply(&diff::revert(staged, &hunk));
+
+                index::stage_text(path, &text).map_err(error)?;
+            }
+
+            gutter.peek = None;
+        }
+        Action::Revert(index) => {
+            let hunk = &marker(index).hunk;
+            let content = diff::revert(&file.content, hunk);
+            let offset = line_offset(&content, hunk.new_lines.start);
+            let selection = editor::char_range(&content, offset..offset);
+
+            editor::apply_edit(ctx, id, file, content, selection);
+            gutter.peek = None;
+        }
+        Action::Go(index) => {
+            let start = marker(index).hunk.new_lines.start;
+
+            editor::reveal(ctx, id, file, line_offset(&file.content, start));
+            gutter.peek = Some((file.path.clone(), index));
+        }
+        Action::Close => gutter.peek = None,
+    }
+
+    Ok(())
+}
+
+/// The old text of the change, below its last line and as wide as the
+/// editor.
+fn show_peek(
+    ui: &Ui,
+    id: Id,
+    file_diff: &FileDiff,
+    index: usize,
+    error: Option<&str>,
+    output: &TextEditOutput,
+    theme: &Theme,
+) -> Option<Action> {
+    let marker = &file_diff.markers[index];
+    let rows = rows_rect(output, marker.hunk.new_lines.clone());
+    let count = file_diff.markers.len();
+    let mut action = None;
+
+    Area::new(id.with("git-peek"))
+        .order(Order::Foreground)
+        .fixed_pos(Pos2::new(output.text_clip_rect.left(), rows.bottom() + 2.0))
+        .show(ui.ctx(), |ui| {
+            Frame::popup(ui.style()).show(ui, |ui| {
+                ui.set_width(output.text_clip_rect.width() - 16.0);
+
+                ui.horizontal(|ui| {
+                    let what = match (marker.hunk.kind(), marker.staged) {
+                        (HunkKind::Added, false) => "Added lines",
+                        (HunkKind::Modified, false) => "Modified lines",
+                        (HunkKind::Deleted, false) => "Deleted lines",
+                        (_, true) => "Staged change",
+                    };
+
+                    ui.strong(what);
+                    ui.weak(format!("{} of {count}", index + 1));
+
+                    ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                        if ui.small_button("x").on_hover_text("Close").clicked() {
+                            action = Some(Action::Close);
+                        }
+
+                        if ui.small_button("↓").on_hover_text("Next Change").clicked() {
+                            action = Some(Action::Go((index + 1) % count));
+                        }
+
+                        if ui
+                            .small_button("↑")
+                            .on_hover_text("Previous Change")
+                            .clicked()
+                        {
+                            action = Some(Action::Go((index + count - 1) % count));
+                        }
+
+                        if marker.staged {
+                            if ui.small_button("Unstage").clicked() {
+                                action = Some(Action::Unstage(index));
+                            }
+                        } else {
+                            if ui.small_button("Revert").clicked() {
+                                action = Some(Action::Revert(index));
+                            }
+
+                            if ui.small_button("Stage").clicked() {
+                                action = Some(Action::Stage(index));
+                            }
+                        }
+                    });
+                });
+
+                if let Some(error) = error {
+                    ui.colored_label(theme.error.main, error);
+                }
+
+                ui.separator();
+
+                let old_text = marker.hunk.old_text.trim_end_matches('\n');
+
+                if old_text.is_empty() {
+                    ui.weak("These lines are new.");
+                    return;
+                }
+
+                ScrollArea::both().max_height(240.0).show(ui, |ui| {
+                    Frame::none()
+                        .fill(theme.error.main.gamma_multiply(0.15))
+                        .show(ui, |ui| {
+                            ui.set_min_width(ui.available_width());
+                            ui.style_mut().wrap_mode = Some(egui::TextWrapMode::Extend);
+
+                            for line in old_text.lines() {
+                                ui.label(
+                                    RichText::new(line)
+                                        .text_style(TextStyle::Monospace)
+                                        .color(theme.text_color.secondary),
+                                );
+                            }
+                        });
+                });
+            });
+        });
+
+    if ui.input(|input| input.key_pressed(Key::Escape)) {
+        action = Some(Action::Close);
+    }
+
+    action
+}
+
+fn color(kind: HunkKind, theme: &Theme) -> egui::Color32 {
+    match kind {
+        HunkKind::Added => theme.success.main,
+        HunkKind::Modified => theme.info.main,
+        HunkKind::Deleted => theme.error.main,
+    }
+}
+
+/// The screen rect of `lines`; for an empty range, a zero height rect at
+/// the top of its line.
+fn rows_rect(output: &TextEditOutput, lines: Range<usize>) -> Rect {
+    let text = output.galley.text();
+    let top = row_rect(output, line_offset(text, lines.start));
+
+    if lines.is_empty() {
+        return Rect::from_x_y_ranges(top.x_range(), top.top()..=top.top());
+    }
+
+    let bottom = row_rect(output, line_offset(text, lines.end - 1));
+
+    Rect::from_x_y_ranges(top.x_range(), top.top()..=bottom.bottom())
+}
+
+fn row_rect(output: &TextEditOutput, offset: usize) -> Rect {
+    let text = output.galley.text();
+    let cursor = output
+        .galley
+        .from_ccursor(egui::text::CCursor::new(editor::char_offset(text, offset)));
+
+    output
+        .galley
+        .pos_from_cursor(&cursor)
+        .translate(output.galley_pos.to_vec2())
+}
+
+/// Where line `line` starts, or the end of the text past the last line.
+fn line_offset(text: &str, line: usize) -> usize {
+    match line {
+        0 => 0,
+        _ => text
+            .match_indices('\n')
+            .nth(line - 1)
+            .map_or(text.len(), |(index, _)| index + 1),
+    }
+}
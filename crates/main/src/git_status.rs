@@ -0,0 +1,152 @@
+//! The git status of the workspace, read again whenever something in it
+//! changes on disk, and how the file explorer shows it.
+
+use egui::{Color32, Context};
+use git::{FileStatus, Statuses};
+use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
+use std::{
+    path::{Path, PathBuf},
+    sync::mpsc::{self, Receiver},
+    time::{Duration, Instant},
+};
+use theme::Theme;
+
+use crate::{file_utils, State};
+
+/// Saving a file usually comes as a burst of events; they're read at most
+/// this often.
+const REFRESH_INTERVAL: Duration = Duration::from_millis(300);
+
+#[derive(Default)]
+pub struct GitStatus {
+    /// `None` outside of a repository.
+    statuses: Option<Statuses>,
+    /// The folder the editor was started in, canonicalized.
+    root: PathBuf,
+    watcher: Option<RecommendedWatcher>,
+    events: Option<Receiver<Vec<PathBuf>>>,
+    pending: bool,
+    refreshed: Option<Instant>,
+    /// Bumped on every refresh, so views of the repository know to read it
+    /// again.
+    generation: u64,
+}
+
+impl GitStatus {
+    pub fn file(&self, path: &Path) -> Option<FileStatus> {
+        self.statuses.as_ref()?.file(&self.root.join(path))
+    }
+
+    pub fn folder(&self, path: &Path) -> Option<FileStatus> {
+        self.statuses.as_ref()?.folder(&self.root.join(path))
+    }
+
+    /// The folder the editor was started in.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// The repository's working directory, `None` outside of a repository.
+    pub fn workdir(&self) -> Option<&Path> {
+        self.statuses.as_ref().map(Statuses::root)
+    }
+
+    /// Reads the status again, e.g. after committing.
+    pub fn refresh(&mut self) {
+        self.statuses = Statuses::load(&self.root).ok();
+        self.refreshed = Some(Instant::now());
+        self.pending = false;
+        self.generation += 1;
+    }
+
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    fn watch(&mut self, ctx: &Context) {
+        let (sender, receiver) = mpsc::channel();
+        let ctx = ctx.clone();
+
+        let watcher = notify::recommended_watcher(move |event: notify::Result<Event>| {
+            if let Ok(event) = event {
+                let _ = sender.send(event.paths);
+                ctx.request_repaint();
+            }
+        });
+
+        if let Ok(mut watcher) = watcher {
+            if watcher.watch(&self.root, RecursiveMode::Recursive).is_ok() {
+                self.watcher = Some(watcher);
+                self.events = Some(receiver);
+            }
+        }
+    }
+
+    /// Whether a change to `path` can change a status: anything but the
+    /// git objects and the contents of ignored folders like `target`.
+    fn matters(&self, path: &Path) -> bool {
+        let Ok(relative) = path.strip_prefix(&self.root) else {
+            return false;
+        };
+
+        if relative.starts_with(".git/objects") || relative.starts_with(".git/logs") {
+            return false;
+        }
+
+        let ignored = relative
+            .parent()
+            .and_then(|parent| self.folder(parent))
+            .is_some_and(|status| status == FileStatus::Ignored);
+
+        !ignored
+    }
+}
+
+/// Starts watching the workspace the first time, then reads the status
+/// again once things settle after a change.
+pub fn update(ctx: &Context, state: &mut State) {
+    let git = &mut state.git;
+
+    if git.refreshed.is_none() {
+        git.root = std::env::current_dir()
+            .and_then(|directory| directory.canonicalize())
+            .unwrap_or_default();
+        git.refresh();
+        git.watch(ctx);
+        return;
+    }
+
+    if let Some(events) = &git.events {
+        let changed: Vec<PathBuf> = events.try_iter().flatten().collect();
+
+        if changed.iter().any(|path| git.matters(path)) {
+            git.pending = true;
+        }
+    }
+
+    if !git.pending {
+        return;
+    }
+
+    let elapsed = git.refreshed.map_or(REFRESH_INTERVAL, |at| at.elapsed());
+
+    if elapsed < REFRESH_INTERVAL {
+        ctx.request_repaint_after(REFRESH_INTERVAL - elapsed);
+        return;
+    }
+
+    git.refresh();
+
+    // Files may have been created or deleted at the top level too.
+    state.paths = file_utils::map_paths(Path::new("./"));
+}
+
+pub fn color(status: FileStatus, theme: &Theme) -> Color32 {
+    match status {
+        FileStatus::Conflicted | FileStatus::Deleted => theme.error.main,
+        FileStatus::Modified => theme.warning.main,
+        FileStatus::Added | FileStatus::Untracked => theme.success.main,
+        FileStatus::Renamed => theme.info.main,
+        FileStatus::Ignored => theme.text_color.disabled,
+    }
+}
@@ -0,0 +1,211 @@
+//! Carrying the state over when the host swaps in a rebuilt library. The
+//! host keeps the state it holds while [`layout`] stays the same; otherwise
+//! the old library takes it apart into a versioned snapshot of the session,
+//! which the new one migrates and rebuilds from, or starts over when it
+//! can't.
+
+use egui::{Align2, Context, RichText, Window};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    any,
+    hash::{DefaultHasher, Hash, Hasher},
+    mem,
+};
+
+use crate::{recovery, session, State};
+
+/// Each step turns a snapshot of one version into the next: the first one
+/// takes version 1 to 2. Adding a step bumps [`SNAPSHOT_VERSION`].
+const MIGRATIONS: &[fn(Value) -> Value] = &[];
+
+const SNAPSHOT_VERSION: u32 = MIGRATIONS.len() as u32 + 1;
+
+#[derive(Default)]
+pub struct HotReload {
+    /// Why the last reload started over, until dismissed.
+    notice: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Snapshot {
+    version: u32,
+    session: Value,
+}
+
+/// Hashes where `State`'s fields are, and the size, alignment and name of
+/// each one's type. The host's `State` is only valid to the new library
+/// while this doesn't change. A field type changing inside without its size
+/// changing goes unnoticed.
+pub fn layout() -> u64 {
+    let mut hasher = DefaultHasher::new();
+
+    macro_rules! fields {
+        ($($field:ident),* $(,)?) => {
+            // Fails to build when a field is missing from the list.
+            let _ = |state: State| {
+                let State { $($field: _),* } = state;
+            };
+
+            (mem::size_of::<State>(), mem::align_of::<State>()).hash(&mut hasher);
+            $(hash_field(
+                &mut hasher,
+                stringify!($field),
+                mem::offset_of!(State, $field),
+                |state| &state.$field,
+            );)*
+        };
+    }
+
+    fields!(
+        paths,
+        explorer_reveal,
+        file_store,
+        theme,
+        lsp,
+        show_language_servers,
+        diagnostics,
+        problems,
+        completion,
+        navigation,
+        references,
+        rename,
+        formatting,
+        terminal,
+        tasks,
+        command_palette,
+        git,
+        git_gutter,
+        source_control,
+        git_blame,
+        file_history,
+        compare,
+        tab_switcher,
+        breadcrumbs,
+        session,
+        hot_reload,
+        recovery,
+        autosave,
+        text_encoding,
+    );
+
+    hasher.finish()
+}
+
+fn hash_field<T>(hasher: &mut DefaultHasher, name: &str, offset: usize, _: fn(&State) -> &T) {
+    let type_layout = (mem::size_of::<T>(), mem::align_of::<T>());
+
+    (name, offset, type_layout, any::type_name::<T>()).hash(hasher);
+}
+
+/// Before a reload that keeps the state: the panic hook is this library's
+/// code, the new one installs its own.
+pub fn keep() {
+    recovery::unload();
+}
+
+/// Takes the state apart before a reload that changes its layout, while
+/// its code is still loaded: what's kept is the session, with every unsaved
+/// edit. The rest is dropped here, along with anything else the new
+/// library must not find: values egui keeps for this code and the panic
+/// hook.
+pub fn unload(ctx: &Context, state: Box<State>) -> String {
+    let snapshot = session::capture(ctx, &state)
+        .and_then(|session| serde_json::from_str(&session).ok())
+        .map(|session| Snapshot {
+            version: SNAPSHOT_VERSION,
+            session,
+        })
+        .and_then(|snapshot| serde_json::to_string(&snapshot).ok())
+        .unwrap_or_default();
+
+    drop(state);
+    ctx.data_mut(|data| data.clear());
+    recovery::unload();
+
+    snapshot
+}
+
+/// A new state from the snapshot the old library took, migrated to this
+/// version, or a clean one saying why when it can't be read.
+pub fn restore(ctx: &Context, snapshot: &str) -> State {
+    let mut state = State::default();
+    let session = migrate(snapshot).map(|session| session.to_string());
+
+    // Never the session on disk instead, which is older than the snapshot.
+    state.session.skip_restore();
+
+    if !session.is_some_and(|session| session::apply(ctx, &mut state, &session)) {
+        state.hot_reload.notice = Some(
+            "The editor's state changed in a way the reloaded code couldn't read, so it \
+             started over. Unsaved edits were lost."
+                .into(),
+        );
+    }
+
+    state
+}
+
+/// The session in the snapshot, as this version writes it. `None` for a
+/// snapshot that doesn't parse or comes from a newer version.
+fn migrate(snapshot: &str) -> Option<Value> {
+    let snapshot: Snapshot = serde_json::from_str(snapshot).ok()?;
+    let steps = MIGRATIONS.get(snapshot.version.checked_sub(1)? as usize..)?;
+
+    Some(
+        steps
+            .iter()
+            .fold(snapshot.session, |session, step| step(session)),
+    )
+}
+
+pub fn show_notice(ctx: &Context, state: &mut State) {
+    let Some(notice) = &state.hot_reload.notice else {
+        return;
+    };
+
+    let mut dismissed = false;
+
+    Window::new("Reloaded")
+        .collapsible(false)
+        .resizable(false)
+        .anchor(Align2::RIGHT_BOTTOM, [-12.0, -12.0])
+        .show(ctx, |ui| {
+            ui.set_max_width(360.0);
+            ui.label(RichText::new(notice).color(state.theme.error.main));
+
+            if ui.button("Dismiss").clicked() {
+                dismissed = true;
+            }
+        });
+
+    if dismissed {
+        state.hot_reload.notice = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn snapshot(version: u32) -> String {
+        json!({ "version": version, "session": { "root": "/project" } }).to_string()
+    }
+
+    #[test]
+    fn reads_this_version() {
+        assert_eq!(
+            migrate(&snapshot(SNAPSHOT_VERSION)),
+            Some(json!({ "root": "/project" }))
+        );
+    }
+
+    #[test]
+    fn refuses_unknown_versions() {
+        assert_eq!(migrate(&snapshot(0)), None);
+        assert_eq!(migrate(&snapshot(SNAPSHOT_VERSION + 1)), None);
+        assert_eq!(migrate(""), None);
+        assert_eq!(migrate("{\"root\":\"/project\"}"), None);
+    }
+}
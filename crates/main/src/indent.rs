@@ -0,0 +1,486 @@
+use crate::languages::{self, Language};
+use lazy_static::lazy_static;
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+use tree_sitter::{Node, Parser, Query, QueryCursor, Tree};
+
+lazy_static! {
+    static ref INDENTS_QUERIES: HashMap<&'static str, Query> = languages::all()
+        .iter()
+        .filter_map(|language| {
+            let grammar = language.grammar?;
+            let query = Query::new(&grammar(), language.indents_query)
+                .expect("Should have been able to parse the indents query");
+
+            Some((language.name, query))
+        })
+        .collect();
+}
+
+pub const OPENING_BRACKETS: [char; 3] = ['{', '[', '('];
+pub const CLOSING_BRACKETS: [char; 3] = ['}', ']', ')'];
+
+/// Node kinds whose inner lines are never touched when reindenting.
+const VERBATIM_KINDS: [&str; 8] = [
+    "comment",
+    "string",
+    "string_fragment",
+    "template_string",
+    "raw_text",
+    "block_comment",
+    "string_literal",
+    "raw_string_literal",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IndentStyle {
+    Tabs,
+    Spaces(usize),
+}
+
+impl Default for IndentStyle {
+    fn default() -> Self {
+        IndentStyle::Spaces(4)
+    }
+}
+
+impl IndentStyle {
+    /// Guesses the indentation of an existing file, falling back to the
+    /// default when the file has no indented lines.
+    pub fn detect(text: &str) -> Self {
+        let mut tabs = 0;
+        let mut spaces = 0;
+        let mut widths: HashMap<usize, usize> = HashMap::new();
+        let mut previous_width = 0;
+
+        for line in text.lines().filter(|line| !line.trim().is_empty()) {
+            let whitespace = leading_whitespace(line);
+
+            if whitespace.starts_with('\t') {
+                tabs += 1;
+                continue;
+            }
+
+            let width = whitespace.len();
+
+            if width > 0 {
+                spaces += 1;
+            }
+
+            if width > previous_width && width - previous_width <= 8 {
+                *widths.entry(width - previous_width).or_default() += 1;
+            }
+
+            previous_width = width;
+        }
+
+        if tabs > spaces {
+            return IndentStyle::Tabs;
+        }
+
+        widths
+            .into_iter()
+            .max_by_key(|(width, count)| (*count, usize::MAX - width))
+            .map(|(width, _)| IndentStyle::Spaces(width))
+            .unwrap_or_default()
+    }
+
+    pub fn unit(&self) -> String {
+        match self {
+            IndentStyle::Tabs => "\t".into(),
+            IndentStyle::Spaces(width) => " ".repeat(*width),
+        }
+    }
+
+    pub fn indentation(&self, level: usize) -> String {
+        self.unit().repeat(level)
+    }
+
+    /// Number of whole indentation levels at the start of `line`.
+    pub fn level_of(&self, line: &str) -> usize {
+        self.columns_of(line) / self.tab_width()
+    }
+
+    /// How wide the whitespace at the start of `line` is.
+    fn columns_of(&self, line: &str) -> usize {
+        leading_whitespace(line)
+            .chars()
+            .map(|c| if c == '\t' { self.tab_width() } else { 1 })
+            .sum()
+    }
+
+    /// Whitespace `columns` wide: tabs then the spaces left over, or spaces.
+    fn whitespace(&self, columns: usize) -> String {
+        match self {
+            IndentStyle::Tabs => {
+                "\t".repeat(columns / self.tab_width()) + &" ".repeat(columns % self.tab_width())
+            }
+            IndentStyle::Spaces(_) => " ".repeat(columns),
+        }
+    }
+
+    fn tab_width(&self) -> usize {
+        match self {
+            IndentStyle::Tabs => 4,
+            IndentStyle::Spaces(width) => (*width).max(1),
+        }
+    }
+}
+
+impl std::fmt::Display for IndentStyle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IndentStyle::Tabs => write!(f, "Tabs"),
+            IndentStyle::Spaces(width) => write!(f, "Spaces: {width}"),
+        }
+    }
+}
+
+pub fn leading_whitespace(line: &str) -> &str {
+    &line[..line.len() - line.trim_start_matches([' ', '\t']).len()]
+}
+
+/// Byte range of every line in `text`, without the trailing newline.
+pub fn line_ranges(text: &str) -> Vec<Range<usize>> {
+    let mut start = 0;
+
+    text.split('\n')
+        .map(|line| {
+            let range = start..start + line.len();
+            start = range.end + 1;
+            range
+        })
+        .collect()
+}
+
+/// The indentation `row` should have, in levels of `style`, or `None` if the
+/// line sits inside a string or comment.
+pub fn indent_level(
+    language: Option<&Language>,
+    text: &str,
+    row: usize,
+    style: IndentStyle,
+) -> Option<usize> {
+    match language.and_then(|language| parse(language, text)) {
+        Some((tree, query)) => query_level(&tree, query, text, &line_ranges(text), row),
+        None => {
+            let lines: Vec<&str> = text.split('\n').collect();
+            Some(heuristic_columns(&lines, row, style) / style.tab_width())
+        }
+    }
+}
+
+/// Rewrites the leading whitespace of every line in `rows` and returns the
+/// new text. Blank lines are emptied and lines inside strings or comments are
+/// left alone.
+pub fn reindent(
+    language: Option<&Language>,
+    text: &str,
+    rows: Range<usize>,
+    style: IndentStyle,
+) -> String {
+    let mut lines: Vec<String> = text.split('\n').map(String::from).collect();
+    let rows = rows.start.min(lines.len())..rows.end.min(lines.len());
+
+    match language.and_then(|language| parse(language, text)) {
+        Some((tree, query)) => {
+            let ranges = line_ranges(text);
+
+            for row in rows {
+                let level = query_level(&tree, query, text, &ranges, row);
+                set_level(&mut lines[row], level, style);
+            }
+        }
+        None => {
+            for row in rows {
+                let delta = heuristic_columns(&lines, row, style) as isize
+                    - style.columns_of(&lines[row]) as isize;
+
+                shift(&mut lines[row], delta, style);
+            }
+        }
+    }
+
+    lines.join("\n")
+}
+
+/// [`reindent`] for pasted lines. Without an indents query, the lines move
+/// together by what the first non-blank one needs, keeping the indentation
+/// they have relative to each other.
+pub fn reindent_pasted(
+    language: Option<&Language>,
+    text: &str,
+    rows: Range<usize>,
+    style: IndentStyle,
+) -> String {
+    if language.is_some_and(|language| INDENTS_QUERIES.contains_key(language.name)) {
+        return reindent(language, text, rows, style);
+    }
+
+    let mut lines: Vec<String> = text.split('\n').map(String::from).collect();
+    let rows = rows.start.min(lines.len())..rows.end.min(lines.len());
+    let Some(first) = rows.clone().find(|row| !lines[*row].trim().is_empty()) else {
+        return text.into();
+    };
+
+    let delta =
+        heuristic_columns(&lines, first, style) as isize - style.columns_of(&lines[first]) as isize;
+
+    for row in rows {
+        shift(&mut lines[row], delta, style);
+    }
+
+    lines.join("\n")
+}
+
+fn parse(language: &Language, text: &str) -> Option<(Tree, &'static Query)> {
+    let query = INDENTS_QUERIES.get(language.name)?;
+    let grammar = language.grammar?;
+
+    let mut parser = Parser::new();
+    parser.set_language(&grammar()).ok()?;
+
+    Some((parser.parse(text, None)?, query))
+}
+
+fn set_level(line: &mut String, level: Option<usize>, style: IndentStyle) {
+    let Some(level) = level else {
+        return;
+    };
+
+    let content = line.trim_start_matches([' ', '\t']).to_string();
+
+    *line = if content.is_empty() {
+        content
+    } else {
+        style.indentation(level) + &content
+    };
+}
+
+/// Counts the `@indent` captures that started on an earlier line and are
+/// still open (or missing their closing token) at the first character of
+/// `row`, one per starting line, minus one if the line opens with an
+/// `@outdent` capture.
+fn query_level(
+    tree: &Tree,
+    query: &Query,
+    text: &str,
+    ranges: &[Range<usize>],
+    row: usize,
+) -> Option<usize> {
+    let range = ranges.get(row)?;
+    let position = range.start + leading_whitespace(&text[range.clone()]).len();
+    let root = tree.root_node();
+
+    if let Some(node) = root.descendant_for_byte_range(position, position) {
+        let verbatim = VERBATIM_KINDS.contains(&node.kind())
+            && node.start_position().row < row
+            && node.start_byte() < position;
+
+        if verbatim {
+            return None;
+        }
+    }
+
+    let mut indent_rows = HashSet::new();
+    let mut outdent = false;
+    let mut cursor = QueryCursor::new();
+
+    for query_match in cursor.matches(query, root, text.as_bytes()) {
+        for capture in query_match.captures {
+            let node = capture.node;
+
+            match query.capture_names()[capture.index as usize] {
+                "indent" => {
+                    let unterminated = node
+                        .child(node.child_count().saturating_sub(1))
+                        .is_some_and(|last| last.is_missing());
+
+                    if node.start_position().row < row
+                        && (node.end_byte() > position || unterminated)
+                    {
+                        indent_rows.insert(node.start_position().row);
+                    }
+                }
+                "outdent" => outdent |= node.start_byte() == position && !node.is_missing(),
+                _ => {}
+            }
+        }
+    }
+
+    unclosed_rows(root, position, row, &mut indent_rows);
+
+    Some(indent_rows.len().saturating_sub(outdent as usize))
+}
+
+/// Half-typed code usually parses as an `ERROR` node holding the brackets
+/// that were opened but not yet closed, so those are counted as well.
+fn unclosed_rows(node: Node, position: usize, row: usize, rows: &mut HashSet<usize>) {
+    if !node.has_error() || node.start_byte() >= position {
+        return;
+    }
+
+    let mut cursor = node.walk();
+
+    if node.is_error() {
+        let mut open = Vec::new();
+
+        for child in node.children(&mut cursor) {
+            if child.start_byte() >= position {
+                break;
+            }
+
+            match child.kind() {
+                "{" | "[" | "(" | "start_tag" | "jsx_opening_element" => {
+                    open.push(child.start_position().row)
+                }
+                "}" | "]" | ")" | "end_tag" | "jsx_closing_element" => {
+                    open.pop();
+                }
+                _ => {}
+            }
+        }
+
+        rows.extend(open.into_iter().filter(|open_row| *open_row < row));
+    }
+
+    for child in node.children(&mut cursor) {
+        unclosed_rows(child, position, row, rows);
+    }
+}
+
+/// Moves the line's content `delta` columns right, or left down to the
+/// start of the line. Blank lines are emptied.
+fn shift(line: &mut String, delta: isize, style: IndentStyle) {
+    let content = line.trim_start_matches([' ', '\t']).to_string();
+
+    *line = match content.is_empty() {
+        true => content,
+        false => {
+            let columns = (style.columns_of(line) as isize + delta).max(0) as usize;
+            style.whitespace(columns) + &content
+        }
+    };
+}
+
+/// Used for languages without a grammar: keeps the indentation of the
+/// previous non-blank line, one level deeper after an opening bracket and one
+/// level shallower before a closing one. In columns, so partial indents are
+/// kept.
+fn heuristic_columns<S: AsRef<str>>(lines: &[S], row: usize, style: IndentStyle) -> usize {
+    let previous = lines[..row.min(lines.len())]
+        .iter()
+        .rev()
+        .map(AsRef::as_ref)
+        .find(|line| !line.trim().is_empty());
+
+    let mut columns = match previous {
+        Some(line) if line.trim_end().ends_with(OPENING_BRACKETS) => {
+            style.columns_of(line) + style.tab_width()
+        }
+        Some(line) => style.columns_of(line),
+        None => 0,
+    };
+
+    let current = lines.get(row).map(AsRef::as_ref).unwrap_or_default();
+
+    if current.trim_start().starts_with(CLOSING_BRACKETS) {
+        columns = columns.saturating_sub(style.tab_width());
+    }
+
+    columns
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn language(name: &str) -> Option<&'static Language> {
+        languages::all()
+            .iter()
+            .find(|language| language.name == name)
+    }
+
+    #[test]
+    fn detects_the_indentation() {
+        assert_eq!(
+            IndentStyle::detect("a {\n  b {\n    c\n  }\n}\n"),
+            IndentStyle::Spaces(2)
+        );
+        assert_eq!(
+            IndentStyle::detect("a {\n    b\n    c\n}\n"),
+            IndentStyle::Spaces(4)
+        );
+        assert_eq!(
+            IndentStyle::detect("a {\n\tb\n\t\tc\n  d\n}\n"),
+            IndentStyle::Tabs
+        );
+        assert_eq!(IndentStyle::detect("a\nb\n"), IndentStyle::default());
+    }
+
+    #[test]
+    fn counts_whole_levels() {
+        assert_eq!(IndentStyle::Spaces(4).level_of("        a"), 2);
+        assert_eq!(IndentStyle::Spaces(4).level_of("      a"), 1);
+        assert_eq!(IndentStyle::Tabs.level_of("\t\ta"), 2);
+        assert_eq!(IndentStyle::Spaces(2).level_of("\ta"), 1);
+    }
+
+    #[test]
+    fn reindents_with_the_query() {
+        let text = "{\n\"a\": [\n1,\n2\n],\n\"b\": 3\n}";
+
+        assert_eq!(
+            reindent(language("json"), text, 0..7, IndentStyle::Spaces(2)),
+            "{\n  \"a\": [\n    1,\n    2\n  ],\n  \"b\": 3\n}"
+        );
+    }
+
+    #[test]
+    fn reindents_without_a_grammar() {
+        let text = "a = {\nb\n  c\n}";
+
+        assert_eq!(
+            reindent(None, text, 0..4, IndentStyle::Spaces(4)),
+            "a = {\n    b\n    c\n}"
+        );
+        assert_eq!(
+            reindent(language("toml"), "[a]\n\n   b = 1", 1..3, IndentStyle::Tabs),
+            "[a]\n\nb = 1"
+        );
+    }
+
+    #[test]
+    fn moves_pasted_lines_together_without_a_grammar() {
+        let text = "list:\n  - a\n- b\n    - c\n  - d\n\nafter";
+
+        assert_eq!(
+            reindent_pasted(None, text, 2..6, IndentStyle::Spaces(4)),
+            "list:\n  - a\n  - b\n      - c\n    - d\n\nafter"
+        );
+    }
+
+    #[test]
+    fn keeps_partial_indents_when_pasting() {
+        let text = "      x\ny\n  z";
+
+        assert_eq!(
+            reindent_pasted(None, text, 1..3, IndentStyle::Spaces(4)),
+            "      x\n      y\n        z"
+        );
+        assert_eq!(
+            reindent_pasted(None, "\t  x\ny", 1..2, IndentStyle::Tabs),
+            "\t  x\n\t  y"
+        );
+    }
+
+    #[test]
+    fn reindents_pasted_lines_with_the_query() {
+        let text = "{\n\"a\": [\n        1\n]\n}";
+
+        assert_eq!(
+            reindent_pasted(language("json"), text, 1..4, IndentStyle::Spaces(2)),
+            "{\n  \"a\": [\n    1\n  ]\n}"
+        );
+    }
+}
@@ -0,0 +1,188 @@
+use egui::{Color32, Context, RichText, ScrollArea, Ui, Window};
+use lsp::{
+    lsp_types::{
+        notification::PublishDiagnostics, request::Request, OneOf, Position, ServerCapabilities,
+        Url,
+    },
+    position, LogLevel, LspManager, PendingRequest, ServerEvent, ServerStatus,
+};
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use crate::{
+    editor,
+    file_store::{FileData, FileStore},
+    languages, State,
+};
+
+/// Keeps the language servers in step with the open buffers. Runs every
+/// frame; documents are only sent again when their version changed.
+pub fn sync(ctx: &Context, state: &mut State) {
+    if !state.lsp.has_waker() {
+        let ctx = ctx.clone();
+        state.lsp.set_waker(Arc::new(move || ctx.request_repaint()));
+    }
+
+    for file in state.file_store.files.values() {
+        if let Some(language) = languages::from_path(&file.path) {
+            state.lsp.sync_document(
+                Path::new(&file.path),
+                language.language_id,
+                file.version,
+                &file.content,
+            );
+        }
+    }
+
+    let open: Vec<PathBuf> = state.file_store.files.keys().map(PathBuf::from).collect();
+    state.lsp.retain_documents(&open);
+
+    for (server, event) in state.lsp.poll() {
+        if let Some(params) = event.notification::<PublishDiagnostics>() {
+            state.diagnostics.publish(&server, params);
+        } else if let ServerEvent::Exited = event {
+            state.diagnostics.clear_server(&server);
+        }
+    }
+}
+
+/// Opens the document at `uri`, reusing its tab if it's open already, and
+/// puts the cursor at `position`.
+pub fn open_location(ctx: &Context, state: &mut State, uri: &Url, position: Position) {
+    let open = state
+        .file_store
+        .files
+        .keys()
+        .find(|path| state.lsp.uri(Path::new(path)).as_ref() == Some(uri))
+        .cloned();
+
+    // Under the key the explorer would open it by, for one buffer per file.
+    let Some(path) = open.or_else(|| {
+        let path = uri.to_file_path().ok().filter(|path| path.is_file())?;
+        Some(FileStore::store_path(&path))
+    }) else {
+        return;
+    };
+
+    state.file_store.insert(&path, true);
+
+    if !state.file_store.files.contains_key(&path) {
+        return;
+    }
+
+    let id = state.file_store.get_active_file_id().into();
+
+    if let Some(file) = state.file_store.get_active_file() {
+        let offset = position::to_offset(&file.content, position);
+        editor::reveal(ctx, id, file, offset);
+    }
+}
+
+/// The open buffer's content, or for other files what's on disk, as its
+/// buffer would have it.
+pub fn document_text(state: &mut State, uri: &Url) -> Option<String> {
+    let open = state
+        .file_store
+        .files
+        .values()
+        .find(|file| state.lsp.uri(Path::new(&file.path)).as_ref() == Some(uri));
+
+    match open {
+        Some(file) => Some(file.content.clone()),
+        None => FileStore::read_saved(uri.to_file_path().ok()?.to_str()?).ok(),
+    }
+}
+
+/// Sends `R` about `file` to its server, after syncing the latest content so
+/// the request sees what's on screen. `None` when no running server
+/// `supports` it.
+pub fn request<R: Request>(
+    lsp: &mut LspManager,
+    file: &FileData,
+    supports: impl Fn(&ServerCapabilities) -> bool,
+    params: impl FnOnce(Url) -> R::Params,
+) -> Option<PendingRequest<R>> {
+    let language = languages::from_path(&file.path)?;
+    let path = Path::new(&file.path);
+    let uri = lsp.uri(path)?;
+
+    lsp.sync_document(path, language.language_id, file.version, &file.content);
+
+    let server = lsp.server_for(path, language.language_id)?;
+
+    if !supports(server.capabilities()) {
+        return None;
+    }
+
+    server.request::<R>(params(uri)).ok()
+}
+
+/// Whether a `bool`-or-options capability is turned on.
+pub fn enabled<T>(provider: Option<&OneOf<bool, T>>) -> bool {
+    !matches!(provider, None | Some(OneOf::Left(false)))
+}
+
+pub fn window(ctx: &Context, state: &mut State) {
+    let mut restart = None;
+
+    Window::new("Language Servers")
+        .open(&mut state.show_language_servers)
+        .default_size([520.0, 320.0])
+        .show(ctx, |ui| {
+            if state.lsp.servers().is_empty() {
+                ui.weak("No language server has been started yet.");
+            }
+
+            for (index, server) in state.lsp.servers().iter().enumerate() {
+                ui.horizontal(|ui| {
+                    ui.strong(server.name());
+                    status_label(ui, server.status());
+
+                    if ui.small_button("Restart").clicked() {
+                        restart = Some(index);
+                    }
+                });
+
+                ui.weak(server.root().to_string_lossy());
+
+                ScrollArea::vertical()
+                    .id_source(("language-server-logs", index))
+                    .max_height(200.0)
+                    .stick_to_bottom(true)
+                    .show(ui, |ui| {
+                        for entry in server.logs() {
+                            let color = log_color(ui, entry.level);
+                            ui.label(RichText::new(entry.message).monospace().color(color));
+                        }
+                    });
+
+                ui.separator();
+            }
+        });
+
+    if let Some(index) = restart {
+        state.lsp.restart(index);
+    }
+}
+
+fn status_label(ui: &mut Ui, status: &ServerStatus) {
+    match status {
+        ServerStatus::Starting => ui.weak("starting"),
+        ServerStatus::Running => ui.label("running"),
+        ServerStatus::Exited => ui.weak("exited"),
+        ServerStatus::Failed(reason) => ui
+            .colored_label(ui.visuals().error_fg_color, "failed")
+            .on_hover_text(reason),
+    };
+}
+
+fn log_color(ui: &Ui, level: LogLevel) -> Color32 {
+    match level {
+        LogLevel::Error => ui.visuals().error_fg_color,
+        LogLevel::Warning => ui.visuals().warn_fg_color,
+        LogLevel::Info | LogLevel::Log => ui.visuals().text_color(),
+        LogLevel::Stderr => ui.visuals().weak_text_color(),
+    }
+}
@@ -0,0 +1,109 @@
+use lazy_static::lazy_static;
+use std::path::Path;
+use tree_sitter::Language as Grammar;
+
+pub struct Language {
+    pub name: &'static str,
+    /// The identifier language servers know this language by.
+    pub language_id: &'static str,
+    pub extensions: &'static [&'static str],
+    pub grammar: Option<fn() -> Grammar>,
+    pub indents_query: &'static str,
+    pub line_comment: Option<&'static str>,
+    pub block_comment: Option<(&'static str, &'static str)>,
+    /// The command formatting the file when no language server does, unless
+    /// the user set another. It reads the content on stdin and writes the
+    /// result to stdout; `{file}` in an argument is replaced by the file's
+    /// path.
+    pub formatter: Option<&'static [&'static str]>,
+}
+
+lazy_static! {
+    static ref LANGUAGES: [Language; 7] = [
+        Language {
+            name: "javascript",
+            language_id: "javascript",
+            extensions: &["js", "jsx", "cjs", "mjs"],
+            grammar: Some(tree_sitter_javascript::language),
+            indents_query: include_str!("../queries/javascript/indents.scm"),
+            line_comment: Some("//"),
+            block_comment: Some(("/*", "*/")),
+            formatter: Some(&["prettier", "--stdin-filepath", "{file}"]),
+        },
+        Language {
+            name: "typescript",
+            language_id: "typescript",
+            extensions: &["ts", "cts", "mts"],
+            grammar: Some(tree_sitter_typescript::language_typescript),
+            indents_query: include_str!("../queries/typescript/indents.scm"),
+            line_comment: Some("//"),
+            block_comment: Some(("/*", "*/")),
+            formatter: Some(&["prettier", "--stdin-filepath", "{file}"]),
+        },
+        Language {
+            name: "tsx",
+            language_id: "typescriptreact",
+            extensions: &["tsx"],
+            grammar: Some(tree_sitter_typescript::language_tsx),
+            indents_query: concat!(
+                include_str!("../queries/typescript/indents.scm"),
+                include_str!("../queries/tsx/indents.scm")
+            ),
+            line_comment: Some("//"),
+            block_comment: Some(("/*", "*/")),
+            formatter: Some(&["prettier", "--stdin-filepath", "{file}"]),
+        },
+        Language {
+            name: "json",
+            language_id: "json",
+            extensions: &["json"],
+            grammar: Some(tree_sitter_json::language),
+            indents_query: include_str!("../queries/json/indents.scm"),
+            line_comment: None,
+            block_comment: None,
+            formatter: Some(&["prettier", "--stdin-filepath", "{file}"]),
+        },
+        Language {
+            name: "html",
+            language_id: "html",
+            extensions: &["html", "htm"],
+            grammar: Some(tree_sitter_html::language),
+            indents_query: include_str!("../queries/html/indents.scm"),
+            line_comment: None,
+            block_comment: Some(("<!--", "-->")),
+            formatter: Some(&["prettier", "--stdin-filepath", "{file}"]),
+        },
+        Language {
+            name: "rust",
+            language_id: "rust",
+            extensions: &["rs"],
+            grammar: Some(tree_sitter_rust::language),
+            indents_query: include_str!("../queries/rust/indents.scm"),
+            line_comment: Some("//"),
+            block_comment: Some(("/*", "*/")),
+            formatter: Some(&["rustfmt", "--edition", "2021"]),
+        },
+        Language {
+            name: "toml",
+            language_id: "toml",
+            extensions: &["toml"],
+            grammar: None,
+            indents_query: "",
+            line_comment: Some("#"),
+            block_comment: None,
+            formatter: Some(&["taplo", "format", "-"]),
+        },
+    ];
+}
+
+pub fn all() -> &'static [Language] {
+    LANGUAGES.as_slice()
+}
+
+pub fn from_path(path: &str) -> Option<&'static Language> {
+    let extension = Path::new(path).extension()?.to_str()?;
+
+    LANGUAGES
+        .iter()
+        .find(|language| language.extensions.contains(&extension))
+}
@@ -1,23 +1,56 @@
 #![recursion_limit = "256"]
 
-use components::{
-    default_message_modal::OpenFolderCard as DefaultMessage, selectable_label::SelectableLabel,
-};
+use components::default_message_modal::OpenFolderCard as DefaultMessage;
 use egui::{
-    self, emath::RectTransform, menu, scroll_area::ScrollBarVisibility, text::Fonts, vec2, Button,
-    CentralPanel, Color32, Context, CursorIcon, Frame, Label, Link, Margin, Pos2, Rect, RichText,
-    ScrollArea, Sense, SidePanel, Stroke, TextEdit, TextStyle, TopBottomPanel, Ui, Vec2,
+    self, menu, Align, CentralPanel, Context, Frame, Layout, Pos2, Rect, ScrollArea, SidePanel,
+    TopBottomPanel, Vec2,
 };
-use file_store::FileData;
 use layout::get_responsive_size;
 use lazy_static::lazy_static;
-use std::{fs::DirEntry, path::Path};
+use std::{
+    fs::DirEntry,
+    path::{Path, PathBuf},
+};
 
+mod autosave;
+mod breadcrumbs;
+mod command_palette;
+mod compare;
+mod completion;
+mod diagnostics;
+mod editor;
+mod editor_groups;
+mod file_history;
 mod file_menu;
 mod file_store;
 mod file_tree;
 mod file_utils;
+mod formatting;
+mod git_blame;
+mod git_gutter;
+mod git_status;
+mod hot_reload;
+mod indent;
+mod language_servers;
+mod languages;
+mod line_commands;
+mod line_ending;
+mod markdown;
+mod navigation;
+mod problem_matchers;
+mod problems;
+mod recovery;
+mod references;
+mod rename;
+mod session;
+mod snippet;
+mod source_control;
+mod symbols;
 mod syntax_highlighter;
+mod tab_switcher;
+mod tasks;
+mod terminal;
+mod text_encoding;
 
 lazy_static! {
     static ref CENTRAL_PANE_FRAME: Frame = Frame {
@@ -26,12 +59,37 @@ lazy_static! {
     };
 }
 
-#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
-#[cfg_attr(feature = "serde", serde(default))]
 pub struct State {
     paths: Vec<DirEntry>,
+    /// The file the explorer opens its folders to and scrolls to.
+    explorer_reveal: Option<PathBuf>,
     file_store: file_store::FileStore,
     theme: theme::Theme,
+    lsp: lsp::LspManager,
+    show_language_servers: bool,
+    diagnostics: diagnostics::Diagnostics,
+    problems: problems::ProblemsPanel,
+    completion: completion::Completion,
+    navigation: navigation::Navigation,
+    references: references::ReferencesPanel,
+    rename: rename::Rename,
+    formatting: formatting::Formatting,
+    terminal: terminal::TerminalPanel,
+    tasks: tasks::Tasks,
+    command_palette: command_palette::CommandPalette,
+    git: git_status::GitStatus,
+    git_gutter: git_gutter::GitGutter,
+    source_control: source_control::SourceControl,
+    git_blame: git_blame::GitBlame,
+    file_history: file_history::FileHistory,
+    compare: compare::Compare,
+    tab_switcher: tab_switcher::TabSwitcher,
+    breadcrumbs: breadcrumbs::Breadcrumbs,
+    session: session::Session,
+    hot_reload: hot_reload::HotReload,
+    recovery: recovery::Recovery,
+    autosave: autosave::AutoSave,
+    text_encoding: text_encoding::TextEncoding,
 }
 
 impl Default for State {
@@ -42,30 +100,92 @@ impl Default for State {
 
         State {
             paths,
+            explorer_reveal: None,
             file_store: file_list,
             theme,
+            lsp: lsp::LspManager::default(),
+            show_language_servers: false,
+            diagnostics: diagnostics::Diagnostics::default(),
+            problems: problems::ProblemsPanel::default(),
+            completion: completion::Completion::default(),
+            navigation: navigation::Navigation::default(),
+            references: references::ReferencesPanel::default(),
+            rename: rename::Rename::default(),
+            formatting: formatting::Formatting::default(),
+            terminal: terminal::TerminalPanel::default(),
+            tasks: tasks::Tasks::default(),
+            command_palette: command_palette::CommandPalette::default(),
+            git: git_status::GitStatus::default(),
+            git_gutter: git_gutter::GitGutter::default(),
+            source_control: source_control::SourceControl::default(),
+            git_blame: git_blame::GitBlame::default(),
+            file_history: file_history::FileHistory::default(),
+            compare: compare::Compare::default(),
+            tab_switcher: tab_switcher::TabSwitcher::default(),
+            breadcrumbs: breadcrumbs::Breadcrumbs::default(),
+            session: session::Session::default(),
+            hot_reload: hot_reload::HotReload::default(),
+            recovery: recovery::Recovery::default(),
+            autosave: autosave::AutoSave::default(),
+            text_encoding: text_encoding::TextEncoding::default(),
         }
     }
 }
 
 #[no_mangle]
 pub fn render(state: &mut State, ctx: &Context, _frame: &mut eframe::Frame) {
+    session::restore(ctx, state);
+    recovery::update(ctx, state);
+    language_servers::sync(ctx, state);
+    git_status::update(ctx, state);
+    navigation::handle_input(ctx, state);
+    navigation::poll(ctx, state);
+    rename::handle_input(ctx, state);
+    formatting::handle_input(ctx, state);
+    git_gutter::handle_input(ctx, state);
+    source_control::handle_input(ctx, state);
+    editor_groups::handle_input(ctx, state);
+    tab_switcher::handle_input(ctx, state);
+    command_palette::handle_input(ctx, state);
+    autosave::handle_input(ctx, state);
+
     self::TopBottomPanel::top("menu_bar").show(ctx, |ui| {
         menu::bar(ui, |ui| {
             file_menu::create(ui, state);
+
+            ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
+                line_ending::status(ui, state);
+                text_encoding::status(ui, state);
+                autosave::indicator(ui, state);
+            });
         });
     });
 
+    tasks::show(ctx, state);
+    terminal::show(ctx, state);
+    problems::show(ctx, state);
+    references::show(ctx, state);
+    file_history::show(ctx, state);
+
     SidePanel::left("file_explorer")
         .resizable(true)
-        .default_width(200.0)
+        .default_width(state.session.panel_width("file_explorer", 200.0))
         .min_width(200.0)
         .show(ctx, |ui| {
             ScrollArea::vertical().auto_shrink(false).show(ui, |ui| {
-                file_tree::create(ui, &mut state.paths, &mut state.file_store);
+                file_tree::create(
+                    ui,
+                    &mut state.paths,
+                    &mut state.file_store,
+                    &mut state.explorer_reveal,
+                    &state.git,
+                    &state.theme,
+                );
             });
         });
 
+    source_control::show(ctx, state);
+
     CentralPanel::default()
         .frame(*CENTRAL_PANE_FRAME)
         .show(ctx, |ui| {
@@ -83,176 +203,53 @@ pub fn render(state: &mut State, ctx: &Context, _frame: &mut eframe::Frame) {
                     file_utils::open_file(state, "/");
                 }
             } else {
-                ScrollArea::horizontal()
-                    .id_source("tabs-scroll-container")
-                    .scroll_bar_visibility(ScrollBarVisibility::AlwaysHidden)
-                    .show(ui, |ui| {
-                        ui.horizontal(|ui| {
-                            let mut files = state.file_store.files.clone().into_iter().peekable();
-
-                            while let Some((path, FileData { name, .. })) = files.next() {
-                                ui.spacing_mut().item_spacing = Vec2::default();
-
-                                let label = ui.add(
-                                    SelectableLabel::new(
-                                        path == state.file_store.active_file,
-                                        RichText::new(&name),
-                                    )
-                                    .rounding(theme::Theme::ROUNDING_NONE)
-                                    .padding(Vec2 {
-                                        x: 26.0,
-                                        ..theme::Theme::PADDING
-                                    }),
-                                );
-
-                                if files.peek().is_some() {
-                                    let available_space = if ui.is_sizing_pass() {
-                                        Vec2::ZERO
-                                    } else {
-                                        ui.available_size_before_wrap()
-                                    };
-
-                                    let size = vec2(0.0, available_space.y);
-                                    let (rect, response) =
-                                        ui.allocate_at_least(size, Sense::hover());
-
-                                    if ui.is_rect_visible(response.rect) {
-                                        let stroke =
-                                            Stroke::new(1.0, state.theme.action.disabled_bg);
-                                        let painter = ui.painter();
-                                        painter.vline(
-                                            painter.round_to_pixel(rect.center().x),
-                                            (rect.top())..=(rect.bottom()),
-                                            stroke,
-                                        );
-                                    }
-                                }
-
-                                let to_screen = RectTransform::from_to(
-                                    Rect::from_min_size(Pos2::ZERO, label.rect.size()),
-                                    label.rect,
-                                );
-
-                                if label.clicked() {
-                                    state.file_store.set_active_file(&path);
-                                }
-
-                                ui.add_visible_ui(ui.rect_contains_pointer(label.rect), |ui| {
-                                    let size = 5.0;
-
-                                    let position = Pos2 {
-                                        x: label.rect.width() - 13.0,
-                                        y: label.rect.height() / 2.0,
-                                    };
-
-                                    let max_rect = Rect {
-                                        min: to_screen.transform_pos(position),
-                                        max: to_screen.transform_pos(Pos2 {
-                                            x: position.x,
-                                            y: -size,
-                                        }),
-                                    };
-
-                                    ui.style_mut().visuals.widgets.inactive.weak_bg_fill =
-                                        Color32::TRANSPARENT;
-                                    ui.style_mut().visuals.widgets.hovered.weak_bg_fill =
-                                        state.theme.action.hover.gamma_multiply(0.3);
-
-                                    let button = Button::new(
-                                        RichText::new("x").color(state.theme.text_color.primary),
-                                    )
-                                    .rounding(state.theme.rounding)
-                                    .stroke(Stroke::NONE);
-
-                                    let response = ui
-                                        .put(max_rect, button)
-                                        .on_hover_cursor(CursorIcon::PointingHand);
-
-                                    if response.clicked() {
-                                        state.file_store.close_file(&path);
-                                    }
-                                    response
-                                });
-                            }
-                        });
-                    });
-
-                if !state.file_store.active_file.is_empty() {
-                    let (.., max_rect) = ui.allocate_space(ui.available_size_before_wrap());
-
-                    ui.spacing_mut().item_spacing = Vec2::default();
-
-                    ui.allocate_ui_at_rect(max_rect, |ui| {
-                        egui::Frame::none()
-                            .fill(Color32::BLACK)
-                            .inner_margin(vec2(5.0, 4.0))
-                            .outer_margin(vec2(0.0, 0.0))
-                            .stroke(Stroke::NONE)
-                            .show(ui, |ui| {
-                                ui.set_width(ui.available_width());
-
-                                let mut components = state
-                                    .file_store
-                                    .get_active_file_components()
-                                    .into_iter()
-                                    .peekable();
-
-                                ui.horizontal(|ui| {
-                                    while let Some((dirent_name, file_path)) = components.next() {
-                                        ui.style_mut().visuals.hyperlink_color =
-                                            state.theme.text_color.primary;
-
-                                        ui.add(Link::new(RichText::new(dirent_name)));
+                editor_groups::show(ui, state);
+            }
+        });
 
-                                        if components.peek().is_some() {
-                                            ui.add(Label::new(
-                                                RichText::new(format!(
-                                                    "{:^width$}",
-                                                    "/",
-                                                    width = 5
-                                                ))
-                                                .color(state.theme.text_color.primary),
-                                            ));
-                                        }
-                                    }
-                                });
-                            });
-                    });
+    language_servers::window(ctx, state);
+    rename::show(ctx, state);
+    compare::show(ctx, state);
+    formatting::show_error(ctx, state);
+    text_encoding::show_error(ctx, state);
+    autosave::show_error(ctx, state);
+    file_utils::show_open_error(ctx, state);
+    command_palette::show(ctx, state);
+    tab_switcher::show(ctx, state);
+    recovery::show(ctx, state);
+    hot_reload::show_notice(ctx, state);
+    autosave::update(ctx, state);
+    session::save(ctx, state);
+}
 
-                    let id = state.file_store.get_active_file_id().into();
+/// Changes when `State`'s layout does, see [`hot_reload::layout`].
+#[no_mangle]
+pub fn state_layout() -> u64 {
+    hot_reload::layout()
+}
 
-                    match state.file_store.get_active_file_as_mut() {
-                        Some(active_file) => {
-                            ScrollArea::vertical().show(ui, |ui| {
-                                let mut layouter = |ui: &Ui, string: &str, wrap_width: f32| {
-                                    let mut layout_job =
-                                        syntax_highlighter::highlight(string.to_string());
-                                    layout_job.wrap.max_width = wrap_width;
+/// Called by the host before a reload that keeps the state, see
+/// [`hot_reload::keep`].
+#[no_mangle]
+pub fn keep_state() {
+    hot_reload::keep();
+}
 
-                                    ui.fonts(|f: &Fonts| f.layout_job(layout_job))
-                                };
+/// Taken apart by the host before a reload that changes the state's
+/// layout, see [`hot_reload::unload`].
+#[no_mangle]
+pub fn unload_state(state: Box<State>, ctx: &Context) -> String {
+    hot_reload::unload(ctx, state)
+}
 
-                                if !active_file.content.is_empty() {
-                                    ui.style_mut().visuals.widgets.hovered.bg_stroke = Stroke::NONE;
-                                    ui.style_mut().visuals.selection.stroke = Stroke::NONE;
+/// The state for the swapped-in library, from the old one's snapshot.
+#[no_mangle]
+pub fn restore_state(snapshot: String, ctx: &Context) -> Box<State> {
+    Box::new(hot_reload::restore(ctx, &snapshot))
+}
 
-                                    ui.add_sized(
-                                        ui.available_size(),
-                                        TextEdit::multiline(&mut active_file.content)
-                                            .id(id)
-                                            .font(TextStyle::Monospace)
-                                            .code_editor()
-                                            .desired_rows(10)
-                                            .lock_focus(true)
-                                            .layouter(&mut layouter)
-                                            .margin(Margin::symmetric(5.0, 5.0)),
-                                    );
-                                }
-                            });
-                        }
-                        None => println!("todo"),
-                    }
-                }
-            }
-        });
+/// Drops the state with the library that laid it out.
+#[no_mangle]
+pub fn drop_state(state: Box<State>) {
+    drop(state);
 }
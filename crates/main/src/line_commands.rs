@@ -0,0 +1,519 @@
+use egui::{Key, KeyboardShortcut, Modifiers};
+use std::ops::Range;
+
+use crate::{
+    indent::{self, leading_whitespace},
+    languages::Language,
+};
+
+const ALT_SHIFT: Modifiers = Modifiers {
+    shift: true,
+    ..Modifiers::ALT
+};
+
+const COMMAND_SHIFT: Modifiers = Modifiers {
+    shift: true,
+    ..Modifiers::COMMAND
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineCommand {
+    MoveUp,
+    MoveDown,
+    DuplicateUp,
+    DuplicateDown,
+    Delete,
+    Join,
+    Sort,
+    ToggleLineComment,
+    ToggleBlockComment,
+}
+
+impl LineCommand {
+    pub const ALL: [LineCommand; 9] = [
+        LineCommand::MoveUp,
+        LineCommand::MoveDown,
+        LineCommand::DuplicateUp,
+        LineCommand::DuplicateDown,
+        LineCommand::Delete,
+        LineCommand::Join,
+        LineCommand::Sort,
+        LineCommand::ToggleLineComment,
+        LineCommand::ToggleBlockComment,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            LineCommand::MoveUp => "Move Line Up",
+            LineCommand::MoveDown => "Move Line Down",
+            LineCommand::DuplicateUp => "Copy Line Up",
+            LineCommand::DuplicateDown => "Copy Line Down",
+            LineCommand::Delete => "Delete Line",
+            LineCommand::Join => "Join Lines",
+            LineCommand::Sort => "Sort Lines",
+            LineCommand::ToggleLineComment => "Toggle Line Comment",
+            LineCommand::ToggleBlockComment => "Toggle Block Comment",
+        }
+    }
+
+    pub fn shortcut(&self) -> Option<KeyboardShortcut> {
+        match self {
+            LineCommand::MoveUp => Some(KeyboardShortcut::new(Modifiers::ALT, Key::ArrowUp)),
+            LineCommand::MoveDown => Some(KeyboardShortcut::new(Modifiers::ALT, Key::ArrowDown)),
+            LineCommand::DuplicateUp => Some(KeyboardShortcut::new(ALT_SHIFT, Key::ArrowUp)),
+            LineCommand::DuplicateDown => Some(KeyboardShortcut::new(ALT_SHIFT, Key::ArrowDown)),
+            LineCommand::Delete => Some(KeyboardShortcut::new(COMMAND_SHIFT, Key::K)),
+            LineCommand::Join => Some(KeyboardShortcut::new(Modifiers::COMMAND, Key::J)),
+            LineCommand::Sort => None,
+            LineCommand::ToggleLineComment => {
+                Some(KeyboardShortcut::new(Modifiers::COMMAND, Key::Slash))
+            }
+            LineCommand::ToggleBlockComment => Some(KeyboardShortcut::new(COMMAND_SHIFT, Key::A)),
+        }
+    }
+
+    /// Applies the command to `selection` (a sorted byte range) and returns
+    /// the new text with the byte range that should be selected afterwards,
+    /// or `None` when there is nothing to do.
+    pub fn apply(
+        &self,
+        text: &str,
+        selection: Range<usize>,
+        language: Option<&Language>,
+    ) -> Option<(String, Range<usize>)> {
+        match self {
+            LineCommand::MoveUp => move_lines(text, selection, true),
+            LineCommand::MoveDown => move_lines(text, selection, false),
+            LineCommand::DuplicateUp => Some(duplicate_lines(text, selection, true)),
+            LineCommand::DuplicateDown => Some(duplicate_lines(text, selection, false)),
+            LineCommand::Delete => Some(delete_lines(text, selection)),
+            LineCommand::Join => join_lines(text, selection),
+            LineCommand::Sort => sort_lines(text, selection),
+            LineCommand::ToggleLineComment => {
+                let language = language?;
+
+                match (language.line_comment, language.block_comment) {
+                    (Some(token), _) => Some(toggle_line_comment(text, selection, token)),
+                    (None, Some(tokens)) => {
+                        let span = trimmed(text, line_span(text, &selection));
+                        toggle_block_comment(text, span, tokens)
+                    }
+                    (None, None) => None,
+                }
+            }
+            LineCommand::ToggleBlockComment => {
+                let tokens = language?.block_comment?;
+                let span = if selection.is_empty() {
+                    trimmed(text, line_span(text, &selection))
+                } else {
+                    selection
+                };
+
+                toggle_block_comment(text, span, tokens)
+            }
+        }
+    }
+}
+
+/// Rows covered by the selection. A selection ending at the very start of a
+/// line does not include that line.
+fn rows(text: &str, selection: &Range<usize>) -> Range<usize> {
+    let first = text[..selection.start].matches('\n').count();
+    let mut last = text[..selection.end].matches('\n').count();
+
+    if last > first && text[..selection.end].ends_with('\n') {
+        last -= 1;
+    }
+
+    first..last + 1
+}
+
+/// Byte range of the whole lines covered by the selection, without the
+/// trailing newline.
+fn line_span(text: &str, selection: &Range<usize>) -> Range<usize> {
+    let ranges = indent::line_ranges(text);
+    let rows = rows(text, selection);
+
+    ranges[rows.start].start..ranges[rows.end - 1].end
+}
+
+fn trimmed(text: &str, span: Range<usize>) -> Range<usize> {
+    let content = &text[span.clone()];
+    let start = span.start + leading_whitespace(content).len();
+
+    start..start.max(span.start + content.trim_end().len())
+}
+
+fn shift(range: &Range<usize>, by: isize) -> Range<usize> {
+    range.start.saturating_add_signed(by)..range.end.saturating_add_signed(by)
+}
+
+fn move_lines(text: &str, selection: Range<usize>, up: bool) -> Option<(String, Range<usize>)> {
+    let ranges = indent::line_ranges(text);
+    let rows = rows(text, &selection);
+    let span = ranges[rows.start].start..ranges[rows.end - 1].end;
+    let block = &text[span.clone()];
+
+    if up {
+        let previous = ranges.get(rows.start.checked_sub(1)?)?.clone();
+        let content = format!(
+            "{}{block}\n{}{}",
+            &text[..previous.start],
+            &text[previous.clone()],
+            &text[span.end..]
+        );
+
+        Some((content, shift(&selection, -(previous.len() as isize + 1))))
+    } else {
+        let next = ranges.get(rows.end)?.clone();
+        let content = format!(
+            "{}{}\n{block}{}",
+            &text[..span.start],
+            &text[next.clone()],
+            &text[next.end..]
+        );
+
+        Some((content, shift(&selection, next.len() as isize + 1)))
+    }
+}
+
+fn duplicate_lines(text: &str, selection: Range<usize>, up: bool) -> (String, Range<usize>) {
+    let span = line_span(text, &selection);
+    let block = &text[span.clone()];
+    let content = format!("{}\n{block}{}", &text[..span.end], &text[span.end..]);
+
+    if up {
+        (content, selection)
+    } else {
+        (content, shift(&selection, block.len() as isize + 1))
+    }
+}
+
+fn delete_lines(text: &str, selection: Range<usize>) -> (String, Range<usize>) {
+    let span = line_span(text, &selection);
+    let column = selection.start - span.start;
+
+    let removed = if span.end < text.len() {
+        span.start..span.end + 1
+    } else {
+        span.start.saturating_sub(1)..span.end
+    };
+
+    let content = format!("{}{}", &text[..removed.start], &text[removed.end..]);
+    let line_start = content[..removed.start.min(content.len())]
+        .rfind('\n')
+        .map_or(0, |i| i + 1);
+    let line_end = content[line_start..]
+        .find('\n')
+        .map_or(content.len(), |i| line_start + i);
+
+    let mut cursor = (line_start + column).min(line_end);
+
+    while !content.is_char_boundary(cursor) {
+        cursor -= 1;
+    }
+
+    (content, cursor..cursor)
+}
+
+fn join_lines(text: &str, selection: Range<usize>) -> Option<(String, Range<usize>)> {
+    let ranges = indent::line_ranges(text);
+    let mut rows = rows(text, &selection);
+
+    if rows.len() == 1 {
+        rows.end += 1;
+    }
+
+    let lines = ranges.get(rows.start..rows.end)?;
+    let mut joined = text[lines[0].clone()].trim_end().to_string();
+    let mut joint = joined.len();
+
+    for line in &lines[1..] {
+        let line = text[line.clone()].trim();
+
+        joint = joined.len();
+
+        if !line.is_empty() {
+            joined.push(' ');
+            joined.push_str(line);
+        }
+    }
+
+    let span = lines[0].start..lines[lines.len() - 1].end;
+    let content = format!("{}{joined}{}", &text[..span.start], &text[span.end..]);
+
+    let selection = if selection.is_empty() {
+        span.start + joint..span.start + joint
+    } else {
+        span.start..span.start + joined.len()
+    };
+
+    Some((content, selection))
+}
+
+fn sort_lines(text: &str, selection: Range<usize>) -> Option<(String, Range<usize>)> {
+    let span = line_span(text, &selection);
+    let mut lines: Vec<&str> = text[span.clone()].split('\n').collect();
+
+    if lines.len() < 2 {
+        return None;
+    }
+
+    lines.sort();
+
+    let sorted = lines.join("\n");
+    let content = format!("{}{sorted}{}", &text[..span.start], &text[span.end..]);
+
+    Some((content, span))
+}
+
+/// Comments out every non-blank line of the selection at their shared
+/// indentation, or uncomments them if they are all commented already.
+fn toggle_line_comment(text: &str, selection: Range<usize>, token: &str) -> (String, Range<usize>) {
+    let ranges = indent::line_ranges(text);
+    let rows = rows(text, &selection);
+
+    let lines: Vec<Range<usize>> = ranges[rows]
+        .iter()
+        .filter(|range| !text[(*range).clone()].trim().is_empty())
+        .cloned()
+        .collect();
+
+    let commented = !lines.is_empty()
+        && lines
+            .iter()
+            .all(|range| text[range.clone()].trim_start().starts_with(token));
+
+    // (byte offset, bytes removed, text inserted), in ascending order.
+    let edits: Vec<(usize, usize, String)> = if commented {
+        lines
+            .iter()
+            .map(|range| {
+                let line = &text[range.clone()];
+                let start = range.start + leading_whitespace(line).len();
+                let after = &text[start + token.len()..range.end];
+
+                (
+                    start,
+                    token.len() + usize::from(after.starts_with(' ')),
+                    "".into(),
+                )
+            })
+            .collect()
+    } else {
+        let column = lines
+            .iter()
+            .map(|range| leading_whitespace(&text[range.clone()]).len())
+            .min()
+            .unwrap_or(0);
+
+        lines
+            .iter()
+            .map(|range| (range.start + column, 0, format!("{token} ")))
+            .collect()
+    };
+
+    let mut content = String::with_capacity(text.len());
+    let mut last = 0;
+
+    for (offset, removed, inserted) in &edits {
+        content.push_str(&text[last..*offset]);
+        content.push_str(inserted);
+        last = offset + removed;
+    }
+
+    content.push_str(&text[last..]);
+
+    let map = |position: usize| {
+        let mut mapped = position as isize;
+
+        for (offset, removed, inserted) in &edits {
+            if *offset < position || (*offset == position && !commented) {
+                let removed = (*removed).min(position - offset) as isize;
+                mapped += inserted.len() as isize - removed;
+            }
+        }
+
+        mapped as usize
+    };
+
+    (content, map(selection.start)..map(selection.end))
+}
+
+fn toggle_block_comment(
+    text: &str,
+    span: Range<usize>,
+    (open, close): (&str, &str),
+) -> Option<(String, Range<usize>)> {
+    let content = &text[span.clone()];
+
+    if content.len() >= open.len() + close.len()
+        && content.starts_with(open)
+        && content.ends_with(close)
+    {
+        let inner = &content[open.len()..content.len() - close.len()];
+        let inner = inner.strip_prefix(' ').unwrap_or(inner);
+        let inner = inner.strip_suffix(' ').unwrap_or(inner);
+
+        let replaced = format!("{}{inner}{}", &text[..span.start], &text[span.end..]);
+
+        return Some((replaced, span.start..span.start + inner.len()));
+    }
+
+    let wrapped = format!("{open} {content} {close}");
+    let replaced = format!("{}{wrapped}{}", &text[..span.start], &text[span.end..]);
+
+    Some((replaced, span.start..span.start + wrapped.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{file_store::FileData, languages};
+
+    fn apply(
+        command: LineCommand,
+        text: &str,
+        selection: Range<usize>,
+    ) -> Option<(String, Range<usize>)> {
+        command.apply(text, selection, languages::from_path("file.rs"))
+    }
+
+    fn edited(command: LineCommand, text: &str, selection: Range<usize>) -> Option<String> {
+        apply(command, text, selection).map(|(text, _)| text)
+    }
+
+    #[test]
+    fn moves_lines_within_the_text() {
+        let text = "a\nb\nc";
+
+        assert_eq!(
+            apply(LineCommand::MoveUp, text, 2..2),
+            Some(("b\na\nc".into(), 0..0))
+        );
+        assert_eq!(
+            apply(LineCommand::MoveDown, text, 2..3),
+            Some(("a\nc\nb".into(), 4..5))
+        );
+        assert_eq!(apply(LineCommand::MoveUp, text, 0..0), None);
+        assert_eq!(apply(LineCommand::MoveDown, text, 4..5), None);
+        assert_eq!(
+            edited(LineCommand::MoveDown, text, 0..2),
+            Some("b\na\nc".into()),
+            "A selection ending at a line start leaves that line alone."
+        );
+    }
+
+    #[test]
+    fn copies_lines() {
+        let text = "a\nb";
+
+        assert_eq!(
+            apply(LineCommand::DuplicateDown, text, 3..3),
+            Some(("a\nb\nb".into(), 5..5))
+        );
+        assert_eq!(
+            apply(LineCommand::DuplicateUp, text, 0..1),
+            Some(("a\na\nb".into(), 0..1))
+        );
+    }
+
+    #[test]
+    fn deletes_the_first_and_last_lines() {
+        assert_eq!(
+            apply(LineCommand::Delete, "ab\ncd\nef", 1..1),
+            Some(("cd\nef".into(), 1..1))
+        );
+        assert_eq!(
+            apply(LineCommand::Delete, "ab\ncd\nef", 7..7),
+            Some(("ab\ncd".into(), 4..4))
+        );
+        assert_eq!(
+            apply(LineCommand::Delete, "", 0..0),
+            Some(("".into(), 0..0))
+        );
+    }
+
+    #[test]
+    fn joins_lines() {
+        assert_eq!(
+            apply(LineCommand::Join, "a  \n    b\nc", 0..0),
+            Some(("a b\nc".into(), 1..1))
+        );
+        assert_eq!(
+            edited(LineCommand::Join, "a\n\nb\nc", 0..4),
+            Some("a b\nc".into())
+        );
+        assert_eq!(apply(LineCommand::Join, "a\nb", 2..2), None);
+    }
+
+    #[test]
+    fn sorts_the_selected_lines() {
+        assert_eq!(
+            apply(LineCommand::Sort, "c\nb\na\n0", 0..5),
+            Some(("a\nb\nc\n0".into(), 0..5))
+        );
+        assert_eq!(apply(LineCommand::Sort, "b\na", 0..0), None);
+    }
+
+    #[test]
+    fn keeps_crlf_line_endings() {
+        // Files are edited with `\n` only and written with the line ending
+        // they were read with.
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("file.txt");
+
+        std::fs::write(&path, "b\r\na\r\nc\r\n").unwrap();
+
+        let mut file = FileData::read(path.to_str().unwrap(), None).unwrap();
+
+        let edits = [
+            (LineCommand::MoveDown, 0..0),
+            (LineCommand::Sort, 0..5),
+            (LineCommand::Join, 0..3),
+        ];
+
+        for (command, selection) in edits {
+            (file.content, _) = command.apply(&file.content, selection, None).unwrap();
+        }
+
+        assert_eq!(file.content, "a b\nc\n");
+        assert_eq!(file.to_disk().unwrap(), b"a b\r\nc\r\n");
+    }
+
+    #[test]
+    fn toggles_line_comments_back_and_forth() {
+        let text = "fn a() {\n    b();\n\n        c();\n}";
+        let selection = 9..text.len() - 2;
+        let (commented, selected) = apply(LineCommand::ToggleLineComment, text, selection).unwrap();
+
+        assert_eq!(commented, "fn a() {\n    // b();\n\n    //     c();\n}");
+
+        let (uncommented, _) = apply(LineCommand::ToggleLineComment, &commented, selected).unwrap();
+
+        assert_eq!(uncommented, text);
+    }
+
+    #[test]
+    fn toggles_block_comments_back_and_forth() {
+        let text = "let a = b + c;";
+        let (commented, selected) = apply(LineCommand::ToggleBlockComment, text, 8..13).unwrap();
+
+        assert_eq!(commented, "let a = /* b + c */;");
+        assert_eq!(
+            apply(LineCommand::ToggleBlockComment, &commented, selected),
+            Some((text.into(), 8..13))
+        );
+    }
+
+    #[test]
+    fn falls_back_to_block_comments() {
+        let html = languages::from_path("file.html");
+
+        assert_eq!(
+            LineCommand::ToggleLineComment.apply("  <p>\n", 0..0, html),
+            Some(("  <!-- <p> -->\n".into(), 2..14))
+        );
+        assert_eq!(LineCommand::ToggleLineComment.apply("a", 0..0, None), None);
+    }
+}
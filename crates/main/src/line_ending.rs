@@ -0,0 +1,99 @@
+//! Line endings. The buffer only ever has `\n`; the file's own are kept in
+//! its `FileData` and put back on save, or changed from the menu bar.
+
+use egui::Ui;
+
+use crate::State;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LineEnding {
+    #[default]
+    Lf,
+    CrLf,
+}
+
+impl LineEnding {
+    pub const ALL: [LineEnding; 2] = [LineEnding::Lf, LineEnding::CrLf];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            LineEnding::Lf => "LF",
+            LineEnding::CrLf => "CRLF",
+        }
+    }
+
+    /// The one most lines of `text` end with, LF when there's a single line.
+    pub fn detect(text: &str) -> Self {
+        let crlf = text.matches("\r\n").count();
+        let lf = text.matches('\n').count() - crlf;
+
+        match crlf > lf {
+            true => LineEnding::CrLf,
+            false => LineEnding::Lf,
+        }
+    }
+
+    /// `text`, as the buffer has it, with these line endings.
+    pub fn apply(self, text: &str) -> String {
+        match self {
+            LineEnding::Lf => text.into(),
+            LineEnding::CrLf => text.replace('\n', "\r\n"),
+        }
+    }
+}
+
+/// `text` with `\n` for every line ending, as the buffer has it.
+pub fn normalize(text: String) -> String {
+    match text.contains("\r\n") {
+        true => text.replace("\r\n", "\n"),
+        false => text,
+    }
+}
+
+/// The active file's line endings, in the menu bar, to convert them.
+pub fn status(ui: &mut Ui, state: &mut State) {
+    let Some(file) = state.file_store.get_active_file_as_mut() else {
+        return;
+    };
+
+    ui.menu_button(file.line_ending.label(), |ui| {
+        for line_ending in LineEnding::ALL {
+            if ui
+                .radio(file.line_ending == line_ending, line_ending.label())
+                .clicked()
+            {
+                // Unsaved from now on, as far as anyone tracking edits knows.
+                if file.line_ending != line_ending {
+                    file.line_ending = line_ending;
+                    file.version += 1;
+                }
+
+                ui.close_menu();
+            }
+        }
+    })
+    .response
+    .on_hover_text("Line endings, converted when the file is saved");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_the_most_common_ending() {
+        assert_eq!(LineEnding::detect("a\r\nb\r\nc\n"), LineEnding::CrLf);
+        assert_eq!(LineEnding::detect("a\nb\nc\r\n"), LineEnding::Lf);
+        assert_eq!(LineEnding::detect("a"), LineEnding::Lf);
+    }
+
+    #[test]
+    fn applies_to_normalized_text() {
+        let text = "a\r\nb\r\n".to_string();
+        let normalized = normalize(text.clone());
+
+        assert_eq!(normalized, "a\nb\n");
+        assert_eq!(LineEnding::CrLf.apply(&normalized), text);
+        assert_eq!(LineEnding::Lf.apply(&normalized), normalized);
+    }
+}
@@ -0,0 +1,152 @@
+//! Just enough Markdown for what language servers send in documentation and
+//! hovers: headings, fenced code, lists, rules, `code`, **strong** and
+//! *emphasis*.
+
+use egui::{text::LayoutJob, Color32, FontId, Frame, Margin, RichText, TextFormat, TextStyle, Ui};
+
+use crate::syntax_highlighter;
+
+pub fn show(ui: &mut Ui, markdown: &str) {
+    let mut lines = markdown.lines();
+    let mut paragraph = String::new();
+
+    while let Some(line) = lines.next() {
+        let trimmed = line.trim_start();
+
+        if let Some(fence) = trimmed.strip_prefix("```") {
+            flush(ui, &mut paragraph);
+
+            let mut code = Vec::new();
+
+            for line in lines.by_ref() {
+                if line.trim_start().starts_with("```") {
+                    break;
+                }
+
+                code.push(line);
+            }
+
+            code_block(ui, fence.trim(), &code.join("\n"));
+        } else if trimmed.is_empty() {
+            flush(ui, &mut paragraph);
+        } else if let Some(heading) = heading(trimmed) {
+            flush(ui, &mut paragraph);
+            ui.label(RichText::new(heading).strong().size(15.0));
+        } else if trimmed.chars().all(|char| matches!(char, '-' | '*' | '_')) && trimmed.len() >= 3
+        {
+            flush(ui, &mut paragraph);
+            ui.separator();
+        } else if let Some(item) = trimmed
+            .strip_prefix("- ")
+            .or_else(|| trimmed.strip_prefix("* "))
+        {
+            flush(ui, &mut paragraph);
+            ui.horizontal_wrapped(|ui| {
+                ui.label("•");
+                inline(ui, item);
+            });
+        } else {
+            if !paragraph.is_empty() {
+                paragraph.push(' ');
+            }
+
+            paragraph.push_str(trimmed.trim_end_matches('\\'));
+        }
+    }
+
+    flush(ui, &mut paragraph);
+}
+
+fn heading(line: &str) -> Option<&str> {
+    let text = line.trim_start_matches('#');
+    (text.len() < line.len() && text.starts_with(' ')).then(|| text.trim())
+}
+
+fn flush(ui: &mut Ui, paragraph: &mut String) {
+    if !paragraph.is_empty() {
+        inline(ui, paragraph);
+        paragraph.clear();
+    }
+}
+
+fn code_block(ui: &mut Ui, language: &str, code: &str) {
+    Frame::none()
+        .fill(ui.visuals().extreme_bg_color)
+        .inner_margin(Margin::same(6.0))
+        .rounding(theme::Theme::ROUNDING)
+        .show(ui, |ui| {
+            ui.set_min_width(ui.available_width());
+
+            // Only the JavaScript family is highlighted for now.
+            let job = match language {
+                "js" | "javascript" | "jsx" | "ts" | "typescript" | "tsx" => {
+                    syntax_highlighter::highlight(code.to_string())
+                }
+                _ => {
+                    let mut job = LayoutJob::default();
+                    job.append(
+                        code,
+                        0.0,
+                        TextFormat {
+                            font_id: TextStyle::Monospace.resolve(ui.style()),
+                            color: ui.visuals().text_color(),
+                            ..TextFormat::default()
+                        },
+                    );
+                    job
+                }
+            };
+
+            ui.label(job);
+        });
+}
+
+/// Renders `code`, **strong** and *emphasis* spans. Underscores are left
+/// alone, they're far more common in identifiers than as emphasis.
+fn inline(ui: &mut Ui, text: &str) {
+    let body = TextStyle::Body.resolve(ui.style());
+    let monospace = TextStyle::Monospace.resolve(ui.style());
+    let color = ui.visuals().text_color();
+    let strong = ui.visuals().strong_text_color();
+
+    let mut job = LayoutJob::default();
+    let mut rest = text;
+
+    let format = |font_id: FontId, color: Color32, italics: bool| TextFormat {
+        font_id,
+        color,
+        italics,
+        ..TextFormat::default()
+    };
+
+    while !rest.is_empty() {
+        let next = rest.find(['`', '*']).unwrap_or(rest.len());
+        job.append(&rest[..next], 0.0, format(body.clone(), color, false));
+        rest = &rest[next..];
+
+        let (delimiter, font_id, color, italics) = if rest.starts_with('`') {
+            ("`", monospace.clone(), color, false)
+        } else if rest.starts_with("**") {
+            ("**", body.clone(), strong, false)
+        } else if rest.starts_with('*') {
+            ("*", body.clone(), color, true)
+        } else {
+            break;
+        };
+
+        match rest[delimiter.len()..].find(delimiter) {
+            Some(end) if end > 0 => {
+                let span = &rest[delimiter.len()..delimiter.len() + end];
+                job.append(span, 0.0, format(font_id, color, italics));
+                rest = &rest[delimiter.len() * 2 + end..];
+            }
+            _ => {
+                job.append(delimiter, 0.0, format(body.clone(), color, false));
+                rest = &rest[delimiter.len()..];
+            }
+        }
+    }
+
+    job.wrap.max_width = ui.available_width();
+    ui.label(job);
+}
@@ -0,0 +1,297 @@
+//! Go to definition and hover, both answered by the file's language server.
+
+use egui::{
+    text_edit::TextEditOutput, Context, CursorIcon, Id, Key, KeyboardShortcut, Modifiers, Rect,
+    ScrollArea, Stroke, Ui,
+};
+use lsp::{
+    lsp_types::{
+        request, GotoDefinitionParams, GotoDefinitionResponse, HoverContents, HoverParams,
+        HoverProviderCapability, Location, MarkedString, TextDocumentIdentifier,
+        TextDocumentPositionParams,
+    },
+    position, LspManager, PendingRequest,
+};
+use std::ops::Range;
+use theme::Theme;
+
+use crate::{editor, file_store::FileData, language_servers, markdown, references, State};
+
+pub const DEFINITION_SHORTCUT: KeyboardShortcut = KeyboardShortcut::new(Modifiers::NONE, Key::F12);
+pub const REFERENCES_SHORTCUT: KeyboardShortcut = KeyboardShortcut::new(Modifiers::SHIFT, Key::F12);
+
+/// How long the pointer has to rest on a symbol before its hover is shown.
+const HOVER_DELAY: f32 = 0.5;
+
+#[derive(Default)]
+pub struct Navigation {
+    definition: Option<PendingRequest<request::GotoDefinition>>,
+    hover: Option<Hover>,
+}
+
+struct Hover {
+    id: Id,
+    version: i32,
+    range: Range<usize>,
+    request: Option<PendingRequest<request::HoverRequest>>,
+    markdown: Option<String>,
+}
+
+/// F12 and Shift+F12 while the editor has focus.
+pub fn handle_input(ctx: &Context, state: &mut State) {
+    let id = state.file_store.get_active_file_id().into();
+
+    if !ctx.memory(|memory| memory.has_focus(id)) {
+        return;
+    }
+
+    // Shift+F12 first, consuming F12 would swallow it too.
+    if ctx.input_mut(|input| input.consume_shortcut(&REFERENCES_SHORTCUT)) {
+        find_references(ctx, state);
+    } else if ctx.input_mut(|input| input.consume_shortcut(&DEFINITION_SHORTCUT)) {
+        go_to_definition(ctx, state);
+    }
+}
+
+/// Asks for the definition of the symbol under the cursor; it's opened once
+/// the server answers, see [`poll`].
+pub fn go_to_definition(ctx: &Context, state: &mut State) {
+    let id = state.file_store.get_active_file_id().into();
+
+    if let Some(file) = state.file_store.get_active_file() {
+        let offset = cursor(ctx, id, file);
+        state.navigation.definition = request_definition(&mut state.lsp, file, offset);
+    }
+}
+
+pub fn find_references(ctx: &Context, state: &mut State) {
+    let id = state.file_store.get_active_file_id().into();
+
+    if let Some(file) = state.file_store.get_active_file() {
+        let offset = cursor(ctx, id, file);
+        references::find(&mut state.references, &mut state.lsp, file, offset);
+    }
+}
+
+/// Opens the definition once it arrives. Several candidates are listed in
+/// the references panel instead.
+pub fn poll(ctx: &Context, state: &mut State) {
+    let Some(result) = state
+        .navigation
+        .definition
+        .as_ref()
+        .and_then(PendingRequest::try_take)
+    else {
+        return;
+    };
+
+    state.navigation.definition = None;
+
+    let locations = match result {
+        Ok(Some(GotoDefinitionResponse::Scalar(location))) => vec![location],
+        Ok(Some(GotoDefinitionResponse::Array(locations))) => locations,
+        Ok(Some(GotoDefinitionResponse::Link(links))) => links
+            .into_iter()
+            .map(|link| Location {
+                uri: link.target_uri,
+                range: link.target_selection_range,
+            })
+            .collect(),
+        Ok(None) | Err(_) => Vec::new(),
+    };
+
+    match locations.as_slice() {
+        [] => {}
+        [location] => {
+            language_servers::open_location(ctx, state, &location.uri, location.range.start)
+        }
+        _ => references::show_locations(state, "Definitions".into(), locations),
+    }
+}
+
+/// Runs after the editor's `TextEdit`: Cmd+Click on a symbol goes to its
+/// definition, resting the pointer on one shows its hover.
+pub fn update(
+    ui: &Ui,
+    id: Id,
+    file: &FileData,
+    navigation: &mut Navigation,
+    lsp: &mut LspManager,
+    output: &TextEditOutput,
+    theme: &Theme,
+) {
+    let ctx = ui.ctx();
+    let hovered = output.response.hover_pos().and_then(|pointer| {
+        let cursor = output.galley.cursor_from_pos(pointer - output.galley_pos);
+        let offset = editor::byte_offset(&file.content, cursor.ccursor.index);
+        let word = editor::word_at(&file.content, offset);
+        let rect = word_rect(output, &file.content, word.clone())?;
+
+        rect.contains(pointer).then_some((word, rect))
+    });
+
+    let Some((word, rect)) = hovered else {
+        navigation.hover = None;
+        return;
+    };
+
+    if ctx.input(|input| input.modifiers.command) {
+        ctx.set_cursor_icon(CursorIcon::PointingHand);
+        ui.painter().hline(
+            rect.x_range(),
+            rect.bottom(),
+            Stroke::new(1.0, theme.primary.main),
+        );
+
+        if output.response.clicked() {
+            navigation.definition = request_definition(lsp, file, word.start);
+        }
+    }
+
+    let current = navigation.hover.as_ref().is_some_and(|hover| {
+        hover.id == id
+            && hover.version == file.version
+            && hover.range.start <= word.start
+            && word.end <= hover.range.end
+    });
+
+    if !current {
+        navigation.hover = None;
+
+        let resting = ctx.input(|input| input.pointer.time_since_last_movement());
+
+        if resting < HOVER_DELAY {
+            ctx.request_repaint_after(std::time::Duration::from_secs_f32(HOVER_DELAY - resting));
+            return;
+        }
+
+        navigation.hover = Some(Hover {
+            id,
+            version: file.version,
+            range: word.clone(),
+            request: request_hover(lsp, file, word.start),
+            markdown: None,
+        });
+    }
+
+    let Some(hover) = &mut navigation.hover else {
+        return;
+    };
+
+    if let Some(result) = hover.request.as_ref().and_then(PendingRequest::try_take) {
+        hover.request = None;
+
+        if let Ok(Some(answer)) = result {
+            if let Some(range) = answer.range {
+                hover.range = position::to_range(&file.content, range);
+            }
+
+            hover.markdown = Some(hover_markdown(answer.contents));
+        }
+    }
+
+    if let Some(markdown) = hover
+        .markdown
+        .as_deref()
+        .filter(|text| !text.trim().is_empty())
+    {
+        egui::show_tooltip_at_pointer(ctx, ui.layer_id(), output.response.id, |ui| {
+            ui.set_max_width(480.0);
+
+            ScrollArea::vertical().max_height(320.0).show(ui, |ui| {
+                markdown::show(ui, markdown);
+            });
+        });
+    }
+}
+
+/// Where `range` is drawn, if it fits on one row.
+pub fn word_rect(output: &TextEditOutput, text: &str, range: Range<usize>) -> Option<Rect> {
+    if range.is_empty() {
+        return None;
+    }
+
+    let rect_of = |offset: usize| {
+        let cursor = output
+            .galley
+            .from_ccursor(egui::text::CCursor::new(editor::char_offset(text, offset)));
+        output
+            .galley
+            .pos_from_cursor(&cursor)
+            .translate(output.galley_pos.to_vec2())
+    };
+
+    let start = rect_of(range.start);
+    let end = rect_of(range.end);
+
+    (start.top() == end.top()).then(|| Rect::from_min_max(start.min, end.max))
+}
+
+fn request_definition(
+    lsp: &mut LspManager,
+    file: &FileData,
+    offset: usize,
+) -> Option<PendingRequest<request::GotoDefinition>> {
+    language_servers::request::<request::GotoDefinition>(
+        lsp,
+        file,
+        |capabilities| language_servers::enabled(capabilities.definition_provider.as_ref()),
+        |uri| GotoDefinitionParams {
+            text_document_position_params: TextDocumentPositionParams {
+                text_document: TextDocumentIdentifier { uri },
+                position: position::from_offset(&file.content, offset),
+            },
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+        },
+    )
+}
+
+fn request_hover(
+    lsp: &mut LspManager,
+    file: &FileData,
+    offset: usize,
+) -> Option<PendingRequest<request::HoverRequest>> {
+    language_servers::request::<request::HoverRequest>(
+        lsp,
+        file,
+        |capabilities| {
+            !matches!(
+                capabilities.hover_provider,
+                None | Some(HoverProviderCapability::Simple(false))
+            )
+        },
+        |uri| HoverParams {
+            text_document_position_params: TextDocumentPositionParams {
+                text_document: TextDocumentIdentifier { uri },
+                position: position::from_offset(&file.content, offset),
+            },
+            work_done_progress_params: Default::default(),
+        },
+    )
+}
+
+/// Older servers send marked strings, which are Markdown or a code block.
+fn hover_markdown(contents: HoverContents) -> String {
+    let marked = |marked: MarkedString| match marked {
+        MarkedString::String(text) => text,
+        MarkedString::LanguageString(code) => {
+            format!("```{}\n{}\n```", code.language, code.value)
+        }
+    };
+
+    match contents {
+        HoverContents::Scalar(contents) => marked(contents),
+        HoverContents::Array(contents) => contents
+            .into_iter()
+            .map(marked)
+            .collect::<Vec<_>>()
+            .join("\n\n---\n\n"),
+        HoverContents::Markup(markup) => markup.value,
+    }
+}
+
+fn cursor(ctx: &Context, id: Id, file: &FileData) -> usize {
+    let selection = editor::get_selection(ctx, id, file);
+    editor::byte_offset(&file.content, selection.primary.index)
+}
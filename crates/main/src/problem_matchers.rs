@@ -0,0 +1,417 @@
+//! Turns the output of tasks into problems: rustc's JSON diagnostics, `tsc`
+//! and ESLint's default formatter.
+
+use lsp::lsp_types::{Diagnostic, DiagnosticSeverity, NumberOrString, Position, Range};
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProblemMatcher {
+    /// `cargo … --message-format=json`
+    Rustc,
+    Tsc,
+    Eslint,
+}
+
+impl ProblemMatcher {
+    pub fn name(self) -> &'static str {
+        match self {
+            ProblemMatcher::Rustc => "rustc",
+            ProblemMatcher::Tsc => "tsc",
+            ProblemMatcher::Eslint => "eslint",
+        }
+    }
+}
+
+/// A line of output after matching it.
+pub struct Matched {
+    /// What to print instead, `None` to hide the line. rustc's JSON is
+    /// replaced by the message it renders, which spans several lines.
+    pub text: Option<String>,
+    pub problem: Option<Problem>,
+}
+
+pub struct Problem {
+    pub path: PathBuf,
+    pub diagnostic: Diagnostic,
+}
+
+/// Matches a task's output line by line, keeping the state formats that
+/// span lines need, like the file ESLint is listing problems for.
+pub struct Matcher {
+    kind: ProblemMatcher,
+    cwd: PathBuf,
+    file: Option<PathBuf>,
+}
+
+impl Matcher {
+    pub fn new(kind: ProblemMatcher, cwd: PathBuf) -> Self {
+        Self {
+            kind,
+            cwd,
+            file: None,
+        }
+    }
+
+    pub fn line(&mut self, line: &str) -> Matched {
+        match self.kind {
+            ProblemMatcher::Rustc => self.rustc(line),
+            ProblemMatcher::Tsc => Matched {
+                problem: self.tsc(line),
+                text: Some(line.into()),
+            },
+            ProblemMatcher::Eslint => Matched {
+                problem: self.eslint(line),
+                text: Some(line.into()),
+            },
+        }
+    }
+
+    fn rustc(&self, line: &str) -> Matched {
+        let Ok(CargoMessage {
+            reason,
+            manifest_path,
+            message,
+        }) = serde_json::from_str(line)
+        else {
+            // Everything that isn't JSON, like the output of tests.
+            return Matched {
+                text: Some(line.into()),
+                problem: None,
+            };
+        };
+
+        let Some(message) = message.filter(|_| reason == "compiler-message") else {
+            return Matched {
+                text: None,
+                problem: None,
+            };
+        };
+
+        let problem = message
+            .spans
+            .iter()
+            .find(|span| span.is_primary)
+            .map(|span| Problem {
+                path: self.resolve_rustc_path(&span.file_name, manifest_path.as_deref()),
+                diagnostic: Diagnostic {
+                    range: Range {
+                        start: position(span.line_start, span.column_start),
+                        end: position(span.line_end, span.column_end),
+                    },
+                    severity: Some(match message.level.as_str() {
+                        "warning" => DiagnosticSeverity::WARNING,
+                        "note" => DiagnosticSeverity::INFORMATION,
+                        "help" => DiagnosticSeverity::HINT,
+                        _ => DiagnosticSeverity::ERROR,
+                    }),
+                    code: message
+                        .code
+                        .as_ref()
+                        .map(|code| NumberOrString::String(code.code.clone())),
+                    source: Some(self.kind.name().into()),
+                    message: message.message.clone(),
+                    ..Default::default()
+                },
+            });
+
+        Matched {
+            text: message
+                .rendered
+                .map(|rendered| rendered.trim_end().to_string()),
+            problem,
+        }
+    }
+
+    /// Spans are relative to the workspace root, which may be any folder
+    /// above the package's manifest.
+    fn resolve_rustc_path(&self, file_name: &str, manifest_path: Option<&Path>) -> PathBuf {
+        manifest_path
+            .into_iter()
+            .flat_map(Path::ancestors)
+            .skip(1)
+            .chain([self.cwd.as_path()])
+            .map(|root| root.join(file_name))
+            .find(|path| path.is_file())
+            .unwrap_or_else(|| self.cwd.join(file_name))
+    }
+
+    /// `src/a.ts(3,5): error TS2322: …`, or with `--pretty`,
+    /// `src/a.ts:3:5 - error TS2322: …`.
+    fn tsc(&self, line: &str) -> Option<Problem> {
+        let (path, line_number, column, rest) = match line.split_once("): ") {
+            Some((head, rest)) => {
+                let (path, position) = head.rsplit_once('(')?;
+                let (line, column) = position.split_once(',')?;
+                (path, line.parse().ok()?, column.parse().ok()?, rest)
+            }
+            None => {
+                let (head, rest) = line.split_once(" - ")?;
+                let (head, column) = head.rsplit_once(':')?;
+                let (path, line) = head.rsplit_once(':')?;
+                (path, line.parse().ok()?, column.parse().ok()?, rest)
+            }
+        };
+
+        let (severity, rest) = rest.split_once(' ')?;
+        let (code, message) = rest.split_once(": ")?;
+
+        if !code.starts_with("TS") {
+            return None;
+        }
+
+        Some(Problem {
+            path: self.cwd.join(path.trim()),
+            diagnostic: Diagnostic {
+                range: Range::new(position(line_number, column), position(line_number, column)),
+                severity: Some(match severity {
+                    "error" => DiagnosticSeverity::ERROR,
+                    "warning" => DiagnosticSeverity::WARNING,
+                    _ => DiagnosticSeverity::INFORMATION,
+                }),
+                code: Some(NumberOrString::String(code.into())),
+                source: Some(self.kind.name().into()),
+                message: message.into(),
+                ..Default::default()
+            },
+        })
+    }
+
+    /// The file on a line of its own, then its problems indented below it:
+    ///
+    /// ```text
+    /// /project/src/index.js
+    ///   3:5  error  'x' is defined but never used  no-unused-vars
+    /// ```
+    fn eslint(&mut self, line: &str) -> Option<Problem> {
+        if line.trim().is_empty() {
+            self.file = None;
+            return None;
+        }
+
+        if !line.starts_with(char::is_whitespace) {
+            let path = self.cwd.join(line.trim());
+            self.file = path.is_file().then_some(path);
+            return None;
+        }
+
+        let file = self.file.clone()?;
+        let (location, rest) = line.trim().split_once(char::is_whitespace)?;
+        let (line_number, column) = location.split_once(':')?;
+        let (severity, rest) = rest.trim_start().split_once(char::is_whitespace)?;
+
+        let severity = match severity {
+            "error" => DiagnosticSeverity::ERROR,
+            "warning" => DiagnosticSeverity::WARNING,
+            _ => return None,
+        };
+
+        // The rule is separated by at least two spaces, and missing for
+        // parsing errors.
+        let rest = rest.trim();
+        let (message, rule) = match rest.rsplit_once("  ") {
+            Some((message, rule)) if !rule.contains(' ') => (message.trim_end(), Some(rule)),
+            _ => (rest, None),
+        };
+
+        let start = position(line_number.parse().ok()?, column.parse().ok()?);
+
+        Some(Problem {
+            path: file,
+            diagnostic: Diagnostic {
+                range: Range::new(start, start),
+                severity: Some(severity),
+                code: rule.map(|rule| NumberOrString::String(rule.into())),
+                source: Some(self.kind.name().into()),
+                message: message.into(),
+                ..Default::default()
+            },
+        })
+    }
+}
+
+/// Tools count lines and columns from 1.
+fn position(line: u32, column: u32) -> Position {
+    Position::new(line.saturating_sub(1), column.saturating_sub(1))
+}
+
+#[derive(Deserialize)]
+struct CargoMessage {
+    reason: String,
+    #[serde(default)]
+    manifest_path: Option<PathBuf>,
+    #[serde(default)]
+    message: Option<CompilerMessage>,
+}
+
+#[derive(Deserialize)]
+struct CompilerMessage {
+    message: String,
+    level: String,
+    #[serde(default)]
+    code: Option<CompilerCode>,
+    #[serde(default)]
+    spans: Vec<CompilerSpan>,
+    #[serde(default)]
+    rendered: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct CompilerCode {
+    code: String,
+}
+
+#[derive(Deserialize)]
+struct CompilerSpan {
+    file_name: String,
+    line_start: u32,
+    line_end: u32,
+    column_start: u32,
+    column_end: u32,
+    is_primary: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matcher(kind: ProblemMatcher) -> Matcher {
+        Matcher::new(kind, PathBuf::from(env!("CARGO_MANIFEST_DIR")))
+    }
+
+    fn code(problem: &Problem) -> Option<&str> {
+        match &problem.diagnostic.code {
+            Some(NumberOrString::String(code)) => Some(code),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn rustc_compiler_message() {
+        let line = r#"{"reason":"compiler-message","manifest_path":"/nowhere/Cargo.toml","message":{"message":"unused variable: `x`","level":"warning","code":{"code":"unused_variables"},"spans":[{"file_name":"src/lib.rs","line_start":3,"line_end":3,"column_start":9,"column_end":10,"is_primary":true}],"rendered":"warning: unused variable: `x`\n"}}"#;
+
+        let matched = matcher(ProblemMatcher::Rustc).line(line);
+        let problem = matched.problem.unwrap();
+
+        assert_eq!(
+            matched.text.as_deref(),
+            Some("warning: unused variable: `x`")
+        );
+        assert_eq!(
+            problem.path,
+            Path::new(env!("CARGO_MANIFEST_DIR")).join("src/lib.rs")
+        );
+        assert_eq!(
+            problem.diagnostic.range,
+            Range::new(Position::new(2, 8), Position::new(2, 9))
+        );
+        assert_eq!(
+            problem.diagnostic.severity,
+            Some(DiagnosticSeverity::WARNING)
+        );
+        assert_eq!(code(&problem), Some("unused_variables"));
+        assert_eq!(problem.diagnostic.message, "unused variable: `x`");
+    }
+
+    #[test]
+    fn rustc_hides_other_json_and_keeps_plain_lines() {
+        let mut matcher = matcher(ProblemMatcher::Rustc);
+
+        let artifact = matcher.line(r#"{"reason":"compiler-artifact","package_id":"a"}"#);
+        assert!(artifact.text.is_none() && artifact.problem.is_none());
+
+        let plain = matcher.line("test tests::it_works ... ok");
+        assert_eq!(plain.text.as_deref(), Some("test tests::it_works ... ok"));
+        assert!(plain.problem.is_none());
+    }
+
+    #[test]
+    fn tsc_plain() {
+        let line = "src/a.ts(3,5): error TS2322: Type 'string' is not assignable to type 'number'.";
+        let problem = matcher(ProblemMatcher::Tsc).line(line).problem.unwrap();
+
+        assert!(problem.path.ends_with("src/a.ts"));
+        assert_eq!(problem.diagnostic.range.start, Position::new(2, 4));
+        assert_eq!(problem.diagnostic.severity, Some(DiagnosticSeverity::ERROR));
+        assert_eq!(code(&problem), Some("TS2322"));
+        assert_eq!(
+            problem.diagnostic.message,
+            "Type 'string' is not assignable to type 'number'."
+        );
+    }
+
+    #[test]
+    fn tsc_pretty() {
+        let line = "src/a.ts:12:1 - warning TS6133: 'x' is declared but its value is never read.";
+        let problem = matcher(ProblemMatcher::Tsc).line(line).problem.unwrap();
+
+        assert!(problem.path.ends_with("src/a.ts"));
+        assert_eq!(problem.diagnostic.range.start, Position::new(11, 0));
+        assert_eq!(
+            problem.diagnostic.severity,
+            Some(DiagnosticSeverity::WARNING)
+        );
+        assert_eq!(code(&problem), Some("TS6133"));
+    }
+
+    #[test]
+    fn tsc_ignores_other_lines() {
+        let mut matcher = matcher(ProblemMatcher::Tsc);
+
+        assert!(matcher.line("Found 2 errors in 1 file.").problem.is_none());
+        assert!(matcher.line("12 - const x = 1;").problem.is_none());
+    }
+
+    #[test]
+    fn eslint_problems_under_their_file() {
+        let mut matcher = matcher(ProblemMatcher::Eslint);
+
+        assert!(matcher.line("src/lib.rs").problem.is_none());
+
+        let unused = matcher
+            .line("  3:5  error  'x' is defined but never used  no-unused-vars")
+            .problem
+            .unwrap();
+        assert!(unused.path.ends_with("src/lib.rs"));
+        assert_eq!(unused.diagnostic.range.start, Position::new(2, 4));
+        assert_eq!(unused.diagnostic.severity, Some(DiagnosticSeverity::ERROR));
+        assert_eq!(code(&unused), Some("no-unused-vars"));
+        assert_eq!(unused.diagnostic.message, "'x' is defined but never used");
+
+        let parsing = matcher
+            .line("  7:1  warning  Parsing error: Unexpected token")
+            .problem
+            .unwrap();
+        assert_eq!(
+            parsing.diagnostic.severity,
+            Some(DiagnosticSeverity::WARNING)
+        );
+        assert_eq!(code(&parsing), None);
+        assert_eq!(
+            parsing.diagnostic.message,
+            "Parsing error: Unexpected token"
+        );
+
+        // A blank line ends the file's problems.
+        assert!(matcher.line("").problem.is_none());
+        assert!(matcher
+            .line("  1:1  error  Unexpected var  no-var")
+            .problem
+            .is_none());
+    }
+
+    #[test]
+    fn eslint_summary_is_not_a_file() {
+        let mut matcher = matcher(ProblemMatcher::Eslint);
+
+        assert!(matcher
+            .line("✖ 2 problems (1 error, 1 warning)")
+            .problem
+            .is_none());
+        assert!(matcher
+            .line("  3:5  error  'x' is defined but never used  no-unused-vars")
+            .problem
+            .is_none());
+    }
+}
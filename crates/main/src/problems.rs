@@ -0,0 +1,227 @@
+use components::selectable_label::SelectableLabel;
+use egui::{Context, RichText, Sense, TextEdit, TopBottomPanel, Ui, Vec2};
+use egui_extras::{Column, TableBuilder};
+use lsp::lsp_types::{Position, Url};
+use std::{cmp::Ordering, collections::HashSet, path::Path};
+
+use crate::{
+    diagnostics::{Diagnostic, Severity},
+    language_servers, State,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SortKey {
+    Severity,
+    Message,
+    File,
+    Source,
+}
+
+/// The bottom panel listing every diagnostic.
+pub struct ProblemsPanel {
+    pub open: bool,
+    sort: SortKey,
+    ascending: bool,
+    filter: String,
+    hidden: HashSet<Severity>,
+}
+
+impl Default for ProblemsPanel {
+    fn default() -> Self {
+        Self {
+            open: false,
+            sort: SortKey::Severity,
+            ascending: true,
+            filter: String::new(),
+            hidden: HashSet::new(),
+        }
+    }
+}
+
+struct Row<'a> {
+    uri: &'a Url,
+    file: String,
+    diagnostic: &'a Diagnostic,
+}
+
+pub fn show(ctx: &Context, state: &mut State) {
+    if !state.problems.open {
+        return;
+    }
+
+    let mut navigate: Option<(Url, Position)> = None;
+
+    TopBottomPanel::bottom("problems")
+        .resizable(true)
+        .default_height(180.0)
+        .min_height(80.0)
+        .show(ctx, |ui| {
+            toolbar(ui, state);
+            ui.separator();
+
+            let panel = &mut state.problems;
+            let filter = panel.filter.to_lowercase();
+            let root = std::env::current_dir().unwrap_or_default();
+
+            let mut rows: Vec<Row> = state
+                .diagnostics
+                .iter()
+                .filter(|(_, diagnostic)| !panel.hidden.contains(&diagnostic.severity))
+                .map(|(uri, diagnostic)| Row {
+                    uri,
+                    file: display_path(uri, &root),
+                    diagnostic,
+                })
+                .filter(|row| {
+                    filter.is_empty()
+                        || row.diagnostic.message.to_lowercase().contains(&filter)
+                        || row.file.to_lowercase().contains(&filter)
+                        || row.diagnostic.origin().to_lowercase().contains(&filter)
+                })
+                .collect();
+
+            rows.sort_by(|a, b| {
+                let ordering = compare(panel.sort, a, b);
+
+                if panel.ascending {
+                    ordering
+                } else {
+                    ordering.reverse()
+                }
+            });
+
+            let row_height = ui.text_style_height(&egui::TextStyle::Body) + 4.0;
+
+            TableBuilder::new(ui)
+                .striped(true)
+                .sense(Sense::click())
+                .resizable(true)
+                .auto_shrink(false)
+                .column(Column::exact(20.0))
+                .column(Column::remainder().at_least(200.0).clip(true))
+                .column(Column::initial(220.0).clip(true))
+                .column(Column::initial(120.0).clip(true))
+                .header(row_height, |mut header| {
+                    header.col(|ui| sort_button(ui, panel, SortKey::Severity, ""));
+                    header.col(|ui| sort_button(ui, panel, SortKey::Message, "Message"));
+                    header.col(|ui| sort_button(ui, panel, SortKey::File, "File"));
+                    header.col(|ui| sort_button(ui, panel, SortKey::Source, "Source"));
+                })
+                .body(|body| {
+                    body.rows(row_height, rows.len(), |mut table_row| {
+                        let Row {
+                            uri,
+                            file,
+                            diagnostic,
+                        } = &rows[table_row.index()];
+
+                        table_row.col(|ui| {
+                            let center = ui.max_rect().center();
+                            diagnostic
+                                .severity
+                                .paint_icon(ui.painter(), center, 8.0, &state.theme);
+                        });
+                        table_row.col(|ui| {
+                            ui.label(&diagnostic.message);
+                        });
+                        table_row.col(|ui| {
+                            let start = diagnostic.range.start;
+                            ui.label(format!("{file}:{}:{}", start.line + 1, start.character + 1));
+                        });
+                        table_row.col(|ui| {
+                            ui.weak(diagnostic.origin());
+                        });
+
+                        if table_row.response().clicked() {
+                            navigate = Some(((*uri).clone(), diagnostic.range.start));
+                        }
+                    });
+                });
+        });
+
+    if let Some((uri, position)) = navigate {
+        language_servers::open_location(ctx, state, &uri, position);
+    }
+}
+
+fn toolbar(ui: &mut Ui, state: &mut State) {
+    ui.horizontal(|ui| {
+        ui.strong("Problems");
+
+        for severity in Severity::ALL {
+            let count = state.diagnostics.count(severity);
+            let shown = !state.problems.hidden.contains(&severity);
+
+            let label = ui.add(
+                SelectableLabel::new(shown, format!("{} {count}", severity.label()))
+                    .padding(Vec2::new(8.0, 2.0)),
+            );
+
+            if label.clicked() && !state.problems.hidden.remove(&severity) {
+                state.problems.hidden.insert(severity);
+            }
+        }
+
+        ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+            if ui.small_button("x").clicked() {
+                state.problems.open = false;
+            }
+
+            ui.add(
+                TextEdit::singleline(&mut state.problems.filter)
+                    .hint_text("Filter")
+                    .desired_width(180.0),
+            );
+        });
+    });
+}
+
+fn sort_button(ui: &mut Ui, panel: &mut ProblemsPanel, key: SortKey, label: &str) {
+    let arrow = match (panel.sort == key, panel.ascending) {
+        (false, _) => "",
+        (true, true) => " ⏶",
+        (true, false) => " ⏷",
+    };
+
+    let response = ui.add(
+        egui::Label::new(RichText::new(format!("{label}{arrow}")).strong()).sense(Sense::click()),
+    );
+
+    if response.clicked() {
+        if panel.sort == key {
+            panel.ascending = !panel.ascending;
+        } else {
+            panel.sort = key;
+            panel.ascending = true;
+        }
+    }
+}
+
+/// Orders by `key`, then by file and position so equal rows stay put.
+fn compare(key: SortKey, a: &Row, b: &Row) -> Ordering {
+    let by_location = || {
+        a.file
+            .cmp(&b.file)
+            .then(a.diagnostic.range.start.cmp(&b.diagnostic.range.start))
+    };
+
+    match key {
+        SortKey::Severity => a.diagnostic.severity.cmp(&b.diagnostic.severity),
+        SortKey::Message => a.diagnostic.message.cmp(&b.diagnostic.message),
+        SortKey::File => Ordering::Equal,
+        SortKey::Source => a.diagnostic.origin().cmp(&b.diagnostic.origin()),
+    }
+    .then_with(by_location)
+}
+
+/// The file's path relative to the workspace, if it's inside it.
+pub fn display_path(uri: &Url, root: &Path) -> String {
+    match uri.to_file_path() {
+        Ok(path) => path
+            .strip_prefix(root)
+            .unwrap_or(&path)
+            .to_string_lossy()
+            .into(),
+        Err(_) => uri.to_string(),
+    }
+}
@@ -0,0 +1,359 @@
+//! Crash recovery: the unsaved edits of every buffer go to a swap file in
+//! the recovery folder once typing pauses, every little while and when the
+//! editor panics. Swap files a run left behind are offered back, next to
+//! the file on disk, on the next launch.
+
+use components::diff_view::{DiffLayout, DiffView};
+use egui::{Context, Id, ScrollArea, Window};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    env, fs,
+    hash::{DefaultHasher, Hash, Hasher},
+    mem, panic,
+    path::{Path, PathBuf},
+    sync::{Mutex, MutexGuard, Once, PoisonError, TryLockError},
+    time::{Duration, Instant},
+};
+
+use crate::{file_store::FileStore, session, syntax_highlighter, State};
+
+/// How long typing pauses before the edits are backed up.
+const IDLE: Duration = Duration::from_secs(2);
+
+/// How often edits are backed up while typing goes on.
+const INTERVAL: Duration = Duration::from_secs(30);
+
+/// The content of the buffers changed since they were opened or saved, by
+/// absolute path, for the panic hook to write out.
+static BUFFERS: Mutex<BTreeMap<String, String>> = Mutex::new(BTreeMap::new());
+
+#[derive(Serialize, Deserialize)]
+struct Swap {
+    /// Absolute, for the file to be found from any workspace.
+    path: String,
+    content: String,
+}
+
+struct Recoverable {
+    swap: Swap,
+    /// None when the file isn't there anymore.
+    disk: Option<String>,
+}
+
+#[derive(Default)]
+pub struct Recovery {
+    started: bool,
+    /// The version of every buffer as of the last frame.
+    versions: HashMap<String, i32>,
+    /// Buffers changed, saved or closed since the last backup.
+    pending: HashSet<String>,
+    /// Buffers with a swap file.
+    swapped: HashSet<String>,
+    changed_at: Option<Instant>,
+    backed_up_at: Option<Instant>,
+    /// Swap files from an earlier run, waiting for the user.
+    recoverable: Vec<Recoverable>,
+    selected: usize,
+    layout: DiffLayout,
+}
+
+fn buffers() -> MutexGuard<'static, BTreeMap<String, String>> {
+    BUFFERS.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn recovery_dir() -> Option<PathBuf> {
+    Some(session::state_dir()?.join("recovery"))
+}
+
+fn swap_file(absolute: &str) -> Option<PathBuf> {
+    let mut hasher = DefaultHasher::new();
+
+    absolute.hash(&mut hasher);
+    Some(recovery_dir()?.join(format!("{:016x}.json", hasher.finish())))
+}
+
+fn absolute(path: &str) -> String {
+    let root = env::current_dir().unwrap_or_default();
+
+    FileStore::get_file_path(&root.join(path).components().collect::<PathBuf>())
+}
+
+fn write_swap(absolute: &str, content: &str) {
+    let Some(file) = swap_file(absolute) else {
+        return;
+    };
+
+    let swap = Swap {
+        path: absolute.into(),
+        content: content.into(),
+    };
+
+    if let (Some(folder), Ok(json)) = (file.parent(), serde_json::to_string(&swap)) {
+        let _ = fs::create_dir_all(folder);
+        let _ = fs::write(file, json);
+    }
+}
+
+fn remove_swap(absolute: &str) {
+    if let Some(file) = swap_file(absolute) {
+        let _ = fs::remove_file(file);
+    }
+}
+
+/// Writes every changed buffer out before the panic takes the editor down.
+fn install_panic_hook() {
+    static INSTALLED: Once = Once::new();
+
+    INSTALLED.call_once(|| {
+        let previous = panic::take_hook();
+
+        panic::set_hook(Box::new(move |info| {
+            // Not waiting on a lock the panicking code may be holding.
+            let buffers = match BUFFERS.try_lock() {
+                Ok(buffers) => Some(buffers),
+                Err(TryLockError::Poisoned(poisoned)) => Some(poisoned.into_inner()),
+                Err(TryLockError::WouldBlock) => None,
+            };
+
+            for (path, content) in buffers.iter().flat_map(|buffers| buffers.iter()) {
+                write_swap(path, content);
+            }
+
+            previous(info);
+        }));
+    });
+}
+
+/// Puts the default panic hook back, before the code of this one goes away.
+pub fn unload() {
+    let _ = panic::take_hook();
+}
+
+/// The swap files of an earlier run still worth offering: not what's on
+/// disk or in the buffer already.
+fn read_recoverable(state: &State) -> Vec<Recoverable> {
+    let Some(read_dir) = recovery_dir().and_then(|folder| fs::read_dir(folder).ok()) else {
+        return Vec::new();
+    };
+
+    let mut recoverable: Vec<Recoverable> = read_dir
+        .filter_map(|entry| {
+            let file = entry.ok()?.path();
+            let swap: Swap = serde_json::from_str(&fs::read_to_string(&file).ok()?).ok()?;
+            let disk = FileStore::read_saved(&swap.path).ok();
+            let buffer = state
+                .file_store
+                .files
+                .get(&FileStore::store_path(Path::new(&swap.path)))
+                .map(|file| file.content.as_str());
+
+            if disk.as_deref() == Some(swap.content.as_str()) || buffer == Some(&swap.content) {
+                let _ = fs::remove_file(file);
+                return None;
+            }
+
+            Some(Recoverable { swap, disk })
+        })
+        .collect();
+
+    recoverable.sort_by(|a, b| a.swap.path.cmp(&b.swap.path));
+    recoverable
+}
+
+pub fn update(ctx: &Context, state: &mut State) {
+    if !state.recovery.started {
+        state.recovery.started = true;
+        install_panic_hook();
+        state.recovery.recoverable = read_recoverable(state);
+    }
+
+    let now = Instant::now();
+    let files = &state.file_store.files;
+    let recovery = &mut state.recovery;
+
+    for (path, file) in files {
+        if recovery.versions.insert(path.clone(), file.version) != Some(file.version) {
+            recovery.pending.insert(path.clone());
+            recovery.changed_at = Some(now);
+            buffers().insert(absolute(path), file.content.clone());
+        }
+    }
+
+    recovery.versions.retain(|path, _| {
+        let open = files.contains_key(path);
+
+        if !open {
+            recovery.pending.insert(path.clone());
+        }
+
+        open
+    });
+
+    // Closing cleanly, the session keeps the unsaved edits.
+    if ctx.input(|input| input.viewport().close_requested()) {
+        for path in recovery.swapped.drain() {
+            remove_swap(&absolute(&path));
+        }
+
+        return;
+    }
+
+    let due = recovery
+        .backed_up_at
+        .is_none_or(|backed_up_at| backed_up_at.elapsed() >= INTERVAL);
+
+    // Saving doesn't change the version, so the swapped ones are looked at
+    // again now and then.
+    if due {
+        recovery.pending.extend(recovery.swapped.iter().cloned());
+    }
+
+    if recovery.pending.is_empty() {
+        return;
+    }
+
+    let idle = recovery
+        .changed_at
+        .is_none_or(|changed_at| changed_at.elapsed() >= IDLE);
+
+    if !(idle || due) {
+        ctx.request_repaint_after(IDLE);
+        return;
+    }
+
+    recovery.backed_up_at = Some(now);
+
+    for path in mem::take(&mut recovery.pending) {
+        let absolute = absolute(&path);
+
+        // Left alone until the user picks between the two.
+        if recovery
+            .recoverable
+            .iter()
+            .any(|recoverable| recoverable.swap.path == absolute)
+        {
+            continue;
+        }
+
+        match files.get(&path) {
+            Some(file) if !file.is_saved() => {
+                write_swap(&absolute, &file.content);
+                recovery.swapped.insert(path);
+            }
+            _ => {
+                buffers().remove(&absolute);
+                remove_swap(&absolute);
+                recovery.swapped.remove(&path);
+            }
+        }
+    }
+}
+
+enum Action {
+    Recover,
+    Discard,
+}
+
+/// The prompt to recover what an earlier run left unsaved.
+pub fn show(ctx: &Context, state: &mut State) {
+    if state.recovery.recoverable.is_empty() {
+        return;
+    }
+
+    let recovery = &mut state.recovery;
+    let mut open = true;
+    let mut action = None;
+
+    recovery.selected = recovery.selected.min(recovery.recoverable.len() - 1);
+
+    Window::new("Recover Unsaved Changes")
+        .id(Id::new("recovery"))
+        .open(&mut open)
+        .default_size([900.0, 560.0])
+        .resizable(true)
+        .collapsible(false)
+        .show(ctx, |ui| {
+            ui.label(
+                "The editor didn't close properly. These files had changes that weren't saved:",
+            );
+
+            ScrollArea::vertical()
+                .id_source("recoverable")
+                .max_height(120.0)
+                .show(ui, |ui| {
+                    for (index, recoverable) in recovery.recoverable.iter().enumerate() {
+                        let label = match recoverable.disk {
+                            Some(_) => recoverable.swap.path.clone(),
+                            None => format!("{} (deleted)", recoverable.swap.path),
+                        };
+
+                        ui.selectable_value(&mut recovery.selected, index, label);
+                    }
+                });
+
+            ui.separator();
+
+            ui.horizontal(|ui| {
+                if ui.button("Recover").clicked() {
+                    action = Some(Action::Recover);
+                }
+
+                if ui.button("Discard").clicked() {
+                    action = Some(Action::Discard);
+                }
+
+                ui.separator();
+                ui.selectable_value(&mut recovery.layout, DiffLayout::SideBySide, "Side by Side");
+                ui.selectable_value(&mut recovery.layout, DiffLayout::Inline, "Inline");
+            });
+
+            ui.separator();
+
+            let recoverable = &recovery.recoverable[recovery.selected];
+            let highlight = |text: &str| syntax_highlighter::highlight(text.to_string());
+
+            ui.add(
+                DiffView::new(
+                    "recovery-diff",
+                    recoverable.disk.as_deref().unwrap_or_default(),
+                    &recoverable.swap.content,
+                )
+                .layout(recovery.layout)
+                .highlighter(&highlight),
+            );
+        });
+
+    // Closed, the swap files wait for the next launch.
+    if !open {
+        recovery.recoverable.clear();
+        return;
+    }
+
+    let Some(action) = action else {
+        return;
+    };
+
+    let Swap { path, content } = recovery.recoverable.remove(recovery.selected).swap;
+    let store_path = FileStore::store_path(Path::new(&path));
+
+    // Either way, the buffer's own backup takes over the swap file.
+    recovery.pending.insert(store_path.clone());
+
+    match action {
+        Action::Recover => {
+            match state.file_store.files.get_mut(&store_path) {
+                Some(file) => {
+                    file.content = content;
+                    file.version += 1;
+                }
+                None => {
+                    state.file_store.restore(&store_path, Some(content));
+                }
+            }
+
+            state.file_store.set_active_file(&store_path);
+        }
+        Action::Discard => remove_swap(&path),
+    }
+}
@@ -0,0 +1,223 @@
+use egui::{text::LayoutJob, Context, Sense, TextFormat, TextStyle, TopBottomPanel, Ui};
+use egui_extras::{Column, TableBuilder};
+use lsp::{
+    lsp_types::{
+        request, Location, Position, ReferenceContext, ReferenceParams, TextDocumentIdentifier,
+        TextDocumentPositionParams, Url,
+    },
+    position, LspManager, PendingRequest,
+};
+use std::collections::{HashMap, HashSet};
+
+use crate::{editor, file_store::FileData, language_servers, problems, State};
+
+/// The bottom panel listing the results of Find All References, or the
+/// candidates when a symbol has several definitions.
+#[derive(Default)]
+pub struct ReferencesPanel {
+    pub open: bool,
+    title: String,
+    references: Vec<Reference>,
+    request: Option<PendingRequest<request::References>>,
+}
+
+struct Reference {
+    uri: Url,
+    position: Position,
+    file: String,
+    /// The trimmed line, and the reference's byte range in it.
+    line: String,
+    highlight: std::ops::Range<usize>,
+}
+
+/// Asks for the references of the symbol at `offset`; they're listed once
+/// the server answers.
+pub fn find(panel: &mut ReferencesPanel, lsp: &mut LspManager, file: &FileData, offset: usize) {
+    let word = &file.content[editor::word_at(&file.content, offset)];
+
+    let request = language_servers::request::<request::References>(
+        lsp,
+        file,
+        |capabilities| language_servers::enabled(capabilities.references_provider.as_ref()),
+        |uri| ReferenceParams {
+            text_document_position: TextDocumentPositionParams {
+                text_document: TextDocumentIdentifier { uri },
+                position: position::from_offset(&file.content, offset),
+            },
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+            context: ReferenceContext {
+                include_declaration: true,
+            },
+        },
+    );
+
+    if request.is_some() {
+        panel.open = true;
+        panel.title = format!("References to {word}");
+        panel.references.clear();
+        panel.request = request;
+    }
+}
+
+pub fn show_locations(state: &mut State, title: String, mut locations: Vec<Location>) {
+    locations.sort_by(|a, b| {
+        a.uri
+            .as_str()
+            .cmp(b.uri.as_str())
+            .then(a.range.start.cmp(&b.range.start))
+    });
+
+    let root = std::env::current_dir().unwrap_or_default();
+    let mut texts: HashMap<Url, Option<String>> = HashMap::new();
+
+    let references = locations
+        .into_iter()
+        .map(|Location { uri, range }| {
+            let text = texts
+                .entry(uri.clone())
+                .or_insert_with(|| language_servers::document_text(state, &uri));
+            let text = text.as_deref().unwrap_or_default();
+
+            let start = position::to_offset(text, range.start);
+            let line_start = text[..start].rfind('\n').map_or(0, |i| i + 1);
+            let line_end = text[start..].find('\n').map_or(text.len(), |i| start + i);
+            let line = &text[line_start..line_end];
+            let indent = line.len() - line.trim_start().len();
+
+            let end = position::to_offset(text, range.end).min(line_end);
+            let column = |offset: usize| offset.saturating_sub(line_start + indent);
+            let highlight = column(start)..column(end.max(start));
+
+            Reference {
+                file: problems::display_path(&uri, &root),
+                uri,
+                position: range.start,
+                line: line.trim().into(),
+                highlight,
+            }
+        })
+        .collect();
+
+    state.references.references = references;
+    state.references.title = title;
+    state.references.open = true;
+}
+
+pub fn show(ctx: &Context, state: &mut State) {
+    if let Some(result) = state
+        .references
+        .request
+        .as_ref()
+        .and_then(PendingRequest::try_take)
+    {
+        state.references.request = None;
+
+        let title = std::mem::take(&mut state.references.title);
+        show_locations(state, title, result.ok().flatten().unwrap_or_default());
+    }
+
+    if !state.references.open {
+        return;
+    }
+
+    let mut navigate: Option<(Url, Position)> = None;
+
+    TopBottomPanel::bottom("references")
+        .resizable(true)
+        .default_height(180.0)
+        .min_height(80.0)
+        .show(ctx, |ui| {
+            toolbar(ui, &mut state.references);
+            ui.separator();
+
+            let panel = &state.references;
+            let row_height = ui.text_style_height(&TextStyle::Body) + 4.0;
+
+            if panel.request.is_some() {
+                ui.weak("Searching…");
+                return;
+            }
+
+            if panel.references.is_empty() {
+                ui.weak("No results.");
+                return;
+            }
+
+            TableBuilder::new(ui)
+                .striped(true)
+                .sense(Sense::click())
+                .resizable(true)
+                .auto_shrink(false)
+                .column(Column::initial(260.0).clip(true))
+                .column(Column::remainder().at_least(200.0).clip(true))
+                .body(|body| {
+                    body.rows(row_height, panel.references.len(), |mut table_row| {
+                        let reference = &panel.references[table_row.index()];
+
+                        table_row.col(|ui| {
+                            let Position { line, character } = reference.position;
+                            ui.label(format!("{}:{}:{}", reference.file, line + 1, character + 1));
+                        });
+                        table_row.col(|ui| {
+                            preview(ui, reference);
+                        });
+
+                        if table_row.response().clicked() {
+                            navigate = Some((reference.uri.clone(), reference.position));
+                        }
+                    });
+                });
+        });
+
+    if let Some((uri, position)) = navigate {
+        language_servers::open_location(ctx, state, &uri, position);
+    }
+}
+
+fn toolbar(ui: &mut Ui, panel: &mut ReferencesPanel) {
+    ui.horizontal(|ui| {
+        ui.strong(&panel.title);
+
+        if panel.request.is_none() {
+            let files: HashSet<&Url> = panel.references.iter().map(|r| &r.uri).collect();
+            ui.weak(format!(
+                "{} results in {} files",
+                panel.references.len(),
+                files.len()
+            ));
+        }
+
+        ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+            if ui.small_button("x").clicked() {
+                panel.open = false;
+            }
+        });
+    });
+}
+
+/// The reference's line with the reference itself in bold.
+fn preview(ui: &mut Ui, reference: &Reference) {
+    let font_id = TextStyle::Monospace.resolve(ui.style());
+    let color = ui.visuals().weak_text_color();
+    let strong = ui.visuals().strong_text_color();
+    let line = &reference.line;
+    let highlight =
+        reference.highlight.start.min(line.len())..reference.highlight.end.min(line.len());
+
+    let mut job = LayoutJob::default();
+
+    for (range, color) in [
+        (0..highlight.start, color),
+        (highlight.clone(), strong),
+        (highlight.end..line.len(), color),
+    ] {
+        job.append(
+            line.get(range).unwrap_or_default(),
+            0.0,
+            TextFormat::simple(font_id.clone(), color),
+        );
+    }
+
+    ui.label(job);
+}
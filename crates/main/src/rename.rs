@@ -0,0 +1,565 @@
+//! F2 rename: an input box over the symbol, then a preview of every change
+//! the server's `WorkspaceEdit` makes before it's applied.
+
+use egui::{
+    text_edit::TextEditOutput, Align2, Area, CollapsingHeader, Color32, Context, Frame, Id, Key,
+    KeyboardShortcut, Modifiers, Order, RichText, ScrollArea, TextEdit, Ui, Window,
+};
+use lsp::{
+    edit,
+    lsp_types::{
+        request, PrepareRenameResponse, RenameParams, TextDocumentIdentifier,
+        TextDocumentPositionParams, TextEdit as LspTextEdit, Url, WorkspaceEdit,
+    },
+    position, LspManager, PendingRequest,
+};
+use std::{
+    mem,
+    ops::Range,
+    path::{Path, PathBuf},
+};
+use theme::Theme;
+
+use crate::{
+    editor, editor_groups, file_store::FileData, file_utils, language_servers, navigation,
+    problems, State,
+};
+
+pub const SHORTCUT: KeyboardShortcut = KeyboardShortcut::new(Modifiers::NONE, Key::F2);
+
+#[derive(Default)]
+pub struct Rename {
+    input: Option<Input>,
+    preview: Option<Preview>,
+}
+
+/// The box where the new name is typed.
+struct Input {
+    id: Id,
+    path: String,
+    version: i32,
+    /// The symbol being renamed.
+    range: Range<usize>,
+    name: String,
+    prepare: Option<PendingRequest<request::PrepareRenameRequest>>,
+    request: Option<PendingRequest<request::Rename>>,
+    error: Option<String>,
+    focus: bool,
+}
+
+struct Preview {
+    name: String,
+    files: Vec<FileChange>,
+    /// Files that couldn't be changed on the last attempt to apply.
+    errors: Vec<String>,
+}
+
+struct FileChange {
+    uri: Url,
+    display: String,
+    /// The content the edits were computed against.
+    original: String,
+    edits: Vec<LspTextEdit>,
+    hunks: Vec<Hunk>,
+    include: bool,
+}
+
+/// Consecutive changed lines, before and after.
+struct Hunk {
+    line: usize,
+    old: String,
+    new: String,
+}
+
+/// F2 while the editor has focus.
+pub fn handle_input(ctx: &Context, state: &mut State) {
+    let id = state.file_store.get_active_file_id().into();
+
+    if ctx.memory(|memory| memory.has_focus(id))
+        && ctx.input_mut(|input| input.consume_shortcut(&SHORTCUT))
+    {
+        start(ctx, state);
+    }
+}
+
+/// Opens the input box on the symbol under the cursor, asking the server
+/// whether and what it can rename first when it supports that.
+pub fn start(ctx: &Context, state: &mut State) {
+    let id = state.file_store.get_active_file_id().into();
+
+    let Some(file) = state.file_store.get_active_file() else {
+        return;
+    };
+
+    let selection = editor::get_selection(ctx, id, file);
+    let offset = editor::byte_offset(&file.content, selection.primary.index);
+    let range = editor::word_at(&file.content, offset);
+
+    let prepare = language_servers::request::<request::PrepareRenameRequest>(
+        &mut state.lsp,
+        file,
+        |capabilities| {
+            matches!(
+                &capabilities.rename_provider,
+                Some(lsp::lsp_types::OneOf::Right(options)) if options.prepare_provider == Some(true)
+            )
+        },
+        |uri| TextDocumentPositionParams {
+            text_document: TextDocumentIdentifier { uri },
+            position: position::from_offset(&file.content, offset),
+        },
+    );
+
+    if range.is_empty() && prepare.is_none() {
+        return;
+    }
+
+    state.rename.input = Some(Input {
+        id,
+        path: file.path.clone(),
+        version: file.version,
+        name: file.content[range.clone()].into(),
+        range,
+        prepare,
+        request: None,
+        error: None,
+        focus: true,
+    });
+}
+
+/// Runs after the editor's `TextEdit` and draws the input box under the
+/// symbol.
+pub fn show_input(
+    ui: &Ui,
+    id: Id,
+    file: &FileData,
+    rename: &mut Rename,
+    lsp: &mut LspManager,
+    output: &TextEditOutput,
+    theme: &Theme,
+) {
+    let Some(input) = rename.input.as_mut().filter(|input| input.id == id) else {
+        return;
+    };
+
+    // The symbol moved under the box.
+    if input.version != file.version {
+        rename.input = None;
+        return;
+    }
+
+    let Some(rect) = navigation::word_rect(output, &file.content, input.range.clone()) else {
+        return;
+    };
+
+    let ctx = ui.ctx();
+    let mut close = false;
+
+    Area::new(id.with("rename"))
+        .order(Order::Foreground)
+        .pivot(Align2::LEFT_TOP)
+        .fixed_pos(rect.left_bottom())
+        .show(ctx, |ui| {
+            Frame::popup(ui.style()).show(ui, |ui| {
+                ui.set_width(280.0);
+
+                let response = ui.add_enabled(
+                    input.prepare.is_none() && input.request.is_none(),
+                    TextEdit::singleline(&mut input.name).desired_width(f32::INFINITY),
+                );
+
+                if input.focus && input.prepare.is_none() {
+                    response.request_focus();
+                    input.focus = false;
+                }
+
+                let submitted =
+                    response.lost_focus() && ui.input(|input| input.key_pressed(Key::Enter));
+                let cancelled = ui.input(|input| input.key_pressed(Key::Escape));
+
+                if cancelled {
+                    close = true;
+                } else if submitted && !input.name.is_empty() {
+                    input.error = None;
+                    input.request = request_rename(lsp, file, input.range.start, &input.name);
+
+                    if input.request.is_none() {
+                        input.error = Some("No language server can rename this symbol.".into());
+                    }
+                }
+
+                match &input.error {
+                    Some(error) => {
+                        ui.label(RichText::new(error).color(theme.error.main));
+                    }
+                    None if input.request.is_some() => {
+                        ui.weak("Renaming…");
+                    }
+                    None => {
+                        ui.weak("Enter to preview, Escape to cancel");
+                    }
+                }
+            });
+        });
+
+    if close {
+        rename.input = None;
+        ctx.memory_mut(|memory| memory.request_focus(id));
+    }
+}
+
+fn request_rename(
+    lsp: &mut LspManager,
+    file: &FileData,
+    offset: usize,
+    name: &str,
+) -> Option<PendingRequest<request::Rename>> {
+    language_servers::request::<request::Rename>(
+        lsp,
+        file,
+        |capabilities| language_servers::enabled(capabilities.rename_provider.as_ref()),
+        |uri| RenameParams {
+            text_document_position: TextDocumentPositionParams {
+                text_document: TextDocumentIdentifier { uri },
+                position: position::from_offset(&file.content, offset),
+            },
+            new_name: name.into(),
+            work_done_progress_params: Default::default(),
+        },
+    )
+}
+
+/// Collects the server's answers and draws the preview window.
+pub fn show(ctx: &Context, state: &mut State) {
+    poll(state);
+
+    let Some(preview) = &mut state.rename.preview else {
+        return;
+    };
+
+    let mut open = true;
+    let mut apply = false;
+    let mut cancel = false;
+    let theme = &state.theme;
+
+    Window::new("Rename Preview")
+        .open(&mut open)
+        .collapsible(false)
+        .default_size([640.0, 420.0])
+        .show(ctx, |ui| {
+            let changes: usize = preview.files.iter().map(|file| file.edits.len()).sum();
+
+            ui.label(format!(
+                "Renaming to {} makes {changes} changes in {} files.",
+                preview.name,
+                preview.files.len()
+            ));
+
+            for error in &preview.errors {
+                ui.label(RichText::new(error).color(theme.error.main));
+            }
+
+            ui.separator();
+
+            ScrollArea::vertical()
+                .auto_shrink([false, true])
+                .max_height(ui.available_height() - 40.0)
+                .show(ui, |ui| {
+                    for file in &mut preview.files {
+                        file_changes(ui, file, theme);
+                    }
+                });
+
+            ui.separator();
+
+            ui.horizontal(|ui| {
+                let included = preview.files.iter().any(|file| file.include);
+
+                if ui
+                    .add_enabled(included, egui::Button::new("Apply"))
+                    .clicked()
+                {
+                    apply = true;
+                }
+
+                if ui.button("Cancel").clicked() {
+                    cancel = true;
+                }
+            });
+        });
+
+    if apply {
+        apply_preview(ctx, state);
+    } else if !open || cancel {
+        state.rename.preview = None;
+    }
+}
+
+fn poll(state: &mut State) {
+    let Some(input) = &mut state.rename.input else {
+        return;
+    };
+
+    if let Some(result) = input.prepare.as_ref().and_then(PendingRequest::try_take) {
+        input.prepare = None;
+
+        let text = state
+            .file_store
+            .files
+            .get(&input.path)
+            .map_or("", |file| file.content.as_str());
+
+        match result {
+            Ok(Some(PrepareRenameResponse::Range(range))) => {
+                input.range = position::to_range(text, range);
+                input.name = text[input.range.clone()].into();
+            }
+            Ok(Some(PrepareRenameResponse::RangeWithPlaceholder { range, placeholder })) => {
+                input.range = position::to_range(text, range);
+                input.name = placeholder;
+            }
+            Ok(Some(PrepareRenameResponse::DefaultBehavior { .. })) => {}
+            Ok(None) => input.error = Some("This symbol can't be renamed.".into()),
+            Err(error) => input.error = Some(error.to_string()),
+        }
+
+        // Nothing to rename and nothing to explain.
+        if input.range.is_empty() && input.error.is_none() {
+            state.rename.input = None;
+        }
+
+        return;
+    }
+
+    let Some(result) = input.request.as_ref().and_then(PendingRequest::try_take) else {
+        return;
+    };
+
+    input.request = None;
+
+    match result {
+        Ok(Some(edit)) => {
+            let name = input.name.clone();
+
+            state.rename.input = None;
+            state.rename.preview = Some(preview(state, name, edit));
+        }
+        Ok(None) => input.error = Some("Nothing to rename.".into()),
+        Err(error) => input.error = Some(error.to_string()),
+    }
+}
+
+fn preview(state: &mut State, name: String, edit: WorkspaceEdit) -> Preview {
+    let root = std::env::current_dir().unwrap_or_default();
+    let mut errors = Vec::new();
+
+    let files = edit::workspace_changes(edit)
+        .into_iter()
+        .filter_map(|(uri, edits)| {
+            let display = problems::display_path(&uri, &root);
+
+            let Some(original) = language_servers::document_text(state, &uri) else {
+                errors.push(format!("Couldn't read {display}."));
+                return None;
+            };
+
+            Some(FileChange {
+                hunks: hunks(&original, &edits),
+                uri,
+                display,
+                original,
+                edits,
+                include: true,
+            })
+        })
+        .collect();
+
+    Preview {
+        name,
+        files,
+        errors,
+    }
+}
+
+fn file_changes(ui: &mut Ui, file: &mut FileChange, theme: &Theme) {
+    ui.horizontal(|ui| {
+        ui.checkbox(&mut file.include, "");
+
+        CollapsingHeader::new(format!("{} ({})", file.display, file.edits.len()))
+            .id_source(file.uri.as_str())
+            .default_open(true)
+            .show(ui, |ui| {
+                for hunk in &file.hunks {
+                    diff_lines(ui, hunk.line, &hunk.old, '-', theme.error.dark);
+                    diff_lines(ui, hunk.line, &hunk.new, '+', theme.success.dark);
+                }
+            });
+    });
+}
+
+fn diff_lines(ui: &mut Ui, first_line: usize, text: &str, sign: char, background: Color32) {
+    for (index, line) in text.split('\n').enumerate() {
+        let text = format!("{:>5} {sign} {line}", first_line + index + 1);
+
+        ui.label(
+            RichText::new(text)
+                .monospace()
+                .background_color(background.gamma_multiply(0.4)),
+        );
+    }
+}
+
+/// A byte range of the original text and what replaces it.
+type Replacement<'a> = (Range<usize>, &'a str);
+
+/// Groups the edits by the lines they touch, with every line shown whole.
+fn hunks(text: &str, edits: &[LspTextEdit]) -> Vec<Hunk> {
+    let mut ranges: Vec<Replacement> = edits
+        .iter()
+        .map(|edit| (position::to_range(text, edit.range), edit.new_text.as_str()))
+        .collect();
+    ranges.sort_by_key(|(range, _)| range.start);
+
+    let mut groups: Vec<(Range<usize>, Vec<Replacement>)> = Vec::new();
+
+    for (range, new_text) in ranges {
+        let line_start = text[..range.start].rfind('\n').map_or(0, |i| i + 1);
+        let line_end = text[range.end..]
+            .find('\n')
+            .map_or(text.len(), |i| range.end + i);
+
+        match groups.last_mut() {
+            Some((lines, edits)) if lines.end >= line_start => {
+                lines.end = lines.end.max(line_end);
+                edits.push((range, new_text));
+            }
+            _ => groups.push((line_start..line_end, vec![(range, new_text)])),
+        }
+    }
+
+    groups
+        .into_iter()
+        .map(|(lines, edits)| {
+            let mut new = text[lines.clone()].to_string();
+
+            for (range, new_text) in edits.iter().rev() {
+                new.replace_range(range.start - lines.start..range.end - lines.start, new_text);
+            }
+
+            Hunk {
+                line: editor::row_of(text, lines.start),
+                old: text[lines].into(),
+                new,
+            }
+        })
+        .collect()
+}
+
+/// Applies the included changes: open buffers as one undo step each, other
+/// files straight on disk. Files changed since the preview are left alone.
+fn apply_preview(ctx: &Context, state: &mut State) {
+    let Some(mut preview) = state.rename.preview.take() else {
+        return;
+    };
+
+    preview.errors.clear();
+
+    let mut failed = Vec::new();
+
+    for (index, change) in preview.files.iter().enumerate() {
+        if !change.include {
+            continue;
+        }
+
+        let open = state
+            .file_store
+            .files
+            .keys()
+            .find(|path| state.lsp.uri(Path::new(path)).as_ref() == Some(&change.uri))
+            .cloned();
+
+        let result = match open {
+            Some(path) => apply_to_buffer(ctx, state, &path, change),
+            None => apply_to_disk(change),
+        };
+
+        if let Err(error) = result {
+            preview.errors.push(format!("{}: {error}", change.display));
+            failed.push(index);
+        }
+    }
+
+    // Shown again with only the files that failed, to be tried again.
+    if !failed.is_empty() {
+        preview.files = mem::take(&mut preview.files)
+            .into_iter()
+            .enumerate()
+            .filter_map(|(index, change)| failed.contains(&index).then_some(change))
+            .collect();
+        state.rename.preview = Some(preview);
+    }
+}
+
+fn apply_to_buffer(
+    ctx: &Context,
+    state: &mut State,
+    path: &str,
+    change: &FileChange,
+) -> Result<(), String> {
+    let ids: Vec<Id> = state
+        .file_store
+        .groups
+        .groups
+        .iter()
+        .filter(|group| group.tabs.iter().any(|tab| tab == path))
+        .map(|group| editor_groups::editor_id(group.id, path).into())
+        .collect();
+    let file = state.file_store.files.get_mut(path).unwrap();
+
+    if file.content != change.original {
+        return Err("changed since the preview, left alone".into());
+    }
+
+    let content = edit::apply(&file.content, &change.edits);
+
+    if ids.is_empty() {
+        file.content = content;
+        file.version += 1;
+        return Ok(());
+    }
+
+    // Every editor showing the file gets the rename as its own undo step.
+    for id in ids {
+        file.content.clone_from(&change.original);
+
+        let selection = editor::get_selection(ctx, id, file);
+        let [start, end] = [selection.secondary, selection.primary]
+            .map(|cursor| editor::byte_offset(&file.content, cursor.index))
+            .map(|offset| edit::map_offset(&file.content, &change.edits, offset));
+        let selection = editor::char_range(&content, start..end);
+
+        editor::apply_edit(ctx, id, file, content.clone(), selection);
+    }
+
+    Ok(())
+}
+
+fn apply_to_disk(change: &FileChange) -> Result<(), String> {
+    let path: PathBuf = change
+        .uri
+        .to_file_path()
+        .map_err(|_| "not a local file".to_string())?;
+    let path = path.to_str().ok_or("not a local file")?;
+    // In the file's own encoding and line endings.
+    let mut file = FileData::read(path, None).map_err(|error| error.to_string())?;
+
+    if file.content != change.original {
+        return Err("changed since the preview, left alone".into());
+    }
+
+    file.content = edit::apply(&file.content, &change.edits);
+
+    let bytes = file.to_disk().map_err(|error| error.to_string())?;
+
+    file_utils::write_atomic(Path::new(path), &bytes).map_err(|error| error.to_string())
+}
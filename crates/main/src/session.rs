@@ -0,0 +1,383 @@
+//! The session: the workspace, its editor groups and tabs, where the cursor
+//! and scroll were in each, the side panel widths, the edits not saved yet
+//! and the save and formatting settings. Written now and then and on close,
+//! one file per workspace, and read back the next time the editor starts in
+//! it.
+
+use egui::{
+    containers::panel::PanelState,
+    text::{CCursor, CCursorRange},
+    text_edit::TextEditState,
+    Context, Id,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    env, fs,
+    hash::{DefaultHasher, Hash, Hasher},
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use crate::{autosave, editor, editor_groups, file_utils, State};
+
+/// In the editor's state folder, a session per workspace.
+const SESSIONS_DIR: &str = "sessions";
+
+/// How often the session is written while the editor runs.
+const SAVE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// The side panels whose widths are kept.
+const PANELS: [&str; 2] = ["file_explorer", "source_control"];
+
+#[derive(Default)]
+pub struct Session {
+    restored: bool,
+    /// What was last written, to only write changes.
+    written: String,
+    saved_at: Option<Instant>,
+    /// The scroll offset of every editor, by editor id.
+    scroll: HashMap<String, f32>,
+    /// Offsets read back and not applied yet.
+    pending_scroll: HashMap<String, f32>,
+    panels: BTreeMap<String, f32>,
+}
+
+impl Session {
+    /// The offset to scroll the editor to, once, after a restore.
+    pub fn restored_scroll(&mut self, editor_id: &str) -> Option<f32> {
+        self.pending_scroll.remove(editor_id)
+    }
+
+    pub fn scrolled(&mut self, editor_id: String, offset: f32) {
+        self.scroll.insert(editor_id, offset);
+    }
+
+    /// Keeps the session on disk from being read back: the state comes
+    /// from elsewhere.
+    pub fn skip_restore(&mut self) {
+        self.restored = true;
+    }
+
+    /// The width the panel had last time, `default` the first time.
+    pub fn panel_width(&self, panel: &str, default: f32) -> f32 {
+        self.panels.get(panel).copied().unwrap_or(default)
+    }
+}
+
+/// Sorted maps, so an unchanged session serializes the same. Fields added
+/// later read as their default from older sessions.
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+struct Saved {
+    root: PathBuf,
+    groups: editor_groups::EditorGroups,
+    /// The content of the files with unsaved edits.
+    unsaved: BTreeMap<String, String>,
+    editors: Vec<SavedEditor>,
+    panels: BTreeMap<String, f32>,
+    autosave: autosave::AutoSaveMode,
+    /// The languages formatted on save.
+    format_on_save: BTreeSet<String>,
+    /// The formatter commands set by language.
+    formatters: BTreeMap<String, String>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SavedEditor {
+    group: u64,
+    path: String,
+    /// The selection as char indices, the end it was made from first.
+    selection: (usize, usize),
+    scroll: f32,
+}
+
+/// Where the editor keeps what outlives a run, in the user's state folder.
+pub fn state_dir() -> Option<PathBuf> {
+    let user_state_dir = env::var_os("XDG_STATE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".local/state")))
+        .or_else(|| env::var_os("APPDATA").map(PathBuf::from))?;
+
+    Some(user_state_dir.join("rust-editor"))
+}
+
+/// The workspace: the folder the editor was started in.
+fn workspace_root() -> Option<PathBuf> {
+    env::current_dir().ok()?.canonicalize().ok()
+}
+
+fn session_file(root: &Path) -> Option<PathBuf> {
+    let mut hasher = DefaultHasher::new();
+
+    root.hash(&mut hasher);
+    Some(
+        state_dir()?
+            .join(SESSIONS_DIR)
+            .join(format!("{:016x}.json", hasher.finish())),
+    )
+}
+
+/// Reads the last session back, on the first frame.
+pub fn restore(ctx: &Context, state: &mut State) {
+    if state.session.restored {
+        return;
+    }
+
+    state.session.restored = true;
+
+    let file = workspace_root().and_then(|root| session_file(&root));
+
+    if let Some(json) = file.and_then(|file| fs::read_to_string(file).ok()) {
+        apply(ctx, state, &json);
+    }
+}
+
+/// Opens the tabs and files of a session in `json`. Returns whether it
+/// could be read; a session of another workspace isn't.
+pub fn apply(ctx: &Context, state: &mut State, json: &str) -> bool {
+    state.session.restored = true;
+
+    let Ok(saved) = serde_json::from_str::<Saved>(json) else {
+        return false;
+    };
+
+    if saved.root.canonicalize().ok() != workspace_root() {
+        return false;
+    }
+
+    let mut unsaved = saved.unsaved;
+    let mut groups = saved.groups;
+    let mut open: Vec<String> = groups
+        .groups
+        .iter()
+        .flat_map(|group| group.tabs.iter().cloned())
+        .collect();
+    open.sort();
+    open.dedup();
+
+    let file_store = &mut state.file_store;
+    open.retain(|path| file_store.restore(path, unsaved.remove(path)));
+
+    groups.retain(|path| open.iter().any(|open| open == path));
+    file_store.active_file = groups.focused().active.clone();
+    file_store.groups = groups;
+
+    for saved_editor in saved.editors {
+        let Some(file) = file_store.files.get(&saved_editor.path) else {
+            continue;
+        };
+
+        let editor_id = editor_groups::editor_id(saved_editor.group, &saved_editor.path);
+        let id = Id::new(editor_id.as_str());
+        let chars = file.content.chars().count();
+        let (secondary, primary) = saved_editor.selection;
+        let mut text_edit = TextEditState::load(ctx, id).unwrap_or_default();
+
+        text_edit.cursor.set_char_range(Some(CCursorRange::two(
+            CCursor::new(secondary.min(chars)),
+            CCursor::new(primary.min(chars)),
+        )));
+        text_edit.store(ctx, id);
+
+        state
+            .session
+            .scroll
+            .insert(editor_id.clone(), saved_editor.scroll);
+        state
+            .session
+            .pending_scroll
+            .insert(editor_id, saved_editor.scroll);
+    }
+
+    state.session.panels = saved.panels;
+    state.autosave.mode = saved.autosave;
+    state.formatting.format_on_save = saved.format_on_save;
+    state.formatting.commands = saved.formatters;
+    true
+}
+
+/// Writes the session every few seconds and when the window closes, if
+/// anything changed.
+pub fn save(ctx: &Context, state: &mut State) {
+    let closing = ctx.input(|input| input.viewport().close_requested());
+    let due = state
+        .session
+        .saved_at
+        .is_none_or(|saved_at| saved_at.elapsed() >= SAVE_INTERVAL);
+
+    if !(closing || due) {
+        return;
+    }
+
+    state.session.saved_at = Some(Instant::now());
+
+    let Some(file) = workspace_root().and_then(|root| session_file(&root)) else {
+        return;
+    };
+    let Some(json) = capture(ctx, state) else {
+        return;
+    };
+
+    if json == state.session.written {
+        return;
+    }
+
+    if let Some(folder) = file.parent() {
+        let _ = fs::create_dir_all(folder);
+    }
+
+    // A session cut short would lose the unsaved edits: the swap files are
+    // gone once the window closes.
+    if file_utils::write_atomic(&file, json.as_bytes()).is_ok() {
+        state.session.written = json;
+    }
+}
+
+/// The session as JSON, for [`apply`].
+pub fn capture(ctx: &Context, state: &State) -> Option<String> {
+    let root = workspace_root()?;
+
+    let file_store = &state.file_store;
+    let mut editors = Vec::new();
+
+    for group in &file_store.groups.groups {
+        for path in &group.tabs {
+            let Some(file) = file_store.files.get(path) else {
+                continue;
+            };
+
+            let editor_id = editor_groups::editor_id(group.id, path);
+            let selection = editor::get_selection(ctx, Id::new(editor_id.as_str()), file);
+
+            editors.push(SavedEditor {
+                group: group.id,
+                path: path.clone(),
+                selection: (selection.secondary.index, selection.primary.index),
+                scroll: state.session.scroll.get(&editor_id).copied().unwrap_or(0.0),
+            });
+        }
+    }
+
+    let mut panels = state.session.panels.clone();
+
+    for panel in PANELS {
+        if let Some(panel_state) = PanelState::load(ctx, Id::new(panel)) {
+            panels.insert(panel.into(), panel_state.size().x);
+        }
+    }
+
+    let saved = Saved {
+        root,
+        groups: file_store.groups.clone(),
+        unsaved: file_store
+            .files
+            .values()
+            .filter(|file| !file.is_saved())
+            .map(|file| (file.path.clone(), file.content.clone()))
+            .collect(),
+        editors,
+        panels,
+        autosave: state.autosave.mode,
+        format_on_save: state.formatting.format_on_save.clone(),
+        formatters: state.formatting.commands.clone(),
+    };
+
+    serde_json::to_string(&saved).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tests run in the crate's folder, the workspace here.
+    const CARGO_TOML: &str = "./Cargo.toml";
+    const SESSION_RS: &str = "./src/session.rs";
+
+    fn select(ctx: &Context, state: &State, (secondary, primary): (usize, usize)) {
+        let id = Id::new(state.file_store.get_active_file_id());
+        let mut text_edit = TextEditState::default();
+
+        text_edit.cursor.set_char_range(Some(CCursorRange::two(
+            CCursor::new(secondary),
+            CCursor::new(primary),
+        )));
+        text_edit.store(ctx, id);
+    }
+
+    fn selection(ctx: &Context, state: &State) -> (usize, usize) {
+        let id = Id::new(state.file_store.get_active_file_id());
+        let file = state.file_store.get_active_file().unwrap();
+        let selection = editor::get_selection(ctx, id, file);
+
+        (selection.secondary.index, selection.primary.index)
+    }
+
+    #[test]
+    fn round_trips_tabs_edits_and_settings() {
+        let ctx = Context::default();
+        let mut state = State::default();
+
+        state.file_store.insert(&CARGO_TOML.into(), true);
+        state.file_store.insert(&SESSION_RS.into(), true);
+        let file = state.file_store.get_active_file_as_mut().unwrap();
+
+        file.content.push_str("// Not saved.\n");
+        file.version += 1;
+        select(&ctx, &state, (3, 10));
+        state.autosave.mode = autosave::AutoSaveMode::OnFocusChange;
+        state.formatting.format_on_save.insert("rust".into());
+        state
+            .formatting
+            .commands
+            .insert("rust".into(), "rustfmt --edition 2024".into());
+
+        let json = capture(&ctx, &state).unwrap();
+        let restored_ctx = Context::default();
+        let mut restored = State::default();
+
+        assert!(apply(&restored_ctx, &mut restored, &json));
+        assert_eq!(
+            restored.file_store.groups.focused().tabs,
+            [CARGO_TOML, SESSION_RS]
+        );
+        assert_eq!(restored.file_store.active_file, SESSION_RS);
+        assert!(restored.file_store.files[SESSION_RS]
+            .content
+            .ends_with("// Not saved.\n"));
+        assert!(restored.file_store.files[CARGO_TOML].is_saved());
+        assert_eq!(selection(&restored_ctx, &restored), (3, 10));
+        assert_eq!(restored.autosave.mode, state.autosave.mode);
+        assert_eq!(
+            restored.formatting.format_on_save,
+            state.formatting.format_on_save
+        );
+        assert_eq!(restored.formatting.commands, state.formatting.commands);
+        assert_eq!(capture(&restored_ctx, &restored), Some(json));
+    }
+
+    #[test]
+    fn leaves_other_workspaces_sessions_alone() {
+        let ctx = Context::default();
+        let mut state = State::default();
+
+        state.file_store.insert(&CARGO_TOML.into(), true);
+
+        let mut saved: serde_json::Value =
+            serde_json::from_str(&capture(&ctx, &state).unwrap()).unwrap();
+        saved["root"] = env::temp_dir().to_string_lossy().into();
+
+        let mut restored = State::default();
+
+        assert!(!apply(&ctx, &mut restored, &saved.to_string()));
+        assert!(restored.file_store.files.is_empty());
+    }
+
+    #[test]
+    fn keeps_a_session_per_workspace() {
+        let here = workspace_root().unwrap();
+
+        assert_ne!(session_file(&here), session_file(&env::temp_dir()));
+        assert_eq!(session_file(&here), session_file(&here));
+    }
+}
@@ -0,0 +1,557 @@
+//! LSP snippet syntax: `$1`, `${1:placeholder}`, `${1|one,two|}`, `$0` and
+//! variables such as `$TM_FILENAME`.
+
+use std::{iter::Peekable, ops::Range, str::Chars};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tabstop {
+    pub index: u32,
+    /// Where the stop and its mirrors ended up in the expanded text.
+    pub ranges: Vec<Range<usize>>,
+    pub choices: Vec<String>,
+}
+
+impl Tabstop {
+    /// The empty `$0` stop at `offset`, where the cursor ends up.
+    pub fn end(offset: usize) -> Self {
+        let range = offset..offset;
+
+        Self {
+            index: 0,
+            ranges: vec![range],
+            choices: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snippet {
+    pub text: String,
+    /// In the order they're visited, `$0` last.
+    pub tabstops: Vec<Tabstop>,
+}
+
+/// Expands `source`, indenting every line after the first with
+/// `line_prefix` and turning tabs into `indent_unit`. Variables are looked up
+/// with `variable`; unknown ones are replaced by their name.
+pub fn parse(
+    source: &str,
+    line_prefix: &str,
+    indent_unit: &str,
+    variable: impl Fn(&str) -> Option<String>,
+) -> Snippet {
+    let mut parser = Parser {
+        chars: source.chars().peekable(),
+        text: String::new(),
+        tabstops: Vec::new(),
+        line_prefix,
+        indent_unit,
+        variable: &variable,
+    };
+
+    parser.parse_any(&[]);
+
+    let Parser {
+        mut text,
+        mut tabstops,
+        ..
+    } = parser;
+
+    fill_mirrors(&mut text, &mut tabstops);
+
+    if !tabstops.iter().any(|tabstop| tabstop.index == 0) {
+        tabstops.push(Tabstop::end(text.len()));
+    }
+
+    // The first occurrence of a stop is the one that gets selected.
+    for tabstop in &mut tabstops {
+        tabstop.ranges.sort_by_key(|range| range.start);
+    }
+
+    tabstops.sort_by_key(|tabstop| (tabstop.index == 0, tabstop.index));
+
+    Snippet { text, tabstops }
+}
+
+/// Gives empty mirrors like the second `$1` in `${1:i} < $1` the text of
+/// their placeholder.
+fn fill_mirrors(text: &mut String, tabstops: &mut [Tabstop]) {
+    loop {
+        let empty = tabstops.iter().enumerate().find_map(|(index, tabstop)| {
+            let value = tabstop.ranges.iter().find(|range| !range.is_empty())?;
+            let mirror = tabstop.ranges.iter().position(Range::is_empty)?;
+
+            Some((index, mirror, text[value.clone()].to_string()))
+        });
+
+        let Some((index, mirror, value)) = empty else {
+            return;
+        };
+
+        let offset = tabstops[index].ranges[mirror].start;
+        text.insert_str(offset, &value);
+
+        for (other, tabstop) in tabstops.iter_mut().enumerate() {
+            for (position, range) in tabstop.ranges.iter_mut().enumerate() {
+                if other == index && position == mirror {
+                    range.end += value.len();
+                } else if range.start >= offset {
+                    *range = range.start + value.len()..range.end + value.len();
+                } else if range.end >= offset {
+                    range.end += value.len();
+                }
+            }
+        }
+    }
+}
+
+struct Parser<'a, F: Fn(&str) -> Option<String>> {
+    chars: Peekable<Chars<'a>>,
+    text: String,
+    tabstops: Vec<Tabstop>,
+    line_prefix: &'a str,
+    indent_unit: &'a str,
+    variable: &'a F,
+}
+
+impl<F: Fn(&str) -> Option<String>> Parser<'_, F> {
+    /// Parses until one of `terminators` or the end of the source.
+    fn parse_any(&mut self, terminators: &[char]) {
+        while let Some(&char) = self.chars.peek() {
+            if terminators.contains(&char) {
+                return;
+            }
+
+            self.chars.next();
+
+            match char {
+                '\\' => match self.chars.peek() {
+                    Some(&escaped @ ('$' | '}' | '\\')) => {
+                        self.chars.next();
+                        self.push(escaped);
+                    }
+                    _ => self.push('\\'),
+                },
+                '$' => self.parse_dollar(),
+                _ => self.push(char),
+            }
+        }
+    }
+
+    fn push(&mut self, char: char) {
+        match char {
+            '\n' => {
+                self.text.push('\n');
+                self.text.push_str(self.line_prefix);
+            }
+            '\t' => self.text.push_str(self.indent_unit),
+            _ => self.text.push(char),
+        }
+    }
+
+    fn push_str(&mut self, text: &str) {
+        for char in text.chars() {
+            self.push(char);
+        }
+    }
+
+    fn parse_dollar(&mut self) {
+        match self.chars.peek() {
+            Some(char) if char.is_ascii_digit() => {
+                let index = self.parse_int();
+                self.add_tabstop(index, self.text.len()..self.text.len(), Vec::new());
+            }
+            Some(char) if is_variable_start(*char) => {
+                let name = self.parse_name();
+                self.insert_variable(&name);
+            }
+            Some('{') => {
+                self.chars.next();
+                self.parse_braced();
+            }
+            _ => self.push('$'),
+        }
+    }
+
+    /// Everything after `${`.
+    fn parse_braced(&mut self) {
+        match self.chars.peek() {
+            Some(char) if char.is_ascii_digit() => {
+                let index = self.parse_int();
+                let start = self.text.len();
+
+                match self.chars.next() {
+                    Some(':') => {
+                        self.parse_any(&['}']);
+                        self.chars.next();
+                        self.add_tabstop(index, start..self.text.len(), Vec::new());
+                    }
+                    Some('|') => {
+                        let choices = self.parse_choices();
+                        let first = choices.first().cloned().unwrap_or_default();
+
+                        self.push_str(&first);
+                        self.add_tabstop(index, start..self.text.len(), choices);
+                    }
+                    Some('/') => {
+                        self.skip_transform();
+                        self.add_tabstop(index, start..start, Vec::new());
+                    }
+                    _ => self.add_tabstop(index, start..start, Vec::new()),
+                }
+            }
+            Some(char) if is_variable_start(*char) => {
+                let name = self.parse_name();
+
+                match self.chars.next() {
+                    Some(':') => {
+                        let start = self.text.len();
+
+                        self.parse_any(&['}']);
+                        self.chars.next();
+
+                        // The default, which may contain tab stops, is only
+                        // kept when the variable has no value.
+                        if let Some(value) = self.resolve(&name) {
+                            self.text.truncate(start);
+                            self.tabstops.retain_mut(|tabstop| {
+                                tabstop.ranges.retain(|range| range.start < start);
+                                !tabstop.ranges.is_empty()
+                            });
+                            self.push_str(&value);
+                        }
+                    }
+                    Some('/') => {
+                        self.skip_transform();
+                        self.insert_variable(&name);
+                    }
+                    _ => self.insert_variable(&name),
+                }
+            }
+            _ => self.text.push_str("${"),
+        }
+    }
+
+    fn parse_int(&mut self) -> u32 {
+        let mut digits = String::new();
+
+        while let Some(char) = self.chars.next_if(char::is_ascii_digit) {
+            digits.push(char);
+        }
+
+        digits.parse().unwrap_or_default()
+    }
+
+    fn parse_name(&mut self) -> String {
+        let mut name = String::new();
+
+        while let Some(char) = self
+            .chars
+            .next_if(|char| char.is_ascii_alphanumeric() || *char == '_')
+        {
+            name.push(char);
+        }
+
+        name
+    }
+
+    /// `one,two|}` with `\,` and `\|` escaped.
+    fn parse_choices(&mut self) -> Vec<String> {
+        let mut choices = vec![String::new()];
+
+        while let Some(char) = self.chars.next() {
+            match char {
+                '\\' => {
+                    if let Some(escaped) = self.chars.next() {
+                        choices.last_mut().unwrap().push(escaped);
+                    }
+                }
+                ',' => choices.push(String::new()),
+                '|' => {
+                    self.chars.next_if_eq(&'}');
+                    break;
+                }
+                _ => choices.last_mut().unwrap().push(char),
+            }
+        }
+
+        choices
+    }
+
+    /// Regex transforms aren't supported, they're skipped up to their `}`.
+    fn skip_transform(&mut self) {
+        while let Some(char) = self.chars.next() {
+            match char {
+                '\\' => {
+                    self.chars.next();
+                }
+                '}' => return,
+                _ => {}
+            }
+        }
+    }
+
+    fn resolve(&self, name: &str) -> Option<String> {
+        (self.variable)(name).filter(|value| !value.is_empty())
+    }
+
+    fn insert_variable(&mut self, name: &str) {
+        let value = self.resolve(name).unwrap_or_else(|| name.into());
+        self.push_str(&value);
+    }
+
+    fn add_tabstop(&mut self, index: u32, range: Range<usize>, choices: Vec<String>) {
+        match self
+            .tabstops
+            .iter_mut()
+            .find(|tabstop| tabstop.index == index)
+        {
+            Some(tabstop) => {
+                tabstop.ranges.push(range);
+
+                if tabstop.choices.is_empty() {
+                    tabstop.choices = choices;
+                }
+            }
+            None => self.tabstops.push(Tabstop {
+                index,
+                ranges: vec![range],
+                choices,
+            }),
+        }
+    }
+}
+
+fn is_variable_start(char: char) -> bool {
+    char.is_ascii_alphabetic() || char == '_'
+}
+
+/// The tab stops of an accepted snippet, kept in step with the edits made
+/// while the user moves through them.
+#[derive(Debug, Clone)]
+pub struct SnippetSession {
+    tabstops: Vec<Tabstop>,
+    current: usize,
+}
+
+impl SnippetSession {
+    /// `offset` is where the snippet's text was inserted. Returns `None`
+    /// when there is nothing to visit besides the final cursor position.
+    pub fn new(snippet: &Snippet, offset: usize) -> Option<Self> {
+        let tabstops: Vec<Tabstop> = snippet
+            .tabstops
+            .iter()
+            .map(|tabstop| Tabstop {
+                ranges: tabstop
+                    .ranges
+                    .iter()
+                    .map(|range| range.start + offset..range.end + offset)
+                    .collect(),
+                ..tabstop.clone()
+            })
+            .collect();
+
+        (tabstops.len() > 1).then_some(Self {
+            tabstops,
+            current: 0,
+        })
+    }
+
+    pub fn current(&self) -> &Tabstop {
+        &self.tabstops[self.current]
+    }
+
+    pub fn is_last(&self) -> bool {
+        self.current + 1 == self.tabstops.len()
+    }
+
+    pub fn next(&mut self) -> &Tabstop {
+        self.current = (self.current + 1).min(self.tabstops.len() - 1);
+        self.current()
+    }
+
+    pub fn previous(&mut self) -> &Tabstop {
+        self.current = self.current.saturating_sub(1);
+        self.current()
+    }
+
+    /// Moves the ranges after an edit replacing `removed` with `inserted`
+    /// bytes. Only the current stop grows when text is typed at its end.
+    pub fn shift(&mut self, removed: Range<usize>, inserted: usize) {
+        let shift = |offset: usize, is_end: bool, grows: bool| {
+            if removed.is_empty() && offset == removed.start {
+                match is_end || !grows {
+                    true => offset + inserted,
+                    false => offset,
+                }
+            } else if offset <= removed.start {
+                offset
+            } else if offset >= removed.end {
+                offset + inserted - removed.len()
+            } else if is_end {
+                removed.start + inserted
+            } else {
+                removed.start
+            }
+        };
+
+        for (index, tabstop) in self.tabstops.iter_mut().enumerate() {
+            let grows = index == self.current;
+
+            for range in &mut tabstop.ranges {
+                let start = shift(range.start, false, grows);
+                let end = shift(range.end, true, grows);
+
+                *range = start..end.max(start);
+            }
+        }
+    }
+
+    /// Whether `offset` lies in one of the current stop's ranges.
+    pub fn contains(&self, offset: usize) -> bool {
+        self.current()
+            .ranges
+            .iter()
+            .any(|range| range.start <= offset && offset <= range.end)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expand(source: &str) -> Snippet {
+        parse(source, "", "    ", |name| {
+            (name == "TM_FILENAME").then(|| "main.rs".into())
+        })
+    }
+
+    fn tabstop(index: u32, range: Range<usize>, choices: &[&str]) -> Tabstop {
+        Tabstop {
+            index,
+            ranges: vec![range],
+            choices: choices.iter().map(|choice| choice.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn orders_tabstops_with_the_end_last() {
+        assert_eq!(
+            expand("$2 + $1;$0"),
+            Snippet {
+                text: " + ;".into(),
+                tabstops: vec![
+                    tabstop(1, 3..3, &[]),
+                    tabstop(2, 0..0, &[]),
+                    tabstop(0, 4..4, &[]),
+                ],
+            }
+        );
+        assert_eq!(expand("a").tabstops, vec![Tabstop::end(1)]);
+    }
+
+    #[test]
+    fn selects_placeholders() {
+        assert_eq!(
+            expand("fn ${1:name}(${2:${3:arg}: u32}) {}"),
+            Snippet {
+                text: "fn name(arg: u32) {}".into(),
+                tabstops: vec![
+                    tabstop(1, 3..7, &[]),
+                    tabstop(2, 8..16, &[]),
+                    tabstop(3, 8..11, &[]),
+                    Tabstop::end(20),
+                ],
+            }
+        );
+    }
+
+    #[test]
+    fn fills_mirrors_with_their_placeholder() {
+        assert_eq!(
+            expand("for $1 in 0..${1:i} {$1}"),
+            Snippet {
+                text: "for i in 0..i {i}".into(),
+                tabstops: vec![
+                    Tabstop {
+                        ranges: vec![4..5, 12..13, 15..16],
+                        ..tabstop(1, 4..5, &[])
+                    },
+                    Tabstop::end(17)
+                ],
+            }
+        );
+    }
+
+    #[test]
+    fn offers_choices() {
+        assert_eq!(
+            expand(r"${1|pub,pub(crate),a\,b|} fn"),
+            Snippet {
+                text: "pub fn".into(),
+                tabstops: vec![
+                    tabstop(1, 0..3, &["pub", "pub(crate)", "a,b"]),
+                    Tabstop::end(6),
+                ],
+            }
+        );
+    }
+
+    #[test]
+    fn resolves_variables() {
+        assert_eq!(
+            expand("$TM_FILENAME ${TM_FILENAME}").text,
+            "main.rs main.rs"
+        );
+        assert_eq!(expand("${UNKNOWN} $UNKNOWN").text, "UNKNOWN UNKNOWN");
+        assert_eq!(
+            expand("${TM_FILENAME:${1:default}} ${UNKNOWN:${1:default}}"),
+            Snippet {
+                text: "main.rs default".into(),
+                tabstops: vec![tabstop(1, 8..15, &[]), Tabstop::end(15)],
+            }
+        );
+        assert_eq!(expand("${TM_FILENAME/(.*)/$1/}").text, "main.rs");
+    }
+
+    #[test]
+    fn unescapes_dollars_braces_and_backslashes() {
+        assert_eq!(expand(r"\$1 \} \\ \n $ ${").text, r"$1 } \ \n $ ${");
+        assert_eq!(expand(r"${1:a\}b}").tabstops[0], tabstop(1, 0..3, &[]));
+    }
+
+    #[test]
+    fn indents_following_lines() {
+        let snippet = parse("{\n\t$0\n}", "  ", "    ", |_| None);
+
+        assert_eq!(snippet.text, "{\n      \n  }");
+        assert_eq!(snippet.tabstops, vec![Tabstop::end(8)]);
+    }
+
+    #[test]
+    fn takes_unclosed_syntax_to_the_end() {
+        assert_eq!(
+            expand("${1:"),
+            Snippet {
+                text: "".into(),
+                tabstops: vec![tabstop(1, 0..0, &[]), Tabstop::end(0)],
+            }
+        );
+        assert_eq!(
+            expand("a${1:b $2"),
+            Snippet {
+                text: "ab ".into(),
+                tabstops: vec![
+                    tabstop(1, 1..3, &[]),
+                    tabstop(2, 3..3, &[]),
+                    Tabstop::end(3),
+                ],
+            }
+        );
+        assert_eq!(expand("${1|a,b").tabstops[0], tabstop(1, 0..1, &["a", "b"]));
+        assert_eq!(expand("${1/x/y").text, "");
+        assert_eq!(expand("${TM_FILENAME").text, "main.rs");
+        assert_eq!(expand("${").text, "${");
+        assert_eq!(expand("$").text, "$");
+    }
+}
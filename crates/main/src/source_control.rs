@@ -0,0 +1,568 @@
+//! The side panel next to the file explorer: staged and unstaged changes,
+//! the commit message, branches and stashes of the workspace repository.
+
+use egui::{
+    Align, CollapsingHeader, ComboBox, Context, CursorIcon, Id, Key, KeyboardShortcut, Layout,
+    Modifiers, RichText, ScrollArea, Sense, SidePanel, TextEdit, Ui,
+};
+use git::{
+    branches::{self, Branch},
+    changes::{self, Change, Changes},
+    conventional_commit::{self, ConventionalCommit},
+    stash::{self, Stash},
+};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+use theme::Theme;
+
+use crate::{git_status, State};
+
+pub const TOGGLE_SHORTCUT: KeyboardShortcut =
+    KeyboardShortcut::new(Modifiers::COMMAND.plus(Modifiers::SHIFT), Key::G);
+pub const COMMIT_SHORTCUT: KeyboardShortcut = KeyboardShortcut::new(Modifiers::COMMAND, Key::Enter);
+
+const MESSAGE_ID: &str = "source-control-message";
+const BODY_ID: &str = "source-control-body";
+
+#[derive(Default)]
+pub struct SourceControl {
+    pub open: bool,
+    /// The `GitStatus` generation the lists were read at.
+    generation: Option<u64>,
+    changes: Changes,
+    branches: Vec<Branch>,
+    stashes: Vec<Stash>,
+    message: String,
+    /// Builds the message from its parts instead, when set.
+    composer: Option<ConventionalCommit>,
+    new_branch: String,
+    stash_message: String,
+    /// Asked before throwing the file's changes away.
+    discarding: Option<PathBuf>,
+    error: Option<String>,
+}
+
+enum Action {
+    Open(PathBuf),
+    Stage(Vec<PathBuf>),
+    Unstage(Vec<PathBuf>),
+    Discard(PathBuf),
+    Commit,
+    Checkout(String),
+    CreateBranch,
+    Stash,
+    ApplyStash { index: usize, pop: bool },
+    DropStash(usize),
+}
+
+impl SourceControl {
+    fn load(&mut self, root: &Path) {
+        let result = changes::changes(root).and_then(|changes| {
+            self.changes = changes;
+            self.branches = branches::branches(root)?;
+            self.stashes = stash::list(root)?;
+            Ok(())
+        });
+
+        if let Err(error) = result {
+            self.error = Some(error.message().into());
+        }
+    }
+}
+
+pub fn handle_input(ctx: &Context, state: &mut State) {
+    if ctx.input_mut(|input| input.consume_shortcut(&TOGGLE_SHORTCUT)) {
+        state.source_control.open = !state.source_control.open;
+    }
+}
+
+pub fn show(ctx: &Context, state: &mut State) {
+    if !state.source_control.open {
+        return;
+    }
+
+    let generation = state.git.generation();
+
+    if state.source_control.generation != Some(generation) {
+        state.source_control.generation = Some(generation);
+
+        if state.git.workdir().is_some() {
+            state.source_control.load(state.git.root());
+        }
+    }
+
+    let in_repository = state.git.workdir().is_some();
+    let panel = &mut state.source_control;
+    let theme = &state.theme;
+    let mut action = None;
+
+    let default_width = state.session.panel_width("source_control", 260.0);
+
+    SidePanel::left("source_control")
+        .resizable(true)
+        .default_width(default_width)
+        .min_width(200.0)
+        .show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.strong("Source Control");
+
+                ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
+                    if ui.small_button("x").clicked() {
+                        panel.open = false;
+                    }
+                });
+            });
+
+            ui.separator();
+
+            if !in_repository {
+                ui.weak("The workspace isn't a git repository.");
+                return;
+            }
+
+            if let Some(error) = &panel.error {
+                ui.colored_label(theme.error.main, error);
+            }
+
+            ScrollArea::vertical().auto_shrink(false).show(ui, |ui| {
+                branch_section(ui, panel, &mut action);
+                ui.separator();
+                commit_section(ui, panel, &mut action, theme);
+                ui.separator();
+                changes_section(ui, panel, &mut action, theme);
+                stash_section(ui, panel, &mut action);
+            });
+        });
+
+    if let Some(action) = action {
+        let result = run(state, action);
+
+        state.source_control.error = result.err().map(|error| error.message().into());
+    }
+}
+
+fn branch_section(ui: &mut Ui, panel: &mut SourceControl, action: &mut Option<Action>) {
+    let current = panel
+        .branches
+        .iter()
+        .find(|branch| branch.is_head)
+        .map_or("(no branch)", |branch| &branch.name);
+
+    ui.horizontal(|ui| {
+        ui.label("Branch");
+
+        ComboBox::from_id_source("source-control-branch")
+            .selected_text(current)
+            .width(ui.available_width())
+            .show_ui(ui, |ui| {
+                for branch in &panel.branches {
+                    if ui.selectable_label(branch.is_head, &branch.name).clicked()
+                        && !branch.is_head
+                    {
+                        *action = Some(Action::Checkout(branch.name.clone()));
+                    }
+                }
+            });
+    });
+
+    ui.horizontal(|ui| {
+        let create = ui.add_enabled(
+            !panel.new_branch.trim().is_empty(),
+            egui::Button::new("Create"),
+        );
+
+        let input = ui.add(
+            TextEdit::singleline(&mut panel.new_branch)
+                .hint_text("New branch")
+                .desired_width(f32::INFINITY),
+        );
+        let entered = input.lost_focus() && ui.input(|input| input.key_pressed(Key::Enter));
+
+        if (create.clicked() || entered) && !panel.new_branch.trim().is_empty() {
+            *action = Some(Action::CreateBranch);
+        }
+    });
+}
+
+fn commit_section(
+    ui: &mut Ui,
+    panel: &mut SourceControl,
+    action: &mut Option<Action>,
+    theme: &Theme,
+) {
+    let composing = panel.composer.is_some();
+
+    if ui
+        .selectable_label(composing, "Conventional Commit")
+        .clicked()
+    {
+        panel.composer = match composing {
+            true => None,
+            false => Some(
+                ConventionalCommit::parse(&panel.message).unwrap_or_else(|| ConventionalCommit {
+                    kind: conventional_commit::TYPES[0].0.into(),
+                    subject: panel.message.trim().into(),
+                    ..Default::default()
+                }),
+            ),
+        };
+    }
+
+    // Before the text boxes see the keys, or Enter would add a line.
+    let focused = ui.memory(|memory| {
+        [MESSAGE_ID, BODY_ID]
+            .into_iter()
+            .any(|id| memory.has_focus(Id::new(id)))
+    });
+    let shortcut = focused && ui.input_mut(|input| input.consume_shortcut(&COMMIT_SHORTCUT));
+
+    match &mut panel.composer {
+        Some(composer) => {
+            composer_fields(ui, composer);
+            panel.message = composer.message();
+        }
+        None => {
+            ui.add(
+                TextEdit::multiline(&mut panel.message)
+                    .id(Id::new(MESSAGE_ID))
+                    .hint_text("Message")
+                    .desired_rows(3)
+                    .desired_width(f32::INFINITY),
+            );
+        }
+    }
+
+    if !panel.message.trim().is_empty() {
+        if let Some(problem) = conventional_commit::lint(&panel.message) {
+            ui.colored_label(theme.warning.main, problem);
+        }
+    }
+
+    let can_commit = !panel.message.trim().is_empty() && !panel.changes.staged.is_empty();
+
+    let commit = ui
+        .add_enabled(
+            can_commit,
+            egui::Button::new("Commit")
+                .shortcut_text(ui.ctx().format_shortcut(&COMMIT_SHORTCUT))
+                .min_size(egui::vec2(ui.available_width(), 0.0)),
+        )
+        .on_disabled_hover_text("Stage changes and write a message first");
+
+    if can_commit && (commit.clicked() || shortcut) {
+        *action = Some(Action::Commit);
+    }
+}
+
+fn composer_fields(ui: &mut Ui, composer: &mut ConventionalCommit) {
+    egui::Grid::new("conventional-commit")
+        .num_columns(2)
+        .show(ui, |ui| {
+            ui.label("Type");
+            ComboBox::from_id_source("conventional-commit-type")
+                .selected_text(&composer.kind)
+                .width(ui.available_width())
+                .show_ui(ui, |ui| {
+                    for (kind, description) in conventional_commit::TYPES {
+                        ui.selectable_value(&mut composer.kind, kind.to_string(), kind)
+                            .on_hover_text(description);
+                    }
+                });
+            ui.end_row();
+
+            for (label, value, hint) in [
+                ("Scope", &mut composer.scope, "Optional"),
+                ("Subject", &mut composer.subject, "Imperative, lower case"),
+            ] {
+                ui.label(label);
+                ui.add(
+                    TextEdit::singleline(value)
+                        .hint_text(hint)
+                        .desired_width(f32::INFINITY),
+                );
+                ui.end_row();
+            }
+        });
+
+    ui.add(
+        TextEdit::multiline(&mut composer.body)
+            .id(Id::new(BODY_ID))
+            .hint_text("Body")
+            .desired_rows(2)
+            .desired_width(f32::INFINITY),
+    );
+    ui.add(
+        TextEdit::singleline(&mut composer.breaking_change)
+            .hint_text("Breaking change, if any")
+            .desired_width(f32::INFINITY),
+    );
+}
+
+fn changes_section(
+    ui: &mut Ui,
+    panel: &mut SourceControl,
+    action: &mut Option<Action>,
+    theme: &Theme,
+) {
+    let staged: Vec<PathBuf> = panel
+        .changes
+        .staged
+        .iter()
+        .map(|change| change.path.clone())
+        .collect();
+    let unstaged: Vec<PathBuf> = panel
+        .changes
+        .unstaged
+        .iter()
+        .map(|change| change.path.clone())
+        .collect();
+
+    let header = |title: &str, count: usize| format!("{title} ({count})");
+
+    CollapsingHeader::new(header("Staged Changes", staged.len()))
+        .id_source("staged-changes")
+        .default_open(true)
+        .show(ui, |ui| {
+            if !staged.is_empty() && ui.small_button("Unstage All").clicked() {
+                *action = Some(Action::Unstage(staged.clone()));
+            }
+
+            for change in &panel.changes.staged {
+                change_row(ui, change, theme, action, |ui, action| {
+                    if ui.small_button("−").on_hover_text("Unstage").clicked() {
+                        *action = Some(Action::Unstage(vec![change.path.clone()]));
+                    }
+                });
+            }
+        });
+
+    CollapsingHeader::new(header("Changes", unstaged.len()))
+        .id_source("unstaged-changes")
+        .default_open(true)
+        .show(ui, |ui| {
+            if !unstaged.is_empty() && ui.small_button("Stage All").clicked() {
+                *action = Some(Action::Stage(unstaged.clone()));
+            }
+
+            for change in &panel.changes.unstaged {
+                let discarding = &mut panel.discarding;
+
+                change_row(ui, change, theme, action, |ui, action| {
+                    if ui.small_button("+").on_hover_text("Stage").clicked() {
+                        *action = Some(Action::Stage(vec![change.path.clone()]));
+                    }
+
+                    if ui
+                        .small_button("↺")
+                        .on_hover_text("Discard Changes")
+                        .clicked()
+                    {
+                        *discarding = Some(change.path.clone());
+                    }
+                });
+
+                if panel.discarding.as_ref() == Some(&change.path) {
+                    ui.horizontal_wrapped(|ui| {
+                        ui.colored_label(theme.warning.main, "Discard these changes?");
+
+                        if ui.small_button("Discard").clicked() {
+                            *action = Some(Action::Discard(change.path.clone()));
+                        }
+
+                        if ui.small_button("Cancel").clicked() {
+                            panel.discarding = None;
+                        }
+                    });
+                }
+            }
+        });
+}
+
+/// The file name and its folder, the status letter and the `buttons` on
+/// the right. Clicking the name opens the file.
+fn change_row(
+    ui: &mut Ui,
+    change: &Change,
+    theme: &Theme,
+    action: &mut Option<Action>,
+    buttons: impl FnOnce(&mut Ui, &mut Option<Action>),
+) {
+    let color = git_status::color(change.status, theme);
+    let name = change
+        .path
+        .file_name()
+        .map_or_else(String::new, |name| name.to_string_lossy().into());
+    let folder = change
+        .path
+        .parent()
+        .map(|folder| folder.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    ui.horizontal(|ui| {
+        ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
+            ui.colored_label(color, change.status.letter())
+                .on_hover_text(change.status.label());
+            buttons(ui, action);
+
+            ui.with_layout(Layout::left_to_right(Align::Center), |ui| {
+                ui.style_mut().wrap_mode = Some(egui::TextWrapMode::Truncate);
+
+                let label = ui
+                    .add(egui::Label::new(RichText::new(name).color(color)).sense(Sense::click()))
+                    .on_hover_cursor(CursorIcon::PointingHand)
+                    .on_hover_text(change.path.to_string_lossy());
+
+                if !folder.is_empty() {
+                    ui.weak(folder);
+                }
+
+                if label.clicked() {
+                    *action = Some(Action::Open(change.path.clone()));
+                }
+            });
+        });
+    });
+}
+
+fn stash_section(ui: &mut Ui, panel: &mut SourceControl, action: &mut Option<Action>) {
+    CollapsingHeader::new(format!("Stashes ({})", panel.stashes.len()))
+        .id_source("stashes")
+        .show(ui, |ui| {
+            ui.horizontal(|ui| {
+                let has_changes =
+                    !panel.changes.staged.is_empty() || !panel.changes.unstaged.is_empty();
+
+                if ui
+                    .add_enabled(has_changes, egui::Button::new("Stash"))
+                    .on_hover_text("Set aside every change, untracked files too")
+                    .clicked()
+                {
+                    *action = Some(Action::Stash);
+                }
+
+                ui.add(
+                    TextEdit::singleline(&mut panel.stash_message)
+                        .hint_text("Message")
+                        .desired_width(f32::INFINITY),
+                );
+            });
+
+            for stash in &panel.stashes {
+                ui.horizontal(|ui| {
+                    ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
+                        if ui.small_button("Drop").clicked() {
+                            *action = Some(Action::DropStash(stash.index));
+                        }
+
+                        if ui.small_button("Pop").clicked() {
+                            *action = Some(Action::ApplyStash {
+                                index: stash.index,
+                                pop: true,
+                            });
+                        }
+
+                        if ui.small_button("Apply").clicked() {
+                            *action = Some(Action::ApplyStash {
+                                index: stash.index,
+                                pop: false,
+                            });
+                        }
+
+                        ui.with_layout(Layout::left_to_right(Align::Center), |ui| {
+                            ui.style_mut().wrap_mode = Some(egui::TextWrapMode::Truncate);
+                            ui.label(&stash.message);
+                        });
+                    });
+                });
+            }
+        });
+}
+
+fn run(state: &mut State, action: Action) -> Result<(), git::Error> {
+    let root = state.git.root().to_path_buf();
+    let workdir = state.git.workdir().unwrap_or(&root).to_path_buf();
+
+    // Open files that match what's on disk follow the working directory;
+    // unsaved edits are left alone.
+    let unchanged: Vec<String> = state
+        .file_store
+        .files
+        .values()
+        .filter(|file| file.is_saved())
+        .map(|file| file.path.clone())
+        .collect();
+
+    let panel = &mut state.source_control;
+
+    match action {
+        Action::Open(path) => {
+            open(state, &workdir.join(path));
+            return Ok(());
+        }
+        Action::Stage(paths) => {
+            for path in paths {
+                changes::stage(&root, &path)?;
+            }
+        }
+        Action::Unstage(paths) => {
+            for path in paths {
+                changes::unstage(&root, &path)?;
+            }
+        }
+        Action::Discard(path) => {
+            panel.discarding = None;
+            changes::discard(&root, &path)?;
+        }
+        Action::Commit => {
+            changes::commit(&root, panel.message.trim())?;
+            panel.message.clear();
+            panel.composer = panel.composer.as_ref().map(|_| ConventionalCommit {
+                kind: conventional_commit::TYPES[0].0.into(),
+                ..Default::default()
+            });
+        }
+        Action::Checkout(name) => branches::checkout(&root, &name)?,
+        Action::CreateBranch => {
+            branches::create(&root, panel.new_branch.trim())?;
+            panel.new_branch.clear();
+        }
+        Action::Stash => {
+            let message = panel.stash_message.trim();
+
+            stash::save(&root, (!message.is_empty()).then_some(message))?;
+            panel.stash_message.clear();
+        }
+        Action::ApplyStash { index, pop } => stash::apply(&root, index, pop)?,
+        Action::DropStash(index) => stash::drop(&root, index)?,
+    }
+
+    for path in unchanged {
+        state.file_store.reload(&path);
+    }
+
+    state.git.refresh();
+
+    Ok(())
+}
+
+/// Switches to the file's buffer when it's open, however its path was
+/// written, or opens it.
+fn open(state: &mut State, path: &Path) {
+    let canonical = fs::canonicalize(path).ok();
+    let open = state
+        .file_store
+        .files
+        .keys()
+        .find(|file| canonical.is_some() && fs::canonicalize(file).ok() == canonical)
+        .cloned();
+
+    match open {
+        Some(file) => state.file_store.insert(&file, true),
+        None if path.is_file() => state
+            .file_store
+            .insert(&path.to_string_lossy().into(), true),
+        None => {}
+    }
+}
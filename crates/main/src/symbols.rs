@@ -0,0 +1,137 @@
+//! The declarations of a file, nested as in its tree-sitter syntax tree:
+//! classes and their methods, impls and their functions, interfaces, JSON
+//! keys and so on.
+
+use std::ops::Range;
+use tree_sitter::{Node, Parser};
+
+use crate::languages::Language;
+
+/// Node kinds that declare a symbol, and the word shown before its name.
+const SYMBOL_KINDS: [(&str, &str); 20] = [
+    ("impl_item", "impl"),
+    ("function_item", "fn"),
+    ("function_signature_item", "fn"),
+    ("struct_item", "struct"),
+    ("enum_item", "enum"),
+    ("union_item", "union"),
+    ("trait_item", "trait"),
+    ("mod_item", "mod"),
+    ("class_declaration", "class"),
+    ("abstract_class_declaration", "class"),
+    ("function_declaration", "function"),
+    ("generator_function_declaration", "function"),
+    ("method_definition", "method"),
+    ("method_signature", "method"),
+    ("abstract_method_signature", "method"),
+    ("interface_declaration", "interface"),
+    ("type_alias_declaration", "type"),
+    ("enum_declaration", "enum"),
+    ("internal_module", "namespace"),
+    ("pair", "key"),
+];
+
+/// Values that make a variable declaration read as a function or class.
+const DECLARED_VALUES: [(&str, &str); 4] = [
+    ("arrow_function", "function"),
+    ("function_expression", "function"),
+    ("generator_function", "function"),
+    ("class", "class"),
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Symbol {
+    pub kind: &'static str,
+    pub name: String,
+    /// The bytes of the whole declaration.
+    pub range: Range<usize>,
+    pub children: Vec<Symbol>,
+}
+
+impl Symbol {
+    pub fn label(&self) -> String {
+        match self.kind {
+            "key" => self.name.clone(),
+            kind => format!("{kind} {}", self.name),
+        }
+    }
+}
+
+/// The top-level symbols, empty for languages without a grammar.
+pub fn outline(language: &Language, text: &str) -> Vec<Symbol> {
+    let Some(grammar) = language.grammar else {
+        return Vec::new();
+    };
+
+    let mut parser = Parser::new();
+
+    if parser.set_language(&grammar()).is_err() {
+        return Vec::new();
+    }
+
+    let Some(tree) = parser.parse(text, None) else {
+        return Vec::new();
+    };
+
+    let mut symbols = Vec::new();
+
+    collect(tree.root_node(), text.as_bytes(), &mut symbols);
+    symbols
+}
+
+/// The symbols around `offset`, outermost first, each with its peers.
+pub fn path_at(symbols: &[Symbol], offset: usize) -> Vec<(&Symbol, &[Symbol])> {
+    let mut path = Vec::new();
+    let mut peers = symbols;
+
+    while let Some(symbol) = peers.iter().find(|symbol| symbol.range.contains(&offset)) {
+        path.push((symbol, peers));
+        peers = &symbol.children;
+    }
+
+    path
+}
+
+fn collect(node: Node, source: &[u8], symbols: &mut Vec<Symbol>) {
+    let mut cursor = node.walk();
+
+    for child in node.named_children(&mut cursor) {
+        match symbol(child, source) {
+            Some(mut symbol) => {
+                collect(child, source, &mut symbol.children);
+                symbols.push(symbol);
+            }
+            None => collect(child, source, symbols),
+        }
+    }
+}
+
+fn symbol(node: Node, source: &[u8]) -> Option<Symbol> {
+    let kind = match node.kind() {
+        "variable_declarator" => {
+            let value = node.child_by_field_name("value")?.kind();
+
+            DECLARED_VALUES.iter().find(|(kind, _)| *kind == value)?.1
+        }
+        kind => SYMBOL_KINDS.iter().find(|(symbol, _)| *symbol == kind)?.1,
+    };
+
+    let text = |field: &str| node.child_by_field_name(field)?.utf8_text(source).ok();
+
+    let name = match kind {
+        "key" => text("key")?.trim_matches(['"', '\'']).into(),
+        // `impl Type`, or `impl Trait for Type`.
+        "impl" => match text("trait") {
+            Some(name) => format!("{name} for {}", text("type")?),
+            None => text("type")?.into(),
+        },
+        _ => text("name")?.trim_matches(['"', '\'']).into(),
+    };
+
+    Some(Symbol {
+        kind,
+        name,
+        range: node.byte_range(),
+        children: Vec::new(),
+    })
+}
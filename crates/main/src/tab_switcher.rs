@@ -0,0 +1,123 @@
+//! Ctrl+Tab: the focused group's tabs, most recently used first. Tab again
+//! with Ctrl held moves down the list, letting go of Ctrl opens the
+//! selected one.
+
+use egui::{
+    Align2, Area, Context, Frame, Id, Key, KeyboardShortcut, Modifiers, Order, Sense, Vec2,
+};
+
+use crate::{editor_groups, State};
+
+pub const SHORTCUT: KeyboardShortcut = KeyboardShortcut::new(Modifiers::CTRL, Key::Tab);
+pub const BACK_SHORTCUT: KeyboardShortcut =
+    KeyboardShortcut::new(Modifiers::CTRL.plus(Modifiers::SHIFT), Key::Tab);
+
+const WIDTH: f32 = 360.0;
+
+#[derive(Default)]
+pub struct TabSwitcher {
+    open: bool,
+    selected: usize,
+}
+
+pub fn handle_input(ctx: &Context, state: &mut State) {
+    // Ctrl+Shift+Tab first, consuming Ctrl+Tab would swallow it too.
+    let back = ctx.input_mut(|input| input.consume_shortcut(&BACK_SHORTCUT));
+    let forward = !back && ctx.input_mut(|input| input.consume_shortcut(&SHORTCUT));
+    let count = state.file_store.groups.focused().recent.len();
+
+    if !(back || forward) || count == 0 {
+        return;
+    }
+
+    let switcher = &mut state.tab_switcher;
+
+    if !switcher.open {
+        switcher.open = true;
+        switcher.selected = 0;
+    }
+
+    switcher.selected = match back {
+        true => (switcher.selected + count - 1) % count,
+        false => (switcher.selected + 1) % count,
+    };
+}
+
+pub fn show(ctx: &Context, state: &mut State) {
+    if !state.tab_switcher.open {
+        return;
+    }
+
+    let recent = state.file_store.groups.focused().recent.clone();
+
+    if recent.is_empty() || ctx.input(|input| input.key_pressed(Key::Escape)) {
+        state.tab_switcher.open = false;
+        return;
+    }
+
+    let switcher = &mut state.tab_switcher;
+    let theme = &state.theme;
+    let mut chosen = None;
+
+    switcher.selected = switcher.selected.min(recent.len() - 1);
+
+    if !ctx.input(|input| input.modifiers.ctrl) {
+        chosen = Some(switcher.selected);
+    }
+
+    Area::new(Id::new("tab-switcher"))
+        .order(Order::Foreground)
+        .anchor(Align2::CENTER_TOP, [0.0, 48.0])
+        .show(ctx, |ui| {
+            Frame::popup(ui.style()).show(ui, |ui| {
+                ui.set_width(WIDTH);
+                ui.spacing_mut().item_spacing = Vec2::ZERO;
+
+                for (index, path) in recent.iter().enumerate() {
+                    let Some(file) = state.file_store.files.get(path) else {
+                        continue;
+                    };
+
+                    let height = ui.text_style_height(&egui::TextStyle::Body) + 10.0;
+                    let (rect, response) = ui.allocate_exact_size(
+                        Vec2::new(ui.available_width(), height),
+                        Sense::click(),
+                    );
+                    let painter = ui.painter();
+
+                    if index == switcher.selected {
+                        painter.rect_filled(rect, theme.rounding, theme.action.selected);
+                    } else if response.hovered() {
+                        painter.rect_filled(rect, theme.rounding, theme.action.hover);
+                    }
+
+                    painter.text(
+                        rect.left_center() + Vec2::new(8.0, 0.0),
+                        Align2::LEFT_CENTER,
+                        &file.name,
+                        egui::TextStyle::Body.resolve(ui.style()),
+                        theme.text_color.primary,
+                    );
+                    painter.text(
+                        rect.right_center() - Vec2::new(8.0, 0.0),
+                        Align2::RIGHT_CENTER,
+                        path,
+                        egui::TextStyle::Small.resolve(ui.style()),
+                        theme.text_color.disabled,
+                    );
+
+                    if response.clicked() {
+                        chosen = Some(index);
+                    }
+                }
+            });
+        });
+
+    let Some(index) = chosen else {
+        return;
+    };
+
+    state.tab_switcher.open = false;
+    state.file_store.set_active_file(&recent[index]);
+    editor_groups::focus(ctx, state, state.file_store.groups.focused);
+}
@@ -0,0 +1,604 @@
+//! Tasks: the commands of the workspace's tasks file and the ones detected
+//! for Cargo and npm. A running task streams its output into the Output
+//! panel and, through its problem matcher, its problems into Problems.
+
+use components::selectable_label::SelectableLabel;
+use egui::{Context, RichText, ScrollArea, Sense, TextStyle, TopBottomPanel, Ui, Vec2};
+use lsp::lsp_types::{Diagnostic, Position, PublishDiagnosticsParams, Url};
+use serde::Deserialize;
+use std::{
+    collections::HashMap,
+    fs,
+    io::{BufRead, BufReader, Read},
+    path::{Path, PathBuf},
+    process::{Child, Command, Stdio},
+    sync::mpsc::{self, Receiver, Sender, TryRecvError},
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    diagnostics::Diagnostics,
+    language_servers,
+    problem_matchers::{Matcher, ProblemMatcher},
+    State,
+};
+
+/// Relative to the folder the editor was started in.
+pub const TASKS_FILE: &str = ".rust-editor/tasks.json";
+
+/// Older output is dropped past this many lines.
+const MAX_LINES: usize = 50_000;
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Task {
+    pub label: String,
+    pub command: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// Relative to the workspace, which is the default.
+    #[serde(default)]
+    pub cwd: Option<PathBuf>,
+    #[serde(default)]
+    pub problem_matcher: Option<ProblemMatcher>,
+}
+
+impl Task {
+    fn command_line(&self) -> String {
+        [self.command.as_str()]
+            .into_iter()
+            .chain(self.args.iter().map(String::as_str))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+#[derive(Deserialize)]
+struct TasksFile {
+    tasks: Vec<Task>,
+}
+
+#[derive(Default)]
+pub struct Tasks {
+    pub output_open: bool,
+    /// `None` until the tasks are first needed.
+    tasks: Option<Vec<Task>>,
+    runs: Vec<Run>,
+    active: usize,
+    error: Option<String>,
+}
+
+struct Run {
+    task: Task,
+    child: Option<Child>,
+    receiver: Receiver<String>,
+    matcher: Option<Matcher>,
+    output: Vec<OutputLine>,
+    problems: HashMap<Url, Vec<Diagnostic>>,
+    status: Status,
+    started: Instant,
+    elapsed: Duration,
+}
+
+struct OutputLine {
+    text: String,
+    /// Where the problem reported on this line is.
+    location: Option<(Url, Position)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Status {
+    Running,
+    Exited(Option<i32>),
+    Cancelled,
+}
+
+impl Tasks {
+    /// The workspace's tasks followed by the detected ones.
+    pub fn list(&mut self) -> &[Task] {
+        if self.tasks.is_none() {
+            self.reload();
+        }
+
+        self.tasks.as_deref().unwrap_or_default()
+    }
+
+    /// Reads the tasks file again and redoes the detection.
+    pub fn reload(&mut self) {
+        let root = std::env::current_dir().unwrap_or_default();
+        let mut tasks = Vec::new();
+
+        self.error = None;
+
+        match read_tasks_file(&root) {
+            Ok(workspace) => tasks.extend(workspace),
+            Err(error) => self.error = Some(error),
+        }
+
+        tasks.extend(cargo_tasks(&root));
+        tasks.extend(npm_tasks(&root));
+
+        self.tasks = Some(tasks);
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.runs.iter().any(|run| run.status == Status::Running)
+    }
+
+    /// Stops every task that is still running.
+    pub fn cancel_all(&mut self) {
+        for run in &mut self.runs {
+            run.cancel();
+        }
+    }
+}
+
+fn read_tasks_file(root: &Path) -> Result<Vec<Task>, String> {
+    let path = root.join(TASKS_FILE);
+
+    let Ok(text) = fs::read_to_string(&path) else {
+        return Ok(Vec::new());
+    };
+
+    serde_json::from_str::<TasksFile>(&text)
+        .map(|file| file.tasks)
+        .map_err(|error| format!("Couldn't read {TASKS_FILE}: {error}"))
+}
+
+fn cargo_tasks(root: &Path) -> Vec<Task> {
+    if !root.join("Cargo.toml").is_file() {
+        return Vec::new();
+    }
+
+    ["build", "check", "test", "clippy", "run"]
+        .into_iter()
+        .map(|subcommand| Task {
+            label: format!("cargo {subcommand}"),
+            command: "cargo".into(),
+            args: vec![subcommand.into(), "--message-format=json".into()],
+            cwd: None,
+            problem_matcher: Some(ProblemMatcher::Rustc),
+        })
+        .collect()
+}
+
+/// An `npm run` task per script, matching the problems of the scripts that
+/// run `tsc` or ESLint.
+fn npm_tasks(root: &Path) -> Vec<Task> {
+    let scripts = fs::read_to_string(root.join("package.json"))
+        .ok()
+        .and_then(|text| serde_json::from_str::<serde_json::Value>(&text).ok())
+        .and_then(|package| package.get("scripts")?.as_object().cloned())
+        .unwrap_or_default();
+
+    let npm = match cfg!(windows) {
+        true => "npm.cmd",
+        false => "npm",
+    };
+
+    scripts
+        .into_iter()
+        .map(|(name, script)| {
+            let script = script.as_str().unwrap_or_default();
+            let problem_matcher = if script.contains("tsc") {
+                Some(ProblemMatcher::Tsc)
+            } else if script.contains("eslint") {
+                Some(ProblemMatcher::Eslint)
+            } else {
+                None
+            };
+
+            Task {
+                label: format!("npm: {name}"),
+                command: npm.into(),
+                args: vec!["run".into(), name],
+                cwd: None,
+                problem_matcher,
+            }
+        })
+        .collect()
+}
+
+/// Runs the task, replacing its previous run and the problems it found.
+pub fn run(ctx: &Context, state: &mut State, task: Task) {
+    let tasks = &mut state.tasks;
+
+    if let Some(index) = tasks
+        .runs
+        .iter()
+        .position(|run| run.task.label == task.label)
+    {
+        tasks
+            .runs
+            .remove(index)
+            .clear_problems(&mut state.diagnostics);
+    }
+
+    let root = std::env::current_dir().unwrap_or_default();
+    let cwd = task.cwd.as_ref().map_or(root.clone(), |cwd| root.join(cwd));
+
+    match spawn(ctx, &task, &cwd) {
+        Ok((child, receiver)) => {
+            tasks.runs.push(Run {
+                matcher: task
+                    .problem_matcher
+                    .map(|matcher| Matcher::new(matcher, cwd)),
+                task,
+                child: Some(child),
+                receiver,
+                output: Vec::new(),
+                problems: HashMap::new(),
+                status: Status::Running,
+                started: Instant::now(),
+                elapsed: Duration::ZERO,
+            });
+            tasks.active = tasks.runs.len() - 1;
+            tasks.output_open = true;
+            tasks.error = None;
+        }
+        Err(error) => {
+            tasks.error = Some(error);
+            tasks.output_open = true;
+        }
+    }
+}
+
+fn spawn(ctx: &Context, task: &Task, cwd: &Path) -> Result<(Child, Receiver<String>), String> {
+    let mut command = Command::new(&task.command);
+
+    command
+        .args(&task.args)
+        .current_dir(cwd)
+        .env("NO_COLOR", "1")
+        .env("CARGO_TERM_COLOR", "never")
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+
+    // Its own process group, so cancelling also stops what it started.
+    #[cfg(unix)]
+    std::os::unix::process::CommandExt::process_group(&mut command, 0);
+
+    let mut child = command
+        .spawn()
+        .map_err(|error| format!("Couldn't run {}: {error}", task.command))?;
+
+    let (sender, receiver) = mpsc::channel();
+
+    stream_lines(ctx, child.stdout.take().unwrap(), sender.clone());
+    stream_lines(ctx, child.stderr.take().unwrap(), sender);
+
+    Ok((child, receiver))
+}
+
+fn stream_lines(ctx: &Context, pipe: impl Read + Send + 'static, sender: Sender<String>) {
+    let ctx = ctx.clone();
+
+    thread::spawn(move || {
+        let mut reader = BufReader::new(pipe);
+        let mut line = Vec::new();
+
+        while let Ok(1..) = reader.read_until(b'\n', &mut line) {
+            let text = String::from_utf8_lossy(&line);
+
+            if sender.send(clean_line(&text)).is_err() {
+                break;
+            }
+
+            line.clear();
+            ctx.request_repaint();
+        }
+    });
+}
+
+/// Without the line break, the colors and whatever a progress bar drew
+/// before its last carriage return.
+fn clean_line(line: &str) -> String {
+    let line = line.trim_end_matches(['\n', '\r']);
+    let line = line.rsplit('\r').next().unwrap_or_default();
+    let mut clean = String::with_capacity(line.len());
+    let mut chars = line.chars().peekable();
+
+    while let Some(char) = chars.next() {
+        if char != '\x1b' {
+            clean.push(char);
+            continue;
+        }
+
+        match chars.next() {
+            // CSI, up to its final byte.
+            Some('[') => {
+                for char in chars.by_ref() {
+                    if ('@'..='~').contains(&char) {
+                        break;
+                    }
+                }
+            }
+            // OSC, up to BEL or ST.
+            Some(']') => {
+                while let Some(char) = chars.next() {
+                    if char == '\x07' || (char == '\x1b' && chars.next_if_eq(&'\\').is_some()) {
+                        break;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    clean
+}
+
+impl Run {
+    fn server(&self) -> String {
+        format!("task: {}", self.task.label)
+    }
+
+    fn cancel(&mut self) {
+        if self.status != Status::Running {
+            return;
+        }
+
+        if let Some(child) = &mut self.child {
+            #[cfg(unix)]
+            let _ = Command::new("kill")
+                .arg("-TERM")
+                .arg(format!("-{}", child.id()))
+                .status();
+
+            let _ = child.kill();
+        }
+
+        self.status = Status::Cancelled;
+        self.elapsed = self.started.elapsed();
+    }
+
+    fn clear_problems(&self, diagnostics: &mut Diagnostics) {
+        diagnostics.clear_server(&self.server());
+    }
+
+    /// Takes in the output that arrived since the last frame. The task is
+    /// over once both of its pipes are closed.
+    fn poll(&mut self, diagnostics: &mut Diagnostics) {
+        loop {
+            match self.receiver.try_recv() {
+                Ok(line) => self.push(&line, diagnostics),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    if let Some(mut child) = self.child.take() {
+                        let code = child.wait().ok().and_then(|status| status.code());
+
+                        if self.status == Status::Running {
+                            self.status = Status::Exited(code);
+                            self.elapsed = self.started.elapsed();
+                        }
+                    }
+
+                    break;
+                }
+            }
+        }
+    }
+
+    fn push(&mut self, line: &str, diagnostics: &mut Diagnostics) {
+        let (text, problem) = match &mut self.matcher {
+            Some(matcher) => {
+                let matched = matcher.line(line);
+                (matched.text, matched.problem)
+            }
+            None => (Some(line.to_string()), None),
+        };
+
+        let server = self.server();
+        let location = problem.and_then(|problem| {
+            let uri = problem
+                .path
+                .canonicalize()
+                .ok()
+                .and_then(|path| Url::from_file_path(path).ok())?;
+            let position = problem.diagnostic.range.start;
+            let problems = self.problems.entry(uri.clone()).or_default();
+
+            problems.push(problem.diagnostic);
+            diagnostics.publish(
+                &server,
+                PublishDiagnosticsParams {
+                    uri: uri.clone(),
+                    diagnostics: problems.clone(),
+                    version: None,
+                },
+            );
+
+            Some((uri, position))
+        });
+
+        let Some(text) = text else {
+            return;
+        };
+
+        for (index, text) in text.lines().enumerate() {
+            self.output.push(OutputLine {
+                text: text.into(),
+                location: location.clone().filter(|_| index == 0),
+            });
+        }
+
+        if self.output.len() > MAX_LINES {
+            self.output.drain(..self.output.len() - MAX_LINES);
+        }
+    }
+
+    fn title(&self) -> String {
+        let icon = match self.status {
+            Status::Running => "⏵",
+            Status::Exited(Some(0)) => "✔",
+            Status::Exited(_) => "✖",
+            Status::Cancelled => "◼",
+        };
+
+        format!("{icon} {}", self.task.label)
+    }
+
+    fn summary(&self) -> String {
+        let seconds = self.elapsed.as_secs_f32();
+
+        match self.status {
+            Status::Running => format!("Running {}", self.task.command_line()),
+            Status::Exited(Some(code)) => format!("Exited with code {code} after {seconds:.1}s"),
+            Status::Exited(None) => format!("Killed after {seconds:.1}s"),
+            Status::Cancelled => format!("Cancelled after {seconds:.1}s"),
+        }
+    }
+}
+
+impl Drop for Run {
+    fn drop(&mut self) {
+        self.cancel();
+
+        if let Some(child) = &mut self.child {
+            let _ = child.wait();
+        }
+    }
+}
+
+/// Collects the output of the running tasks and shows the Output panel.
+pub fn show(ctx: &Context, state: &mut State) {
+    for run in &mut state.tasks.runs {
+        run.poll(&mut state.diagnostics);
+    }
+
+    if !state.tasks.output_open {
+        return;
+    }
+
+    let mut navigate = None;
+    let mut rerun = None;
+
+    TopBottomPanel::bottom("output")
+        .resizable(true)
+        .default_height(200.0)
+        .min_height(80.0)
+        .show(ctx, |ui| {
+            rerun = toolbar(ui, state);
+            ui.separator();
+
+            let tasks = &state.tasks;
+
+            if let Some(error) = &tasks.error {
+                ui.colored_label(state.theme.error.main, error);
+            }
+
+            let Some(run) = tasks.runs.get(tasks.active) else {
+                ui.weak("Run a task from the command palette to see its output here.");
+                return;
+            };
+
+            ui.weak(run.summary());
+            navigate = output(ui, run);
+        });
+
+    if let Some((uri, position)) = navigate {
+        language_servers::open_location(ctx, state, &uri, position);
+    }
+
+    if let Some(task) = rerun {
+        run(ctx, state, task);
+    }
+}
+
+/// Returns the task to run again, if asked.
+fn toolbar(ui: &mut Ui, state: &mut State) -> Option<Task> {
+    let tasks = &mut state.tasks;
+    let mut close = None;
+    let mut rerun = None;
+
+    ui.horizontal(|ui| {
+        ui.strong("Output");
+
+        for (index, run) in tasks.runs.iter().enumerate() {
+            let label = ui.add(
+                SelectableLabel::new(index == tasks.active, run.title())
+                    .padding(Vec2::new(8.0, 2.0)),
+            );
+
+            if label.clicked() {
+                tasks.active = index;
+            }
+
+            if label.middle_clicked() {
+                close = Some(index);
+            }
+        }
+
+        ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+            if ui.small_button("x").on_hover_text("Hide").clicked() {
+                tasks.output_open = false;
+            }
+
+            let Some(run) = tasks.runs.get_mut(tasks.active) else {
+                return;
+            };
+
+            if run.status == Status::Running {
+                if ui.small_button("◼").on_hover_text("Cancel Task").clicked() {
+                    run.cancel();
+                }
+            } else {
+                if ui.small_button("🗑").on_hover_text("Close").clicked() {
+                    close = Some(tasks.active);
+                }
+
+                if ui.small_button("⟲").on_hover_text("Run Again").clicked() {
+                    rerun = Some(run.task.clone());
+                }
+            }
+        });
+    });
+
+    if let Some(index) = close {
+        tasks
+            .runs
+            .remove(index)
+            .clear_problems(&mut state.diagnostics);
+        tasks.active = tasks.active.min(tasks.runs.len().saturating_sub(1));
+    }
+
+    rerun
+}
+
+/// The output, following new lines while scrolled to the bottom. Returns
+/// the problem that was clicked.
+fn output(ui: &mut Ui, run: &Run) -> Option<(Url, Position)> {
+    let row_height = ui.text_style_height(&TextStyle::Monospace);
+    let mut clicked = None;
+
+    ScrollArea::both()
+        .auto_shrink(false)
+        .stick_to_bottom(true)
+        .show_rows(ui, row_height, run.output.len(), |ui, rows| {
+            ui.spacing_mut().item_spacing.y = 0.0;
+            ui.style_mut().wrap_mode = Some(egui::TextWrapMode::Extend);
+
+            for line in &run.output[rows] {
+                let text = RichText::new(&line.text).monospace();
+
+                match &line.location {
+                    Some(location) => {
+                        let link = ui
+                            .add(egui::Label::new(text.underline()).sense(Sense::click()))
+                            .on_hover_cursor(egui::CursorIcon::PointingHand);
+
+                        if link.clicked() {
+                            clicked = Some(location.clone());
+                        }
+                    }
+                    None => {
+                        ui.label(text);
+                    }
+                }
+            }
+        });
+
+    clicked
+}
@@ -0,0 +1,723 @@
+//! The terminal panel: the user's shell on a pseudo-terminal, its output
+//! parsed into a grid of cells by `vt100` and painted with Fira Code.
+
+use components::selectable_label::SelectableLabel;
+use egui::{
+    Color32, Context, CursorIcon, Event, EventFilter, FontFamily, FontId, Key, KeyboardShortcut,
+    Modifiers, Pos2, Rect, Response, Sense, Stroke, TextStyle, TopBottomPanel, Ui, Vec2,
+};
+use lsp::lsp_types::{Position, Url};
+use portable_pty::{native_pty_system, Child, CommandBuilder, MasterPty, PtySize};
+use std::{
+    io::{Read, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+};
+use theme::Theme;
+use vt100::{Color, Parser};
+
+use crate::{language_servers, State};
+
+pub const TOGGLE_SHORTCUT: KeyboardShortcut = KeyboardShortcut::new(Modifiers::CTRL, Key::Backtick);
+
+/// Lines kept above the screen.
+const SCROLLBACK: usize = 10_000;
+/// Registered by the app next to the regular Fira Code, see `hotreload`.
+const BOLD_FAMILY: &str = "fira_code_bold";
+
+#[derive(Default)]
+pub struct TerminalPanel {
+    pub open: bool,
+    terminals: Vec<Terminal>,
+    active: usize,
+    /// Terminals are numbered in the order they're opened.
+    opened: usize,
+    error: Option<String>,
+}
+
+struct Terminal {
+    number: usize,
+    shell: String,
+    cwd: PathBuf,
+    parser: Arc<Mutex<Parser>>,
+    exited: Arc<AtomicBool>,
+    master: Box<dyn MasterPty + Send>,
+    writer: Box<dyn Write + Send>,
+    child: Box<dyn Child + Send + Sync>,
+    size: (u16, u16),
+    /// Where the selection started and where it ends, as `(row, column)` on
+    /// the screen. Scrolling clears it.
+    selection: Option<(CellPosition, CellPosition)>,
+}
+
+type CellPosition = (u16, u16);
+
+/// A `path:line:column` printed in the terminal.
+struct Link {
+    path: PathBuf,
+    line: u32,
+    column: u32,
+    row: u16,
+    columns: std::ops::Range<u16>,
+}
+
+impl TerminalPanel {
+    /// Shows or hides the panel, starting a shell the first time.
+    pub fn toggle(&mut self, ctx: &Context) {
+        self.open = !self.open;
+
+        if self.open && self.terminals.is_empty() {
+            self.add(ctx);
+        }
+    }
+
+    fn add(&mut self, ctx: &Context) {
+        self.opened += 1;
+
+        match Terminal::spawn(ctx, self.opened) {
+            Ok(terminal) => {
+                self.terminals.push(terminal);
+                self.active = self.terminals.len() - 1;
+                self.error = None;
+            }
+            Err(error) => self.error = Some(error),
+        }
+    }
+
+    fn close(&mut self, index: usize) {
+        self.terminals.remove(index);
+        self.active = self.active.min(self.terminals.len().saturating_sub(1));
+    }
+}
+
+impl Terminal {
+    fn spawn(ctx: &Context, number: usize) -> Result<Self, String> {
+        let size = (24, 80);
+        let pair = native_pty_system()
+            .openpty(pty_size(size))
+            .map_err(|error| format!("Couldn't open a terminal: {error}"))?;
+
+        let cwd = std::env::current_dir().unwrap_or_default();
+        let mut command = CommandBuilder::new_default_prog();
+        command.cwd(&cwd);
+        command.env("TERM", "xterm-256color");
+
+        let child = pair
+            .slave
+            .spawn_command(command)
+            .map_err(|error| format!("Couldn't start the shell: {error}"))?;
+        let mut reader = pair
+            .master
+            .try_clone_reader()
+            .map_err(|error| error.to_string())?;
+        let writer = pair
+            .master
+            .take_writer()
+            .map_err(|error| error.to_string())?;
+
+        let parser = Arc::new(Mutex::new(Parser::new(size.0, size.1, SCROLLBACK)));
+        let exited = Arc::new(AtomicBool::new(false));
+
+        {
+            let parser = parser.clone();
+            let exited = exited.clone();
+            let ctx = ctx.clone();
+
+            thread::spawn(move || {
+                let mut buffer = [0; 8192];
+
+                while let Ok(read @ 1..) = reader.read(&mut buffer) {
+                    parser.lock().unwrap().process(&buffer[..read]);
+                    ctx.request_repaint();
+                }
+
+                exited.store(true, Ordering::Relaxed);
+                ctx.request_repaint();
+            });
+        }
+
+        let shell = std::env::var("SHELL")
+            .ok()
+            .and_then(|shell| Some(Path::new(&shell).file_name()?.to_str()?.to_string()))
+            .unwrap_or_else(|| "shell".into());
+
+        Ok(Self {
+            number,
+            shell,
+            cwd,
+            parser,
+            exited,
+            master: pair.master,
+            writer,
+            child,
+            size,
+            selection: None,
+        })
+    }
+
+    fn title(&self) -> String {
+        match self.exited.load(Ordering::Relaxed) {
+            true => format!("{} {} (exited)", self.shell, self.number),
+            false => format!("{} {}", self.shell, self.number),
+        }
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        if self.writer.write_all(bytes).is_ok() {
+            let _ = self.writer.flush();
+        }
+
+        // Typing jumps back to the prompt.
+        self.scroll_to(0);
+    }
+
+    /// Tells both the parser and the shell about the new size, so programs
+    /// get `SIGWINCH` and redraw.
+    fn resize(&mut self, size: (u16, u16)) {
+        self.size = size;
+        self.selection = None;
+        self.parser.lock().unwrap().set_size(size.0, size.1);
+        let _ = self.master.resize(pty_size(size));
+    }
+
+    /// How many lines the view is scrolled up into the scrollback.
+    fn scrolled(&self) -> usize {
+        self.parser.lock().unwrap().screen().scrollback()
+    }
+
+    fn scroll_to(&mut self, lines: usize) {
+        let mut parser = self.parser.lock().unwrap();
+
+        if parser.screen().scrollback() != lines {
+            parser.set_scrollback(lines);
+            self.selection = None;
+        }
+    }
+
+    fn selected_text(&self) -> Option<String> {
+        let (start, end) = self.sorted_selection()?;
+        let parser = self.parser.lock().unwrap();
+
+        Some(
+            parser
+                .screen()
+                .contents_between(start.0, start.1, end.0, end.1 + 1),
+        )
+    }
+
+    fn sorted_selection(&self) -> Option<(CellPosition, CellPosition)> {
+        let (anchor, head) = self.selection?;
+        Some((anchor.min(head), anchor.max(head)))
+    }
+
+    fn is_selected(&self, row: u16, column: u16) -> bool {
+        self.sorted_selection()
+            .is_some_and(|(start, end)| start <= (row, column) && (row, column) <= end)
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+    }
+}
+
+fn pty_size((rows, cols): (u16, u16)) -> PtySize {
+    PtySize {
+        rows,
+        cols,
+        pixel_width: 0,
+        pixel_height: 0,
+    }
+}
+
+pub fn show(ctx: &Context, state: &mut State) {
+    if !state.terminal.open {
+        return;
+    }
+
+    let mut link = None;
+
+    TopBottomPanel::bottom("terminal")
+        .resizable(true)
+        .default_height(240.0)
+        .min_height(100.0)
+        .show(ctx, |ui| {
+            let panel = &mut state.terminal;
+
+            tabs(ui, panel);
+            ui.separator();
+
+            if let Some(error) = &panel.error {
+                ui.colored_label(state.theme.error.main, error);
+            }
+
+            if let Some(terminal) = panel.terminals.get_mut(panel.active) {
+                link = view(ui, terminal, &state.theme);
+            }
+        });
+
+    if let Some(Link {
+        path, line, column, ..
+    }) = link
+    {
+        let uri = path
+            .canonicalize()
+            .ok()
+            .and_then(|path| Url::from_file_path(path).ok());
+
+        if let Some(uri) = uri {
+            let position = Position {
+                line: line.saturating_sub(1),
+                character: column.saturating_sub(1),
+            };
+
+            language_servers::open_location(ctx, state, &uri, position);
+        }
+    }
+}
+
+fn tabs(ui: &mut Ui, panel: &mut TerminalPanel) {
+    let mut close = None;
+
+    ui.horizontal(|ui| {
+        ui.strong("Terminal");
+
+        for (index, terminal) in panel.terminals.iter().enumerate() {
+            let label = ui.add(
+                SelectableLabel::new(index == panel.active, terminal.title())
+                    .padding(Vec2::new(8.0, 2.0)),
+            );
+
+            if label.clicked() {
+                panel.active = index;
+            }
+
+            if label.middle_clicked() {
+                close = Some(index);
+            }
+        }
+
+        if ui.small_button("+").on_hover_text("New Terminal").clicked() {
+            panel.add(ui.ctx());
+        }
+
+        ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+            if ui.small_button("x").on_hover_text("Hide").clicked() {
+                panel.open = false;
+            }
+
+            if !panel.terminals.is_empty()
+                && ui
+                    .small_button("🗑")
+                    .on_hover_text("Kill Terminal")
+                    .clicked()
+            {
+                close = Some(panel.active);
+            }
+        });
+    });
+
+    if let Some(index) = close {
+        panel.close(index);
+    }
+}
+
+/// Draws the grid and handles the input meant for the shell. Returns the
+/// link that was Cmd+Clicked, if any.
+fn view(ui: &mut Ui, terminal: &mut Terminal, theme: &Theme) -> Option<Link> {
+    let font_id = TextStyle::Monospace.resolve(ui.style());
+    let bold_font_id = bold(ui.ctx(), &font_id);
+    let cell =
+        ui.fonts(|fonts| Vec2::new(fonts.glyph_width(&font_id, 'M'), fonts.row_height(&font_id)));
+
+    let (rect, response) = ui.allocate_exact_size(ui.available_size(), Sense::click_and_drag());
+    let size = (
+        ((rect.height() / cell.y) as u16).max(1),
+        ((rect.width() / cell.x) as u16).max(2),
+    );
+
+    if size != terminal.size {
+        terminal.resize(size);
+    }
+
+    if response.clicked() || response.drag_started() {
+        response.request_focus();
+    }
+
+    if response.has_focus() {
+        ui.memory_mut(|memory| {
+            memory.set_focus_lock_filter(
+                response.id,
+                EventFilter {
+                    tab: true,
+                    horizontal_arrows: true,
+                    vertical_arrows: true,
+                    escape: true,
+                },
+            )
+        });
+
+        handle_input(ui, terminal);
+    }
+
+    if response.hovered() {
+        let lines = ui.input(|input| input.raw_scroll_delta.y) / cell.y;
+
+        if lines.abs() >= 1.0 {
+            let scrolled = terminal.scrolled() as isize + lines.round() as isize;
+            terminal.scroll_to(scrolled.max(0) as usize);
+        }
+    }
+
+    let cell_at = |pos: Pos2| -> CellPosition {
+        let row = ((pos.y - rect.top()) / cell.y).floor().max(0.0) as u16;
+        let column = ((pos.x - rect.left()) / cell.x).floor().max(0.0) as u16;
+
+        (row.min(size.0 - 1), column.min(size.1 - 1))
+    };
+
+    select(ui, &response, terminal, cell_at);
+
+    let hovered_link = response
+        .hover_pos()
+        .filter(|_| ui.input(|input| input.modifiers.command))
+        .and_then(|pointer| link_at(terminal, cell_at(pointer)));
+
+    paint(
+        ui,
+        rect,
+        cell,
+        [&font_id, &bold_font_id],
+        terminal,
+        response.has_focus(),
+        theme,
+    );
+
+    let link = hovered_link?;
+    let start = rect.min + Vec2::new(link.columns.start as f32, link.row as f32 + 1.0) * cell;
+    let end = start.x + link.columns.len() as f32 * cell.x;
+
+    ui.painter()
+        .hline(start.x..=end, start.y, Stroke::new(1.0, theme.primary.main));
+    ui.ctx().set_cursor_icon(CursorIcon::PointingHand);
+
+    response.clicked().then_some(link)
+}
+
+/// Dragging selects cells; a click without a drag clears the selection.
+fn select(
+    ui: &Ui,
+    response: &Response,
+    terminal: &mut Terminal,
+    cell_at: impl Fn(Pos2) -> CellPosition,
+) {
+    if response.drag_started() {
+        if let Some(origin) = ui.input(|input| input.pointer.press_origin()) {
+            terminal.selection = Some((cell_at(origin), cell_at(origin)));
+        }
+    }
+
+    if response.dragged() {
+        if let (Some(pointer), Some((_, head))) =
+            (response.interact_pointer_pos(), &mut terminal.selection)
+        {
+            *head = cell_at(pointer);
+        }
+    }
+
+    if response.clicked() {
+        terminal.selection = None;
+    }
+}
+
+fn handle_input(ui: &Ui, terminal: &mut Terminal) {
+    let (events, application_cursor, bracketed_paste) = {
+        let parser = terminal.parser.lock().unwrap();
+        let screen = parser.screen();
+
+        (
+            ui.input(|input| input.events.clone()),
+            screen.application_cursor(),
+            screen.bracketed_paste(),
+        )
+    };
+
+    for event in events {
+        match event {
+            Event::Text(text) => terminal.write(text.as_bytes()),
+            Event::Paste(text) => {
+                if bracketed_paste {
+                    terminal.write(format!("\x1b[200~{text}\x1b[201~").as_bytes());
+                } else {
+                    terminal.write(text.replace('\n', "\r").as_bytes());
+                }
+            }
+            // Cmd+C on macOS and Ctrl+C elsewhere come as Copy, not as a key:
+            // they copy when there is something to, otherwise interrupt.
+            Event::Copy => match terminal.selected_text() {
+                Some(text) => {
+                    ui.ctx().copy_text(text);
+                    terminal.selection = None;
+                }
+                None => terminal.write(b"\x03"),
+            },
+            Event::Cut => terminal.write(b"\x18"),
+            Event::Key {
+                key,
+                pressed: true,
+                modifiers,
+                ..
+            } => {
+                if let Some(bytes) = key_bytes(key, modifiers, application_cursor) {
+                    terminal.write(&bytes);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// What a key sends to the shell, for keys that don't produce text.
+fn key_bytes(key: Key, modifiers: Modifiers, application_cursor: bool) -> Option<Vec<u8>> {
+    let cursor = |code: char| match application_cursor {
+        true => format!("\x1bO{code}"),
+        false => format!("\x1b[{code}"),
+    };
+
+    let sequence = match key {
+        Key::Enter => "\r".into(),
+        Key::Backspace => "\x7f".into(),
+        Key::Tab if modifiers.shift => "\x1b[Z".into(),
+        Key::Tab => "\t".into(),
+        Key::Escape => "\x1b".into(),
+        Key::ArrowUp => cursor('A'),
+        Key::ArrowDown => cursor('B'),
+        Key::ArrowRight => cursor('C'),
+        Key::ArrowLeft => cursor('D'),
+        Key::Home => cursor('H'),
+        Key::End => cursor('F'),
+        Key::Insert => "\x1b[2~".into(),
+        Key::Delete => "\x1b[3~".into(),
+        Key::PageUp => "\x1b[5~".into(),
+        Key::PageDown => "\x1b[6~".into(),
+        _ if modifiers.ctrl => {
+            // Ctrl+A through Ctrl+Z are the control characters 1 to 26.
+            let name = key.name();
+            let letter = name
+                .chars()
+                .next()
+                .filter(|char| name.len() == 1 && char.is_ascii_alphabetic())?;
+
+            return Some(vec![letter.to_ascii_uppercase() as u8 - b'A' + 1]);
+        }
+        _ => return None,
+    };
+
+    Some(sequence.into_bytes())
+}
+
+fn paint(
+    ui: &Ui,
+    rect: Rect,
+    cell: Vec2,
+    [font_id, bold_font_id]: [&FontId; 2],
+    terminal: &Terminal,
+    focused: bool,
+    theme: &Theme,
+) {
+    let painter = ui.painter_at(rect);
+    let default_fg = theme.text_color.primary;
+    let default_bg = ui.visuals().extreme_bg_color;
+    let selection_bg = ui.visuals().selection.bg_fill;
+
+    painter.rect_filled(rect, 0.0, default_bg);
+
+    let parser = terminal.parser.lock().unwrap();
+    let screen = parser.screen();
+    let (rows, columns) = screen.size();
+
+    for row in 0..rows {
+        let top = rect.top() + row as f32 * cell.y;
+        let mut column = 0;
+
+        while column < columns {
+            let Some(grid_cell) = screen.cell(row, column) else {
+                break;
+            };
+
+            let (mut fg, mut bg) = (
+                color(grid_cell.fgcolor(), default_fg),
+                color(grid_cell.bgcolor(), default_bg),
+            );
+
+            if grid_cell.inverse() {
+                std::mem::swap(&mut fg, &mut bg);
+            }
+
+            if terminal.is_selected(row, column) {
+                bg = selection_bg;
+            }
+
+            let width = if grid_cell.is_wide() { 2 } else { 1 };
+            let cell_rect = Rect::from_min_size(
+                Pos2::new(rect.left() + column as f32 * cell.x, top),
+                Vec2::new(cell.x * width as f32, cell.y),
+            );
+
+            if bg != default_bg {
+                painter.rect_filled(cell_rect, 0.0, bg);
+            }
+
+            if grid_cell.has_contents() {
+                let font_id = match grid_cell.bold() {
+                    true => bold_font_id,
+                    false => font_id,
+                };
+
+                painter.text(
+                    cell_rect.left_top(),
+                    egui::Align2::LEFT_TOP,
+                    grid_cell.contents(),
+                    font_id.clone(),
+                    fg,
+                );
+
+                if grid_cell.underline() {
+                    painter.hline(
+                        cell_rect.x_range(),
+                        cell_rect.bottom() - 1.0,
+                        Stroke::new(1.0, fg),
+                    );
+                }
+            }
+
+            column += width;
+        }
+    }
+
+    if screen.scrollback() == 0 && !screen.hide_cursor() {
+        let (row, column) = screen.cursor_position();
+        let cursor =
+            Rect::from_min_size(rect.min + Vec2::new(column as f32, row as f32) * cell, cell);
+
+        match focused {
+            true => painter.rect_filled(cursor, 0.0, default_fg.gamma_multiply(0.6)),
+            false => painter.rect_stroke(cursor, 0.0, Stroke::new(1.0, default_fg)),
+        };
+    }
+}
+
+/// The bold Fira Code when the app registered it, the regular one otherwise.
+fn bold(ctx: &Context, font_id: &FontId) -> FontId {
+    let family = FontFamily::Name(BOLD_FAMILY.into());
+
+    match ctx.fonts(|fonts| fonts.families().contains(&family)) {
+        true => FontId::new(font_id.size, family),
+        false => font_id.clone(),
+    }
+}
+
+/// The xterm palette: 16 named colors, a 6×6×6 cube and a gray ramp.
+fn color(color: Color, default: Color32) -> Color32 {
+    const NAMED: [(u8, u8, u8); 16] = [
+        (0, 0, 0),
+        (205, 49, 49),
+        (13, 188, 121),
+        (229, 229, 16),
+        (36, 114, 200),
+        (188, 63, 188),
+        (17, 168, 205),
+        (229, 229, 229),
+        (102, 102, 102),
+        (241, 76, 76),
+        (35, 209, 139),
+        (245, 245, 67),
+        (59, 142, 234),
+        (214, 112, 214),
+        (41, 184, 219),
+        (255, 255, 255),
+    ];
+
+    match color {
+        Color::Default => default,
+        Color::Rgb(r, g, b) => Color32::from_rgb(r, g, b),
+        Color::Idx(index @ 0..=15) => {
+            let (r, g, b) = NAMED[index as usize];
+            Color32::from_rgb(r, g, b)
+        }
+        Color::Idx(index @ 16..=231) => {
+            let level = |value: u8| if value == 0 { 0 } else { 55 + value * 40 };
+            let index = index - 16;
+
+            Color32::from_rgb(level(index / 36), level(index / 6 % 6), level(index % 6))
+        }
+        Color::Idx(index) => {
+            let gray = 8 + (index - 232) * 10;
+            Color32::from_gray(gray)
+        }
+    }
+}
+
+/// The `path:line` or `path:line:column` under `position`, if the path is
+/// a file relative to the shell's starting directory or absolute.
+fn link_at(terminal: &Terminal, (row, column): CellPosition) -> Option<Link> {
+    let parser = terminal.parser.lock().unwrap();
+    let screen = parser.screen();
+    let line: Vec<char> = (0..screen.size().1)
+        .map(|column| {
+            screen
+                .cell(row, column)
+                .map(|cell| cell.contents().chars().next().unwrap_or(' '))
+                .unwrap_or(' ')
+        })
+        .collect();
+
+    let is_delimiter = |char: char| char.is_whitespace() || "\"'`()[]{}<>,;|".contains(char);
+
+    if is_delimiter(*line.get(column as usize)?) {
+        return None;
+    }
+
+    let start = line[..column as usize]
+        .iter()
+        .rposition(|char| is_delimiter(*char))
+        .map_or(0, |index| index + 1);
+    let end = line[column as usize..]
+        .iter()
+        .position(|char| is_delimiter(*char))
+        .map_or(line.len(), |index| column as usize + index);
+
+    let token: String = line[start..end].iter().collect();
+    let token = token.trim_end_matches([':', '.']);
+
+    let (path, line, column) = parse_location(token)?;
+    let path = terminal.cwd.join(path);
+
+    path.is_file().then(|| Link {
+        path,
+        line,
+        column,
+        row,
+        columns: start as u16..(start + token.chars().count()) as u16,
+    })
+}
+
+/// Splits `src/main.rs:12:5` or `src/main.rs:12` into its parts.
+fn parse_location(token: &str) -> Option<(&str, u32, u32)> {
+    let (rest, last) = token.rsplit_once(':')?;
+    let last = last.parse().ok()?;
+
+    let with_line = rest
+        .rsplit_once(':')
+        .and_then(|(path, line)| Some((path, line.parse().ok()?)));
+
+    match with_line {
+        Some((path, line)) => Some((path, line, last)),
+        None => Some((rest, last, 1)),
+    }
+}
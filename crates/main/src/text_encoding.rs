@@ -0,0 +1,318 @@
+//! Text encodings: detected when a file is opened, from its byte order mark
+//! or else by guessing from its bytes, kept in its `FileData` and used again
+//! to save it. Reopen with Encoding and Save with Encoding pick another.
+
+use chardetng::{EncodingDetector, Iso2022JpDetection, Utf8Detection};
+use egui::{Align2, Context, RichText, Ui, Window};
+use encoding_rs::{
+    Encoding, BIG5, EUC_JP, EUC_KR, GB18030, GBK, ISO_8859_15, ISO_8859_2, ISO_8859_7, KOI8_R,
+    SHIFT_JIS, UTF_16BE, UTF_16LE, UTF_8, WINDOWS_1250, WINDOWS_1251, WINDOWS_1252,
+};
+
+use crate::{autosave, State};
+
+/// The encodings files can be reopened or saved with.
+pub const ALL: [&Encoding; 16] = [
+    UTF_8,
+    UTF_16LE,
+    UTF_16BE,
+    WINDOWS_1252,
+    ISO_8859_15,
+    ISO_8859_2,
+    ISO_8859_7,
+    WINDOWS_1250,
+    WINDOWS_1251,
+    KOI8_R,
+    SHIFT_JIS,
+    EUC_JP,
+    EUC_KR,
+    GBK,
+    GB18030,
+    BIG5,
+];
+
+/// How much of a file UTF-16 without a byte order mark is looked for in.
+const SNIFF_LENGTH: usize = 4096;
+
+#[derive(Default)]
+pub struct TextEncoding {
+    /// What went wrong the last time an encoding was picked.
+    error: Option<String>,
+}
+
+/// The encoding of a file's bytes, and whether they start with its byte
+/// order mark.
+pub fn detect(bytes: &[u8]) -> (&'static Encoding, bool) {
+    if let Some((encoding, _)) = Encoding::for_bom(bytes) {
+        return (encoding, true);
+    }
+
+    if let Some(encoding) = sniff_utf16(bytes) {
+        return (encoding, false);
+    }
+
+    if std::str::from_utf8(bytes).is_ok() {
+        return (UTF_8, false);
+    }
+
+    let mut detector = EncodingDetector::new(Iso2022JpDetection::Deny);
+
+    detector.feed(bytes, true);
+    (detector.guess(None, Utf8Detection::Allow), false)
+}
+
+/// Mostly ASCII text in UTF-16 has every other byte zero.
+fn sniff_utf16(bytes: &[u8]) -> Option<&'static Encoding> {
+    let sample = &bytes[..bytes.len().min(SNIFF_LENGTH) & !1];
+
+    if sample.is_empty() {
+        return None;
+    }
+
+    let zeros = |offset: usize| {
+        sample
+            .iter()
+            .skip(offset)
+            .step_by(2)
+            .filter(|byte| **byte == 0)
+            .count()
+    };
+    let (even, odd) = (zeros(0), zeros(1));
+    let pairs = sample.len() / 2;
+
+    match (even, odd) {
+        (0, odd) if odd * 10 >= pairs * 3 => Some(UTF_16LE),
+        (even, 0) if even * 10 >= pairs * 3 => Some(UTF_16BE),
+        _ => None,
+    }
+}
+
+/// Whether `bytes` start with `encoding`'s byte order mark.
+pub fn has_bom(bytes: &[u8], encoding: &'static Encoding) -> bool {
+    Encoding::for_bom(bytes).is_some_and(|(bom, _)| bom == encoding)
+}
+
+pub fn decode(bytes: &[u8], encoding: &'static Encoding, bom: bool) -> String {
+    let bytes = match bom {
+        true => &bytes[bom_bytes(encoding).len()..],
+        false => bytes,
+    };
+
+    encoding.decode_without_bom_handling(bytes).0.into_owned()
+}
+
+/// Fails when the encoding has no bytes for some of the text.
+pub fn encode(text: &str, encoding: &'static Encoding, bom: bool) -> Result<Vec<u8>, String> {
+    let mut bytes = match bom {
+        true => bom_bytes(encoding).to_vec(),
+        false => Vec::new(),
+    };
+
+    // Encoding to UTF-16 isn't something encoding_rs does.
+    if encoding == UTF_16LE {
+        bytes.extend(text.encode_utf16().flat_map(u16::to_le_bytes));
+    } else if encoding == UTF_16BE {
+        bytes.extend(text.encode_utf16().flat_map(u16::to_be_bytes));
+    } else {
+        let (encoded, _, unmappable) = encoding.encode(text);
+
+        if unmappable {
+            return Err(format!(
+                "Some of the text can't be written in {}.",
+                encoding.name()
+            ));
+        }
+
+        bytes.extend_from_slice(&encoded);
+    }
+
+    Ok(bytes)
+}
+
+fn bom_bytes(encoding: &'static Encoding) -> &'static [u8] {
+    match encoding {
+        encoding if encoding == UTF_8 => b"\xEF\xBB\xBF",
+        encoding if encoding == UTF_16LE => b"\xFF\xFE",
+        encoding if encoding == UTF_16BE => b"\xFE\xFF",
+        _ => b"",
+    }
+}
+
+/// Reads the active file again, decoded with `encoding`.
+pub fn reopen_with(state: &mut State, encoding: &'static Encoding) {
+    let path = state.file_store.active_file.clone();
+    let saved = state
+        .file_store
+        .get_active_file()
+        .is_some_and(|file| file.is_saved());
+
+    state.text_encoding.error = match saved {
+        true => state
+            .file_store
+            .reopen(&path, encoding)
+            .err()
+            .map(|error| error.to_string()),
+        false => Some("Save the file before reopening it, its edits would be lost.".into()),
+    };
+}
+
+/// Saves the active file in `encoding` from now on.
+pub fn save_with(ctx: &Context, state: &mut State, encoding: &'static Encoding) {
+    let path = state.file_store.active_file.clone();
+    let Some(file) = state.file_store.get_active_file_as_mut() else {
+        return;
+    };
+
+    if let Err(error) = encode(&file.content, encoding, false) {
+        state.text_encoding.error = Some(error);
+        return;
+    }
+
+    let previous = (file.encoding, file.bom);
+
+    // UTF-16 is hard to tell apart without one.
+    file.bom = encoding == UTF_16LE || encoding == UTF_16BE || (file.bom && encoding == UTF_8);
+    file.encoding = encoding;
+
+    // The file on disk is still in the old one. The error is shown by
+    // `autosave::show_error`.
+    if autosave::save(ctx, state, &path).is_err() {
+        if let Some(file) = state.file_store.files.get_mut(&path) {
+            (file.encoding, file.bom) = previous;
+        }
+    }
+}
+
+/// The active file's encoding, in the menu bar, to reopen or save it with
+/// another.
+pub fn status(ui: &mut Ui, state: &mut State) {
+    let Some(file) = state.file_store.get_active_file() else {
+        return;
+    };
+
+    let label = match file.bom {
+        true => format!("{} with BOM", file.encoding.name()),
+        false => file.encoding.name().to_string(),
+    };
+    let mut reopen = None;
+    let mut save = None;
+
+    ui.menu_button(label, |ui| {
+        ui.menu_button("Reopen with Encoding", |ui| {
+            for encoding in ALL {
+                if ui.button(encoding.name()).clicked() {
+                    reopen = Some(encoding);
+                    ui.close_menu();
+                }
+            }
+        });
+
+        ui.menu_button("Save with Encoding", |ui| {
+            for encoding in ALL {
+                if ui.button(encoding.name()).clicked() {
+                    save = Some(encoding);
+                    ui.close_menu();
+                }
+            }
+        });
+    });
+
+    if let Some(encoding) = reopen {
+        reopen_with(state, encoding);
+    }
+
+    if let Some(encoding) = save {
+        save_with(ui.ctx(), state, encoding);
+    }
+}
+
+pub fn show_error(ctx: &Context, state: &mut State) {
+    let Some(error) = &state.text_encoding.error else {
+        return;
+    };
+
+    let mut dismissed = false;
+
+    Window::new("Encoding")
+        .collapsible(false)
+        .resizable(false)
+        .anchor(Align2::RIGHT_BOTTOM, [-12.0, -12.0])
+        .show(ctx, |ui| {
+            ui.set_max_width(360.0);
+            ui.label(RichText::new(error).color(state.theme.error.main));
+
+            if ui.button("Dismiss").clicked() {
+                dismissed = true;
+            }
+        });
+
+    if dismissed {
+        state.text_encoding.error = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEXT: &str = "Grüße, naïve café\n";
+
+    fn round_trip(encoding: &'static Encoding, bom: bool) {
+        let bytes = encode(TEXT, encoding, bom).unwrap();
+
+        assert_eq!(detect(&bytes), (encoding, bom));
+        assert_eq!(has_bom(&bytes, encoding), bom);
+        assert_eq!(decode(&bytes, encoding, bom), TEXT);
+    }
+
+    fn round_trip_legacy(encoding: &'static Encoding, text: &str) {
+        let bytes = encode(text, encoding, false).unwrap();
+
+        assert_eq!(detect(&bytes), (encoding, false));
+        assert_eq!(decode(&bytes, encoding, false), text);
+    }
+
+    #[test]
+    fn utf8_with_and_without_bom() {
+        round_trip(UTF_8, true);
+        round_trip(UTF_8, false);
+    }
+
+    #[test]
+    fn utf16_with_and_without_bom() {
+        round_trip(UTF_16LE, true);
+        round_trip(UTF_16LE, false);
+        round_trip(UTF_16BE, true);
+        round_trip(UTF_16BE, false);
+    }
+
+    #[test]
+    fn sniffs_utf16_only_from_mostly_ascii_text() {
+        assert_eq!(
+            sniff_utf16(&encode("abc", UTF_16LE, false).unwrap()),
+            Some(UTF_16LE)
+        );
+        assert_eq!(
+            sniff_utf16(&encode("abc", UTF_16BE, false).unwrap()),
+            Some(UTF_16BE)
+        );
+        assert_eq!(sniff_utf16(b"plain text"), None);
+        assert_eq!(sniff_utf16(b""), None);
+    }
+
+    #[test]
+    fn guesses_legacy_encodings() {
+        let latin = "Les élèves français étaient très contents de leur été à la mer.\n";
+        let japanese = "日本語のテキストです。これは文字コードの判定のための文章です。\n";
+
+        round_trip_legacy(WINDOWS_1252, latin);
+        round_trip_legacy(SHIFT_JIS, japanese);
+    }
+
+    #[test]
+    fn fails_on_unmappable_text() {
+        let error = encode("日本", WINDOWS_1252, false).unwrap_err();
+
+        assert_eq!(error, "Some of the text can't be written in windows-1252.");
+    }
+}
@@ -45,6 +45,10 @@ pub struct Theme {
 
     pub primary: Palette,
     pub secondary: Palette,
+    pub error: Palette,
+    pub warning: Palette,
+    pub info: Palette,
+    pub success: Palette,
     pub action: Action,
     pub text_color: TextColor,
     pub bg: Color32,
@@ -84,6 +88,30 @@ impl Theme {
                 dark: Color32::from_hex("#ab47bc").unwrap(),
                 contrast_text: Color32::from_hex("#ffffff").unwrap(),
             },
+            error: Palette {
+                main: Color32::from_hex("#f44336").unwrap(),
+                light: Color32::from_hex("#e57373").unwrap(),
+                dark: Color32::from_hex("#d32f2f").unwrap(),
+                contrast_text: Color32::from_hex("#ffffff").unwrap(),
+            },
+            warning: Palette {
+                main: Color32::from_hex("#ffa726").unwrap(),
+                light: Color32::from_hex("#ffb74d").unwrap(),
+                dark: Color32::from_hex("#f57c00").unwrap(),
+                contrast_text: Color32::BLACK.gamma_multiply(0.9),
+            },
+            info: Palette {
+                main: Color32::from_hex("#29b6f6").unwrap(),
+                light: Color32::from_hex("#4fc3f7").unwrap(),
+                dark: Color32::from_hex("#0288d1").unwrap(),
+                contrast_text: Color32::BLACK.gamma_multiply(0.9),
+            },
+            success: Palette {
+                main: Color32::from_hex("#66bb6a").unwrap(),
+                light: Color32::from_hex("#81c784").unwrap(),
+                dark: Color32::from_hex("#388e3c").unwrap(),
+                contrast_text: Color32::BLACK.gamma_multiply(0.9),
+            },
             text_color: TextColor {
                 primary: Color32::from_rgb(255, 255, 255).gamma_multiply(0.7),
                 secondary: Color32::from_rgb(255, 255, 255).gamma_multiply(0.5),